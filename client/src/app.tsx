@@ -1,5 +1,14 @@
 import { Route, Routes, Navigate } from 'react-router-dom';
-import { Editor, Landing, About, Login, Logout, NoMatch, SignUp } from 'pages';
+import {
+  Editor,
+  Landing,
+  About,
+  Login,
+  Logout,
+  NoMatch,
+  PublicNote,
+  SignUp,
+} from 'pages';
 import React, { PropsWithChildren } from 'react';
 import { DefaultLayout, EditorLayout } from 'components';
 import { EditorContextProvider } from 'context';
@@ -27,6 +36,10 @@ export function App() {
       <Route path="/login" element={withDefaultLayout(<Login />)} />
       <Route path="/logout" element={withDefaultLayout(<Logout />)} />
       <Route path="/signup" element={withDefaultLayout(<SignUp />)} />
+      <Route
+        path="/public/:token"
+        element={withDefaultLayout(<PublicNote />)}
+      />
       <Route
         path="/app"
         element={
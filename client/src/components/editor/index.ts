@@ -3,3 +3,4 @@ export { Preview } from './preview';
 export { EditorPane } from './editorPane';
 export { NewNoteForm } from './newNoteForm';
 export { ConfirmDeletionDialog } from './confirmDeletionDialog';
+export { LinkDialog } from './linkDialog';
@@ -0,0 +1,187 @@
+import axios from 'axios';
+import { useEditor } from 'hooks';
+import { FormEvent, SyntheticEvent, useEffect, useState } from 'react';
+import { useTranslation } from 'react-i18next';
+import { Trash } from 'react-feather';
+import { ILink } from 'types';
+import { capitalFirstLetter } from 'utils';
+
+export function LinkDialog() {
+  const { currentNote, refs } = useEditor();
+  const [t] = useTranslation();
+  const [links, setLinks] = useState<ILink[]>([]);
+  const [expiresInDays, setExpiresInDays] = useState('');
+  const [maxViews, setMaxViews] = useState('');
+  const [password, setPassword] = useState('');
+  const [createdUrl, setCreatedUrl] = useState('');
+  const [error, setError] = useState('');
+
+  const getLinks = async (id: string) => {
+    const res = await axios(`/api/note/${id}/links`);
+    if (!res.data.success) {
+      console.error(res.data.message);
+      return;
+    }
+
+    setLinks(res.data.content);
+  };
+
+  const createLink = async (e: FormEvent) => {
+    e.stopPropagation();
+    e.preventDefault();
+    if (!currentNote) return;
+
+    const res = await axios.post(`/api/note/${currentNote.note_id}/links`, {
+      expires_in_days: expiresInDays ? Number(expiresInDays) : null,
+      max_views: maxViews ? Number(maxViews) : null,
+      password: password || null,
+    });
+    if (!res.data.success) {
+      setError(res.data.message);
+      return;
+    }
+
+    // The link is only ever shown once
+    setCreatedUrl(`${window.location.origin}/public/${res.data.content.token}`);
+    setError('');
+    setPassword('');
+    await getLinks(currentNote.note_id);
+  };
+
+  const revokeLink = async (linkId: string) => {
+    if (!currentNote) return;
+
+    const res = await axios.delete(
+      `/api/note/${currentNote.note_id}/links/${linkId}`
+    );
+    if (!res.data.success) {
+      console.error(res.data.message);
+      return;
+    }
+
+    setLinks(links.filter((link) => link.link_id !== linkId));
+  };
+
+  const close = (e: SyntheticEvent) => {
+    e.stopPropagation();
+    e.preventDefault();
+
+    setCreatedUrl('');
+    setError('');
+    refs.linkDialog?.current?.close();
+  };
+
+  const click = (e: SyntheticEvent) => {
+    e.stopPropagation();
+    const el = e.target as HTMLElement;
+    if (el.id === 'linkDialog') {
+      close(e);
+    }
+  };
+
+  useEffect(() => {
+    setLinks([]);
+    setCreatedUrl('');
+    if (currentNote?.allowance === 'Owner') {
+      getLinks(currentNote.note_id);
+    }
+    // eslint-disable-next-line
+  }, [currentNote]);
+
+  if (!currentNote || currentNote.allowance !== 'Owner') {
+    return <></>;
+  }
+
+  return (
+    <dialog onClick={click} id="linkDialog" ref={refs.linkDialog}>
+      <article className="form">
+        <header>
+          <h1>{t('links.title')}</h1>
+          <p>{t('links.description')}</p>
+        </header>
+
+        {links.length !== 0 && (
+          <ul>
+            {links.map((link) => (
+              <li key={link.link_id} className="flex">
+                <span>
+                  {link.link_id} &middot;{' '}
+                  {t('links.views', {
+                    count: link.views,
+                    max: link.max_views ?? '∞',
+                  })}
+                  {link.expires_at &&
+                    ` · ${t('links.expiresAt', {
+                      date: new Date(link.expires_at).toLocaleString(),
+                    })}`}
+                  {link.protected && ` · ${t('links.protected')}`}
+                </span>
+                <button
+                  className="svgButton"
+                  title={t('links.revoke')}
+                  onClick={() => revokeLink(link.link_id)}
+                >
+                  <Trash />
+                </button>
+              </li>
+            ))}
+          </ul>
+        )}
+
+        <form onSubmit={createLink}>
+          {createdUrl && (
+            <span className="banner success">
+              <p>{t('links.createdNotice')}</p>
+              <input type="text" value={createdUrl} readOnly />
+            </span>
+          )}
+
+          <label htmlFor="expiresInDays">
+            {t('links.expiresInDays')}
+            <input
+              type="number"
+              min={1}
+              id="expiresInDays"
+              placeholder={t('links.never')}
+              value={expiresInDays}
+              onChange={(e) => setExpiresInDays(e.target.value)}
+            />
+          </label>
+
+          <label htmlFor="maxViews">
+            {t('links.maxViews')}
+            <input
+              type="number"
+              min={1}
+              id="maxViews"
+              placeholder={t('links.unlimited')}
+              value={maxViews}
+              onChange={(e) => setMaxViews(e.target.value)}
+            />
+          </label>
+
+          <label htmlFor="linkPassword">
+            {t('auth.password')}
+            <input
+              type="password"
+              id="linkPassword"
+              autoComplete="new-password"
+              placeholder={t('links.noPassword')}
+              value={password}
+              onChange={(e) => setPassword(e.target.value)}
+            />
+          </label>
+
+          {error && <span className="danger">{capitalFirstLetter(error)}</span>}
+
+          <span className="flex">
+            <button type="submit">{t('links.create')}</button>
+            <button onClick={close} className="secondary">
+              {t('links.close')}
+            </button>
+          </span>
+        </form>
+      </article>
+    </dialog>
+  );
+}
@@ -1,8 +1,5 @@
 import { useEditor } from 'hooks';
-import { CodeBlock } from 'components';
-import Markdown from 'react-markdown';
-import remarkGfm from 'remark-gfm';
-import remarkEmoji from 'remark-emoji';
+import { MarkdownView } from 'components';
 import styles from 'styles/components/editor/preview.module.scss';
 import { useTranslation } from 'react-i18next';
 
@@ -15,26 +12,7 @@ export function Preview() {
       {currentNote ? (
         <>
           {currentNote.note.content ? (
-            <Markdown
-              remarkPlugins={[remarkGfm, remarkEmoji]}
-              components={{
-                code({ node, inline, className, children, ...props }) {
-                  const match = /language-(\w+)/.exec(className || '');
-                  return !inline && match ? (
-                    <CodeBlock
-                      value={String(children).replace(/\n$/, '')}
-                      language={match[1]}
-                    />
-                  ) : (
-                    <code className={className} {...props}>
-                      {children}
-                    </code>
-                  );
-                },
-              }}
-            >
-              {currentNote?.note.content ?? ''}
-            </Markdown>
+            <MarkdownView content={currentNote.note.content} />
           ) : (
             <div className="center">
               <h2>{t('notes.noNoteBody')}</h2>
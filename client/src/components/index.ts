@@ -1,5 +1,6 @@
 export { PageHeader } from './pageHeader';
 export { PageFooter } from './pageFooter';
 export { CodeBlock } from './codeBlock';
+export { MarkdownView } from './markdownView';
 export * from './layouts';
 export * from './editor';
@@ -0,0 +1,33 @@
+import { CodeBlock } from 'components';
+import Markdown from 'react-markdown';
+import remarkGfm from 'remark-gfm';
+import remarkEmoji from 'remark-emoji';
+
+interface IProps {
+  content: string;
+}
+
+export function MarkdownView(props: IProps) {
+  return (
+    <Markdown
+      remarkPlugins={[remarkGfm, remarkEmoji]}
+      components={{
+        code({ node, inline, className, children, ...props }) {
+          const match = /language-(\w+)/.exec(className || '');
+          return !inline && match ? (
+            <CodeBlock
+              value={String(children).replace(/\n$/, '')}
+              language={match[1]}
+            />
+          ) : (
+            <code className={className} {...props}>
+              {children}
+            </code>
+          );
+        },
+      }}
+    >
+      {props.content}
+    </Markdown>
+  );
+}
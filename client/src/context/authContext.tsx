@@ -1,10 +1,53 @@
-import axios from 'axios';
+import axios, { AxiosError, AxiosRequestConfig } from 'axios';
 import { createContext, PropsWithChildren, useEffect, useState } from 'react';
 import { IUser } from 'types';
 
+/**
+ * Exchanges the refresh-token for a new session-token
+ *
+ * @returns whether the session could be refreshed
+ */
+const refreshSession = async (): Promise<boolean> => {
+  try {
+    const res = await axios.post('/api/auth/refresh');
+    return res.data.success;
+  } catch {
+    return false;
+  }
+};
+
+// Retry requests once the expired session-token has been refreshed
+axios.interceptors.response.use(undefined, async (error: AxiosError) => {
+  const config = error.config as AxiosRequestConfig & { _retried?: boolean };
+  if (
+    error.response?.status !== 401 ||
+    !config ||
+    config._retried ||
+    config.url === '/api/auth/refresh'
+  ) {
+    throw error;
+  }
+  config._retried = true;
+  if (!(await refreshSession())) throw error;
+  return axios(config);
+});
+
+/**
+ * Passes on responses rejecting an attempt due to too many previous ones,
+ * so that their message can be displayed like any other
+ *
+ * @param error
+ * @returns the response of the rejected request
+ */
+const acceptTooManyAttempts = (error: AxiosError) => {
+  if (error.response?.status === 429) return error.response;
+  throw error;
+};
+
 export interface IResponse {
   success: boolean;
   message: string;
+  challenge?: string;
 }
 
 export interface IAuthContext {
@@ -15,6 +58,7 @@ export interface IAuthContext {
     password: string,
     sessionOnly: boolean
   ) => Promise<IResponse>;
+  verifySecondFactor: (challenge: string, code: string) => Promise<IResponse>;
   signUp: (
     username: string,
     password: string,
@@ -29,6 +73,9 @@ export const AuthContext = createContext<IAuthContext>({
   login: async () => {
     return { success: false, message: '' };
   },
+  verifySecondFactor: async () => {
+    return { success: false, message: '' };
+  },
   signUp: async () => {
     return { success: false, message: '' };
   },
@@ -74,11 +121,44 @@ export function AuthContextProvider(props: PropsWithChildren) {
       return { success: false, message: 'provided invalid username' };
     }
 
-    const res = await axios.post('/api/auth', {
-      username,
-      password,
-      session_only: sessionOnly,
-    });
+    const res = await axios
+      .post('/api/auth', {
+        username,
+        password,
+        session_only: sessionOnly,
+      })
+      .catch(acceptTooManyAttempts);
+
+    if (!res.data.success) {
+      console.log(res);
+      return { success: false, message: res.data.message ?? 'Login failed' };
+    }
+
+    if (res.data.content?.second_factor_required) {
+      return {
+        success: false,
+        message: '',
+        challenge: res.data.content.challenge,
+      };
+    }
+
+    await getUser();
+    return { success: true, message: '' };
+  };
+
+  /**
+   * Second factor function, completing a login
+   *
+   * @param challenge
+   * @param code
+   */
+  const verifySecondFactor = async (
+    challenge: string,
+    code: string
+  ): Promise<IResponse> => {
+    const res = await axios
+      .post('/api/auth/totp', { challenge, code })
+      .catch(acceptTooManyAttempts);
 
     if (!res.data.success) {
       console.log(res);
@@ -110,11 +190,13 @@ export function AuthContextProvider(props: PropsWithChildren) {
       return { success: false, message: 'provided invalid betaKey' };
     }
 
-    const res = await axios.post('/api/user', {
-      username,
-      password,
-      beta_key: betaKey,
-    });
+    const res = await axios
+      .post('/api/user', {
+        username,
+        password,
+        beta_key: betaKey,
+      })
+      .catch(acceptTooManyAttempts);
 
     if (!res.data.success) {
       console.log(res);
@@ -130,7 +212,10 @@ export function AuthContextProvider(props: PropsWithChildren) {
    */
   const getUser = async () => {
     setLoading(true);
-    const res = await axios.get('/api/auth');
+    let res = await axios.get('/api/auth');
+    if (!res.data.success && (await refreshSession())) {
+      res = await axios.get('/api/auth');
+    }
 
     if (!res.data.success) {
       setUser(undefined);
@@ -155,7 +240,15 @@ export function AuthContextProvider(props: PropsWithChildren) {
 
   return (
     <AuthContext.Provider
-      value={{ user, loading, login, signUp, logout, getUser }}
+      value={{
+        user,
+        loading,
+        login,
+        verifySecondFactor,
+        signUp,
+        logout,
+        getUser,
+      }}
     >
       {props.children}
     </AuthContext.Provider>
@@ -18,6 +18,7 @@ export interface IEditorContext {
   refs: {
     bodyEditor: RefObject<HTMLDialogElement> | null;
     newNoteDialog: RefObject<HTMLDialogElement> | null;
+    linkDialog: RefObject<HTMLDialogElement> | null;
   };
   getNotes: () => Promise<void>;
   getNote: (id: string) => Promise<void>;
@@ -32,6 +33,7 @@ export const EditorContext = createContext<IEditorContext>({
   refs: {
     bodyEditor: null,
     newNoteDialog: null,
+    linkDialog: null,
   },
   getNotes: async () => {},
   getNote: async () => {},
@@ -43,6 +45,7 @@ export const EditorContextProvider = (props: PropsWithChildren) => {
   const [notes, setNotes] = useState<INoteShallow[]>([]);
   const bodyEditor = useRef(null);
   const newNoteDialog = useRef(null);
+  const linkDialog = useRef(null);
 
   const getNote = async (id: string) => {
     const res = await axios('/api/note/' + id);
@@ -54,12 +57,20 @@ export const EditorContextProvider = (props: PropsWithChildren) => {
   };
 
   const getNotes = async () => {
-    const res = await axios('/api/notes');
-    if (!res.data.success) {
-      console.error(res.data.code);
-    }
+    const allNotes: INoteShallow[] = [];
+    let cursor: string | null = null;
+    do {
+      const res = await axios('/api/notes', { params: { cursor } });
+      if (!res.data.success) {
+        console.error(res.data.code);
+        return;
+      }
+
+      allNotes.push(...res.data.content.notes);
+      cursor = res.data.content.next_cursor;
+    } while (cursor);
 
-    setNotes(res.data.content);
+    setNotes(allNotes);
   };
 
   const deleteNote = async (id: string): Promise<boolean> => {
@@ -80,7 +91,7 @@ export const EditorContextProvider = (props: PropsWithChildren) => {
         setNote,
         notes,
         setNotes,
-        refs: { bodyEditor, newNoteDialog },
+        refs: { bodyEditor, newNoteDialog, linkDialog },
         getNotes,
         getNote,
         deleteNote,
@@ -1,7 +1,13 @@
-import { EditorPane, NewNoteForm, Preview, Sidebar } from 'components';
+import {
+  EditorPane,
+  LinkDialog,
+  NewNoteForm,
+  Preview,
+  Sidebar,
+} from 'components';
 import { useEditor, useMnemonic, useMountEffect } from 'hooks';
 import { useTranslation } from 'react-i18next';
-import { Edit } from 'react-feather';
+import { Edit, Link } from 'react-feather';
 import styles from 'styles/components/editor/editorPage.module.scss';
 
 export function Editor() {
@@ -26,12 +32,19 @@ export function Editor() {
     refs.bodyEditor?.current?.showModal();
   };
 
+  const manageLinks = () => {
+    if (!currentNote || currentNote.allowance !== 'Owner') return;
+
+    refs.linkDialog?.current?.showModal();
+  };
+
   return (
     <div className={styles['editor-page']}>
       <Sidebar />
       <Preview />
       <NewNoteForm />
       <EditorPane />
+      <LinkDialog />
 
       {currentNote && (
         <button
@@ -42,6 +55,16 @@ export function Editor() {
           <Edit />
         </button>
       )}
+
+      {currentNote?.allowance === 'Owner' && (
+        <button
+          onClick={manageLinks}
+          className={`${styles['linkButton']} svgButton round`}
+          title={t('links.title')}
+        >
+          <Link />
+        </button>
+      )}
     </div>
   );
 }
@@ -5,3 +5,4 @@ export { SignUp } from './signup';
 export { Editor } from './editor';
 export { NoMatch } from './noMatch';
 export { About } from './about';
+export { PublicNote } from './publicNote';
@@ -1,23 +1,40 @@
 import { FormEvent, useState } from 'react';
 import { useTranslation } from 'react-i18next';
 import { useNavigate, useSearchParams } from 'react-router-dom';
-import { useAuth } from 'hooks';
+import { useAuth, useMountEffect } from 'hooks';
 import { capitalFirstLetter } from 'utils';
 
 export function Login() {
-  const { login } = useAuth();
+  const { login, verifySecondFactor } = useAuth();
   const [t] = useTranslation();
   const [username, setUsername] = useState('');
   const [password, setPassword] = useState('');
   const [sessionOnly, setSessionOnly] = useState(true);
+  const [challenge, setChallenge] = useState('');
+  const [code, setCode] = useState('');
   const [error, setError] = useState('');
+  const [sso, setSso] = useState(false);
   const [searchParams] = useSearchParams();
   const navigate = useNavigate();
 
+  useMountEffect(() => {
+    fetch('/api/system')
+      .then((res) => res.json())
+      .then((res) => setSso(res.success && res.content.sso === true))
+      .catch((err) => console.error(err));
+  });
+
   const submit = async (e: FormEvent) => {
     e.preventDefault();
 
-    const result = await login(username, password, sessionOnly);
+    const result = challenge
+      ? await verifySecondFactor(challenge, code)
+      : await login(username, password, sessionOnly);
+    if (result.challenge) {
+      setChallenge(result.challenge);
+      setError('');
+      return;
+    }
     if (!result.success) {
       console.log(result);
       setError(result.message);
@@ -44,41 +61,58 @@ export function Login() {
               </span>
             )}
 
-            <label htmlFor="username">
-              {t('auth.username')}
-              <input
-                type="text"
-                name={t('auth.username')}
-                id="username"
-                placeholder={t('auth.username')}
-                value={username}
-                onChange={(e) => setUsername(e.target.value)}
-              />
-            </label>
+            {challenge ? (
+              <label htmlFor="code">
+                {t('auth.login.code')}
+                <input
+                  type="text"
+                  name={t('auth.login.code')}
+                  id="code"
+                  autoComplete="one-time-code"
+                  placeholder={t('auth.login.codePlaceholder')}
+                  value={code}
+                  onChange={(e) => setCode(e.target.value)}
+                />
+              </label>
+            ) : (
+              <>
+                <label htmlFor="username">
+                  {t('auth.username')}
+                  <input
+                    type="text"
+                    name={t('auth.username')}
+                    id="username"
+                    placeholder={t('auth.username')}
+                    value={username}
+                    onChange={(e) => setUsername(e.target.value)}
+                  />
+                </label>
 
-            <label htmlFor="password">
-              {t('auth.password')}
-              <input
-                type="password"
-                name={t('auth.password')}
-                id="password"
-                placeholder={t('auth.password')}
-                value={password}
-                onChange={(e) => setPassword(e.target.value)}
-              />
-            </label>
+                <label htmlFor="password">
+                  {t('auth.password')}
+                  <input
+                    type="password"
+                    name={t('auth.password')}
+                    id="password"
+                    placeholder={t('auth.password')}
+                    value={password}
+                    onChange={(e) => setPassword(e.target.value)}
+                  />
+                </label>
 
-            <label htmlFor="rememberMe" className="flex flex-reverse">
-              {t('auth.login.rememberMe')}
-              <input
-                type="checkbox"
-                name={t('auth.login.rememberMe')}
-                id="rememberMe"
-                placeholder={t('auth.login.rememberMe')}
-                value={password}
-                onChange={(e) => setSessionOnly(!e.target.checked)}
-              />
-            </label>
+                <label htmlFor="rememberMe" className="flex flex-reverse">
+                  {t('auth.login.rememberMe')}
+                  <input
+                    type="checkbox"
+                    name={t('auth.login.rememberMe')}
+                    id="rememberMe"
+                    placeholder={t('auth.login.rememberMe')}
+                    value={password}
+                    onChange={(e) => setSessionOnly(!e.target.checked)}
+                  />
+                </label>
+              </>
+            )}
 
             {error && (
               <span className="danger">{capitalFirstLetter(error)}</span>
@@ -87,6 +121,16 @@ export function Login() {
             <button type="submit" className="w-full">
               {t('auth.login.action')}
             </button>
+
+            {sso && !challenge && (
+              <a
+                href={`/api/auth/oidc?session_only=${sessionOnly}`}
+                role="button"
+                className="secondary w-full"
+              >
+                {t('auth.login.sso')}
+              </a>
+            )}
           </form>
         </article>
       </div>
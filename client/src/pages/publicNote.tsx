@@ -0,0 +1,102 @@
+import axios from 'axios';
+import { FormEvent, useState } from 'react';
+import { useTranslation } from 'react-i18next';
+import { useParams } from 'react-router-dom';
+import { MarkdownView } from 'components';
+import { useMountEffect } from 'hooks';
+import { IPublicNote } from 'types';
+import { capitalFirstLetter } from 'utils';
+
+/** Error-code of a missing or wrong password */
+const INVALID_CREDENTIALS = 11;
+
+export function PublicNote() {
+  const { token } = useParams();
+  const [t] = useTranslation();
+  const [note, setNote] = useState<IPublicNote>();
+  const [passwordRequired, setPasswordRequired] = useState(false);
+  const [password, setPassword] = useState('');
+  const [error, setError] = useState('');
+
+  const load = async (password?: string) => {
+    const res = await axios(`/api/public/${token}`, {
+      headers: password ? { 'X-Share-Password': password } : {},
+      validateStatus: () => true,
+    });
+    if (res.data.success) {
+      setNote(res.data.content);
+      setError('');
+      return;
+    }
+
+    if (res.data.code === INVALID_CREDENTIALS) {
+      setPasswordRequired(true);
+      setError(password ? res.data.message : '');
+      return;
+    }
+    setError(t('links.unavailable'));
+  };
+
+  const submit = async (e: FormEvent) => {
+    e.preventDefault();
+    await load(password);
+  };
+
+  useMountEffect(() => {
+    load();
+  });
+
+  if (note) {
+    return (
+      <div className="container">
+        <article>
+          <header>
+            <h1>{note.title}</h1>
+            <small>
+              {t('links.updatedAt', {
+                date: new Date(note.updated_at).toLocaleString(),
+              })}
+            </small>
+          </header>
+          <MarkdownView content={note.content} />
+        </article>
+      </div>
+    );
+  }
+
+  return (
+    <div className="container">
+      <div className="center">
+        {passwordRequired ? (
+          <article className="form">
+            <header>
+              <h1>{t('links.protectedTitle')}</h1>
+            </header>
+            <form onSubmit={submit}>
+              <label htmlFor="password">
+                {t('auth.password')}
+                <input
+                  type="password"
+                  id="password"
+                  placeholder={t('auth.password')}
+                  value={password}
+                  onChange={(e) => setPassword(e.target.value)}
+                />
+              </label>
+
+              {error && (
+                <span className="danger">{capitalFirstLetter(error)}</span>
+              )}
+
+              <button type="submit" className="w-full">
+                {t('links.open')}
+              </button>
+            </form>
+          </article>
+        ) : (
+          error && <h1>{error}</h1>
+        )}
+      </div>
+    </div>
+  );
+}
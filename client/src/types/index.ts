@@ -1,4 +1,5 @@
 export type { IUser } from './user';
 export type { INote, INoteShallow } from './notes';
+export type { ILink, IPublicNote } from './links';
 export type { IInputValue } from './inputValue';
 export { emptyInputValue } from './inputValue';
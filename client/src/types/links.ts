@@ -0,0 +1,16 @@
+export interface ILink {
+  link_id: string;
+  created_by: string;
+  protected: boolean;
+  created_at: string;
+  expires_at: string | null;
+  max_views: number | null;
+  views: number;
+}
+
+export interface IPublicNote {
+  title: string;
+  content: string;
+  tags: string[];
+  updated_at: string;
+}
@@ -0,0 +1,636 @@
+//! Storage-backend keeping all objects in memory
+//!
+//! Nothing is persisted, every restart begins with an empty database.
+//! Primarily meant to be used in tests and during development.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use crate::db_access::{Allowance, ApiToken, AllowanceLevel, Credential, CredentialStore, DBError, DBInfo, Folder, FolderShare, FolderStore, Group, GroupStore, Identity, IdentityStore, LinkStore, MigrationStore, Note, NoteStore, NoteSummary, PasswordReset, ResetStore, Revision, RevisionStore, Session, SessionStore, ShareLink, Storage, TokenStore, Transfer, TransferStore, User, UserStore};
+use crate::db_access::DBError::{NoDocumentFoundError, QueryError, VersionMismatchError};
+
+/// All objects currently stored
+#[derive(Default)]
+struct MemoryData {
+    /// All credentials mapped by their username
+    credentials: HashMap<String, Credential>,
+    /// All user mapped by their identifier
+    users: HashMap<String, User>,
+    /// All notes mapped by their identifier
+    notes: HashMap<String, Note>,
+    /// The revision-history of all notes mapped by the note's identifier
+    revisions: HashMap<String, Vec<Revision>>,
+    /// All sessions mapped by their identifier
+    sessions: HashMap<String, Session>,
+    /// All personal access-tokens mapped by their identifier
+    tokens: HashMap<String, ApiToken>,
+    /// All password-reset tokens mapped by their identifier
+    resets: HashMap<String, PasswordReset>,
+    /// All accounts at identity-providers mapped by their identifier
+    identities: HashMap<String, Identity>,
+    /// All public links of notes mapped by their identifier
+    links: HashMap<String, ShareLink>,
+    /// All groups of users mapped by their identifier
+    groups: HashMap<String, Group>,
+    /// All folders of users mapped by their identifier
+    folders: HashMap<String, Folder>,
+    /// All pending offers to hand a note over mapped by the note's identifier
+    transfers: HashMap<String, Transfer>,
+    /// The identifier to be assigned to the next inserted note
+    next_note_id: u64,
+    /// The version of the schema recorded by the last applied migration
+    schema_version: u32
+}
+
+/// A storage-backend keeping all objects in memory
+#[derive(Default)]
+pub struct MemoryStorage {
+    /// The stored objects
+    data: Mutex<MemoryData>
+}
+
+impl MemoryStorage {
+    /// Creates a new and empty storage
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+
+    /// Grants exclusive access to the stored objects
+    fn data(&self) -> MutexGuard<'_, MemoryData> {
+        self.data.lock().unwrap()
+    }
+
+    /// Applies a modification to a stored user
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user
+    /// * `modify` - The modification to be applied
+    fn update_user<F: FnOnce(&mut User)>(&self, user_id: &str, modify: F) -> Result<(), DBError> {
+        // Just like an update-query, a missing user is no error
+        if let Some(user) = self.data().users.get_mut(user_id) {
+            modify(user)
+        }
+        Ok(())
+    }
+
+    /// Applies a modification to a stored group
+    ///
+    /// # Arguments
+    ///
+    /// * `group_id` - The identifier of the group
+    /// * `modify` - The modification to be applied
+    fn update_group<F: FnOnce(&mut Group)>(&self, group_id: &str, modify: F) -> Result<(), DBError> {
+        if let Some(group) = self.data().groups.get_mut(group_id) {
+            modify(group)
+        }
+        Ok(())
+    }
+
+    /// Applies a modification to a stored folder
+    ///
+    /// # Arguments
+    ///
+    /// * `folder_id` - The identifier of the folder
+    /// * `modify` - The modification to be applied
+    fn update_folder_with<F: FnOnce(&mut Folder)>(&self, folder_id: &str, modify: F) -> Result<(), DBError> {
+        if let Some(folder) = self.data().folders.get_mut(folder_id) {
+            modify(folder)
+        }
+        Ok(())
+    }
+
+    /// Returns all folders matching a filter, ordered by their creation
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The filter a folder has to match
+    fn find_folders<F: Fn(&Folder) -> bool>(&self, filter: F) -> Vec<Folder> {
+        let mut folders: Vec<Folder> = self.data().folders.values().filter(|folder| filter(folder)).cloned().collect();
+        folders.sort_by_key(|folder| folder.created_at);
+        folders
+    }
+}
+
+#[async_trait]
+impl CredentialStore for MemoryStorage {
+    async fn get_credential(&self, username: &str) -> Result<Credential, DBError> {
+        self.data().credentials.get(username).cloned().ok_or(NoDocumentFoundError)
+    }
+
+    async fn insert_credential(&self, cred: &Credential) -> Result<(), DBError> {
+        let mut data = self.data();
+        if data.credentials.contains_key(&cred._id) {
+            return Err(QueryError) // Duplicate key
+        }
+        data.credentials.insert(cred._id.clone(), cred.clone());
+        Ok(())
+    }
+
+    async fn update_credential(&self, cred: &Credential) -> Result<(), DBError> {
+        // Just like an update-query, missing credentials are no error
+        if let Some(stored) = self.data().credentials.get_mut(&cred._id) {
+            *stored = cred.clone()
+        }
+        Ok(())
+    }
+
+    async fn remove_credential(&self, username: &str) -> Result<(), DBError> {
+        self.data().credentials.remove(username);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserStore for MemoryStorage {
+    async fn get_user(&self, user_id: &str) -> Result<User, DBError> {
+        self.data().users.get(user_id).cloned().ok_or(NoDocumentFoundError)
+    }
+
+    async fn insert_user(&self, user: &User) -> Result<(), DBError> {
+        let mut data = self.data();
+        if data.users.contains_key(&user._id) {
+            return Err(QueryError) // Duplicate key
+        }
+        data.users.insert(user._id.clone(), user.clone());
+        Ok(())
+    }
+
+    async fn remove_user(&self, user_id: &str) -> Result<(), DBError> {
+        self.data().users.remove(user_id);
+        Ok(())
+    }
+
+    async fn add_connection(&self, user_id: &str, connection_id: &str) -> Result<(), DBError> {
+        self.update_user(user_id, |user| user.connections.push(connection_id.to_string()))
+    }
+
+    async fn pull_connection(&self, user_id: &str, connection_id: &str) -> Result<(), DBError> {
+        self.update_user(user_id, |user| user.connections.retain(|conn| conn.ne(connection_id)))
+    }
+
+    async fn add_allowance(&self, user_id: &str, allowance: &Allowance) -> Result<(), DBError> {
+        self.update_user(user_id, |user| user.allowances.push(allowance.clone()))
+    }
+
+    async fn pull_allowances(&self, user_id: &str, note_ids: &[String]) -> Result<(), DBError> {
+        self.update_user(user_id, |user| user.allowances.retain(|allow| !note_ids.contains(&allow.note_id)))
+    }
+
+    async fn pull_note_allowances(&self, note_id: &str) -> Result<(), DBError> {
+        for user in self.data().users.values_mut() {
+            user.allowances.retain(|allow| allow.note_id.ne(note_id))
+        }
+        Ok(())
+    }
+
+    async fn set_note_allowances(&self, note_id: &str, changes: &[(String, AllowanceLevel)]) -> Result<(), DBError> {
+        let mut data = self.data();
+        if changes.iter().any(|(user_id, _)| !data.users.contains_key(user_id)) {
+            return Err(NoDocumentFoundError)
+        }
+        for (user_id, level) in changes {
+            let user = data.users.get_mut(user_id).unwrap();
+            match user.allowances.iter_mut().find(|allow| allow.note_id.eq(note_id)) {
+                _ if *level == AllowanceLevel::Forbidden => user.allowances.retain(|allow| allow.note_id.ne(note_id)),
+                Some(allowance) => allowance.level = *level,
+                None => user.allowances.push(Allowance { note_id: note_id.to_string(), level: *level })
+            }
+        }
+        Ok(())
+    }
+
+    async fn set_roles(&self, user_id: &str, roles: &[String]) -> Result<(), DBError> {
+        self.update_user(user_id, |user| user.roles = roles.to_vec())
+    }
+}
+
+#[async_trait]
+impl NoteStore for MemoryStorage {
+    async fn get_note(&self, note_id: &str) -> Result<Note, DBError> {
+        self.data().notes.get(note_id).cloned().ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_notes(&self, note_ids: &[String]) -> Result<Vec<(String, Note)>, DBError> {
+        let data = self.data();
+        Ok(note_ids.iter().filter_map(|id| data.notes.get(id).map(|note| (id.clone(), note.clone()))).collect())
+    }
+
+    async fn get_note_summaries(&self, note_ids: &[String]) -> Result<Vec<(String, NoteSummary)>, DBError> {
+        let data = self.data();
+        Ok(note_ids.iter().filter_map(|id| data.notes.get(id).map(|note| (id.clone(), NoteSummary {
+            title: note.title.clone(),
+            owner_id: note.owner_id.clone(),
+            tags: note.tags.clone(),
+            version: note.version,
+            created_at: note.created_at,
+            updated_at: note.updated_at,
+            last_editor_id: note.last_editor_id.clone()
+        }))).collect())
+    }
+
+    async fn insert_note(&self, note: &Note) -> Result<String, DBError> {
+        let mut data = self.data();
+        data.next_note_id += 1;
+        let note_id = format!("{:024x}", data.next_note_id);
+        data.notes.insert(note_id.clone(), note.clone());
+        Ok(note_id)
+    }
+
+    async fn set_note_fields(&self, note_id: &str, note: &Note, version: u64, revision: Option<&Revision>) -> Result<u64, DBError> {
+        let mut data = self.data();
+        let stored = data.notes.get_mut(note_id).ok_or(NoDocumentFoundError)?;
+        if stored.version != version {
+            return Err(VersionMismatchError(stored.version))
+        }
+        stored.title = note.title.clone();
+        stored.content = note.content.clone();
+        stored.tags = note.tags.clone();
+        stored.updated_at = note.updated_at;
+        stored.last_editor_id = note.last_editor_id.clone();
+        stored.version += 1;
+        let version = stored.version;
+        if let Some(revision) = revision {
+            let history = data.revisions.entry(note_id.to_string()).or_default();
+            let rev = history.len() as u32 + 1;
+            history.push(Revision { rev, note_id: note_id.to_string(), ..revision.clone() });
+        }
+        Ok(version)
+    }
+
+    async fn set_note_owner(&self, note_id: &str, owner_id: &str) -> Result<(), DBError> {
+        let mut data = self.data();
+        let stored = data.notes.get_mut(note_id).ok_or(NoDocumentFoundError)?;
+        stored.owner_id = owner_id.to_string();
+        Ok(())
+    }
+
+    async fn remove_note(&self, note_id: &str) -> Result<(), DBError> {
+        self.data().notes.remove(note_id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RevisionStore for MemoryStorage {
+    async fn get_revisions(&self, note_id: &str) -> Result<Vec<Revision>, DBError> {
+        Ok(self.data().revisions.get(note_id).cloned().unwrap_or_default())
+    }
+
+    async fn get_revision(&self, note_id: &str, rev: u32) -> Result<Revision, DBError> {
+        self.data().revisions.get(note_id)
+            .and_then(|history| history.iter().find(|revision| revision.rev == rev).cloned())
+            .ok_or(NoDocumentFoundError)
+    }
+
+    async fn remove_revisions(&self, note_id: &str) -> Result<(), DBError> {
+        self.data().revisions.remove(note_id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemoryStorage {
+    async fn get_session(&self, session_id: &str) -> Result<Session, DBError> {
+        self.data().sessions.get(session_id).cloned().ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_sessions(&self, user_id: &str) -> Result<Vec<Session>, DBError> {
+        Ok(self.data().sessions.values().filter(|session| session.user_id.eq(user_id)).cloned().collect())
+    }
+
+    async fn insert_session(&self, session: &Session) -> Result<(), DBError> {
+        let mut data = self.data();
+        if data.sessions.contains_key(&session._id) {
+            return Err(QueryError) // Duplicate key
+        }
+        data.sessions.insert(session._id.clone(), session.clone());
+        Ok(())
+    }
+
+    async fn set_session_fields(&self, session: &Session, refresh_hash: &str) -> Result<(), DBError> {
+        let mut data = self.data();
+        let stored = data.sessions.get_mut(&session._id)
+            .filter(|stored| stored.refresh_hash.eq(refresh_hash)).ok_or(NoDocumentFoundError)?;
+        stored.refresh_hash = session.refresh_hash.clone();
+        stored.ip = session.ip.clone();
+        stored.last_seen = session.last_seen;
+        stored.expires_at = session.expires_at;
+        Ok(())
+    }
+
+    async fn touch_session(&self, session_id: &str, last_seen: DateTime<Utc>) -> Result<(), DBError> {
+        if let Some(session) = self.data().sessions.get_mut(session_id) {
+            session.last_seen = last_seen
+        }
+        Ok(())
+    }
+
+    async fn remove_session(&self, session_id: &str) -> Result<(), DBError> {
+        self.data().sessions.remove(session_id);
+        Ok(())
+    }
+
+    async fn remove_sessions(&self, user_id: &str) -> Result<(), DBError> {
+        self.data().sessions.retain(|_, session| session.user_id.ne(user_id));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TokenStore for MemoryStorage {
+    async fn get_token(&self, token_id: &str) -> Result<ApiToken, DBError> {
+        self.data().tokens.get(token_id).cloned().ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_tokens(&self, user_id: &str) -> Result<Vec<ApiToken>, DBError> {
+        let mut tokens: Vec<ApiToken> = self.data().tokens.values().filter(|token| token.user_id.eq(user_id)).cloned().collect();
+        tokens.sort_by_key(|token| token.created_at);
+        Ok(tokens)
+    }
+
+    async fn insert_token(&self, token: &ApiToken) -> Result<(), DBError> {
+        let mut data = self.data();
+        if data.tokens.contains_key(&token._id) {
+            return Err(QueryError) // Duplicate key
+        }
+        data.tokens.insert(token._id.clone(), token.clone());
+        Ok(())
+    }
+
+    async fn touch_token(&self, token_id: &str, last_used: DateTime<Utc>) -> Result<(), DBError> {
+        if let Some(token) = self.data().tokens.get_mut(token_id) {
+            token.last_used = Some(last_used)
+        }
+        Ok(())
+    }
+
+    async fn remove_token(&self, token_id: &str) -> Result<(), DBError> {
+        self.data().tokens.remove(token_id);
+        Ok(())
+    }
+
+    async fn remove_tokens(&self, user_id: &str) -> Result<(), DBError> {
+        self.data().tokens.retain(|_, token| token.user_id.ne(user_id));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ResetStore for MemoryStorage {
+    async fn get_reset(&self, reset_id: &str) -> Result<PasswordReset, DBError> {
+        self.data().resets.get(reset_id).cloned().ok_or(NoDocumentFoundError)
+    }
+
+    async fn insert_reset(&self, reset: &PasswordReset) -> Result<(), DBError> {
+        let mut data = self.data();
+        if data.resets.contains_key(&reset._id) {
+            return Err(QueryError) // Duplicate key
+        }
+        data.resets.insert(reset._id.clone(), reset.clone());
+        Ok(())
+    }
+
+    async fn remove_resets(&self, user_id: &str) -> Result<(), DBError> {
+        self.data().resets.retain(|_, reset| reset.user_id.ne(user_id));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IdentityStore for MemoryStorage {
+    async fn get_identity(&self, identity_id: &str) -> Result<Identity, DBError> {
+        self.data().identities.get(identity_id).cloned().ok_or(NoDocumentFoundError)
+    }
+
+    async fn insert_identity(&self, identity: &Identity) -> Result<(), DBError> {
+        let mut data = self.data();
+        if data.identities.contains_key(&identity._id) {
+            return Err(QueryError) // Duplicate key
+        }
+        data.identities.insert(identity._id.clone(), identity.clone());
+        Ok(())
+    }
+
+    async fn remove_identities(&self, user_id: &str) -> Result<(), DBError> {
+        self.data().identities.retain(|_, identity| identity.user_id.ne(user_id));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LinkStore for MemoryStorage {
+    async fn get_link(&self, link_id: &str) -> Result<ShareLink, DBError> {
+        self.data().links.get(link_id).cloned().ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_links(&self, note_id: &str) -> Result<Vec<ShareLink>, DBError> {
+        let mut links: Vec<ShareLink> = self.data().links.values().filter(|link| link.note_id.eq(note_id)).cloned().collect();
+        links.sort_by_key(|link| link.created_at);
+        Ok(links)
+    }
+
+    async fn insert_link(&self, link: &ShareLink) -> Result<(), DBError> {
+        let mut data = self.data();
+        if data.links.contains_key(&link._id) {
+            return Err(QueryError) // Duplicate key
+        }
+        data.links.insert(link._id.clone(), link.clone());
+        Ok(())
+    }
+
+    async fn count_view(&self, link_id: &str) -> Result<ShareLink, DBError> {
+        match self.data().links.get_mut(link_id) {
+            Some(link) if link.max_views.is_none_or(|max_views| link.views < max_views) => {
+                link.views += 1;
+                Ok(link.clone())
+            }
+            _ => Err(NoDocumentFoundError)
+        }
+    }
+
+    async fn remove_link(&self, link_id: &str) -> Result<(), DBError> {
+        self.data().links.remove(link_id);
+        Ok(())
+    }
+
+    async fn remove_links(&self, note_id: &str) -> Result<(), DBError> {
+        self.data().links.retain(|_, link| link.note_id.ne(note_id));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl GroupStore for MemoryStorage {
+    async fn get_group(&self, group_id: &str) -> Result<Group, DBError> {
+        self.data().groups.get(group_id).cloned().ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_groups(&self, user_id: &str) -> Result<Vec<Group>, DBError> {
+        let mut groups: Vec<Group> = self.data().groups.values()
+            .filter(|group| group.members.iter().any(|member| member.eq(user_id))).cloned().collect();
+        groups.sort_by_key(|group| group.created_at);
+        Ok(groups)
+    }
+
+    async fn insert_group(&self, group: &Group) -> Result<(), DBError> {
+        let mut data = self.data();
+        if data.groups.contains_key(&group._id) {
+            return Err(QueryError) // Duplicate key
+        }
+        data.groups.insert(group._id.clone(), group.clone());
+        Ok(())
+    }
+
+    async fn remove_group(&self, group_id: &str) -> Result<(), DBError> {
+        self.data().groups.remove(group_id);
+        Ok(())
+    }
+
+    async fn add_member(&self, group_id: &str, user_id: &str) -> Result<(), DBError> {
+        self.update_group(group_id, |group| group.members.push(user_id.to_string()))
+    }
+
+    async fn pull_member(&self, group_id: &str, user_id: &str) -> Result<(), DBError> {
+        self.update_group(group_id, |group| group.members.retain(|member| member.ne(user_id)))
+    }
+
+    async fn set_group_note_allowances(&self, note_id: &str, changes: &[(String, AllowanceLevel)]) -> Result<(), DBError> {
+        let mut data = self.data();
+        if changes.iter().any(|(group_id, _)| !data.groups.contains_key(group_id)) {
+            return Err(NoDocumentFoundError)
+        }
+        for (group_id, level) in changes {
+            let group = data.groups.get_mut(group_id).unwrap();
+            match group.allowances.iter_mut().find(|allow| allow.note_id.eq(note_id)) {
+                _ if *level == AllowanceLevel::Forbidden => group.allowances.retain(|allow| allow.note_id.ne(note_id)),
+                Some(allowance) => allowance.level = *level,
+                None => group.allowances.push(Allowance { note_id: note_id.to_string(), level: *level })
+            }
+        }
+        Ok(())
+    }
+
+    async fn pull_group_allowance(&self, group_id: &str, note_id: &str) -> Result<(), DBError> {
+        self.update_group(group_id, |group| group.allowances.retain(|allow| allow.note_id.ne(note_id)))
+    }
+
+    async fn pull_note_group_allowances(&self, note_id: &str) -> Result<(), DBError> {
+        for group in self.data().groups.values_mut() {
+            group.allowances.retain(|allow| allow.note_id.ne(note_id))
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FolderStore for MemoryStorage {
+    async fn get_folder(&self, folder_id: &str) -> Result<Folder, DBError> {
+        self.data().folders.get(folder_id).cloned().ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_folders(&self, owner_id: &str) -> Result<Vec<Folder>, DBError> {
+        Ok(self.find_folders(|folder| folder.owner_id.eq(owner_id)))
+    }
+
+    async fn get_shared_folders(&self, user_id: &str) -> Result<Vec<Folder>, DBError> {
+        Ok(self.find_folders(|folder| folder.shares.iter().any(|share| share.user_id.eq(user_id))))
+    }
+
+    async fn insert_folder(&self, folder: &Folder) -> Result<(), DBError> {
+        let mut data = self.data();
+        if data.folders.contains_key(&folder._id) {
+            return Err(QueryError) // Duplicate key
+        }
+        data.folders.insert(folder._id.clone(), folder.clone());
+        Ok(())
+    }
+
+    async fn update_folder(&self, folder_id: &str, name: &str, parent_id: Option<&str>) -> Result<(), DBError> {
+        self.update_folder_with(folder_id, |folder| {
+            folder.name = name.to_string();
+            folder.parent_id = parent_id.map(str::to_string);
+        })
+    }
+
+    async fn remove_folder(&self, folder_id: &str) -> Result<(), DBError> {
+        let mut data = self.data();
+        let folder = data.folders.remove(folder_id).ok_or(NoDocumentFoundError)?;
+        for child in data.folders.values_mut().filter(|child| child.parent_id.as_deref() == Some(folder_id)) {
+            child.parent_id = folder.parent_id.clone();
+        }
+        if let Some(parent) = folder.parent_id.as_ref().and_then(|parent_id| data.folders.get_mut(parent_id)) {
+            parent.notes.extend(folder.notes);
+        }
+        Ok(())
+    }
+
+    async fn add_folder_note(&self, folder_id: &str, note_id: &str) -> Result<(), DBError> {
+        self.update_folder_with(folder_id, |folder| folder.notes.push(note_id.to_string()))
+    }
+
+    async fn pull_folder_note(&self, note_id: &str) -> Result<(), DBError> {
+        for folder in self.data().folders.values_mut() {
+            folder.notes.retain(|id| id.ne(note_id))
+        }
+        Ok(())
+    }
+
+    async fn set_folder_shares(&self, folder_id: &str, changes: &[(String, AllowanceLevel)]) -> Result<(), DBError> {
+        self.update_folder_with(folder_id, |folder| for (user_id, level) in changes {
+            match folder.shares.iter_mut().find(|share| share.user_id.eq(user_id)) {
+                _ if *level == AllowanceLevel::Forbidden => folder.shares.retain(|share| share.user_id.ne(user_id)),
+                Some(share) => share.level = *level,
+                None => folder.shares.push(FolderShare { user_id: user_id.to_string(), level: *level })
+            }
+        })
+    }
+
+    async fn pull_folder_share(&self, folder_id: &str, user_id: &str) -> Result<(), DBError> {
+        self.update_folder_with(folder_id, |folder| folder.shares.retain(|share| share.user_id.ne(user_id)))
+    }
+}
+
+#[async_trait]
+impl TransferStore for MemoryStorage {
+    async fn get_transfer(&self, note_id: &str) -> Result<Transfer, DBError> {
+        self.data().transfers.get(note_id).cloned().ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_transfers(&self, user_id: &str) -> Result<Vec<Transfer>, DBError> {
+        let mut transfers: Vec<Transfer> = self.data().transfers.values()
+            .filter(|transfer| transfer.from_id.eq(user_id) || transfer.to_id.eq(user_id)).cloned().collect();
+        transfers.sort_by_key(|transfer| transfer.created_at);
+        Ok(transfers)
+    }
+
+    async fn set_transfer(&self, transfer: &Transfer) -> Result<(), DBError> {
+        self.data().transfers.insert(transfer._id.clone(), transfer.clone());
+        Ok(())
+    }
+
+    async fn remove_transfer(&self, note_id: &str) -> Result<(), DBError> {
+        self.data().transfers.remove(note_id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MigrationStore for MemoryStorage {
+    async fn get_schema_version(&self) -> Result<u32, DBError> {
+        Ok(self.data().schema_version)
+    }
+
+    async fn apply_migration(&self, version: u32, _description: &str) -> Result<(), DBError> {
+        // Objects kept in memory always conform to the newest schema, there is nothing to convert
+        self.data().schema_version = version;
+        Ok(())
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get_info(&self) -> DBInfo {
+        DBInfo { db_type: "memory", version: env!("CARGO_PKG_VERSION").to_string() }
+    }
+}
@@ -0,0 +1,74 @@
+//! Versioned migrations of the database-schema
+//!
+//! Every storage-backend records the version of the schema its data conforms to.
+//! Whenever the stored objects change, a new migration is appended to [`MIGRATIONS`],
+//! which every backend implements inside of [`MigrationStore::apply_migration`].
+//!
+//! Migrations are applied in order and have to be idempotent,
+//! as they are also run against databases that have been created with the newest schema
+//! and a failed migration may leave the database partially migrated.
+//!
+//! # Migrations
+//!
+//! 1. Notes carry a `version` used to detect concurrent modifications
+//! 2. Notes carry the timestamps `created_at` and `updated_at` as well as the `last_editor_id`.
+//!    Existing notes are backfilled from their revision-history, falling back to the time of the migration.
+//!    Their creation is exact on MongoDB (taken from the identifier), while SQLite can only approximate it
+//!    by their first modification, as its identifiers carry no timestamp
+//! 3. Credentials carry an optional second factor (`totp_secret`, `totp_enabled`, `totp_last_step` and `recovery_hashes`)
+//! 4. Revision-numbers are unique per note. On MongoDB, revisions numbered twice by concurrent updates are numbered again
+
+use log::info;
+use crate::db_access::{DBError, MigrationStore};
+
+/// Short descriptions of all migrations, the migration to version `n` being found at index `n - 1`
+pub const MIGRATIONS: &[&str] = &[
+    "add versions to notes",
+    "add timestamps and last editor to notes",
+    "add second factor to credentials",
+    "make revision-numbers unique per note"
+];
+
+/// The version of the schema this build of writeUp works with
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Returns the versions of all migrations yet to be applied to the database, in order.
+/// Fails with an UnsupportedSchemaError if the database is newer than this build of writeUp
+///
+/// # Arguments
+///
+/// * `db` - The storage-backend to be checked
+pub async fn pending_migrations(db: &dyn MigrationStore) -> Result<Vec<u32>, DBError> {
+    let current = db.get_schema_version().await?;
+    if current > SCHEMA_VERSION {
+        return Err(DBError::UnsupportedSchemaError(current))
+    }
+    Ok((current + 1..=SCHEMA_VERSION).collect())
+}
+
+/// Applies all pending migrations in order, returning the versions that have been applied
+///
+/// # Arguments
+///
+/// * `db` - The storage-backend to be migrated
+///
+/// # Examples
+///
+/// ```
+/// use crate::db_access::memory::MemoryStorage;
+/// use crate::db_access::migration::{migrate, SCHEMA_VERSION};
+///
+/// let db = MemoryStorage::new();
+///
+/// assert_eq!(migrate(&db).await.unwrap().len() as u32, SCHEMA_VERSION);
+/// assert!(migrate(&db).await.unwrap().is_empty());
+/// ```
+pub async fn migrate(db: &dyn MigrationStore) -> Result<Vec<u32>, DBError> {
+    let pending = pending_migrations(db).await?;
+    for version in &pending {
+        let description = MIGRATIONS[*version as usize - 1];
+        info!("Applying migration {}: {}", version, description);
+        db.apply_migration(*version, description).await?;
+    }
+    Ok(pending)
+}
@@ -0,0 +1,1393 @@
+//! Contains the schemata of all stored objects and the storage-traits used to access them
+//!
+//! The web-layer only ever talks to a [`Storage`], which bundles the
+//! [`CredentialStore`], [`UserStore`], [`NoteStore`], [`RevisionStore`], [`SessionStore`], [`TokenStore`], [`ResetStore`], [`IdentityStore`], [`LinkStore`], [`GroupStore`], [`FolderStore`] and [`TransferStore`] traits.
+//! The backend implementing these is chosen at startup,
+//! after which its schema is brought up to date using the [`migration`]s.
+//!
+//! # Backends
+//!
+//! + [`mongo`] - Stores all objects inside of a mongodb-server
+//! + [`sqlite`] - Stores all objects inside of an embedded sqlite-database
+//! + [`memory`] - Keeps all objects in memory (volatile, meant for testing and development)
+
+pub mod mongo;
+pub mod sqlite;
+pub mod memory;
+pub mod migration;
+
+use std::collections::HashMap;
+use std::env;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use argonautica::{Hasher, Verifier};
+use argonautica::output::HashRaw;
+use crate::PASSWD_SECRET_ENV_VAR_KEY;
+use crate::secret::{Secret, secret_from_env};
+
+// Various constants
+/// Chars not serving a use outside of a potential injection-attempt
+const FORBIDDEN_CHARS:[char;4] = ['{', '}', '$', ':']; //TODO? Check for '.' (only used in jwt so far)
+/// The name of the environment-variable containing previous password-secrets
+const PASSWD_SECRET_OLD_ENV_VAR_KEY: &str = "PASSWD_SECRET_OLD";
+/// The name of the environment-variable containing the memory-size of password-hashes
+const PASSWD_HASH_MEMORY_ENV_VAR_KEY: &str = "PASSWD_HASH_MEMORY";
+/// The name of the environment-variable containing the iterations of password-hashes
+const PASSWD_HASH_ITERATIONS_ENV_VAR_KEY: &str = "PASSWD_HASH_ITERATIONS";
+
+// Schemata
+// Sub-Structures
+/// The individual levels of access-rights a user can have regarding a note, ordered by the access they grant
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AllowanceLevel {
+    /// The user has no access to the note
+    Forbidden,
+    /// The user can only read the note
+    Read,
+    /// The user can read and modify the note
+    ReadWrite,
+    /// The user owns the note and therefore can read/modify/delete and share the note
+    Owner
+}
+
+/// The individual permissions a personal access-token can be granted
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub enum TokenScope {
+    /// The token can list, search and read notes and their revisions
+    #[serde(rename = "notes:read")]
+    NotesRead,
+    /// The token can create, modify, restore and delete notes
+    #[serde(rename = "notes:write")]
+    NotesWrite,
+    /// The token can manage connections and the sharing of notes
+    #[serde(rename = "share:manage")]
+    ShareManage
+}
+
+/// Serves as a link between a user and a note
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Allowance {
+    /// The identifier of the linked note
+    pub note_id: String,
+    /// The level of access the user has regarding the note
+    pub level: AllowanceLevel
+}
+
+// Database-Objects
+/// Structs representing the different kinds of documents to be found in the db
+pub trait DatabaseObject: Serialize + DeserializeOwned + Unpin + Send + Sync {}
+
+/// A struct modelling the required information to verify yourself as a user
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Credential {
+    /// Username
+    pub _id: String,
+    /// Password-Hash
+    passwd_hash: String,
+    /// Secret (base32) of the time-based one-time passwords serving as a second factor, if enrolled
+    #[serde(default)]
+    pub totp_secret: Option<String>,
+    /// Whether the enrolment has been confirmed, making the second factor mandatory upon login
+    #[serde(default)]
+    pub totp_enabled: bool,
+    /// The last time-step a one-time password has been accepted for, preventing its reuse
+    #[serde(default)]
+    pub totp_last_step: u64,
+    /// Hashes of all unused recovery-codes, each replacing a one-time password once
+    #[serde(default)]
+    pub recovery_hashes: Vec<String>
+}
+impl DatabaseObject for Credential {}
+impl Credential {
+    /// Creates a new set of credentials, hashing the password according to the current [`HashPolicy`]
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - The users name
+    /// * `passwd` - The password from which to generate a hash
+    pub fn new(username: String, passwd: &str) -> Credential {
+        Credential {
+            _id: username,
+            passwd_hash: Credential::gen_hash(passwd, &HashPolicy::from_env()),
+            totp_secret: None,
+            totp_enabled: false,
+            totp_last_step: 0,
+            recovery_hashes: Vec::new()
+        }
+    }
+
+    /// Compares a given password with the one associated with the account,
+    /// accepting hashes peppered with an old secret as well
+    ///
+    /// # Arguments
+    ///
+    /// * `passwd` - A string slice containing the supposed password in plain text
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crate::db_access::Credential;
+    ///
+    /// let cred = Credential::new("testUser".to_string(), "testPass");
+    ///
+    /// assert!(cred.verify("testPass"));
+    /// assert_eq!(cred.verify("passTest"), false);
+    /// ```
+    pub fn verify(&self, passwd: &str) -> bool {
+        self.check(passwd, &HashPolicy::from_env()).is_some()
+    }
+
+    /// Compares a given password with the one associated with the account.
+    /// If it matches, but its hash does not conform to the given policy anymore,
+    /// the password gets re-hashed (the changed credentials still have to be stored)
+    ///
+    /// # Arguments
+    ///
+    /// * `passwd` - A string slice containing the supposed password in plain text
+    /// * `policy` - The policy the hash has to conform to
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crate::db_access::{Credential, HashPolicy, Verification};
+    ///
+    /// let policy = HashPolicy::from_env();
+    /// let mut cred = Credential::new("testUser".to_string(), "testPass");
+    /// let stronger = HashPolicy { iterations: policy.iterations + 1, ..policy };
+    ///
+    /// assert_eq!(cred.verify_and_upgrade("testPass", &stronger), Verification::Upgraded);
+    /// assert_eq!(cred.verify_and_upgrade("testPass", &stronger), Verification::Valid);
+    /// assert_eq!(cred.verify_and_upgrade("passTest", &stronger), Verification::Invalid);
+    /// ```
+    pub fn verify_and_upgrade(&mut self, passwd: &str, policy: &HashPolicy) -> Verification {
+        match self.check(passwd, policy) {
+            None => Verification::Invalid,
+            Some(true) if !self.is_weaker_than(policy) => Verification::Valid,
+            Some(_) => {
+                self.passwd_hash = Credential::gen_hash(passwd, policy);
+                Verification::Upgraded
+            }
+        }
+    }
+
+    /// Replaces the password associated with the account, hashing it according to the current [`HashPolicy`]
+    ///
+    /// # Arguments
+    ///
+    /// * `passwd` - A string slice containing the new password in plain text
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crate::db_access::Credential;
+    ///
+    /// let mut cred = Credential::new("testUser".to_string(), "testPass");
+    /// cred.set_password("passTest");
+    ///
+    /// assert!(cred.verify("passTest"));
+    /// assert_eq!(cred.verify("testPass"), false);
+    /// ```
+    pub fn set_password(&mut self, passwd: &str) {
+        self.set_password_with(passwd, &HashPolicy::from_env());
+    }
+
+    /// Replaces the password associated with the account, hashing it according to the given policy
+    ///
+    /// # Arguments
+    ///
+    /// * `passwd` - A string slice containing the new password in plain text
+    /// * `policy` - The policy to hash the password with
+    pub fn set_password_with(&mut self, passwd: &str, policy: &HashPolicy) {
+        self.passwd_hash = Credential::gen_hash(passwd, policy);
+    }
+
+    /// Returns the memory-size and iterations encoded within the stored hash
+    pub fn hash_params(&self) -> Option<(u32, u32)> {
+        self.passwd_hash.parse::<HashRaw>().ok().map(|raw| (raw.memory_size(), raw.iterations()))
+    }
+
+    /// Checks whether the stored hash has been created using weaker parameters than required by the given policy
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The policy to compare with
+    fn is_weaker_than(&self, policy: &HashPolicy) -> bool {
+        match self.hash_params() {
+            Some((memory_size, iterations)) => memory_size < policy.memory_size || iterations < policy.iterations,
+            None => true // Hashes that can't be read are replaced as well
+        }
+    }
+
+    /// Compares a given password with the stored hash using all peppers of a policy.
+    /// Returns whether the current pepper has been used if it matches
+    ///
+    /// # Arguments
+    ///
+    /// * `passwd` - A string slice containing the supposed password in plain text
+    /// * `policy` - The policy containing the peppers
+    fn check(&self, passwd: &str, policy: &HashPolicy) -> Option<bool> {
+        check_hash(&self.passwd_hash, passwd, policy)
+    }
+
+    /// Generates a password hash to be stored in the db. The parameters used are encoded within it
+    ///
+    /// # Arguments
+    ///
+    /// * `passwd` - A string slice containing the password to be hashed
+    /// * `policy` - The policy to hash the password with
+    fn gen_hash(passwd: &str, policy: &HashPolicy) -> String {
+        let mut hasher = Hasher::default();
+        hasher.configure_memory_size(policy.memory_size)
+            .configure_iterations(policy.iterations)
+            .with_secret_key(policy.pepper.expose());
+        hasher.with_password(passwd).hash().unwrap()
+    }
+}
+
+/// Compares a given password with a stored hash using all peppers of a policy.
+/// Returns whether the current pepper has been used if it matches
+///
+/// # Arguments
+///
+/// * `hash` - The stored hash
+/// * `passwd` - A string slice containing the supposed password in plain text
+/// * `policy` - The policy containing the peppers
+fn check_hash(hash: &str, passwd: &str, policy: &HashPolicy) -> Option<bool> {
+    let matches = |pepper: &Secret| {
+        let mut verifier = Verifier::default();
+        verifier.with_secret_key(pepper.expose());
+        verifier.with_hash(hash).with_password(passwd);
+        verifier.verify().unwrap_or(false) // false if the hash cant be processed
+    };
+    if matches(&policy.pepper) {
+        return Some(true)
+    }
+    policy.old_peppers.iter().any(matches).then_some(false)
+}
+
+/// The outcome of verifying a password using [`Credential::verify_and_upgrade`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verification {
+    /// The password does not match
+    Invalid,
+    /// The password matches
+    Valid,
+    /// The password matches and has been re-hashed according to the current policy
+    Upgraded
+}
+
+/// Describes how passwords are to be hashed and which peppers are accepted when verifying them
+#[derive(Clone, Debug)]
+pub struct HashPolicy {
+    /// Available space in memory per each hash (kiB)
+    pub memory_size: u32,
+    /// Amount of iterations to be done per hash
+    pub iterations: u32,
+    /// The secret new hashes are peppered with
+    pub pepper: Secret,
+    /// Previous secrets, still accepted when verifying a password
+    pub old_peppers: Vec<Secret>
+}
+
+impl HashPolicy {
+    /// Default space in memory per each hash (kiB)
+    const DEFAULT_MEM_SIZE: u32 = 65536;
+    /// Default amount of iterations to be done per hash
+    const DEFAULT_ITER_COUNT: u32 = 8;
+
+    /// Reads the current policy from the following environment-variables:
+    /// * `PASSWD_SECRET` - The current pepper
+    /// * `PASSWD_SECRET_OLD` - A comma-separated list of previous peppers *[default: none]*
+    /// * `PASSWD_HASH_MEMORY` - The space in memory per each hash in kiB *[default: 65536]*
+    /// * `PASSWD_HASH_ITERATIONS` - The amount of iterations per hash *[default: 8]*
+    pub fn from_env() -> HashPolicy {
+        let read = |key: &str, default: u32| match env::var(key) {
+            Ok(value) => value.parse().unwrap_or_else(|_| panic!("Env-Variable '{}' needs to be a positive number", key)),
+            Err(_) => default
+        };
+        HashPolicy {
+            memory_size: read(PASSWD_HASH_MEMORY_ENV_VAR_KEY, HashPolicy::DEFAULT_MEM_SIZE),
+            iterations: read(PASSWD_HASH_ITERATIONS_ENV_VAR_KEY, HashPolicy::DEFAULT_ITER_COUNT),
+            pepper: secret_from_env(PASSWD_SECRET_ENV_VAR_KEY).unwrap(),
+            old_peppers: secret_from_env(PASSWD_SECRET_OLD_ENV_VAR_KEY).map(|peppers| peppers.expose().split(',')
+                .map(str::trim).filter(|pepper| !pepper.is_empty()).map(Secret::new).collect())
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// A struct modelling a user
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct User {
+    /// Username
+    pub _id: String,
+    /// A list of notes the user has access to
+    pub allowances: Vec<Allowance>,
+    /// A list of user this one is connected with
+    pub connections: Vec<String>,
+    /// The roles assigned to the user by a directory (see [`Directory`](crate::directory::Directory))
+    #[serde(default)]
+    pub roles: Vec<String>
+}
+impl DatabaseObject for User {}
+
+/// A struct modelling a group of users, with which notes can be shared as a whole
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Group {
+    /// Identifier of the group
+    pub _id: String,
+    /// The name of the group
+    pub name: String,
+    /// The user who created and manages the group
+    pub owner_id: String,
+    /// All members of the group, including its owner
+    pub members: Vec<String>,
+    /// A list of notes every member has access to
+    pub allowances: Vec<Allowance>,
+    /// Timestamp of the creation
+    pub created_at: DateTime<Utc>
+}
+impl DatabaseObject for Group {}
+
+/// The level of access a user has been given regarding a folder and everything inside of it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FolderShare {
+    /// The user the folder is shared with
+    pub user_id: String,
+    /// The level of access inherited by every note inside of the folder
+    pub level: AllowanceLevel
+}
+
+/// A struct modelling a folder, containing notes and other folders of the same owner
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Folder {
+    /// Identifier of the folder
+    pub _id: String,
+    /// The name of the folder
+    pub name: String,
+    /// The user who owns the folder and all notes inside of it
+    pub owner_id: String,
+    /// The folder containing this one (a top-level folder if not set)
+    pub parent_id: Option<String>,
+    /// The notes directly inside of the folder
+    pub notes: Vec<String>,
+    /// The users the folder is shared with
+    pub shares: Vec<FolderShare>,
+    /// Timestamp of the creation
+    pub created_at: DateTime<Utc>
+}
+impl DatabaseObject for Folder {}
+
+/// A struct modelling the pending offer of the owner of a note to hand it over to another user
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Transfer {
+    /// Identifier of the note being handed over (a note has at most one pending offer)
+    pub _id: String,
+    /// The user currently owning the note
+    pub from_id: String,
+    /// The user being offered the note
+    pub to_id: String,
+    /// The level of access the current owner keeps once the offer is accepted
+    pub previous_owner_level: AllowanceLevel,
+    /// Timestamp of the offer
+    pub created_at: DateTime<Utc>
+}
+impl DatabaseObject for Transfer {}
+
+/// A struct modelling a note
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Note {
+    /// The title
+    pub title: String,
+    /// The actual note
+    pub content: String,
+    /// The user owning this note
+    pub owner_id: String,
+    /// The tags associated with this note
+    pub tags: Vec<String>,
+    /// Counter increased with every modification, used to detect concurrent modifications
+    #[serde(default)]
+    pub version: u64,
+    /// Timestamp of when the note was created
+    pub created_at: DateTime<Utc>,
+    /// Timestamp of when the note was last modified
+    pub updated_at: DateTime<Utc>,
+    /// The user who last modified the note
+    pub last_editor_id: String
+}
+impl DatabaseObject for Note {}
+
+/// A struct modelling the details of a note, leaving out its content
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NoteSummary {
+    /// The title
+    pub title: String,
+    /// The user owning this note
+    pub owner_id: String,
+    /// The tags associated with this note
+    pub tags: Vec<String>,
+    /// Counter increased with every modification, used to detect concurrent modifications
+    #[serde(default)]
+    pub version: u64,
+    /// Timestamp of when the note was created
+    pub created_at: DateTime<Utc>,
+    /// Timestamp of when the note was last modified
+    pub updated_at: DateTime<Utc>,
+    /// The user who last modified the note
+    pub last_editor_id: String
+}
+impl DatabaseObject for NoteSummary {}
+
+/// A struct modelling an immutable snapshot of a note, taken whenever the note gets modified
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Revision {
+    /// The identifier of the note this is a revision of
+    pub note_id: String,
+    /// The number of the revision (counting up from 1 per note)
+    pub rev: u32,
+    /// The user whose modification replaced this revision
+    pub author_id: String,
+    /// Timestamp of when this revision got replaced
+    pub created_at: DateTime<Utc>,
+    /// The title at the time
+    pub title: String,
+    /// The actual note at the time
+    pub content: String,
+    /// The tags at the time
+    pub tags: Vec<String>
+}
+impl DatabaseObject for Revision {}
+
+/// A struct modelling a login of a user on a single device, renewed using a refresh-token
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Session {
+    /// Identifier of the session
+    pub _id: String,
+    /// The user logged in
+    pub user_id: String,
+    /// Hash of the refresh-token currently valid for this session
+    pub refresh_hash: String,
+    /// The device (user-agent) the user logged in from
+    pub device: String,
+    /// The IP-address the session was last used from
+    pub ip: String,
+    /// Whether the session should end together with the browser-session
+    pub session_only: bool,
+    /// Timestamp of the login
+    pub created_at: DateTime<Utc>,
+    /// Timestamp of when the session was last used
+    pub last_seen: DateTime<Utc>,
+    /// Timestamp after which the session can no longer be refreshed
+    pub expires_at: DateTime<Utc>
+}
+impl DatabaseObject for Session {}
+
+/// A struct modelling a long-lived personal access-token, used by scripts instead of a login
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiToken {
+    /// Identifier of the token
+    pub _id: String,
+    /// The user the token acts on behalf of
+    pub user_id: String,
+    /// A name describing the purpose of the token
+    pub name: String,
+    /// Hash of the secret part of the token
+    pub token_hash: String,
+    /// The permissions granted to the token
+    pub scopes: Vec<TokenScope>,
+    /// Timestamp of the creation
+    pub created_at: DateTime<Utc>,
+    /// Timestamp after which the token is no longer accepted (never if not set)
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Timestamp of when the token was last used
+    pub last_used: Option<DateTime<Utc>>
+}
+impl DatabaseObject for ApiToken {}
+
+/// A struct modelling a short-lived token allowing a user to set a new password without knowing the old one
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PasswordReset {
+    /// Identifier of the token
+    pub _id: String,
+    /// The user whose password may be reset
+    pub user_id: String,
+    /// Hash of the secret part of the token
+    pub token_hash: String,
+    /// Timestamp of the creation
+    pub created_at: DateTime<Utc>,
+    /// Timestamp after which the token is no longer accepted
+    pub expires_at: DateTime<Utc>
+}
+impl DatabaseObject for PasswordReset {}
+
+/// A struct modelling the link between an account at an external identity-provider and a user
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Identity {
+    /// Identifier of the account, made up of the issuer and the subject (see [`Identity::gen_id`])
+    pub _id: String,
+    /// The user the account is linked to
+    pub user_id: String,
+    /// Timestamp of when the account was linked
+    pub created_at: DateTime<Utc>
+}
+impl DatabaseObject for Identity {}
+
+/// A struct modelling a public link granting anyone knowing it read-access to a note, without logging in
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShareLink {
+    /// Identifier of the link
+    pub _id: String,
+    /// The note that can be read using the link
+    pub note_id: String,
+    /// The user who created the link
+    pub created_by: String,
+    /// Hash of the secret part of the link
+    pub token_hash: String,
+    /// Hash of the password protecting the link (unprotected if not set)
+    passwd_hash: Option<String>,
+    /// Timestamp of the creation
+    pub created_at: DateTime<Utc>,
+    /// Timestamp after which the link is no longer accepted (never if not set)
+    pub expires_at: Option<DateTime<Utc>>,
+    /// The amount of views after which the link is no longer accepted (unlimited if not set)
+    pub max_views: Option<u32>,
+    /// The amount of times the note has been viewed using the link
+    pub views: u32
+}
+impl DatabaseObject for ShareLink {}
+
+impl ShareLink {
+    /// Creates a new link that has not been viewed yet, hashing its password according to the current [`HashPolicy`]
+    ///
+    /// # Arguments
+    ///
+    /// * `link_id` - The identifier of the link
+    /// * `note_id` - The note that can be read using the link
+    /// * `created_by` - The user creating the link
+    /// * `token_hash` - Hash of the secret part of the link
+    /// * `passwd` - The password protecting the link, if any
+    pub fn new(link_id: String, note_id: String, created_by: String, token_hash: String, passwd: Option<&str>) -> ShareLink {
+        ShareLink {
+            _id: link_id,
+            note_id,
+            created_by,
+            token_hash,
+            passwd_hash: passwd.map(|passwd| Credential::gen_hash(passwd, &HashPolicy::from_env())),
+            created_at: Utc::now(),
+            expires_at: None,
+            max_views: None,
+            views: 0
+        }
+    }
+
+    /// Whether a password has to be supplied to use the link
+    pub fn is_protected(&self) -> bool {
+        self.passwd_hash.is_some()
+    }
+
+    /// Compares a given password with the one protecting the link.
+    /// Unprotected links accept any password, protected ones reject a missing password
+    ///
+    /// # Arguments
+    ///
+    /// * `passwd` - The supposed password in plain text, if any
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crate::db_access::ShareLink;
+    ///
+    /// let link = ShareLink::new("linkId".to_string(), "noteId".to_string(), "testUser".to_string(), "hash".to_string(), Some("testPass"));
+    ///
+    /// assert!(link.verify(Some("testPass")));
+    /// assert_eq!(link.verify(None), false);
+    /// ```
+    pub fn verify(&self, passwd: Option<&str>) -> bool {
+        match (&self.passwd_hash, passwd) {
+            (None, _) => true,
+            (Some(hash), Some(passwd)) => check_hash(hash, passwd, &HashPolicy::from_env()).is_some(),
+            (Some(_), None) => false
+        }
+    }
+}
+
+impl Identity {
+    /// Generates the identifier of an account at an identity-provider
+    ///
+    /// # Arguments
+    ///
+    /// * `issuer` - The identifier of the identity-provider
+    /// * `subject` - The identifier of the account at the identity-provider
+    pub fn gen_id(issuer: &str, subject: &str) -> String {
+        format!("{} {}", issuer, subject)
+    }
+}
+
+// Error-Types
+/// Errors that can appear when accessing the database
+#[allow(dead_code)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Error, Debug)]
+pub enum DBError {
+    /// An error that occurs when the wrong credentials have been supplied
+    #[error("wrong credentials")]
+    WrongCredentialsError,
+    /// An error that occurs when the db-server is not accessible
+    #[error("could not connect to database-server")]
+    ServerConnectionError,
+    /// An error that occurs when a given query fails
+    #[error("query returned an error")]
+    QueryError,
+    /// An error that occurs when the given query could not find a fitting document to return
+    #[error("no document found")]
+    NoDocumentFoundError,
+    /// An error that occurs when a document was modified since the version an update is based on
+    #[error("document has been modified (current version: {0})")]
+    VersionMismatchError(u64),
+    /// An error that occurs when the database uses a schema newer than the one supported
+    #[error("schema of the database is not supported (version: {0})")]
+    UnsupportedSchemaError(u32)
+}
+
+/// Tests a string for potential injection-attempts
+///
+/// # Arguments
+///
+/// * `str` - The string to be checked
+///
+/// # Examples
+///
+/// ```
+/// use crate::db_access::is_safe;
+///
+/// let good_string = "Hey, i can do a whole lot here, can't i?";
+/// let bad_string = "%7B%24ne%3Anull%7D"; // eq: {$ne:null}
+///
+/// assert!(is_safe(good_string));
+/// assert!(!is_safe(bad_string));
+/// ```
+pub fn is_safe(str: &str) -> bool {
+    !FORBIDDEN_CHARS.iter().any(|char| str.contains(*char))
+}
+
+
+// Storage-Traits
+/// Operations regarding the credentials of a user
+#[async_trait]
+pub trait CredentialStore: Send + Sync {
+    /// Searches and returns the credentials belonging to a user
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - The identifier of the user
+    async fn get_credential(&self, username: &str) -> Result<Credential, DBError>;
+
+    /// Attempts to add a new set of credentials
+    ///
+    /// # Arguments
+    ///
+    /// * `cred` - The credentials to be added
+    async fn insert_credential(&self, cred: &Credential) -> Result<(), DBError>;
+
+    /// Overwrites an existing set of credentials
+    ///
+    /// # Arguments
+    ///
+    /// * `cred` - The credentials containing the new values
+    async fn update_credential(&self, cred: &Credential) -> Result<(), DBError>;
+
+    /// Attempts to remove the credentials belonging to a user
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - The identifier of the user
+    async fn remove_credential(&self, username: &str) -> Result<(), DBError>;
+}
+
+/// Operations regarding user-objects, their connections and allowances
+#[async_trait]
+pub trait UserStore: Send + Sync {
+    /// Searches and returns the user with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user
+    async fn get_user(&self, user_id: &str) -> Result<User, DBError>;
+
+    /// Attempts to add a new user
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The user to be added
+    async fn insert_user(&self, user: &User) -> Result<(), DBError>;
+
+    /// Attempts to remove the user with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user
+    async fn remove_user(&self, user_id: &str) -> Result<(), DBError>;
+
+    /// Adds another user to the connections of a user
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user to be updated
+    /// * `connection_id` - The identifier of the user to connect with
+    async fn add_connection(&self, user_id: &str, connection_id: &str) -> Result<(), DBError>;
+
+    /// Removes another user from the connections of a user
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user to be updated
+    /// * `connection_id` - The identifier of the user to disconnect from
+    async fn pull_connection(&self, user_id: &str, connection_id: &str) -> Result<(), DBError>;
+
+    /// Grants a user a new allowance
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user to be updated
+    /// * `allowance` - The allowance to be added
+    async fn add_allowance(&self, user_id: &str, allowance: &Allowance) -> Result<(), DBError>;
+
+    /// Revokes the allowances of a user regarding the given notes
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user to be updated
+    /// * `note_ids` - The identifiers of all notes whose allowances are to be revoked
+    async fn pull_allowances(&self, user_id: &str, note_ids: &[String]) -> Result<(), DBError>;
+
+    /// Revokes the allowances of every user regarding a note
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    async fn pull_note_allowances(&self, note_id: &str) -> Result<(), DBError>;
+
+    /// Changes the allowances of several users regarding a note at once.
+    /// Either all changes are applied or none of them, failing if one of the users does not exist.
+    /// Standalone mongodb-servers lack transactions, the previous state is restored on a best-effort basis there
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    /// * `changes` - The users and their new level of access, `Forbidden` revoking their allowance
+    async fn set_note_allowances(&self, note_id: &str, changes: &[(String, AllowanceLevel)]) -> Result<(), DBError>;
+
+    /// Replaces the roles of a user
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user to be updated
+    /// * `roles` - The new roles of the user
+    async fn set_roles(&self, user_id: &str, roles: &[String]) -> Result<(), DBError>;
+}
+
+/// Operations regarding note-objects
+#[async_trait]
+pub trait NoteStore: Send + Sync {
+    /// Searches and returns the note with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    async fn get_note(&self, note_id: &str) -> Result<Note, DBError>;
+
+    /// Searches and returns all notes with the given ids at once, paired with their identifiers.
+    /// Identifiers without a matching note are skipped, the order of the returned notes is unspecified
+    ///
+    /// # Arguments
+    ///
+    /// * `note_ids` - The identifiers of the notes
+    async fn get_notes(&self, note_ids: &[String]) -> Result<Vec<(String, Note)>, DBError>;
+
+    /// Like [`NoteStore::get_notes`], but without reading the content of the notes
+    ///
+    /// # Arguments
+    ///
+    /// * `note_ids` - The identifiers of the notes
+    async fn get_note_summaries(&self, note_ids: &[String]) -> Result<Vec<(String, NoteSummary)>, DBError>;
+
+    /// Attempts to add a new note, returning its newly assigned identifier
+    ///
+    /// # Arguments
+    ///
+    /// * `note` - The note to be added
+    async fn insert_note(&self, note: &Note) -> Result<String, DBError>;
+
+    /// Overwrites the modifiable fields of a note (title, content, tags, updated_at and last_editor_id),
+    /// given it still is at the expected version. The given revision is only appended to the history of the note
+    /// if the note has been updated (as part of the same transaction, unless the mongodb-server lacks them).
+    /// Returns the new version of the note or a VersionMismatchError carrying the current one
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    /// * `note` - The note containing the new values of all modifiable fields
+    /// * `version` - The version the modification is based on
+    /// * `revision` - The replaced state of the note to be kept, if any (its `rev` is ignored)
+    async fn set_note_fields(&self, note_id: &str, note: &Note, version: u64, revision: Option<&Revision>) -> Result<u64, DBError>;
+
+    /// Hands a note over to another user, leaving their allowances untouched
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    /// * `owner_id` - The identifier of the new owner
+    async fn set_note_owner(&self, note_id: &str, owner_id: &str) -> Result<(), DBError>;
+
+    /// Attempts to remove the note with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    async fn remove_note(&self, note_id: &str) -> Result<(), DBError>;
+}
+
+/// Operations regarding the revision-history of notes.
+/// Revisions are added along with the update of their note, see [`NoteStore::set_note_fields`]
+#[async_trait]
+pub trait RevisionStore: Send + Sync {
+    /// Returns all revisions of a note, ordered by their number
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    async fn get_revisions(&self, note_id: &str) -> Result<Vec<Revision>, DBError>;
+
+    /// Searches and returns a specific revision of a note
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    /// * `rev` - The number of the revision
+    async fn get_revision(&self, note_id: &str, rev: u32) -> Result<Revision, DBError>;
+
+    /// Removes the entire history of a note
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    async fn remove_revisions(&self, note_id: &str) -> Result<(), DBError>;
+}
+
+/// Operations regarding the login-sessions of users
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    /// Searches and returns the session with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - The identifier of the session
+    async fn get_session(&self, session_id: &str) -> Result<Session, DBError>;
+
+    /// Returns all sessions of a user, including expired ones
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user
+    async fn get_sessions(&self, user_id: &str) -> Result<Vec<Session>, DBError>;
+
+    /// Attempts to add a new session
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session to be added
+    async fn insert_session(&self, session: &Session) -> Result<(), DBError>;
+
+    /// Overwrites the refresh_hash, ip, last_seen and expires_at of a session,
+    /// given its refresh-hash still is the expected one.
+    /// Fails with a NoDocumentFoundError otherwise
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session containing the new values
+    /// * `refresh_hash` - The refresh-hash the modification is based on
+    async fn set_session_fields(&self, session: &Session, refresh_hash: &str) -> Result<(), DBError>;
+
+    /// Updates when a session was last used
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - The identifier of the session
+    /// * `last_seen` - The time of the last usage
+    async fn touch_session(&self, session_id: &str, last_seen: DateTime<Utc>) -> Result<(), DBError>;
+
+    /// Attempts to remove the session with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - The identifier of the session
+    async fn remove_session(&self, session_id: &str) -> Result<(), DBError>;
+
+    /// Removes all sessions of a user
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user
+    async fn remove_sessions(&self, user_id: &str) -> Result<(), DBError>;
+}
+
+/// Operations regarding the personal access-tokens of users
+#[async_trait]
+pub trait TokenStore: Send + Sync {
+    /// Searches and returns the token with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `token_id` - The identifier of the token
+    async fn get_token(&self, token_id: &str) -> Result<ApiToken, DBError>;
+
+    /// Returns all tokens of a user, including expired ones
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user
+    async fn get_tokens(&self, user_id: &str) -> Result<Vec<ApiToken>, DBError>;
+
+    /// Attempts to add a new token
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token to be added
+    async fn insert_token(&self, token: &ApiToken) -> Result<(), DBError>;
+
+    /// Updates when a token was last used
+    ///
+    /// # Arguments
+    ///
+    /// * `token_id` - The identifier of the token
+    /// * `last_used` - The time of the last usage
+    async fn touch_token(&self, token_id: &str, last_used: DateTime<Utc>) -> Result<(), DBError>;
+
+    /// Attempts to remove the token with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `token_id` - The identifier of the token
+    async fn remove_token(&self, token_id: &str) -> Result<(), DBError>;
+
+    /// Removes all tokens of a user
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user
+    async fn remove_tokens(&self, user_id: &str) -> Result<(), DBError>;
+}
+
+/// Operations regarding the password-reset tokens of users
+#[async_trait]
+pub trait ResetStore: Send + Sync {
+    /// Searches and returns the password-reset token with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `reset_id` - The identifier of the token
+    async fn get_reset(&self, reset_id: &str) -> Result<PasswordReset, DBError>;
+
+    /// Attempts to add a new password-reset token
+    ///
+    /// # Arguments
+    ///
+    /// * `reset` - The token to be added
+    async fn insert_reset(&self, reset: &PasswordReset) -> Result<(), DBError>;
+
+    /// Removes all password-reset tokens of a user
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user
+    async fn remove_resets(&self, user_id: &str) -> Result<(), DBError>;
+}
+
+/// Operations regarding the accounts of users at external identity-providers
+#[async_trait]
+pub trait IdentityStore: Send + Sync {
+    /// Searches and returns the linked account with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `identity_id` - The identifier of the account
+    async fn get_identity(&self, identity_id: &str) -> Result<Identity, DBError>;
+
+    /// Attempts to link a new account
+    ///
+    /// # Arguments
+    ///
+    /// * `identity` - The account to be linked
+    async fn insert_identity(&self, identity: &Identity) -> Result<(), DBError>;
+
+    /// Removes all linked accounts of a user
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user
+    async fn remove_identities(&self, user_id: &str) -> Result<(), DBError>;
+}
+
+/// Operations regarding the public links of notes
+#[async_trait]
+pub trait LinkStore: Send + Sync {
+    /// Searches and returns the link with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `link_id` - The identifier of the link
+    async fn get_link(&self, link_id: &str) -> Result<ShareLink, DBError>;
+
+    /// Returns all links of a note, including expired ones
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    async fn get_links(&self, note_id: &str) -> Result<Vec<ShareLink>, DBError>;
+
+    /// Attempts to add a new link
+    ///
+    /// # Arguments
+    ///
+    /// * `link` - The link to be added
+    async fn insert_link(&self, link: &ShareLink) -> Result<(), DBError>;
+
+    /// Counts a view of a link, given it has not reached its maximum amount of views yet.
+    /// Returns the updated link or a NoDocumentFoundError if the link is exhausted
+    ///
+    /// # Arguments
+    ///
+    /// * `link_id` - The identifier of the link
+    async fn count_view(&self, link_id: &str) -> Result<ShareLink, DBError>;
+
+    /// Attempts to remove the link with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `link_id` - The identifier of the link
+    async fn remove_link(&self, link_id: &str) -> Result<(), DBError>;
+
+    /// Removes all links of a note
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    async fn remove_links(&self, note_id: &str) -> Result<(), DBError>;
+}
+
+/// Operations regarding groups of users, their members and allowances
+#[async_trait]
+pub trait GroupStore: Send + Sync {
+    /// Searches and returns the group with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `group_id` - The identifier of the group
+    async fn get_group(&self, group_id: &str) -> Result<Group, DBError>;
+
+    /// Returns all groups a user is a member of, ordered by their creation
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user
+    async fn get_groups(&self, user_id: &str) -> Result<Vec<Group>, DBError>;
+
+    /// Attempts to add a new group
+    ///
+    /// # Arguments
+    ///
+    /// * `group` - The group to be added
+    async fn insert_group(&self, group: &Group) -> Result<(), DBError>;
+
+    /// Attempts to remove the group with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `group_id` - The identifier of the group
+    async fn remove_group(&self, group_id: &str) -> Result<(), DBError>;
+
+    /// Adds a user to the members of a group
+    ///
+    /// # Arguments
+    ///
+    /// * `group_id` - The identifier of the group to be updated
+    /// * `user_id` - The identifier of the new member
+    async fn add_member(&self, group_id: &str, user_id: &str) -> Result<(), DBError>;
+
+    /// Removes a user from the members of a group
+    ///
+    /// # Arguments
+    ///
+    /// * `group_id` - The identifier of the group to be updated
+    /// * `user_id` - The identifier of the member
+    async fn pull_member(&self, group_id: &str, user_id: &str) -> Result<(), DBError>;
+
+    /// Revokes the allowance of a group regarding a note
+    ///
+    /// # Arguments
+    ///
+    /// * `group_id` - The identifier of the group to be updated
+    /// * `note_id` - The identifier of the note
+    async fn pull_group_allowance(&self, group_id: &str, note_id: &str) -> Result<(), DBError>;
+
+    /// Changes the allowances of several groups regarding a note at once.
+    /// Either all changes are applied or none of them, failing if one of the groups does not exist.
+    /// Standalone mongodb-servers lack transactions, the previous state is restored on a best-effort basis there
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    /// * `changes` - The groups and their new level of access, `Forbidden` revoking their allowance
+    async fn set_group_note_allowances(&self, note_id: &str, changes: &[(String, AllowanceLevel)]) -> Result<(), DBError>;
+
+    /// Revokes the allowances of every group regarding a note
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    async fn pull_note_group_allowances(&self, note_id: &str) -> Result<(), DBError>;
+}
+
+/// Operations regarding folders, their contents and shares
+#[async_trait]
+pub trait FolderStore: Send + Sync {
+    /// Searches and returns the folder with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `folder_id` - The identifier of the folder
+    async fn get_folder(&self, folder_id: &str) -> Result<Folder, DBError>;
+
+    /// Returns all folders owned by a user, ordered by their creation
+    ///
+    /// # Arguments
+    ///
+    /// * `owner_id` - The identifier of the owner
+    async fn get_folders(&self, owner_id: &str) -> Result<Vec<Folder>, DBError>;
+
+    /// Returns all folders directly shared with a user, ordered by their creation
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user
+    async fn get_shared_folders(&self, user_id: &str) -> Result<Vec<Folder>, DBError>;
+
+    /// Attempts to add a new folder
+    ///
+    /// # Arguments
+    ///
+    /// * `folder` - The folder to be added
+    async fn insert_folder(&self, folder: &Folder) -> Result<(), DBError>;
+
+    /// Renames a folder and moves it into another one
+    ///
+    /// # Arguments
+    ///
+    /// * `folder_id` - The identifier of the folder to be updated
+    /// * `name` - The new name
+    /// * `parent_id` - The new parent (a top-level folder if not set)
+    async fn update_folder(&self, folder_id: &str, name: &str, parent_id: Option<&str>) -> Result<(), DBError>;
+
+    /// Attempts to remove the folder with the given id at once, handing its notes and folders over to the folder that contained it.
+    /// The contents of a top-level folder become top-level themselves
+    ///
+    /// # Arguments
+    ///
+    /// * `folder_id` - The identifier of the folder
+    async fn remove_folder(&self, folder_id: &str) -> Result<(), DBError>;
+
+    /// Places a note inside of a folder
+    ///
+    /// # Arguments
+    ///
+    /// * `folder_id` - The identifier of the folder to be updated
+    /// * `note_id` - The identifier of the note
+    async fn add_folder_note(&self, folder_id: &str, note_id: &str) -> Result<(), DBError>;
+
+    /// Takes a note out of whichever folder it is inside of
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    async fn pull_folder_note(&self, note_id: &str) -> Result<(), DBError>;
+
+    /// Changes the shares of a folder with several users at once, replacing any level they have been given before.
+    /// Either all changes are applied or none of them
+    ///
+    /// # Arguments
+    ///
+    /// * `folder_id` - The identifier of the folder to be updated
+    /// * `changes` - The users and their new level of access, `Forbidden` revoking their share
+    async fn set_folder_shares(&self, folder_id: &str, changes: &[(String, AllowanceLevel)]) -> Result<(), DBError>;
+
+    /// Revokes the share of a folder with a user
+    ///
+    /// # Arguments
+    ///
+    /// * `folder_id` - The identifier of the folder to be updated
+    /// * `user_id` - The identifier of the user
+    async fn pull_folder_share(&self, folder_id: &str, user_id: &str) -> Result<(), DBError>;
+}
+
+/// Operations regarding pending offers to hand a note over to another user
+#[async_trait]
+pub trait TransferStore: Send + Sync {
+    /// Searches and returns the pending offer regarding a note
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    async fn get_transfer(&self, note_id: &str) -> Result<Transfer, DBError>;
+
+    /// Returns all pending offers made by or to a user, ordered by their creation
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user
+    async fn get_transfers(&self, user_id: &str) -> Result<Vec<Transfer>, DBError>;
+
+    /// Records an offer, replacing any pending offer regarding the same note
+    ///
+    /// # Arguments
+    ///
+    /// * `transfer` - The offer to be recorded
+    async fn set_transfer(&self, transfer: &Transfer) -> Result<(), DBError>;
+
+    /// Removes the pending offer regarding a note, if there is one
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    async fn remove_transfer(&self, note_id: &str) -> Result<(), DBError>;
+}
+
+/// Operations regarding the schema of the stored objects (see [`migration`])
+#[async_trait]
+pub trait MigrationStore: Send + Sync {
+    /// Returns the version of the schema the database conforms to (0 if none has been recorded yet)
+    async fn get_schema_version(&self) -> Result<u32, DBError>;
+
+    /// Applies a single migration and records the version of the schema it leads to
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - The version of the schema the migration leads to
+    /// * `description` - A short description of the migration
+    async fn apply_migration(&self, version: u32, description: &str) -> Result<(), DBError>;
+}
+
+/// General information on a storage-backend
+pub struct DBInfo {
+    /// The kind of database in use
+    pub db_type: &'static str,
+    /// The version of the database in use
+    pub version: String
+}
+
+/// A storage-backend able to persist all objects writeUp requires
+pub trait Storage: CredentialStore + UserStore + NoteStore + RevisionStore + SessionStore + TokenStore + ResetStore + IdentityStore + LinkStore + GroupStore + FolderStore + TransferStore + MigrationStore {
+    /// Returns general information on the backend
+    fn get_info(&self) -> DBInfo;
+}
+
+/// Compiles a list of notes shared by a certain user.
+/// Returns either a vector of note_ids or a DBError if the list could not be compiled
+///
+/// # Arguments
+///
+/// * `allowed_user_id` - The identifier of the user to be searched
+/// * `allowing_user_id` - The identifier of the user sharing their notes
+/// * `db` - The storage-backend to be searched
+pub async fn filter_allowances_by_user_id(allowed_user_id: &str, allowing_user_id: &str, db: &dyn Storage) -> Result<Vec<String>, DBError> {
+    // Get the user that is to be searched
+    let allowed_user = db.get_user(allowed_user_id).await?;
+    let mut matched_allowances = Vec::new();
+    for allow in allowed_user.allowances {
+        // If a note is owned by the user, it can't fit the criteria
+        if allow.level == AllowanceLevel::Owner {
+            continue
+        }
+        // Else check the actual note for its owners id
+        if db.get_note(&allow.note_id).await?.owner_id.eq(allowing_user_id) {
+            matched_allowances.push(allow.note_id) //TODO? Add the entire allowance to generalize
+        }
+    }
+    Ok(matched_allowances)
+}
+
+/// Compiles the allowances a user has regarding all notes, combining their own with those of the groups they are a member of.
+/// Notes without an allowance of their own use the level inherited from the folders shared with the user.
+/// Every note is only listed once, using the highest level of access granted to either the user or a group
+///
+/// # Arguments
+///
+/// * `user` - The user whose allowances are to be compiled
+/// * `db` - The storage-backend containing the groups and folders
+pub async fn get_effective_allowances(user: &User, db: &dyn Storage) -> Result<Vec<Allowance>, DBError> {
+    let mut allowances = user.allowances.clone();
+    // Allowances given to the note itself take precedence over inherited ones
+    for inherited in get_inherited_allowances(&user._id, db).await? {
+        if !allowances.iter().any(|allowance| allowance.note_id.eq(&inherited.note_id)) {
+            allowances.push(inherited)
+        }
+    }
+    for group in db.get_groups(&user._id).await? {
+        for group_allowance in group.allowances {
+            match allowances.iter_mut().find(|allowance| allowance.note_id.eq(&group_allowance.note_id)) {
+                Some(allowance) => allowance.level = allowance.level.max(group_allowance.level),
+                None => allowances.push(group_allowance)
+            }
+        }
+    }
+    Ok(allowances)
+}
+
+/// Returns the level of access a user has regarding a folder, given all folders of its owner.
+/// Unless the user owns the folder, the share closest to it is used, be it on the folder itself or on one containing it
+///
+/// # Arguments
+///
+/// * `folder_id` - The identifier of the folder
+/// * `user_id` - The identifier of the user
+/// * `folders` - All folders of the owner, mapped by their identifier
+pub fn get_folder_level(folder_id: &str, user_id: &str, folders: &HashMap<String, Folder>) -> Option<AllowanceLevel> {
+    let mut current = folders.get(folder_id);
+    // A folder can't contain more folders than its owner has, guarding against cycles
+    for _ in 0..folders.len() {
+        let folder = current?;
+        if folder.owner_id.eq(user_id) {
+            return Some(AllowanceLevel::Owner)
+        }
+        if let Some(share) = folder.shares.iter().find(|share| share.user_id.eq(user_id)) {
+            return Some(share.level)
+        }
+        current = folder.parent_id.as_ref().and_then(|parent_id| folders.get(parent_id));
+    }
+    None
+}
+
+/// Returns the allowances a user inherits from the folders shared with them, for every note inside of them
+///
+/// # Arguments
+///
+/// * `user_id` - The identifier of the user
+/// * `db` - The storage-backend containing the folders
+pub async fn get_inherited_allowances(user_id: &str, db: &dyn Storage) -> Result<Vec<Allowance>, DBError> {
+    let mut owners: Vec<String> = db.get_shared_folders(user_id).await?.into_iter().map(|folder| folder.owner_id).collect();
+    owners.sort();
+    owners.dedup();
+    let mut allowances = Vec::new();
+    for owner_id in owners {
+        let folders: HashMap<String, Folder> = db.get_folders(&owner_id).await?.into_iter()
+            .map(|folder| (folder._id.clone(), folder)).collect();
+        for folder in folders.values() {
+            if let Some(level) = get_folder_level(&folder._id, user_id, &folders) {
+                allowances.extend(folder.notes.iter().map(|note_id| Allowance { note_id: note_id.clone(), level }));
+            }
+        }
+    }
+    Ok(allowances)
+}
@@ -0,0 +1,883 @@
+//! Storage-backend persisting all objects inside of a mongodb-server
+
+use std::str::FromStr;
+use async_trait::async_trait;
+use log::warn;
+use mongodb::{bson, Client, ClientSession, Collection, Database, IndexModel};
+use mongodb::bson::{doc, Document};
+use mongodb::bson::oid::ObjectId;
+use mongodb::error::{Error, ErrorKind, WriteFailure};
+use mongodb::options::{ClientOptions, FindOneAndUpdateOptions, FindOneOptions, FindOptions, IndexOptions, ReplaceOptions, ReturnDocument};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde::{Serialize, Deserialize};
+use crate::db_access::{Allowance, ApiToken, AllowanceLevel, Credential, CredentialStore, DatabaseObject, DBError, DBInfo, Folder, FolderShare, FolderStore, Group, GroupStore, Identity, IdentityStore, LinkStore, MigrationStore, Note, NoteStore, NoteSummary, PasswordReset, ResetStore, Revision, RevisionStore, Session, SessionStore, ShareLink, Storage, TokenStore, Transfer, TransferStore, User, UserStore};
+use crate::db_access::DBError::{NoDocumentFoundError, QueryError, ServerConnectionError, VersionMismatchError};
+use crate::secret::Secret;
+
+// Collection-Identifier
+/// Identifier of the collection containing all note-objects
+const NOTES: &str = "notes";
+/// Identifier of the collection containing all credential-objects
+const CREDENTIALS: &str = "creds";
+/// Identifier of the collection containing all user-objects
+const USER: &str = "user";
+/// Identifier of the collection containing all revision-objects
+const REVISIONS: &str = "revisions";
+/// Identifier of the collection containing all session-objects
+const SESSIONS: &str = "sessions";
+/// Identifier of the collection containing all personal access-tokens
+const TOKENS: &str = "tokens";
+/// Identifier of the collection containing all password-reset tokens
+const RESETS: &str = "resets";
+/// Identifier of the collection containing all accounts at identity-providers
+const IDENTITIES: &str = "identities";
+/// Identifier of the collection containing all public links of notes
+const LINKS: &str = "links";
+/// Identifier of the collection containing all groups of users
+const GROUPS: &str = "groups";
+/// Identifier of the collection containing all folders of users
+const FOLDERS: &str = "folders";
+/// Identifier of the collection containing all pending offers to hand a note over
+const TRANSFERS: &str = "transfers";
+/// Identifier of the collection containing a record of all applied migrations
+const MIGRATIONS: &str = "migrations";
+
+/// The code of the error a write violating a unique index fails with
+const DUPLICATE_KEY_ERROR_CODE: i32 = 11000;
+/// The amount of times a revision is numbered again after a concurrent update took its number
+const MAX_REVISION_ATTEMPTS: usize = 5;
+
+/// A storage-backend using a mongodb-database.
+/// Changes spanning several documents are only atomic on deployments supporting transactions (replica sets and sharded clusters)
+pub struct MongoStorage {
+    /// The connection to the db-server, needed to start sessions
+    client: Client,
+    /// The connection to the database
+    db: Database,
+    /// The version of the connected db-server
+    version: String
+}
+
+impl MongoStorage {
+    /// Attempts to create a connection to the db-server and returns it
+    ///
+    /// # Arguments
+    ///
+    /// * `uri` - A tuple containing both the url and the port of the db-server
+    /// * `cred` - A tuple containing both the username and password to login with
+    /// * `db_name` - The identifier of the database inside of the db-server
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crate::db_access::mongo::MongoStorage;
+    /// use crate::secret::Secret;
+    ///
+    /// let (url, port) = ("localhost".to_string(), "27017".to_string());
+    /// let (username, passwd) = ("testUser".to_string(), Secret::new("testPass"));
+    ///
+    /// let db = MongoStorage::connect((url, port), (username, passwd), "writeup").await.unwrap();
+    /// ```
+    pub async fn connect(uri: (String, String), cred: (String, Secret), db_name: &str) -> Result<MongoStorage, DBError> {
+        // Configure the connection
+        let mut client_options = ClientOptions::parse(format!("mongodb://{}:{}@{}:{}", cred.0, cred.1.expose(), uri.0, uri.1))
+            .await.map_err(|_| ServerConnectionError)?;
+        client_options.app_name = Some("writeUp".to_string());
+        // Attempt to connect
+        let client = Client::with_options(client_options).map_err(|_| ServerConnectionError)?;
+        let db = client.database(db_name);
+        // Test the connection and request the servers version
+        let build_info = db.run_command(doc! {"buildInfo": 1}, None).await.map_err(|_| ServerConnectionError)?;
+        let version = build_info.get_str("version").unwrap_or("unknown").to_string();
+        Ok(MongoStorage { client, db, version })
+    }
+
+    /// Returns the typed collection with the given identifier
+    ///
+    /// # Arguments
+    ///
+    /// * `collection` - A string slice containing the collection-identifier
+    fn coll<T: DatabaseObject>(&self, collection: &str) -> Collection<T> {
+        self.db.collection::<T>(collection)
+    }
+
+    /// Creates a filter matching the note with the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    fn note_filter(note_id: &str) -> Result<Document, DBError> {
+        ObjectId::from_str(note_id).map(|id| doc! {"_id": id}).map_err(|_| NoDocumentFoundError)
+    }
+
+    /// Searches and returns the DatabaseObject matching the filter
+    ///
+    /// # Arguments
+    ///
+    /// * `collection` - A string slice containing the collection-identifier
+    /// * `filter` - A document describing the object to look for
+    async fn find_one<T: DatabaseObject>(&self, collection: &str, filter: Document) -> Result<T, DBError> {
+        match self.coll::<T>(collection).find_one(filter, None).await {
+            Ok(Some(doc)) => Ok(doc),
+            Ok(None) => Err(NoDocumentFoundError),
+            Err(_) => Err(QueryError)
+        }
+    }
+
+    /// Attempts to update a specific user-document
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the user
+    /// * `query` - A document describing the update-operation
+    async fn update_user(&self, user_id: &str, query: Document) -> Result<(), DBError> {
+        self.coll::<User>(USER).update_one(doc! {"_id": user_id}, query, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    /// Replaces the allowance of a user or group regarding a note
+    ///
+    /// # Arguments
+    ///
+    /// * `collection` - A string slice containing the collection-identifier
+    /// * `id` - The identifier of the user or group
+    /// * `note_id` - The identifier of the note
+    /// * `allowance` - The new allowance (revoking the current one if not set)
+    /// * `session` - The session holding the transaction to be part of, if any
+    async fn replace_allowance<T: DatabaseObject>(&self, collection: &str, id: &str, note_id: &str, allowance: Option<&Allowance>,
+                                                  mut session: Option<&mut ClientSession>) -> Result<(), DBError> {
+        let mut updates = vec![doc! {"$pull": {"allowances": {"note_id": note_id}}}];
+        if let Some(allowance) = allowance {
+            updates.push(doc! {"$push": {"allowances": bson::to_bson(allowance).map_err(|_| QueryError)?}});
+        }
+        for update in updates {
+            let result = match session.as_deref_mut() {
+                Some(session) => self.coll::<T>(collection).update_one_with_session(doc! {"_id": id}, update, None, session).await,
+                None => self.coll::<T>(collection).update_one(doc! {"_id": id}, update, None).await
+            };
+            match result {
+                Ok(result) if result.matched_count == 0 => return Err(NoDocumentFoundError),
+                Ok(_) => {}
+                Err(_) => return Err(QueryError)
+            }
+        }
+        Ok(())
+    }
+
+    /// Changes the allowances of several users or groups regarding a note, inside of a transaction if the server supports them
+    ///
+    /// # Arguments
+    ///
+    /// * `collection` - A string slice containing the collection-identifier
+    /// * `note_id` - The identifier of the note
+    /// * `changes` - The users or groups and their new level of access, `Forbidden` revoking their allowance
+    /// * `allowances_of` - Returns the allowances of a stored user or group
+    async fn replace_allowances<T: DatabaseObject>(&self, collection: &str, note_id: &str, changes: &[(String, AllowanceLevel)],
+                                                   allowances_of: fn(T) -> Vec<Allowance>) -> Result<(), DBError> {
+        let allowances: Vec<(&String, Option<Allowance>)> = changes.iter()
+            .map(|(id, level)| (id, Some(Allowance { note_id: note_id.to_string(), level: *level }).filter(|_| *level != AllowanceLevel::Forbidden)))
+            .collect();
+        let mut session = self.client.start_session(None).await.map_err(|_| QueryError)?;
+        if session.start_transaction(None).await.is_ok() {
+            for (id, allowance) in &allowances {
+                if let Err(e) = self.replace_allowance::<T>(collection, id, note_id, allowance.as_ref(), Some(&mut session)).await {
+                    let _ = session.abort_transaction().await; // Aborted by the server on its own otherwise
+                    return Err(e)
+                }
+            }
+            return session.commit_transaction().await.map_err(|_| QueryError)
+        }
+
+        // Without transactions, remember the previous allowances, so that they can be restored if a change fails
+        let mut previous = Vec::new();
+        for (id, _) in changes {
+            let object = self.find_one::<T>(collection, doc! {"_id": id}).await?;
+            previous.push((id, allowances_of(object).into_iter().find(|allow| allow.note_id.eq(note_id))));
+        }
+        for (i, (id, allowance)) in allowances.iter().enumerate() {
+            if self.replace_allowance::<T>(collection, id, note_id, allowance.as_ref(), None).await.is_err() {
+                // Undo everything changed so far
+                for (id, allowance) in previous.iter().take(i + 1) {
+                    if self.replace_allowance::<T>(collection, id, note_id, allowance.as_ref(), None).await.is_err() {
+                        warn!("Allowance of {} regarding note {} could not be restored", id, note_id);
+                    }
+                }
+                return Err(QueryError)
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends a revision to the history of its note, returning the number assigned to it
+    ///
+    /// # Arguments
+    ///
+    /// * `revision` - The revision to be added (its `rev` is ignored)
+    /// * `session` - The session holding the transaction to be part of, if any
+    async fn insert_revision_with(&self, revision: &Revision, session: Option<&mut ClientSession>) -> Result<u32, DBError> {
+        let coll = self.coll::<Revision>(REVISIONS);
+        let filter = doc! {"note_id": &revision.note_id};
+        let options = FindOneOptions::builder().sort(doc! {"rev": -1}).build();
+        // Look up the latest revision to continue counting from
+        match session {
+            Some(session) => {
+                let latest = coll.find_one_with_session(filter, options, session).await.map_err(|_| QueryError)?;
+                let rev = latest.map_or(1, |latest| latest.rev + 1);
+                coll.insert_one_with_session(Revision { rev, ..revision.clone() }, None, session).await.map(|_| rev).map_err(|_| QueryError)
+            }
+            None => {
+                // Without a transaction, a concurrent update may take the same number, which the unique index rejects
+                for _ in 0..MAX_REVISION_ATTEMPTS {
+                    let latest = coll.find_one(filter.clone(), options.clone()).await.map_err(|_| QueryError)?;
+                    let rev = latest.map_or(1, |latest| latest.rev + 1);
+                    match coll.insert_one(Revision { rev, ..revision.clone() }, None).await {
+                        Ok(_) => return Ok(rev),
+                        Err(e) if is_duplicate_key(&e) => continue,
+                        Err(_) => return Err(QueryError)
+                    }
+                }
+                Err(QueryError)
+            }
+        }
+    }
+
+    /// Attempts to update a specific group-document
+    ///
+    /// # Arguments
+    ///
+    /// * `group_id` - The identifier of the group
+    /// * `query` - A document describing the update-operation
+    async fn update_group(&self, group_id: &str, query: Document) -> Result<(), DBError> {
+        self.coll::<Group>(GROUPS).update_one(doc! {"_id": group_id}, query, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    /// Returns all folders matching a filter, ordered by their creation
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - A document describing the filter
+    async fn find_folders(&self, filter: Document) -> Result<Vec<Folder>, DBError> {
+        match self.coll::<Folder>(FOLDERS).find(filter, FindOptions::builder().sort(doc! {"created_at": 1}).build()).await {
+            Ok(cursor) => cursor.try_collect().await.map_err(|_| QueryError),
+            Err(_) => Err(QueryError)
+        }
+    }
+}
+
+#[async_trait]
+impl CredentialStore for MongoStorage {
+    async fn get_credential(&self, username: &str) -> Result<Credential, DBError> {
+        self.find_one::<Credential>(CREDENTIALS, doc! {"_id": username}).await
+    }
+
+    async fn insert_credential(&self, cred: &Credential) -> Result<(), DBError> {
+        self.coll::<Credential>(CREDENTIALS).insert_one(cred, None).await.map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn update_credential(&self, cred: &Credential) -> Result<(), DBError> {
+        self.coll::<Credential>(CREDENTIALS).replace_one(doc! {"_id": &cred._id}, cred, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_credential(&self, username: &str) -> Result<(), DBError> {
+        self.coll::<Credential>(CREDENTIALS).delete_one(doc! {"_id": username}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl UserStore for MongoStorage {
+    async fn get_user(&self, user_id: &str) -> Result<User, DBError> {
+        self.find_one::<User>(USER, doc! {"_id": user_id}).await
+    }
+
+    async fn insert_user(&self, user: &User) -> Result<(), DBError> {
+        self.coll::<User>(USER).insert_one(user, None).await.map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_user(&self, user_id: &str) -> Result<(), DBError> {
+        self.coll::<User>(USER).delete_one(doc! {"_id": user_id}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn add_connection(&self, user_id: &str, connection_id: &str) -> Result<(), DBError> {
+        self.update_user(user_id, doc! {"$push": {"connections": connection_id}}).await
+    }
+
+    async fn pull_connection(&self, user_id: &str, connection_id: &str) -> Result<(), DBError> {
+        self.update_user(user_id, doc! {"$pull": {"connections": connection_id}}).await
+    }
+
+    async fn add_allowance(&self, user_id: &str, allowance: &Allowance) -> Result<(), DBError> {
+        let allowance = bson::to_bson(allowance).map_err(|_| QueryError)?;
+        self.update_user(user_id, doc! {"$push": {"allowances": allowance}}).await
+    }
+
+    async fn pull_allowances(&self, user_id: &str, note_ids: &[String]) -> Result<(), DBError> {
+        self.update_user(user_id, doc! {"$pull": {"allowances": {"note_id": {"$in": note_ids}}}}).await
+    }
+
+    async fn pull_note_allowances(&self, note_id: &str) -> Result<(), DBError> {
+        self.coll::<User>(USER).update_many(doc! {"allowances.note_id": note_id},
+                                            doc! {"$pull": {"allowances": {"note_id": note_id}}}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn set_note_allowances(&self, note_id: &str, changes: &[(String, AllowanceLevel)]) -> Result<(), DBError> {
+        self.replace_allowances::<User>(USER, note_id, changes, |user| user.allowances).await
+    }
+
+    async fn set_roles(&self, user_id: &str, roles: &[String]) -> Result<(), DBError> {
+        self.update_user(user_id, doc! {"$set": {"roles": roles}}).await
+    }
+}
+
+#[async_trait]
+impl NoteStore for MongoStorage {
+    async fn get_note(&self, note_id: &str) -> Result<Note, DBError> {
+        self.find_one::<Note>(NOTES, MongoStorage::note_filter(note_id)?).await
+    }
+
+    async fn get_notes(&self, note_ids: &[String]) -> Result<Vec<(String, Note)>, DBError> {
+        /// A note-document together with its identifier
+        #[derive(Serialize, Deserialize)]
+        struct NoteDocument {
+            _id: ObjectId,
+            #[serde(flatten)]
+            note: Note
+        }
+        impl DatabaseObject for NoteDocument {}
+
+        // Malformed identifiers can not belong to any note
+        let ids: Vec<ObjectId> = note_ids.iter().filter_map(|id| ObjectId::from_str(id).ok()).collect();
+        match self.coll::<NoteDocument>(NOTES).find(doc! {"_id": {"$in": ids}}, None).await {
+            Ok(cursor) => cursor.map_ok(|doc| (doc._id.to_string(), doc.note)).try_collect().await.map_err(|_| QueryError),
+            Err(_) => Err(QueryError)
+        }
+    }
+
+    async fn get_note_summaries(&self, note_ids: &[String]) -> Result<Vec<(String, NoteSummary)>, DBError> {
+        /// A note-document without its content together with its identifier
+        #[derive(Serialize, Deserialize)]
+        struct SummaryDocument {
+            _id: ObjectId,
+            #[serde(flatten)]
+            note: NoteSummary
+        }
+        impl DatabaseObject for SummaryDocument {}
+
+        // Malformed identifiers can not belong to any note
+        let ids: Vec<ObjectId> = note_ids.iter().filter_map(|id| ObjectId::from_str(id).ok()).collect();
+        let options = FindOptions::builder().projection(doc! {"content": 0}).build();
+        match self.coll::<SummaryDocument>(NOTES).find(doc! {"_id": {"$in": ids}}, options).await {
+            Ok(cursor) => cursor.map_ok(|doc| (doc._id.to_string(), doc.note)).try_collect().await.map_err(|_| QueryError),
+            Err(_) => Err(QueryError)
+        }
+    }
+
+    async fn insert_note(&self, note: &Note) -> Result<String, DBError> {
+        match self.coll::<Note>(NOTES).insert_one(note, None).await {
+            Ok(res) => res.inserted_id.as_object_id().map(|id| id.to_string()).ok_or(QueryError),
+            Err(_) => Err(QueryError)
+        }
+    }
+
+    async fn set_note_fields(&self, note_id: &str, note: &Note, version: u64, revision: Option<&Revision>) -> Result<u64, DBError> {
+        let mut filter = MongoStorage::note_filter(note_id)?;
+        filter.insert("version", version as i64);
+        let update = doc! {
+            "$set": {
+                "title": &note.title,
+                "content": &note.content,
+                "tags": &note.tags,
+                "updated_at": bson::to_bson(&note.updated_at).map_err(|_| QueryError)?,
+                "last_editor_id": &note.last_editor_id
+            },
+            "$inc": {"version": 1_i64}
+        };
+        // Keep the revision within the same transaction if possible
+        let mut session = match revision {
+            Some(_) => Some(self.client.start_session(None).await.map_err(|_| QueryError)?),
+            None => None
+        };
+        if let Some(started) = session.as_mut() {
+            if started.start_transaction(None).await.is_err() {
+                session = None;
+            }
+        }
+        let coll = self.coll::<Note>(NOTES);
+        let res = match session.as_mut() {
+            Some(session) => coll.update_one_with_session(filter, update, None, session).await,
+            None => coll.update_one(filter, update, None).await
+        }.map_err(|_| QueryError)?;
+        if res.matched_count == 0 {
+            // Either the note is gone or it was modified in the meantime (dropping the session aborts the transaction)
+            return Err(VersionMismatchError(self.get_note(note_id).await?.version))
+        }
+        match (revision, session.as_mut()) {
+            (Some(revision), Some(session)) => {
+                self.insert_revision_with(revision, Some(&mut *session)).await?;
+                session.commit_transaction().await.map_err(|_| QueryError)?;
+            }
+            // Without transactions, the note has been updated already
+            (Some(revision), None) => if self.insert_revision_with(revision, None).await.is_err() {
+                warn!("Revision of note {} could not be kept", note_id);
+            },
+            (None, _) => {}
+        }
+        Ok(version + 1)
+    }
+
+    async fn set_note_owner(&self, note_id: &str, owner_id: &str) -> Result<(), DBError> {
+        match self.coll::<Note>(NOTES).update_one(MongoStorage::note_filter(note_id)?, doc! {"$set": {"owner_id": owner_id}}, None).await {
+            Ok(res) if res.matched_count == 0 => Err(NoDocumentFoundError),
+            Ok(_) => Ok(()),
+            Err(_) => Err(QueryError)
+        }
+    }
+
+    async fn remove_note(&self, note_id: &str) -> Result<(), DBError> {
+        self.coll::<Note>(NOTES).delete_one(MongoStorage::note_filter(note_id)?, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl RevisionStore for MongoStorage {
+    async fn get_revisions(&self, note_id: &str) -> Result<Vec<Revision>, DBError> {
+        match self.coll::<Revision>(REVISIONS).find(doc! {"note_id": note_id},
+                                                    FindOptions::builder().sort(doc! {"rev": 1}).build()).await {
+            Ok(cursor) => cursor.try_collect().await.map_err(|_| QueryError),
+            Err(_) => Err(QueryError)
+        }
+    }
+
+    async fn get_revision(&self, note_id: &str, rev: u32) -> Result<Revision, DBError> {
+        self.find_one::<Revision>(REVISIONS, doc! {"note_id": note_id, "rev": rev}).await
+    }
+
+    async fn remove_revisions(&self, note_id: &str) -> Result<(), DBError> {
+        self.coll::<Revision>(REVISIONS).delete_many(doc! {"note_id": note_id}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl SessionStore for MongoStorage {
+    async fn get_session(&self, session_id: &str) -> Result<Session, DBError> {
+        self.find_one::<Session>(SESSIONS, doc! {"_id": session_id}).await
+    }
+
+    async fn get_sessions(&self, user_id: &str) -> Result<Vec<Session>, DBError> {
+        match self.coll::<Session>(SESSIONS).find(doc! {"user_id": user_id},
+                                                  FindOptions::builder().sort(doc! {"created_at": 1}).build()).await {
+            Ok(cursor) => cursor.try_collect().await.map_err(|_| QueryError),
+            Err(_) => Err(QueryError)
+        }
+    }
+
+    async fn insert_session(&self, session: &Session) -> Result<(), DBError> {
+        self.coll::<Session>(SESSIONS).insert_one(session, None).await.map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn set_session_fields(&self, session: &Session, refresh_hash: &str) -> Result<(), DBError> {
+        let res = self.coll::<Session>(SESSIONS).update_one(doc! {"_id": &session._id, "refresh_hash": refresh_hash}, doc! {
+            "$set": {
+                "refresh_hash": &session.refresh_hash,
+                "ip": &session.ip,
+                "last_seen": bson::to_bson(&session.last_seen).map_err(|_| QueryError)?,
+                "expires_at": bson::to_bson(&session.expires_at).map_err(|_| QueryError)?
+            }
+        }, None).await.map_err(|_| QueryError)?;
+        if res.matched_count == 0 {
+            // Either the session is gone or it was refreshed in the meantime
+            return Err(NoDocumentFoundError)
+        }
+        Ok(())
+    }
+
+    async fn touch_session(&self, session_id: &str, last_seen: DateTime<Utc>) -> Result<(), DBError> {
+        self.coll::<Session>(SESSIONS).update_one(doc! {"_id": session_id},
+                                                  doc! {"$set": {"last_seen": bson::to_bson(&last_seen).map_err(|_| QueryError)?}}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_session(&self, session_id: &str) -> Result<(), DBError> {
+        self.coll::<Session>(SESSIONS).delete_one(doc! {"_id": session_id}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_sessions(&self, user_id: &str) -> Result<(), DBError> {
+        self.coll::<Session>(SESSIONS).delete_many(doc! {"user_id": user_id}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl TokenStore for MongoStorage {
+    async fn get_token(&self, token_id: &str) -> Result<ApiToken, DBError> {
+        self.find_one::<ApiToken>(TOKENS, doc! {"_id": token_id}).await
+    }
+
+    async fn get_tokens(&self, user_id: &str) -> Result<Vec<ApiToken>, DBError> {
+        match self.coll::<ApiToken>(TOKENS).find(doc! {"user_id": user_id},
+                                                 FindOptions::builder().sort(doc! {"created_at": 1}).build()).await {
+            Ok(cursor) => cursor.try_collect().await.map_err(|_| QueryError),
+            Err(_) => Err(QueryError)
+        }
+    }
+
+    async fn insert_token(&self, token: &ApiToken) -> Result<(), DBError> {
+        self.coll::<ApiToken>(TOKENS).insert_one(token, None).await.map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn touch_token(&self, token_id: &str, last_used: DateTime<Utc>) -> Result<(), DBError> {
+        self.coll::<ApiToken>(TOKENS).update_one(doc! {"_id": token_id},
+                                                 doc! {"$set": {"last_used": bson::to_bson(&last_used).map_err(|_| QueryError)?}}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_token(&self, token_id: &str) -> Result<(), DBError> {
+        self.coll::<ApiToken>(TOKENS).delete_one(doc! {"_id": token_id}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_tokens(&self, user_id: &str) -> Result<(), DBError> {
+        self.coll::<ApiToken>(TOKENS).delete_many(doc! {"user_id": user_id}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl ResetStore for MongoStorage {
+    async fn get_reset(&self, reset_id: &str) -> Result<PasswordReset, DBError> {
+        self.find_one::<PasswordReset>(RESETS, doc! {"_id": reset_id}).await
+    }
+
+    async fn insert_reset(&self, reset: &PasswordReset) -> Result<(), DBError> {
+        self.coll::<PasswordReset>(RESETS).insert_one(reset, None).await.map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_resets(&self, user_id: &str) -> Result<(), DBError> {
+        self.coll::<PasswordReset>(RESETS).delete_many(doc! {"user_id": user_id}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl IdentityStore for MongoStorage {
+    async fn get_identity(&self, identity_id: &str) -> Result<Identity, DBError> {
+        self.find_one::<Identity>(IDENTITIES, doc! {"_id": identity_id}).await
+    }
+
+    async fn insert_identity(&self, identity: &Identity) -> Result<(), DBError> {
+        self.coll::<Identity>(IDENTITIES).insert_one(identity, None).await.map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_identities(&self, user_id: &str) -> Result<(), DBError> {
+        self.coll::<Identity>(IDENTITIES).delete_many(doc! {"user_id": user_id}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl LinkStore for MongoStorage {
+    async fn get_link(&self, link_id: &str) -> Result<ShareLink, DBError> {
+        self.find_one::<ShareLink>(LINKS, doc! {"_id": link_id}).await
+    }
+
+    async fn get_links(&self, note_id: &str) -> Result<Vec<ShareLink>, DBError> {
+        match self.coll::<ShareLink>(LINKS).find(doc! {"note_id": note_id},
+                                                 FindOptions::builder().sort(doc! {"created_at": 1}).build()).await {
+            Ok(cursor) => cursor.try_collect().await.map_err(|_| QueryError),
+            Err(_) => Err(QueryError)
+        }
+    }
+
+    async fn insert_link(&self, link: &ShareLink) -> Result<(), DBError> {
+        self.coll::<ShareLink>(LINKS).insert_one(link, None).await.map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn count_view(&self, link_id: &str) -> Result<ShareLink, DBError> {
+        // Checking and counting within a single operation, so that concurrent views can't exceed the maximum
+        let filter = doc! {"_id": link_id, "$or": [{"max_views": null}, {"$expr": {"$lt": ["$views", "$max_views"]}}]};
+        match self.coll::<ShareLink>(LINKS).find_one_and_update(filter, doc! {"$inc": {"views": 1}},
+                                                                FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build()).await {
+            Ok(Some(link)) => Ok(link),
+            Ok(None) => Err(NoDocumentFoundError),
+            Err(_) => Err(QueryError)
+        }
+    }
+
+    async fn remove_link(&self, link_id: &str) -> Result<(), DBError> {
+        self.coll::<ShareLink>(LINKS).delete_one(doc! {"_id": link_id}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_links(&self, note_id: &str) -> Result<(), DBError> {
+        self.coll::<ShareLink>(LINKS).delete_many(doc! {"note_id": note_id}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl GroupStore for MongoStorage {
+    async fn get_group(&self, group_id: &str) -> Result<Group, DBError> {
+        self.find_one::<Group>(GROUPS, doc! {"_id": group_id}).await
+    }
+
+    async fn get_groups(&self, user_id: &str) -> Result<Vec<Group>, DBError> {
+        match self.coll::<Group>(GROUPS).find(doc! {"members": user_id},
+                                              FindOptions::builder().sort(doc! {"created_at": 1}).build()).await {
+            Ok(cursor) => cursor.try_collect().await.map_err(|_| QueryError),
+            Err(_) => Err(QueryError)
+        }
+    }
+
+    async fn insert_group(&self, group: &Group) -> Result<(), DBError> {
+        self.coll::<Group>(GROUPS).insert_one(group, None).await.map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_group(&self, group_id: &str) -> Result<(), DBError> {
+        self.coll::<Group>(GROUPS).delete_one(doc! {"_id": group_id}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn add_member(&self, group_id: &str, user_id: &str) -> Result<(), DBError> {
+        self.update_group(group_id, doc! {"$push": {"members": user_id}}).await
+    }
+
+    async fn pull_member(&self, group_id: &str, user_id: &str) -> Result<(), DBError> {
+        self.update_group(group_id, doc! {"$pull": {"members": user_id}}).await
+    }
+
+    async fn pull_group_allowance(&self, group_id: &str, note_id: &str) -> Result<(), DBError> {
+        self.update_group(group_id, doc! {"$pull": {"allowances": {"note_id": note_id}}}).await
+    }
+
+    async fn set_group_note_allowances(&self, note_id: &str, changes: &[(String, AllowanceLevel)]) -> Result<(), DBError> {
+        self.replace_allowances::<Group>(GROUPS, note_id, changes, |group| group.allowances).await
+    }
+
+    async fn pull_note_group_allowances(&self, note_id: &str) -> Result<(), DBError> {
+        self.coll::<Group>(GROUPS).update_many(doc! {"allowances.note_id": note_id},
+                                               doc! {"$pull": {"allowances": {"note_id": note_id}}}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl FolderStore for MongoStorage {
+    async fn get_folder(&self, folder_id: &str) -> Result<Folder, DBError> {
+        self.find_one::<Folder>(FOLDERS, doc! {"_id": folder_id}).await
+    }
+
+    async fn get_folders(&self, owner_id: &str) -> Result<Vec<Folder>, DBError> {
+        self.find_folders(doc! {"owner_id": owner_id}).await
+    }
+
+    async fn get_shared_folders(&self, user_id: &str) -> Result<Vec<Folder>, DBError> {
+        self.find_folders(doc! {"shares.user_id": user_id}).await
+    }
+
+    async fn insert_folder(&self, folder: &Folder) -> Result<(), DBError> {
+        self.coll::<Folder>(FOLDERS).insert_one(folder, None).await.map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn update_folder(&self, folder_id: &str, name: &str, parent_id: Option<&str>) -> Result<(), DBError> {
+        self.coll::<Folder>(FOLDERS).update_one(doc! {"_id": folder_id},
+                                                doc! {"$set": {"name": name, "parent_id": parent_id}}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_folder(&self, folder_id: &str) -> Result<(), DBError> {
+        let folder = self.get_folder(folder_id).await?;
+        let mut updates = vec![(doc! {"parent_id": folder_id}, doc! {"$set": {"parent_id": &folder.parent_id}})];
+        if let Some(parent_id) = &folder.parent_id {
+            updates.push((doc! {"_id": parent_id}, doc! {"$push": {"notes": {"$each": &folder.notes}}}));
+        }
+        let coll = self.coll::<Folder>(FOLDERS);
+        let mut session = self.client.start_session(None).await.map_err(|_| QueryError)?;
+        if session.start_transaction(None).await.is_ok() {
+            // Dropping the session aborts the transaction if anything fails
+            for (filter, update) in updates {
+                coll.update_many_with_session(filter, update, None, &mut session).await.map_err(|_| QueryError)?;
+            }
+            coll.delete_one_with_session(doc! {"_id": folder_id}, None, &mut session).await.map_err(|_| QueryError)?;
+            return session.commit_transaction().await.map_err(|_| QueryError)
+        }
+
+        // Without transactions, the contents are handed over first, so that none of them get lost
+        for (filter, update) in updates {
+            coll.update_many(filter, update, None).await.map_err(|_| QueryError)?;
+        }
+        coll.delete_one(doc! {"_id": folder_id}, None).await.map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn add_folder_note(&self, folder_id: &str, note_id: &str) -> Result<(), DBError> {
+        self.coll::<Folder>(FOLDERS).update_one(doc! {"_id": folder_id}, doc! {"$push": {"notes": note_id}}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn pull_folder_note(&self, note_id: &str) -> Result<(), DBError> {
+        self.coll::<Folder>(FOLDERS).update_many(doc! {"notes": note_id}, doc! {"$pull": {"notes": note_id}}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn set_folder_shares(&self, folder_id: &str, changes: &[(String, AllowanceLevel)]) -> Result<(), DBError> {
+        if changes.is_empty() {
+            return Ok(())
+        }
+        // All shares are part of the folder, so a single update applies every change at once, without leaving
+        // two entries for the same user: earlier shares get their new level in place, the others are appended
+        let forbidden = bson::to_bson(&AllowanceLevel::Forbidden).map_err(|_| QueryError)?;
+        let mut branches = Vec::new();
+        let mut added = Vec::new();
+        for (user_id, level) in changes {
+            let share = bson::to_bson(&FolderShare { user_id: user_id.to_string(), level: *level }).map_err(|_| QueryError)?;
+            branches.push(doc! {"case": {"$eq": ["$$share.user_id", {"$literal": user_id}]}, "then": {"$literal": share.clone()}});
+            if *level != AllowanceLevel::Forbidden {
+                added.push(doc! {"$cond": [{"$in": [{"$literal": user_id}, "$shares.user_id"]}, [], [{"$literal": share}]]});
+            }
+        }
+        let replaced = doc! {"$map": {"input": "$shares", "as": "share", "in": {"$switch": {"branches": branches, "default": "$$share"}}}};
+        let kept = doc! {"$filter": {"input": replaced, "cond": {"$ne": ["$$this.level", forbidden]}}};
+        let mut shares = vec![bson::Bson::Document(kept)];
+        shares.extend(added.into_iter().map(bson::Bson::Document));
+        self.coll::<Folder>(FOLDERS).update_one(doc! {"_id": folder_id}, vec![doc! {"$set": {"shares": {"$concatArrays": shares}}}], None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn pull_folder_share(&self, folder_id: &str, user_id: &str) -> Result<(), DBError> {
+        self.coll::<Folder>(FOLDERS).update_one(doc! {"_id": folder_id}, doc! {"$pull": {"shares": {"user_id": user_id}}}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl TransferStore for MongoStorage {
+    async fn get_transfer(&self, note_id: &str) -> Result<Transfer, DBError> {
+        self.find_one::<Transfer>(TRANSFERS, doc! {"_id": note_id}).await
+    }
+
+    async fn get_transfers(&self, user_id: &str) -> Result<Vec<Transfer>, DBError> {
+        match self.coll::<Transfer>(TRANSFERS).find(doc! {"$or": [{"from_id": user_id}, {"to_id": user_id}]},
+                                                    FindOptions::builder().sort(doc! {"created_at": 1}).build()).await {
+            Ok(cursor) => cursor.try_collect().await.map_err(|_| QueryError),
+            Err(_) => Err(QueryError)
+        }
+    }
+
+    async fn set_transfer(&self, transfer: &Transfer) -> Result<(), DBError> {
+        self.coll::<Transfer>(TRANSFERS).replace_one(doc! {"_id": &transfer._id}, transfer,
+                                                     ReplaceOptions::builder().upsert(true).build()).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_transfer(&self, note_id: &str) -> Result<(), DBError> {
+        self.coll::<Transfer>(TRANSFERS).delete_one(doc! {"_id": note_id}, None).await
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl MigrationStore for MongoStorage {
+    async fn get_schema_version(&self) -> Result<u32, DBError> {
+        let latest = self.db.collection::<Document>(MIGRATIONS)
+            .find_one(None, FindOneOptions::builder().sort(doc! {"_id": -1}).build()).await.map_err(|_| QueryError)?;
+        match latest {
+            Some(migration) => migration.get_i64("_id").map(|version| version as u32).map_err(|_| QueryError),
+            None => Ok(0)
+        }
+    }
+
+    async fn apply_migration(&self, version: u32, description: &str) -> Result<(), DBError> {
+        match version {
+            1 => {
+                // Notes created before versioning was introduced count as version 0
+                self.db.collection::<Document>(NOTES).update_many(doc! {"version": {"$exists": false}},
+                                                                  doc! {"$set": {"version": 0_i64}}, None).await
+                    .map_err(|_| QueryError)?;
+            }
+            2 => {
+                // The creation is taken from the identifier of a note, the last modification from its revision-history where possible
+                let notes = self.db.collection::<Document>(NOTES);
+                let outdated: Vec<Document> = notes.find(doc! {"created_at": {"$exists": false}}, None).await
+                    .map_err(|_| QueryError)?.try_collect().await.map_err(|_| QueryError)?;
+                for note in outdated {
+                    let id = note.get_object_id("_id").map_err(|_| QueryError)?;
+                    let created_at: DateTime<Utc> = id.timestamp().to_system_time().into();
+                    let latest = self.coll::<Revision>(REVISIONS).find_one(doc! {"note_id": id.to_hex()},
+                                                                           FindOneOptions::builder().sort(doc! {"rev": -1}).build()).await
+                        .map_err(|_| QueryError)?;
+                    let (updated_at, last_editor_id) = match latest {
+                        Some(revision) => (revision.created_at, revision.author_id),
+                        None => (created_at, note.get_str("owner_id").unwrap_or_default().to_string())
+                    };
+                    notes.update_one(doc! {"_id": id}, doc! {"$set": {
+                        "created_at": bson::to_bson(&created_at).map_err(|_| QueryError)?,
+                        "updated_at": bson::to_bson(&updated_at).map_err(|_| QueryError)?,
+                        "last_editor_id": last_editor_id
+                    }}, None).await.map_err(|_| QueryError)?;
+                }
+            }
+            3 => {
+                // Credentials created before two-factor authentication was introduced have no second factor
+                self.db.collection::<Document>(CREDENTIALS).update_many(doc! {"totp_enabled": {"$exists": false}}, doc! {"$set": {
+                    "totp_secret": null,
+                    "totp_enabled": false,
+                    "totp_last_step": 0_i64,
+                    "recovery_hashes": []
+                }}, None).await.map_err(|_| QueryError)?;
+            }
+            4 => {
+                // Revisions numbered twice by concurrent updates are numbered again in the order they were kept
+                let revisions = self.db.collection::<Document>(REVISIONS);
+                let duplicates: Vec<Document> = revisions.aggregate([
+                    doc! {"$group": {"_id": {"note_id": "$note_id", "rev": "$rev"}, "count": {"$sum": 1}}},
+                    doc! {"$match": {"count": {"$gt": 1}}}
+                ], None).await.map_err(|_| QueryError)?.try_collect().await.map_err(|_| QueryError)?;
+                let mut note_ids: Vec<String> = duplicates.iter()
+                    .filter_map(|duplicate| duplicate.get_document("_id").ok()?.get_str("note_id").ok().map(str::to_string))
+                    .collect();
+                note_ids.sort();
+                note_ids.dedup();
+                for note_id in note_ids {
+                    let history: Vec<Document> = revisions.find(doc! {"note_id": &note_id},
+                                                                 FindOptions::builder().sort(doc! {"rev": 1, "created_at": 1}).build()).await
+                        .map_err(|_| QueryError)?.try_collect().await.map_err(|_| QueryError)?;
+                    for (rev, revision) in (1_i64..).zip(history) {
+                        let id = revision.get_object_id("_id").map_err(|_| QueryError)?;
+                        revisions.update_one(doc! {"_id": id}, doc! {"$set": {"rev": rev}}, None).await.map_err(|_| QueryError)?;
+                    }
+                }
+                let index = IndexModel::builder().keys(doc! {"note_id": 1, "rev": 1})
+                    .options(IndexOptions::builder().unique(true).build()).build();
+                revisions.create_index(index, None).await.map_err(|_| QueryError)?;
+            }
+            _ => return Err(QueryError) //unknown migration
+        }
+        self.db.collection::<Document>(MIGRATIONS).replace_one(doc! {"_id": version as i64}, doc! {
+            "_id": version as i64,
+            "description": description,
+            "applied_at": bson::to_bson(&Utc::now()).map_err(|_| QueryError)?
+        }, ReplaceOptions::builder().upsert(true).build()).await.map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+/// Checks whether a write failed because it violated a unique index
+///
+/// # Arguments
+///
+/// * `e` - The error the write failed with
+fn is_duplicate_key(e: &Error) -> bool {
+    matches!(e.kind.as_ref(), ErrorKind::Write(WriteFailure::WriteError(e)) if e.code == DUPLICATE_KEY_ERROR_CODE)
+}
+
+impl Storage for MongoStorage {
+    fn get_info(&self) -> DBInfo {
+        DBInfo { db_type: "mongo", version: self.version.clone() }
+    }
+}
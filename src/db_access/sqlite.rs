@@ -0,0 +1,1162 @@
+//! Storage-backend persisting all objects inside of an embedded sqlite-database
+
+use std::sync::{Mutex, MutexGuard};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter, Row};
+use crate::db_access::{Allowance, ApiToken, AllowanceLevel, Credential, CredentialStore, DBError, DBInfo, Folder, FolderShare, FolderStore, Group, GroupStore, Identity, IdentityStore, LinkStore, MigrationStore, Note, NoteStore, NoteSummary, PasswordReset, ResetStore, Revision, RevisionStore, Session, SessionStore, ShareLink, Storage, TokenStore, Transfer, TransferStore, User, UserStore};
+use crate::db_access::DBError::{NoDocumentFoundError, QueryError, ServerConnectionError, VersionMismatchError};
+
+/// Statements creating all tables required by writeUp
+const SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+    CREATE TABLE IF NOT EXISTS user (
+        id TEXT PRIMARY KEY NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS credential (
+        username TEXT PRIMARY KEY NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        passwd_hash TEXT NOT NULL,
+        totp_secret TEXT,
+        totp_enabled INTEGER NOT NULL DEFAULT 0,
+        totp_last_step INTEGER NOT NULL DEFAULT 0,
+        recovery_hashes TEXT NOT NULL DEFAULT '[]'
+    );
+    CREATE TABLE IF NOT EXISTS connection (
+        user_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        connection_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        PRIMARY KEY (user_id, connection_id)
+    );
+    CREATE TABLE IF NOT EXISTS note (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        title TEXT NOT NULL,
+        content TEXT NOT NULL,
+        owner_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        version INTEGER NOT NULL DEFAULT 0,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        last_editor_id TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS note_tag (
+        note_id INTEGER NOT NULL REFERENCES note(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (note_id, position)
+    );
+    CREATE TABLE IF NOT EXISTS allowance (
+        user_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        note_id INTEGER NOT NULL REFERENCES note(id) ON DELETE CASCADE,
+        level TEXT NOT NULL,
+        PRIMARY KEY (user_id, note_id)
+    );
+    CREATE TABLE IF NOT EXISTS user_role (
+        user_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        role TEXT NOT NULL,
+        PRIMARY KEY (user_id, role)
+    );
+    CREATE TABLE IF NOT EXISTS revision (
+        note_id INTEGER NOT NULL REFERENCES note(id) ON DELETE CASCADE,
+        rev INTEGER NOT NULL,
+        author_id TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        title TEXT NOT NULL,
+        content TEXT NOT NULL,
+        tags TEXT NOT NULL,
+        PRIMARY KEY (note_id, rev)
+    );
+    CREATE TABLE IF NOT EXISTS session (
+        id TEXT PRIMARY KEY NOT NULL,
+        user_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        refresh_hash TEXT NOT NULL,
+        device TEXT NOT NULL,
+        ip TEXT NOT NULL,
+        session_only INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        last_seen TEXT NOT NULL,
+        expires_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS api_token (
+        id TEXT PRIMARY KEY NOT NULL,
+        user_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        token_hash TEXT NOT NULL,
+        scopes TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        expires_at TEXT,
+        last_used TEXT
+    );
+    CREATE TABLE IF NOT EXISTS password_reset (
+        id TEXT PRIMARY KEY NOT NULL,
+        user_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        token_hash TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        expires_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS identity (
+        id TEXT PRIMARY KEY NOT NULL,
+        user_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS share_link (
+        id TEXT PRIMARY KEY NOT NULL,
+        note_id INTEGER NOT NULL REFERENCES note(id) ON DELETE CASCADE,
+        created_by TEXT NOT NULL,
+        token_hash TEXT NOT NULL,
+        passwd_hash TEXT,
+        created_at TEXT NOT NULL,
+        expires_at TEXT,
+        max_views INTEGER,
+        views INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS user_group (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL,
+        owner_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS group_member (
+        group_id TEXT NOT NULL REFERENCES user_group(id) ON DELETE CASCADE,
+        user_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        PRIMARY KEY (group_id, user_id)
+    );
+    CREATE TABLE IF NOT EXISTS group_allowance (
+        group_id TEXT NOT NULL REFERENCES user_group(id) ON DELETE CASCADE,
+        note_id INTEGER NOT NULL REFERENCES note(id) ON DELETE CASCADE,
+        level TEXT NOT NULL,
+        PRIMARY KEY (group_id, note_id)
+    );
+    CREATE TABLE IF NOT EXISTS folder (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL,
+        owner_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        parent_id TEXT,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS folder_note (
+        folder_id TEXT NOT NULL REFERENCES folder(id) ON DELETE CASCADE,
+        note_id INTEGER PRIMARY KEY NOT NULL REFERENCES note(id) ON DELETE CASCADE
+    );
+    CREATE TABLE IF NOT EXISTS folder_share (
+        folder_id TEXT NOT NULL REFERENCES folder(id) ON DELETE CASCADE,
+        user_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        level TEXT NOT NULL,
+        PRIMARY KEY (folder_id, user_id)
+    );
+    CREATE TABLE IF NOT EXISTS note_transfer (
+        note_id INTEGER PRIMARY KEY NOT NULL REFERENCES note(id) ON DELETE CASCADE,
+        from_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        to_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+        previous_owner_level TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS migration (
+        version INTEGER PRIMARY KEY NOT NULL,
+        description TEXT NOT NULL,
+        applied_at TEXT NOT NULL
+    );
+";
+
+/// The maximum amount of identifiers bound to a single statement
+const MAX_BATCH_SIZE: usize = 500;
+
+/// A storage-backend using an embedded sqlite-database
+pub struct SqliteStorage {
+    /// The connection to the database-file
+    conn: Mutex<Connection>
+}
+
+impl SqliteStorage {
+    /// Opens (or creates) the database-file at the given path and prepares all tables
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the database-file
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crate::db_access::sqlite::SqliteStorage;
+    ///
+    /// let db = SqliteStorage::open("writeup.db").unwrap();
+    /// ```
+    pub fn open(path: &str) -> Result<SqliteStorage, DBError> {
+        let conn = Connection::open(path).map_err(|_| ServerConnectionError)?;
+        conn.execute_batch(SCHEMA).map_err(|_| ServerConnectionError)?;
+        Ok(SqliteStorage { conn: Mutex::new(conn) })
+    }
+
+    /// Checks whether a table contains a certain column
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The connection (or transaction) to be used
+    /// * `table` - The name of the table
+    /// * `column` - The name of the column
+    fn has_column(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+        conn.prepare("SELECT name FROM pragma_table_info(?1) WHERE name = ?2")?.exists(params![table, column])
+    }
+
+    /// Grants exclusive access to the connection
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap()
+    }
+
+    /// Converts a note-identifier into the key of the note-table
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    fn note_key(note_id: &str) -> Result<i64, DBError> {
+        note_id.parse::<i64>().map_err(|_| NoDocumentFoundError)
+    }
+
+    /// Reads the group with the given id including its members and allowances
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The connection (or transaction) to be used
+    /// * `group_id` - The identifier of the group
+    fn read_group(conn: &Connection, group_id: &str) -> rusqlite::Result<Option<Group>> {
+        let group = conn.query_row("SELECT * FROM user_group WHERE id = ?1", params![group_id], |row| Ok(Group {
+            _id: row.get("id")?,
+            name: row.get("name")?,
+            owner_id: row.get("owner_id")?,
+            members: Vec::new(),
+            allowances: Vec::new(),
+            created_at: row.get("created_at")?
+        })).optional()?;
+        let mut group = match group {
+            Some(group) => group,
+            None => return Ok(None)
+        };
+        let mut stmt = conn.prepare("SELECT user_id FROM group_member WHERE group_id = ?1 ORDER BY rowid")?;
+        group.members = stmt.query_map(params![group_id], |row| row.get(0))?.collect::<rusqlite::Result<Vec<String>>>()?;
+        let mut stmt = conn.prepare("SELECT note_id, level FROM group_allowance WHERE group_id = ?1 ORDER BY rowid")?;
+        group.allowances = stmt.query_map(params![group_id], |row| Ok(Allowance {
+            note_id: row.get::<_, i64>(0)?.to_string(),
+            level: level_from_str(&row.get::<_, String>(1)?)
+        }))?.collect::<rusqlite::Result<Vec<Allowance>>>()?;
+        Ok(Some(group))
+    }
+
+    /// Reads the folders matching a condition including their notes and shares, ordered by their creation
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The connection (or transaction) to be used
+    /// * `condition` - The WHERE-clause selecting the folders (aliased as `f`)
+    /// * `param` - The single parameter of the condition
+    fn read_folders(conn: &Connection, condition: &str, param: &str) -> rusqlite::Result<Vec<Folder>> {
+        let mut stmt = conn.prepare(&format!("SELECT * FROM folder f WHERE {} ORDER BY f.created_at", condition))?;
+        let mut folders = stmt.query_map(params![param], |row| Ok(Folder {
+            _id: row.get("id")?,
+            name: row.get("name")?,
+            owner_id: row.get("owner_id")?,
+            parent_id: row.get("parent_id")?,
+            notes: Vec::new(),
+            shares: Vec::new(),
+            created_at: row.get("created_at")?
+        }))?.collect::<rusqlite::Result<Vec<Folder>>>()?;
+        let mut notes = conn.prepare("SELECT note_id FROM folder_note WHERE folder_id = ?1 ORDER BY rowid")?;
+        let mut shares = conn.prepare("SELECT user_id, level FROM folder_share WHERE folder_id = ?1 ORDER BY rowid")?;
+        for folder in folders.iter_mut() {
+            folder.notes = notes.query_map(params![folder._id], |row| Ok(row.get::<_, i64>(0)?.to_string()))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            folder.shares = shares.query_map(params![folder._id], |row| Ok(FolderShare {
+                user_id: row.get(0)?,
+                level: level_from_str(&row.get::<_, String>(1)?)
+            }))?.collect::<rusqlite::Result<Vec<FolderShare>>>()?;
+        }
+        Ok(folders)
+    }
+
+    /// Reads the notes with the given ids including their tags, paired with their identifiers
+    ///
+    /// # Arguments
+    ///
+    /// * `note_ids` - The identifiers of the notes (malformed ones are skipped)
+    /// * `columns` - The columns of the note-table to be read, always including `id`
+    /// * `from_row` - Maps a row containing these columns and the tags of the note to the returned object
+    fn read_notes<T>(&self, note_ids: &[String], columns: &str, from_row: fn(&Row, Vec<String>) -> rusqlite::Result<T>) -> Result<Vec<(String, T)>, DBError> {
+        // Malformed identifiers can not belong to any note
+        let note_keys: Vec<i64> = note_ids.iter().filter_map(|id| SqliteStorage::note_key(id).ok()).collect();
+        let conn = self.conn();
+        let mut notes = Vec::with_capacity(note_keys.len());
+        for chunk in note_keys.chunks(MAX_BATCH_SIZE) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            // Read all tags of the chunk at once
+            let mut stmt = conn.prepare(&format!("SELECT note_id, tag FROM note_tag WHERE note_id IN ({}) ORDER BY note_id, position", placeholders))
+                .map_err(|_| QueryError)?;
+            let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+            let rows = stmt.query_map(params_from_iter(chunk), |row| Ok((row.get(0)?, row.get(1)?))).map_err(|_| QueryError)?;
+            for row in rows {
+                let (note_key, tag) = row.map_err(|_| QueryError)?;
+                tags.entry(note_key).or_default().push(tag);
+            }
+            let mut stmt = conn.prepare(&format!("SELECT {} FROM note WHERE id IN ({})", columns, placeholders))
+                .map_err(|_| QueryError)?;
+            let rows = stmt.query_map(params_from_iter(chunk), |row| {
+                let note_key: i64 = row.get("id")?;
+                Ok((note_key.to_string(), from_row(row, tags.remove(&note_key).unwrap_or_default())?))
+            }).map_err(|_| QueryError)?;
+            notes.extend(rows.collect::<rusqlite::Result<Vec<(String, T)>>>().map_err(|_| QueryError)?);
+        }
+        Ok(notes)
+    }
+
+    /// Appends a revision to the history of a note, returning the number assigned to it
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The connection (or transaction) to be used
+    /// * `note_key` - The key of the note
+    /// * `revision` - The revision to be added (its `rev` is ignored)
+    fn write_revision(conn: &Connection, note_key: i64, revision: &Revision) -> Result<u32, DBError> {
+        let tags = serde_json::to_string(&revision.tags).map_err(|_| QueryError)?;
+        let rev: u32 = conn.query_row("SELECT COALESCE(MAX(rev), 0) + 1 FROM revision WHERE note_id = ?1",
+                                      params![note_key], |row| row.get(0)).map_err(|_| QueryError)?;
+        conn.execute("INSERT INTO revision (note_id, rev, author_id, created_at, title, content, tags) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                     params![note_key, rev, revision.author_id, revision.created_at, revision.title, revision.content, tags])
+            .map(|_| rev).map_err(|_| QueryError)
+    }
+
+    /// Replaces all tags of a note with the given ones
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The connection (or transaction) to be used
+    /// * `note_key` - The key of the note
+    /// * `tags` - The new tags
+    fn write_tags(conn: &Connection, note_key: i64, tags: &[String]) -> rusqlite::Result<()> {
+        conn.execute("DELETE FROM note_tag WHERE note_id = ?1", params![note_key])?;
+        let mut stmt = conn.prepare("INSERT INTO note_tag (note_id, position, tag) VALUES (?1, ?2, ?3)")?;
+        for (position, tag) in tags.iter().enumerate() {
+            stmt.execute(params![note_key, position as i64, tag])?;
+        }
+        Ok(())
+    }
+}
+
+/// Maps a row of the note-table to a Note-object
+///
+/// # Arguments
+///
+/// * `row` - The row containing all columns of the note-table
+/// * `tags` - The tags of the note
+fn note_from_row(row: &Row, tags: Vec<String>) -> rusqlite::Result<Note> {
+    Ok(Note {
+        title: row.get("title")?,
+        content: row.get("content")?,
+        owner_id: row.get("owner_id")?,
+        tags,
+        version: row.get("version")?,
+        created_at: row.get("created_at")?,
+        updated_at: row.get("updated_at")?,
+        last_editor_id: row.get("last_editor_id")?
+    })
+}
+
+/// Maps a row of the note-table to a NoteSummary-object
+///
+/// # Arguments
+///
+/// * `row` - The row containing all columns of the note-table except for its content
+/// * `tags` - The tags of the note
+fn summary_from_row(row: &Row, tags: Vec<String>) -> rusqlite::Result<NoteSummary> {
+    Ok(NoteSummary {
+        title: row.get("title")?,
+        owner_id: row.get("owner_id")?,
+        tags,
+        version: row.get("version")?,
+        created_at: row.get("created_at")?,
+        updated_at: row.get("updated_at")?,
+        last_editor_id: row.get("last_editor_id")?
+    })
+}
+
+/// Maps a row of the revision-table to a Revision-object
+///
+/// # Arguments
+///
+/// * `row` - The row containing all columns of the revision-table
+fn revision_from_row(row: &Row) -> rusqlite::Result<Revision> {
+    Ok(Revision {
+        note_id: row.get::<_, i64>("note_id")?.to_string(),
+        rev: row.get("rev")?,
+        author_id: row.get("author_id")?,
+        created_at: row.get("created_at")?,
+        title: row.get("title")?,
+        content: row.get("content")?,
+        tags: serde_json::from_str(&row.get::<_, String>("tags")?).unwrap_or_default()
+    })
+}
+
+/// Maps a row of the session-table to a Session-object
+///
+/// # Arguments
+///
+/// * `row` - The row containing all columns of the session-table
+fn session_from_row(row: &Row) -> rusqlite::Result<Session> {
+    Ok(Session {
+        _id: row.get("id")?,
+        user_id: row.get("user_id")?,
+        refresh_hash: row.get("refresh_hash")?,
+        device: row.get("device")?,
+        ip: row.get("ip")?,
+        session_only: row.get("session_only")?,
+        created_at: row.get("created_at")?,
+        last_seen: row.get("last_seen")?,
+        expires_at: row.get("expires_at")?
+    })
+}
+
+/// Maps a row of the api_token-table to an ApiToken-object
+///
+/// # Arguments
+///
+/// * `row` - The row containing all columns of the api_token-table
+fn token_from_row(row: &Row) -> rusqlite::Result<ApiToken> {
+    Ok(ApiToken {
+        _id: row.get("id")?,
+        user_id: row.get("user_id")?,
+        name: row.get("name")?,
+        token_hash: row.get("token_hash")?,
+        scopes: serde_json::from_str(&row.get::<_, String>("scopes")?).unwrap_or_default(),
+        created_at: row.get("created_at")?,
+        expires_at: row.get("expires_at")?,
+        last_used: row.get("last_used")?
+    })
+}
+
+/// Maps a row of the share_link-table to a ShareLink-object
+///
+/// # Arguments
+///
+/// * `row` - The row containing all columns of the share_link-table
+fn link_from_row(row: &Row) -> rusqlite::Result<ShareLink> {
+    Ok(ShareLink {
+        _id: row.get("id")?,
+        note_id: row.get::<_, i64>("note_id")?.to_string(),
+        created_by: row.get("created_by")?,
+        token_hash: row.get("token_hash")?,
+        passwd_hash: row.get("passwd_hash")?,
+        created_at: row.get("created_at")?,
+        expires_at: row.get("expires_at")?,
+        max_views: row.get("max_views")?,
+        views: row.get("views")?
+    })
+}
+
+/// Maps a row of the note_transfer-table to a Transfer-object
+///
+/// # Arguments
+///
+/// * `row` - The row containing all columns of the note_transfer-table
+fn transfer_from_row(row: &Row) -> rusqlite::Result<Transfer> {
+    Ok(Transfer {
+        _id: row.get::<_, i64>("note_id")?.to_string(),
+        from_id: row.get("from_id")?,
+        to_id: row.get("to_id")?,
+        previous_owner_level: level_from_str(&row.get::<_, String>("previous_owner_level")?),
+        created_at: row.get("created_at")?
+    })
+}
+
+/// Maps an AllowanceLevel to its textual representation inside of the database
+///
+/// # Arguments
+///
+/// * `level` - The level to be mapped
+fn level_to_str(level: AllowanceLevel) -> &'static str {
+    match level {
+        AllowanceLevel::Forbidden => "Forbidden",
+        AllowanceLevel::Read => "Read",
+        AllowanceLevel::ReadWrite => "ReadWrite",
+        AllowanceLevel::Owner => "Owner"
+    }
+}
+
+/// Maps the textual representation of an AllowanceLevel back to the level
+///
+/// # Arguments
+///
+/// * `level` - The string to be mapped
+fn level_from_str(level: &str) -> AllowanceLevel {
+    match level {
+        "Read" => AllowanceLevel::Read,
+        "ReadWrite" => AllowanceLevel::ReadWrite,
+        "Owner" => AllowanceLevel::Owner,
+        _ => AllowanceLevel::Forbidden
+    }
+}
+
+#[async_trait]
+impl CredentialStore for SqliteStorage {
+    async fn get_credential(&self, username: &str) -> Result<Credential, DBError> {
+        self.conn().query_row("SELECT * FROM credential WHERE username = ?1", params![username], |row| Ok(Credential {
+                _id: row.get("username")?,
+                passwd_hash: row.get("passwd_hash")?,
+                totp_secret: row.get("totp_secret")?,
+                totp_enabled: row.get("totp_enabled")?,
+                totp_last_step: row.get("totp_last_step")?,
+                recovery_hashes: serde_json::from_str(&row.get::<_, String>("recovery_hashes")?).unwrap_or_default()
+            }))
+            .optional().map_err(|_| QueryError)?.ok_or(NoDocumentFoundError)
+    }
+
+    async fn insert_credential(&self, cred: &Credential) -> Result<(), DBError> {
+        let recovery_hashes = serde_json::to_string(&cred.recovery_hashes).map_err(|_| QueryError)?;
+        self.conn().execute("INSERT INTO credential (username, passwd_hash, totp_secret, totp_enabled, totp_last_step, recovery_hashes) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                            params![cred._id, cred.passwd_hash, cred.totp_secret, cred.totp_enabled, cred.totp_last_step, recovery_hashes])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn update_credential(&self, cred: &Credential) -> Result<(), DBError> {
+        let recovery_hashes = serde_json::to_string(&cred.recovery_hashes).map_err(|_| QueryError)?;
+        self.conn().execute("UPDATE credential SET passwd_hash = ?2, totp_secret = ?3, totp_enabled = ?4, totp_last_step = ?5, recovery_hashes = ?6 WHERE username = ?1",
+                            params![cred._id, cred.passwd_hash, cred.totp_secret, cred.totp_enabled, cred.totp_last_step, recovery_hashes])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_credential(&self, username: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM credential WHERE username = ?1", params![username])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl UserStore for SqliteStorage {
+    async fn get_user(&self, user_id: &str) -> Result<User, DBError> {
+        let conn = self.conn();
+        let user_id: String = conn.query_row("SELECT id FROM user WHERE id = ?1", params![user_id], |row| row.get(0))
+            .optional().map_err(|_| QueryError)?.ok_or(NoDocumentFoundError)?;
+        // Collect all allowances
+        let mut stmt = conn.prepare("SELECT note_id, level FROM allowance WHERE user_id = ?1 ORDER BY rowid")
+            .map_err(|_| QueryError)?;
+        let allowances = stmt.query_map(params![user_id], |row| Ok(Allowance {
+                note_id: row.get::<_, i64>(0)?.to_string(),
+                level: level_from_str(&row.get::<_, String>(1)?)
+            })).and_then(|rows| rows.collect::<rusqlite::Result<Vec<Allowance>>>()).map_err(|_| QueryError)?;
+        // Collect all connections
+        let mut stmt = conn.prepare("SELECT connection_id FROM connection WHERE user_id = ?1 ORDER BY rowid")
+            .map_err(|_| QueryError)?;
+        let connections = stmt.query_map(params![user_id], |row| row.get(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>()).map_err(|_| QueryError)?;
+        // Collect all roles
+        let mut stmt = conn.prepare("SELECT role FROM user_role WHERE user_id = ?1 ORDER BY rowid")
+            .map_err(|_| QueryError)?;
+        let roles = stmt.query_map(params![user_id], |row| row.get(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>()).map_err(|_| QueryError)?;
+        Ok(User { _id: user_id, allowances, connections, roles })
+    }
+
+    async fn insert_user(&self, user: &User) -> Result<(), DBError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(|_| QueryError)?;
+        tx.execute("INSERT INTO user (id) VALUES (?1)", params![user._id]).map_err(|_| QueryError)?;
+        for connection in &user.connections {
+            tx.execute("INSERT INTO connection (user_id, connection_id) VALUES (?1, ?2)", params![user._id, connection])
+                .map_err(|_| QueryError)?;
+        }
+        for allowance in &user.allowances {
+            tx.execute("INSERT INTO allowance (user_id, note_id, level) VALUES (?1, ?2, ?3)",
+                       params![user._id, SqliteStorage::note_key(&allowance.note_id)?, level_to_str(allowance.level)])
+                .map_err(|_| QueryError)?;
+        }
+        for role in &user.roles {
+            tx.execute("INSERT INTO user_role (user_id, role) VALUES (?1, ?2)", params![user._id, role])
+                .map_err(|_| QueryError)?;
+        }
+        tx.commit().map_err(|_| QueryError)
+    }
+
+    async fn remove_user(&self, user_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM user WHERE id = ?1", params![user_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn add_connection(&self, user_id: &str, connection_id: &str) -> Result<(), DBError> {
+        self.conn().execute("INSERT INTO connection (user_id, connection_id) VALUES (?1, ?2)", params![user_id, connection_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn pull_connection(&self, user_id: &str, connection_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM connection WHERE user_id = ?1 AND connection_id = ?2", params![user_id, connection_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn add_allowance(&self, user_id: &str, allowance: &Allowance) -> Result<(), DBError> {
+        self.conn().execute("INSERT INTO allowance (user_id, note_id, level) VALUES (?1, ?2, ?3)",
+                            params![user_id, SqliteStorage::note_key(&allowance.note_id)?, level_to_str(allowance.level)])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn pull_allowances(&self, user_id: &str, note_ids: &[String]) -> Result<(), DBError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(|_| QueryError)?;
+        for note_id in note_ids {
+            // Notes that can't exist can't be linked either
+            if let Ok(note_key) = SqliteStorage::note_key(note_id) {
+                tx.execute("DELETE FROM allowance WHERE user_id = ?1 AND note_id = ?2", params![user_id, note_key])
+                    .map_err(|_| QueryError)?;
+            }
+        }
+        tx.commit().map_err(|_| QueryError)
+    }
+
+    async fn pull_note_allowances(&self, note_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM allowance WHERE note_id = ?1", params![SqliteStorage::note_key(note_id)?])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn set_note_allowances(&self, note_id: &str, changes: &[(String, AllowanceLevel)]) -> Result<(), DBError> {
+        let note_key = SqliteStorage::note_key(note_id)?;
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(|_| QueryError)?;
+        for (user_id, level) in changes {
+            let known = tx.query_row("SELECT 1 FROM user WHERE id = ?1", params![user_id], |_| Ok(()))
+                .optional().map_err(|_| QueryError)?;
+            if known.is_none() {
+                return Err(NoDocumentFoundError) // Rolled back when dropped
+            }
+            if *level == AllowanceLevel::Forbidden {
+                tx.execute("DELETE FROM allowance WHERE user_id = ?1 AND note_id = ?2", params![user_id, note_key])
+            } else {
+                tx.execute("INSERT INTO allowance (user_id, note_id, level) VALUES (?1, ?2, ?3)
+                            ON CONFLICT (user_id, note_id) DO UPDATE SET level = excluded.level",
+                           params![user_id, note_key, level_to_str(*level)])
+            }.map_err(|_| QueryError)?;
+        }
+        tx.commit().map_err(|_| QueryError)
+    }
+
+    async fn set_roles(&self, user_id: &str, roles: &[String]) -> Result<(), DBError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(|_| QueryError)?;
+        tx.execute("DELETE FROM user_role WHERE user_id = ?1", params![user_id]).map_err(|_| QueryError)?;
+        for role in roles {
+            tx.execute("INSERT INTO user_role (user_id, role) VALUES (?1, ?2)", params![user_id, role])
+                .map_err(|_| QueryError)?;
+        }
+        tx.commit().map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl NoteStore for SqliteStorage {
+    async fn get_note(&self, note_id: &str) -> Result<Note, DBError> {
+        let note_key = SqliteStorage::note_key(note_id)?;
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT tag FROM note_tag WHERE note_id = ?1 ORDER BY position")
+            .map_err(|_| QueryError)?;
+        let tags = stmt.query_map(params![note_key], |row| row.get(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>()).map_err(|_| QueryError)?;
+        conn.query_row("SELECT * FROM note WHERE id = ?1", params![note_key], |row| note_from_row(row, tags))
+            .optional().map_err(|_| QueryError)?.ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_notes(&self, note_ids: &[String]) -> Result<Vec<(String, Note)>, DBError> {
+        self.read_notes(note_ids, "*", note_from_row)
+    }
+
+    async fn get_note_summaries(&self, note_ids: &[String]) -> Result<Vec<(String, NoteSummary)>, DBError> {
+        self.read_notes(note_ids, "id, title, owner_id, version, created_at, updated_at, last_editor_id", summary_from_row)
+    }
+
+    async fn insert_note(&self, note: &Note) -> Result<String, DBError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(|_| QueryError)?;
+        tx.execute("INSERT INTO note (title, content, owner_id, version, created_at, updated_at, last_editor_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                   params![note.title, note.content, note.owner_id, note.version, note.created_at, note.updated_at, note.last_editor_id])
+            .map_err(|_| QueryError)?;
+        let note_key = tx.last_insert_rowid();
+        SqliteStorage::write_tags(&tx, note_key, &note.tags).map_err(|_| QueryError)?;
+        tx.commit().map(|_| note_key.to_string()).map_err(|_| QueryError)
+    }
+
+    async fn set_note_fields(&self, note_id: &str, note: &Note, version: u64, revision: Option<&Revision>) -> Result<u64, DBError> {
+        let note_key = SqliteStorage::note_key(note_id)?;
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(|_| QueryError)?;
+        let current: u64 = tx.query_row("SELECT version FROM note WHERE id = ?1", params![note_key], |row| row.get(0))
+            .optional().map_err(|_| QueryError)?.ok_or(NoDocumentFoundError)?;
+        if current != version {
+            return Err(VersionMismatchError(current))
+        }
+        tx.execute("UPDATE note SET title = ?2, content = ?3, updated_at = ?4, last_editor_id = ?5, version = version + 1 WHERE id = ?1",
+                   params![note_key, note.title, note.content, note.updated_at, note.last_editor_id]).map_err(|_| QueryError)?;
+        SqliteStorage::write_tags(&tx, note_key, &note.tags).map_err(|_| QueryError)?;
+        if let Some(revision) = revision {
+            SqliteStorage::write_revision(&tx, note_key, revision)?;
+        }
+        tx.commit().map(|_| version + 1).map_err(|_| QueryError)
+    }
+
+    async fn set_note_owner(&self, note_id: &str, owner_id: &str) -> Result<(), DBError> {
+        match self.conn().execute("UPDATE note SET owner_id = ?2 WHERE id = ?1", params![SqliteStorage::note_key(note_id)?, owner_id]) {
+            Ok(0) => Err(NoDocumentFoundError),
+            Ok(_) => Ok(()),
+            Err(_) => Err(QueryError)
+        }
+    }
+
+    async fn remove_note(&self, note_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM note WHERE id = ?1", params![SqliteStorage::note_key(note_id)?])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl RevisionStore for SqliteStorage {
+    async fn get_revisions(&self, note_id: &str) -> Result<Vec<Revision>, DBError> {
+        let note_key = SqliteStorage::note_key(note_id)?;
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT * FROM revision WHERE note_id = ?1 ORDER BY rev").map_err(|_| QueryError)?;
+        let revisions = stmt.query_map(params![note_key], revision_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<Revision>>>()).map_err(|_| QueryError);
+        revisions
+    }
+
+    async fn get_revision(&self, note_id: &str, rev: u32) -> Result<Revision, DBError> {
+        self.conn().query_row("SELECT * FROM revision WHERE note_id = ?1 AND rev = ?2",
+                              params![SqliteStorage::note_key(note_id)?, rev], revision_from_row)
+            .optional().map_err(|_| QueryError)?.ok_or(NoDocumentFoundError)
+    }
+
+    async fn remove_revisions(&self, note_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM revision WHERE note_id = ?1", params![SqliteStorage::note_key(note_id)?])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl SessionStore for SqliteStorage {
+    async fn get_session(&self, session_id: &str) -> Result<Session, DBError> {
+        self.conn().query_row("SELECT * FROM session WHERE id = ?1", params![session_id], session_from_row)
+            .optional().map_err(|_| QueryError)?.ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_sessions(&self, user_id: &str) -> Result<Vec<Session>, DBError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT * FROM session WHERE user_id = ?1 ORDER BY created_at").map_err(|_| QueryError)?;
+        let sessions = stmt.query_map(params![user_id], session_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<Session>>>()).map_err(|_| QueryError);
+        sessions
+    }
+
+    async fn insert_session(&self, session: &Session) -> Result<(), DBError> {
+        self.conn().execute("INSERT INTO session (id, user_id, refresh_hash, device, ip, session_only, created_at, last_seen, expires_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                            params![session._id, session.user_id, session.refresh_hash, session.device, session.ip,
+                                    session.session_only, session.created_at, session.last_seen, session.expires_at])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn set_session_fields(&self, session: &Session, refresh_hash: &str) -> Result<(), DBError> {
+        let updated = self.conn().execute("UPDATE session SET refresh_hash = ?3, ip = ?4, last_seen = ?5, expires_at = ?6 WHERE id = ?1 AND refresh_hash = ?2",
+                                          params![session._id, refresh_hash, session.refresh_hash, session.ip, session.last_seen, session.expires_at])
+            .map_err(|_| QueryError)?;
+        if updated == 0 {
+            // Either the session is gone or it was refreshed in the meantime
+            return Err(NoDocumentFoundError)
+        }
+        Ok(())
+    }
+
+    async fn touch_session(&self, session_id: &str, last_seen: DateTime<Utc>) -> Result<(), DBError> {
+        self.conn().execute("UPDATE session SET last_seen = ?2 WHERE id = ?1", params![session_id, last_seen])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_session(&self, session_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM session WHERE id = ?1", params![session_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_sessions(&self, user_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM session WHERE user_id = ?1", params![user_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl TokenStore for SqliteStorage {
+    async fn get_token(&self, token_id: &str) -> Result<ApiToken, DBError> {
+        self.conn().query_row("SELECT * FROM api_token WHERE id = ?1", params![token_id], token_from_row)
+            .optional().map_err(|_| QueryError)?.ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_tokens(&self, user_id: &str) -> Result<Vec<ApiToken>, DBError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT * FROM api_token WHERE user_id = ?1 ORDER BY created_at").map_err(|_| QueryError)?;
+        let tokens = stmt.query_map(params![user_id], token_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<ApiToken>>>()).map_err(|_| QueryError);
+        tokens
+    }
+
+    async fn insert_token(&self, token: &ApiToken) -> Result<(), DBError> {
+        let scopes = serde_json::to_string(&token.scopes).map_err(|_| QueryError)?;
+        self.conn().execute("INSERT INTO api_token (id, user_id, name, token_hash, scopes, created_at, expires_at, last_used) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                            params![token._id, token.user_id, token.name, token.token_hash, scopes, token.created_at, token.expires_at, token.last_used])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn touch_token(&self, token_id: &str, last_used: DateTime<Utc>) -> Result<(), DBError> {
+        self.conn().execute("UPDATE api_token SET last_used = ?2 WHERE id = ?1", params![token_id, last_used])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_token(&self, token_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM api_token WHERE id = ?1", params![token_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_tokens(&self, user_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM api_token WHERE user_id = ?1", params![user_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl ResetStore for SqliteStorage {
+    async fn get_reset(&self, reset_id: &str) -> Result<PasswordReset, DBError> {
+        self.conn().query_row("SELECT * FROM password_reset WHERE id = ?1", params![reset_id], |row| Ok(PasswordReset {
+            _id: row.get("id")?,
+            user_id: row.get("user_id")?,
+            token_hash: row.get("token_hash")?,
+            created_at: row.get("created_at")?,
+            expires_at: row.get("expires_at")?
+        })).optional().map_err(|_| QueryError)?.ok_or(NoDocumentFoundError)
+    }
+
+    async fn insert_reset(&self, reset: &PasswordReset) -> Result<(), DBError> {
+        self.conn().execute("INSERT INTO password_reset (id, user_id, token_hash, created_at, expires_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                            params![reset._id, reset.user_id, reset.token_hash, reset.created_at, reset.expires_at])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_resets(&self, user_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM password_reset WHERE user_id = ?1", params![user_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl IdentityStore for SqliteStorage {
+    async fn get_identity(&self, identity_id: &str) -> Result<Identity, DBError> {
+        self.conn().query_row("SELECT * FROM identity WHERE id = ?1", params![identity_id], |row| Ok(Identity {
+            _id: row.get("id")?,
+            user_id: row.get("user_id")?,
+            created_at: row.get("created_at")?
+        })).optional().map_err(|_| QueryError)?.ok_or(NoDocumentFoundError)
+    }
+
+    async fn insert_identity(&self, identity: &Identity) -> Result<(), DBError> {
+        self.conn().execute("INSERT INTO identity (id, user_id, created_at) VALUES (?1, ?2, ?3)",
+                            params![identity._id, identity.user_id, identity.created_at])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_identities(&self, user_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM identity WHERE user_id = ?1", params![user_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl LinkStore for SqliteStorage {
+    async fn get_link(&self, link_id: &str) -> Result<ShareLink, DBError> {
+        self.conn().query_row("SELECT * FROM share_link WHERE id = ?1", params![link_id], link_from_row)
+            .optional().map_err(|_| QueryError)?.ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_links(&self, note_id: &str) -> Result<Vec<ShareLink>, DBError> {
+        let note_key = SqliteStorage::note_key(note_id)?;
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT * FROM share_link WHERE note_id = ?1 ORDER BY created_at").map_err(|_| QueryError)?;
+        let links = stmt.query_map(params![note_key], link_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<ShareLink>>>()).map_err(|_| QueryError);
+        links
+    }
+
+    async fn insert_link(&self, link: &ShareLink) -> Result<(), DBError> {
+        self.conn().execute("INSERT INTO share_link (id, note_id, created_by, token_hash, passwd_hash, created_at, expires_at, max_views, views) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                            params![link._id, SqliteStorage::note_key(&link.note_id)?, link.created_by, link.token_hash, link.passwd_hash,
+                                    link.created_at, link.expires_at, link.max_views, link.views])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn count_view(&self, link_id: &str) -> Result<ShareLink, DBError> {
+        let conn = self.conn();
+        let counted = conn.execute("UPDATE share_link SET views = views + 1 WHERE id = ?1 AND (max_views IS NULL OR views < max_views)",
+                                   params![link_id]).map_err(|_| QueryError)?;
+        if counted == 0 {
+            return Err(NoDocumentFoundError) // Unknown or exhausted
+        }
+        conn.query_row("SELECT * FROM share_link WHERE id = ?1", params![link_id], link_from_row).map_err(|_| QueryError)
+    }
+
+    async fn remove_link(&self, link_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM share_link WHERE id = ?1", params![link_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_links(&self, note_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM share_link WHERE note_id = ?1", params![SqliteStorage::note_key(note_id)?])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl GroupStore for SqliteStorage {
+    async fn get_group(&self, group_id: &str) -> Result<Group, DBError> {
+        SqliteStorage::read_group(&self.conn(), group_id).map_err(|_| QueryError)?.ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_groups(&self, user_id: &str) -> Result<Vec<Group>, DBError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT g.id FROM user_group g JOIN group_member m ON m.group_id = g.id WHERE m.user_id = ?1 ORDER BY g.created_at")
+            .map_err(|_| QueryError)?;
+        let group_ids = stmt.query_map(params![user_id], |row| row.get(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>()).map_err(|_| QueryError)?;
+        group_ids.iter().filter_map(|group_id| SqliteStorage::read_group(&conn, group_id).transpose())
+            .collect::<rusqlite::Result<Vec<Group>>>().map_err(|_| QueryError)
+    }
+
+    async fn insert_group(&self, group: &Group) -> Result<(), DBError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(|_| QueryError)?;
+        tx.execute("INSERT INTO user_group (id, name, owner_id, created_at) VALUES (?1, ?2, ?3, ?4)",
+                   params![group._id, group.name, group.owner_id, group.created_at]).map_err(|_| QueryError)?;
+        for member in &group.members {
+            tx.execute("INSERT INTO group_member (group_id, user_id) VALUES (?1, ?2)", params![group._id, member])
+                .map_err(|_| QueryError)?;
+        }
+        for allowance in &group.allowances {
+            tx.execute("INSERT INTO group_allowance (group_id, note_id, level) VALUES (?1, ?2, ?3)",
+                       params![group._id, SqliteStorage::note_key(&allowance.note_id)?, level_to_str(allowance.level)])
+                .map_err(|_| QueryError)?;
+        }
+        tx.commit().map_err(|_| QueryError)
+    }
+
+    async fn remove_group(&self, group_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM user_group WHERE id = ?1", params![group_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn add_member(&self, group_id: &str, user_id: &str) -> Result<(), DBError> {
+        self.conn().execute("INSERT INTO group_member (group_id, user_id) VALUES (?1, ?2)", params![group_id, user_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn pull_member(&self, group_id: &str, user_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM group_member WHERE group_id = ?1 AND user_id = ?2", params![group_id, user_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn pull_group_allowance(&self, group_id: &str, note_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM group_allowance WHERE group_id = ?1 AND note_id = ?2",
+                            params![group_id, SqliteStorage::note_key(note_id)?])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn set_group_note_allowances(&self, note_id: &str, changes: &[(String, AllowanceLevel)]) -> Result<(), DBError> {
+        let note_key = SqliteStorage::note_key(note_id)?;
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(|_| QueryError)?;
+        for (group_id, level) in changes {
+            let known = tx.query_row("SELECT 1 FROM user_group WHERE id = ?1", params![group_id], |_| Ok(()))
+                .optional().map_err(|_| QueryError)?;
+            if known.is_none() {
+                return Err(NoDocumentFoundError) // Rolled back when dropped
+            }
+            if *level == AllowanceLevel::Forbidden {
+                tx.execute("DELETE FROM group_allowance WHERE group_id = ?1 AND note_id = ?2", params![group_id, note_key])
+            } else {
+                tx.execute("INSERT INTO group_allowance (group_id, note_id, level) VALUES (?1, ?2, ?3)
+                            ON CONFLICT (group_id, note_id) DO UPDATE SET level = excluded.level",
+                           params![group_id, note_key, level_to_str(*level)])
+            }.map_err(|_| QueryError)?;
+        }
+        tx.commit().map_err(|_| QueryError)
+    }
+
+    async fn pull_note_group_allowances(&self, note_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM group_allowance WHERE note_id = ?1", params![SqliteStorage::note_key(note_id)?])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl FolderStore for SqliteStorage {
+    async fn get_folder(&self, folder_id: &str) -> Result<Folder, DBError> {
+        SqliteStorage::read_folders(&self.conn(), "f.id = ?1", folder_id).map_err(|_| QueryError)?
+            .pop().ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_folders(&self, owner_id: &str) -> Result<Vec<Folder>, DBError> {
+        SqliteStorage::read_folders(&self.conn(), "f.owner_id = ?1", owner_id).map_err(|_| QueryError)
+    }
+
+    async fn get_shared_folders(&self, user_id: &str) -> Result<Vec<Folder>, DBError> {
+        SqliteStorage::read_folders(&self.conn(), "f.id IN (SELECT folder_id FROM folder_share WHERE user_id = ?1)", user_id)
+            .map_err(|_| QueryError)
+    }
+
+    async fn insert_folder(&self, folder: &Folder) -> Result<(), DBError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(|_| QueryError)?;
+        tx.execute("INSERT INTO folder (id, name, owner_id, parent_id, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                   params![folder._id, folder.name, folder.owner_id, folder.parent_id, folder.created_at]).map_err(|_| QueryError)?;
+        for note_id in &folder.notes {
+            tx.execute("INSERT INTO folder_note (folder_id, note_id) VALUES (?1, ?2)",
+                       params![folder._id, SqliteStorage::note_key(note_id)?]).map_err(|_| QueryError)?;
+        }
+        for share in &folder.shares {
+            tx.execute("INSERT INTO folder_share (folder_id, user_id, level) VALUES (?1, ?2, ?3)",
+                       params![folder._id, share.user_id, level_to_str(share.level)]).map_err(|_| QueryError)?;
+        }
+        tx.commit().map_err(|_| QueryError)
+    }
+
+    async fn update_folder(&self, folder_id: &str, name: &str, parent_id: Option<&str>) -> Result<(), DBError> {
+        self.conn().execute("UPDATE folder SET name = ?2, parent_id = ?3 WHERE id = ?1", params![folder_id, name, parent_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_folder(&self, folder_id: &str) -> Result<(), DBError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(|_| QueryError)?;
+        let parent_id: Option<String> = tx.query_row("SELECT parent_id FROM folder WHERE id = ?1", params![folder_id], |row| row.get(0))
+            .optional().map_err(|_| QueryError)?.ok_or(NoDocumentFoundError)?;
+        tx.execute("UPDATE folder SET parent_id = ?2 WHERE parent_id = ?1", params![folder_id, parent_id]).map_err(|_| QueryError)?;
+        // Notes left inside of a top-level folder are removed from it along with the folder
+        if parent_id.is_some() {
+            tx.execute("UPDATE folder_note SET folder_id = ?2 WHERE folder_id = ?1", params![folder_id, parent_id]).map_err(|_| QueryError)?;
+        }
+        tx.execute("DELETE FROM folder WHERE id = ?1", params![folder_id]).map_err(|_| QueryError)?;
+        tx.commit().map_err(|_| QueryError)
+    }
+
+    async fn add_folder_note(&self, folder_id: &str, note_id: &str) -> Result<(), DBError> {
+        self.conn().execute("INSERT INTO folder_note (folder_id, note_id) VALUES (?1, ?2)",
+                            params![folder_id, SqliteStorage::note_key(note_id)?])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn pull_folder_note(&self, note_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM folder_note WHERE note_id = ?1", params![SqliteStorage::note_key(note_id)?])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn set_folder_shares(&self, folder_id: &str, changes: &[(String, AllowanceLevel)]) -> Result<(), DBError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(|_| QueryError)?;
+        for (user_id, level) in changes {
+            if *level == AllowanceLevel::Forbidden {
+                tx.execute("DELETE FROM folder_share WHERE folder_id = ?1 AND user_id = ?2", params![folder_id, user_id])
+            } else {
+                tx.execute("INSERT INTO folder_share (folder_id, user_id, level) VALUES (?1, ?2, ?3)
+                            ON CONFLICT (folder_id, user_id) DO UPDATE SET level = excluded.level",
+                           params![folder_id, user_id, level_to_str(*level)])
+            }.map_err(|_| QueryError)?; // Rolled back when dropped
+        }
+        tx.commit().map_err(|_| QueryError)
+    }
+
+    async fn pull_folder_share(&self, folder_id: &str, user_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM folder_share WHERE folder_id = ?1 AND user_id = ?2", params![folder_id, user_id])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl TransferStore for SqliteStorage {
+    async fn get_transfer(&self, note_id: &str) -> Result<Transfer, DBError> {
+        self.conn().query_row("SELECT * FROM note_transfer WHERE note_id = ?1", params![SqliteStorage::note_key(note_id)?], transfer_from_row)
+            .optional().map_err(|_| QueryError)?.ok_or(NoDocumentFoundError)
+    }
+
+    async fn get_transfers(&self, user_id: &str) -> Result<Vec<Transfer>, DBError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT * FROM note_transfer WHERE from_id = ?1 OR to_id = ?1 ORDER BY created_at").map_err(|_| QueryError)?;
+        let transfers = stmt.query_map(params![user_id], transfer_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<Transfer>>>()).map_err(|_| QueryError);
+        transfers
+    }
+
+    async fn set_transfer(&self, transfer: &Transfer) -> Result<(), DBError> {
+        self.conn().execute("INSERT OR REPLACE INTO note_transfer (note_id, from_id, to_id, previous_owner_level, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                            params![SqliteStorage::note_key(&transfer._id)?, transfer.from_id, transfer.to_id,
+                                    level_to_str(transfer.previous_owner_level), transfer.created_at])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+
+    async fn remove_transfer(&self, note_id: &str) -> Result<(), DBError> {
+        self.conn().execute("DELETE FROM note_transfer WHERE note_id = ?1", params![SqliteStorage::note_key(note_id)?])
+            .map(|_| ()).map_err(|_| QueryError)
+    }
+}
+
+#[async_trait]
+impl MigrationStore for SqliteStorage {
+    async fn get_schema_version(&self) -> Result<u32, DBError> {
+        self.conn().query_row("SELECT COALESCE(MAX(version), 0) FROM migration", [], |row| row.get(0))
+            .map_err(|_| QueryError)
+    }
+
+    async fn apply_migration(&self, version: u32, description: &str) -> Result<(), DBError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(|_| QueryError)?;
+        match version {
+            1 => {
+                // Notes created before versioning was introduced count as version 0
+                if !SqliteStorage::has_column(&tx, "note", "version").map_err(|_| QueryError)? {
+                    tx.execute("ALTER TABLE note ADD COLUMN version INTEGER NOT NULL DEFAULT 0", []).map_err(|_| QueryError)?;
+                }
+            }
+            2 => {
+                // Both timestamps and the last editor are taken from the revision-history where possible.
+                // Unlike on MongoDB the creation can not be read from the identifier, so the first modification approximates it
+                if !SqliteStorage::has_column(&tx, "note", "created_at").map_err(|_| QueryError)? {
+                    tx.execute_batch("
+                        ALTER TABLE note ADD COLUMN created_at TEXT NOT NULL DEFAULT '';
+                        ALTER TABLE note ADD COLUMN updated_at TEXT NOT NULL DEFAULT '';
+                        ALTER TABLE note ADD COLUMN last_editor_id TEXT NOT NULL DEFAULT '';
+                    ").map_err(|_| QueryError)?;
+                }
+                tx.execute("
+                    UPDATE note SET
+                        created_at = COALESCE((SELECT MIN(created_at) FROM revision WHERE note_id = note.id), ?1),
+                        updated_at = COALESCE((SELECT MAX(created_at) FROM revision WHERE note_id = note.id), ?1),
+                        last_editor_id = COALESCE((SELECT author_id FROM revision WHERE note_id = note.id ORDER BY rev DESC LIMIT 1), owner_id)
+                    WHERE created_at = ''
+                ", params![Utc::now()]).map_err(|_| QueryError)?;
+            }
+            3 => {
+                // Credentials created before two-factor authentication was introduced have no second factor
+                if !SqliteStorage::has_column(&tx, "credential", "totp_secret").map_err(|_| QueryError)? {
+                    tx.execute_batch("
+                        ALTER TABLE credential ADD COLUMN totp_secret TEXT;
+                        ALTER TABLE credential ADD COLUMN totp_enabled INTEGER NOT NULL DEFAULT 0;
+                        ALTER TABLE credential ADD COLUMN totp_last_step INTEGER NOT NULL DEFAULT 0;
+                        ALTER TABLE credential ADD COLUMN recovery_hashes TEXT NOT NULL DEFAULT '[]';
+                    ").map_err(|_| QueryError)?;
+                }
+            }
+            4 => {
+                // Revision-numbers have always been unique per note by the primary key of the revision-table
+            }
+            _ => return Err(QueryError) //unknown migration
+        }
+        tx.execute("INSERT OR REPLACE INTO migration (version, description, applied_at) VALUES (?1, ?2, ?3)",
+                   params![version, description, Utc::now()]).map_err(|_| QueryError)?;
+        tx.commit().map_err(|_| QueryError)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn get_info(&self) -> DBInfo {
+        DBInfo { db_type: "sqlite", version: rusqlite::version().to_string() }
+    }
+}
@@ -0,0 +1,172 @@
+//! Verification of passwords by a directory-server, such as a company-wide LDAP
+//!
+//! Logins of users without local credentials are handed to the configured [`Directory`], so that both kinds of accounts work alongside each other.
+//! Local credentials always take precedence, so a directory-account can't take over a local one of the same name.
+//! Once the directory has verified a password, the user is created if necessary and their roles are updated.
+//! The password itself is never stored, it can only be changed within the directory.
+//! Until then the following Directories are available:
+//! * [`LdapDirectory`] - Looks the user up and binds as them (see [`LdapConfig::from_env`])
+
+use std::env;
+use std::io;
+use std::time::Duration;
+use async_trait::async_trait;
+use ldap3::{ldap_escape, LdapConnAsync, LdapConnSettings, LdapError, Scope, SearchEntry};
+use crate::secret::{Secret, secret_from_env};
+
+/// The result-code of an LDAP-operation rejecting the given credentials
+const LDAP_INVALID_CREDENTIALS: u32 = 49;
+/// Time in seconds to wait for the directory-server to accept a connection
+const LDAP_TIMEOUT_SECONDS: u64 = 5;
+
+/// An account whose password has been verified by a directory
+#[derive(Debug, PartialEq, Eq)]
+pub struct DirectoryAccount {
+    /// The roles the directory assigns to the user
+    pub roles: Vec<String>
+}
+
+/// Something capable of verifying the passwords of users
+#[async_trait]
+pub trait Directory: Send + Sync {
+    /// Verifies the password of a user, returning their account if it matches.
+    /// Returns None for unknown users as well as wrong passwords
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - The name of the user
+    /// * `password` - The supposed password in plain text
+    async fn verify(&self, username: &str, password: &str) -> io::Result<Option<DirectoryAccount>>;
+}
+
+/// The directory-server to verify passwords with
+#[derive(Clone, Debug)]
+pub struct LdapConfig {
+    /// The URL of the directory-server (`ldap://` or `ldaps://`)
+    pub url: String,
+    /// The DN below which users are searched
+    pub base_dn: String,
+    /// The filter finding a user, `{username}` being replaced with the escaped username
+    pub user_filter: String,
+    /// The DN and password to search with (anonymously if not set)
+    pub search_bind: Option<(String, Secret)>,
+    /// The attribute listing the DNs of the groups of a user
+    pub group_attribute: String,
+    /// The roles assigned to the members of a group, mapped by the DN of the group
+    pub group_roles: Vec<(String, String)>
+}
+
+impl LdapConfig {
+    /// Reads the directory-server from the following environment-variables.
+    /// Returns None if `LDAP_URL` is not set, disabling the directory:
+    /// * `LDAP_URL` - The URL of the directory-server
+    /// * `LDAP_BASE_DN` - The DN below which users are searched
+    /// * `LDAP_USER_FILTER` - The filter finding a user *[default: `(uid={username})`]*
+    /// * `LDAP_BIND_DN`, `LDAP_BIND_PASSWORD` - The account to search with *[default: anonymous]*
+    /// * `LDAP_GROUP_ATTRIBUTE` - The attribute listing the groups of a user *[default: `memberOf`]*
+    /// * `LDAP_GROUP_ROLES` - Semicolon-separated pairs of a role and a group, e.g. `admin:cn=admins,ou=groups,dc=example,dc=com` *[default: none]*
+    pub fn from_env() -> Option<LdapConfig> {
+        let url = env::var("LDAP_URL").ok()?;
+        let search_bind = env::var("LDAP_BIND_DN").ok().map(|dn| (dn,
+            secret_from_env("LDAP_BIND_PASSWORD").expect("Env-Variable 'LDAP_BIND_PASSWORD' needs to be set along with 'LDAP_BIND_DN'")));
+        Some(LdapConfig {
+            url,
+            base_dn: env::var("LDAP_BASE_DN").expect("Env-Variable 'LDAP_BASE_DN' needs to be set"),
+            user_filter: env::var("LDAP_USER_FILTER").unwrap_or_else(|_| "(uid={username})".to_string()),
+            search_bind,
+            group_attribute: env::var("LDAP_GROUP_ATTRIBUTE").unwrap_or_else(|_| "memberOf".to_string()),
+            group_roles: env::var("LDAP_GROUP_ROLES").map(|mapping| parse_group_roles(&mapping)
+                .expect("Env-Variable 'LDAP_GROUP_ROLES' needs to consist of 'role:group-dn'-pairs")).unwrap_or_default()
+        })
+    }
+}
+
+/// Parses semicolon-separated pairs of a role and the DN of a group
+///
+/// # Arguments
+///
+/// * `mapping` - The pairs to be parsed
+///
+/// # Examples
+///
+/// ```
+/// use crate::directory::parse_group_roles;
+///
+/// assert_eq!(parse_group_roles("admin:cn=admins,dc=example; editor:cn=staff,dc=example"), Some(vec![
+///     ("cn=admins,dc=example".to_string(), "admin".to_string()), ("cn=staff,dc=example".to_string(), "editor".to_string())]));
+/// assert_eq!(parse_group_roles("cn=admins,dc=example"), None);
+/// ```
+pub fn parse_group_roles(mapping: &str) -> Option<Vec<(String, String)>> {
+    mapping.split(';').map(str::trim).filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once(':') {
+            Some((role, group)) if !role.trim().is_empty() && !group.trim().is_empty() =>
+                Some((group.trim().to_string(), role.trim().to_string())),
+            _ => None
+        }).collect()
+}
+
+/// A Directory verifying passwords by binding to a directory-server as the user
+pub struct LdapDirectory {
+    /// The directory-server
+    config: LdapConfig
+}
+
+impl LdapDirectory {
+    /// Creates a new Directory using the given directory-server
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The directory-server
+    pub fn new(config: LdapConfig) -> LdapDirectory {
+        LdapDirectory { config }
+    }
+
+    /// Returns the roles assigned to the members of the given groups, in the order they have been configured
+    ///
+    /// # Arguments
+    ///
+    /// * `groups` - The DNs of the groups
+    pub fn roles_of(&self, groups: &[String]) -> Vec<String> {
+        let mut roles: Vec<String> = Vec::new();
+        for (group, role) in &self.config.group_roles {
+            if groups.iter().any(|member_of| member_of.eq_ignore_ascii_case(group)) && !roles.contains(role) {
+                roles.push(role.clone());
+            }
+        }
+        roles
+    }
+}
+
+#[async_trait]
+impl Directory for LdapDirectory {
+    async fn verify(&self, username: &str, password: &str) -> io::Result<Option<DirectoryAccount>> {
+        // Binding without a password succeeds anonymously
+        if password.is_empty() {
+            return Ok(None)
+        }
+        let settings = LdapConnSettings::new().set_conn_timeout(Duration::from_secs(LDAP_TIMEOUT_SECONDS));
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.config.url).await.map_err(io::Error::other)?;
+        ldap3::drive!(conn);
+
+        // Look the user up
+        if let Some((dn, passwd)) = &self.config.search_bind {
+            ldap.simple_bind(dn, passwd.expose()).await.and_then(|res| res.success()).map_err(io::Error::other)?;
+        }
+        let filter = self.config.user_filter.replace("{username}", &ldap_escape(username));
+        let (entries, _) = ldap.search(&self.config.base_dn, Scope::Subtree, &filter, vec![self.config.group_attribute.as_str()]).await
+            .and_then(|res| res.success()).map_err(io::Error::other)?;
+        let entry = match <[_; 1]>::try_from(entries) {
+            Ok([entry]) => SearchEntry::construct(entry),
+            Err(_) => return Ok(None) // Unknown or ambiguous
+        };
+
+        // Verify the password by binding as the user
+        let account = match ldap.simple_bind(&entry.dn, password).await.and_then(|res| res.success()) {
+            Ok(_) => Some(DirectoryAccount { roles: self.roles_of(entry.attrs.get(&self.config.group_attribute).map_or(&[], Vec::as_slice)) }),
+            Err(LdapError::LdapResult { result }) if result.rc == LDAP_INVALID_CREDENTIALS => None,
+            Err(e) => return Err(io::Error::other(e))
+        };
+        let _ = ldap.unbind().await; // The connection is closed either way
+        Ok(account)
+    }
+}
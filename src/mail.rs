@@ -0,0 +1,61 @@
+//! Delivery of messages to users, such as password-reset tokens
+//!
+//! Deliveries go through the [`Mailer`]-trait, so that an actual mail-server can be plugged in.
+//! As users are only known by their username, it is up to the Mailer to find out where to deliver to.
+//! Until then the following local stand-ins are available:
+//! * [`LogMailer`] - Logs that a message could not be delivered, withholding its content *[default]*
+//! * [`FileMailer`] - Appends every message to a file (see `MAIL_OUTBOX`)
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use chrono::Utc;
+use log::warn;
+use crate::web::TIME_FORMAT;
+
+/// Something capable of delivering messages to users
+pub trait Mailer: Send + Sync {
+    /// Delivers a message to a user
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient` - The username of the recipient
+    /// * `subject` - The subject of the message
+    /// * `body` - The message itself
+    fn send(&self, recipient: &str, subject: &str, body: &str) -> std::io::Result<()>;
+}
+
+/// A Mailer dropping every message, logging only its recipient and subject.
+/// The body is withheld, as it may contain credentials such as reset-tokens
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    fn send(&self, recipient: &str, subject: &str, _body: &str) -> std::io::Result<()> {
+        warn!("Mail to '{}' - {} could not be delivered, as no mailer is configured", recipient, subject);
+        Ok(())
+    }
+}
+
+/// A Mailer appending every message to a file instead of delivering it
+pub struct FileMailer {
+    /// The file the messages are appended to
+    path: PathBuf
+}
+
+impl FileMailer {
+    /// Creates a new Mailer appending to the given file, which is created if necessary
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file
+    pub fn new(path: impl Into<PathBuf>) -> FileMailer {
+        FileMailer { path: path.into() }
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, recipient: &str, subject: &str, body: &str) -> std::io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "Date: {}\nTo: {}\nSubject: {}\n\n{}\n", Utc::now().format(TIME_FORMAT), recipient, subject, body)
+    }
+}
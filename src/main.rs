@@ -2,28 +2,43 @@
 //!
 //! The writeUp-Crate provides the backend to 'writeUp',
 //! an extensive webapp all about writing and sharing notes using markdown.
-//! It contains support for MongoDB and a REST-API as an interface.
+//! It contains support for MongoDB and SQLite and a REST-API as an interface.
 //!
 //! # Usage
 //!
 //! 1. Make sure the database is up and running
 //!
 //! 2. Make sure the following environment variables are set:
-//!     * `DB_URI` - The address under which to find the Database
-//!     * `DB_PORT` - The port under which to find the Database
-//!     * `DB_USER` - The user under which writeUp will use the database
-//!     * `DB_PASSWD` - The password of above's user
+//!     * `DB_TYPE` - The kind of database to be used (`mongo`, `sqlite` or `memory`) *[default: `mongo`]*
+//!     * `DB_URI` - The address under which to find the Database *[mongo only]*
+//!     * `DB_PORT` - The port under which to find the Database *[mongo only]*
+//!     * `DB_USER` - The user under which writeUp will use the database *[mongo only]*
+//!     * `DB_PASSWD` - The password of above's user *[mongo only]*
+//!     * `DB_NAME` - The name of the database inside of the db-server *[mongo only, default: `test`]*
+//!     * `DB_PATH` - The path to the database-file *[sqlite only, default: `writeup.db`]*
 //!     * `API_PORT` - The port under which to find the REST-API *[default: `8080`]*
 //!     * `PASSWD_SECRET` - The secret used to pepper password-hashes
-//!     * `JWT_SECRET` - The secret used in creating and verifying JWTs *[default: random]*
+//!     * `PASSWD_SECRET_OLD` - Comma-separated previous secrets, still accepted until each password has been re-hashed *[default: none]*
+//!     * `PASSWD_HASH_MEMORY`, `PASSWD_HASH_ITERATIONS` - The Argon2-parameters of new password-hashes *[default: `65536` kiB, `8`]*
+//!     * `JWT_SECRET` - The secret used in creating and verifying JWTs *[default: random, unless `JWT_KEY_FILES` is set]*
+//!     * `JWT_KEY_FILES` - Comma-separated paths to private keys signing JWTs instead, the first one being current *[default: none]*
+//!       [[`KeySet::from_env`](web::KeySet::from_env)]
 //!     * `SHARE_SECRET` - The secret used in creating and verifying invitation-codes *[default: random]*
 //!     * `BETA_KEY` - The key to indicate beta-membership *[default: random]*
+//!     * `MAIL_OUTBOX` - The file messages to users (e.g. password-reset tokens) are appended to *[default: they are dropped]*
+//!     * `RATE_LIMIT_REQUESTS`, `RATE_LIMIT_WINDOW`, `LOCKOUT_THRESHOLD`, `LOCKOUT_SECONDS`, `LOCKOUT_MAX_SECONDS`, `RATE_LIMIT_IP_HEADER` -
+//!       The limits protecting logins and signups [[`RateLimitConfig::from_env`](web::RateLimitConfig::from_env)]
+//!     * `OIDC_ISSUER`, `OIDC_CLIENT_ID`, `OIDC_CLIENT_SECRET`, `OIDC_REDIRECT_URI`, `OIDC_AUTO_PROVISION`, `OIDC_POST_LOGIN_URI` -
+//!       The identity-provider to log in with *[default: none]* [[`OidcConfig::from_env`](web::OidcConfig::from_env)]
+//!     * `LDAP_URL`, `LDAP_BASE_DN`, `LDAP_USER_FILTER`, `LDAP_BIND_DN`, `LDAP_BIND_PASSWORD`, `LDAP_GROUP_ATTRIBUTE`, `LDAP_GROUP_ROLES` -
+//!       The directory verifying users without local credentials *[default: none]* [[`LdapConfig::from_env`](directory::LdapConfig::from_env)]
 //!
 //! 3. Start up the server by executing `writeUp` and wait for
 //!     ```text
 //!     > Starting up writeUp
 //!     > Checking for environment-variables
 //!     > Connecting to Database
+//!     > Checking the database-schema
 //!     > Starting up webserver on port XXXX
 //!     > Initialisation finished - listening for requests
 //!     ```
@@ -32,28 +47,61 @@
 //!
 //!     If the request gets rejected, check your console for error messages
 //!
+//! # Migrations
+//!
+//! Pending migrations of the database-schema are applied on every startup.
+//! When started with `--no-migrate`, writeUp instead refuses to start until they have been applied using `writeUp migrate`.
+//! writeUp never starts using a database whose schema is newer than the one it supports.
+//!
+//! # Secrets
+//!
+//! Instead of `PASSWD_SECRET`, `PASSWD_SECRET_OLD`, `JWT_SECRET`, `SHARE_SECRET`, `BETA_KEY`, `DB_PASSWD`,
+//! `OIDC_CLIENT_SECRET` and `LDAP_BIND_PASSWORD` their `*_FILE`-counterparts (e.g. `PASSWD_SECRET_FILE`) can be set
+//! to the path of a file containing the secret, such as a Docker secret [[`load_secret`](secret::load_secret)].
+//! Secrets are never logged, at startup only whether each of them is set and where it came from is reported.
+//!
+//! # Password-Hashes
+//!
+//! The Argon2-parameters are stored within every password-hash. Whenever a user logs in successfully,
+//! a hash weaker than the configured parameters or peppered with an old secret is replaced.
+//! To rotate `PASSWD_SECRET`, move the current value to `PASSWD_SECRET_OLD` and set a new one.
+//!
+//! # Password-Resets
+//!
+//! Users who forgot their password can request a reset-token, which is handed to the configured [`Mailer`](mail::Mailer).
+//! Alternatively an administrator can issue one using `writeUp reset-password <username>` and pass it on.
+//!
 //! For a comprehensive list of all Endpoints and how to use them please refer to [[`web`](crate::web)]
 
 #![allow(rustdoc::private_intra_doc_links)]
 #![allow(non_snake_case)]
 mod web;
 mod db_access;
+mod mail;
+mod directory;
+mod secret;
 
 use std::env;
 use std::path::{MAIN_SEPARATOR, Path};
-use std::sync::Mutex;
-use clap::Parser;
+use std::sync::Arc;
+use clap::{Parser, Subcommand, ValueEnum};
 use actix_cors::Cors;
 use actix_files::NamedFile;
 use actix_web::{App, HttpServer};
 use actix_web::dev::{fn_service, ServiceRequest, ServiceResponse};
 use actix_web::middleware::Logger;
-use actix_web::web::{Data, JsonConfig};
-use log::{debug, error, info};
-use rand::distributions::Alphanumeric;
-use rand::Rng;
+use actix_web::web::{Data, JsonConfig, QueryConfig};
+use log::{debug, error, info, warn};
 use simple_on_shutdown::on_shutdown;
-use crate::db_access::connect_to_database;
+use crate::db_access::{DBError, HashPolicy, Storage};
+use crate::db_access::memory::MemoryStorage;
+use crate::db_access::migration::{migrate, pending_migrations, SCHEMA_VERSION};
+use crate::db_access::mongo::MongoStorage;
+use crate::db_access::sqlite::SqliteStorage;
+use crate::directory::{Directory, LdapConfig, LdapDirectory};
+use crate::mail::{FileMailer, LogMailer, Mailer};
+use crate::secret::{AppSecrets, load_or_generate_secret, load_secret, secret_from_env, SecretSource};
+use crate::web::{issue_reset_token, JWT_KEY_FILES_ENV_VAR_KEY, KeySet, LiveHub, OidcConfig, RateLimitConfig, RateLimiter, RESET_DURATION_MINUTES};
 
 /// The name of the environment-variable containing the password-secret
 pub const PASSWD_SECRET_ENV_VAR_KEY: &str = "PASSWD_SECRET";
@@ -61,8 +109,14 @@ pub const PASSWD_SECRET_ENV_VAR_KEY: &str = "PASSWD_SECRET";
 pub const JWT_SECRET_ENV_VAR_KEY: &str = "JWT_SECRET";
 /// The name of the environment-variable containing the share-secret
 pub const SHARE_SECRET_ENV_VAR_KEY: &str = "SHARE_SECRET";
+/// The name of the environment-variable containing the beta-key
+pub const BETA_KEY_ENV_VAR_KEY: &str = "BETA_KEY";
+/// The name of the environment-variable containing the password of the database
+const DB_PASSWD_ENV_VAR_KEY: &str = "DB_PASSWD";
 /// The default length at which a given secret-string gets generated
 const SECRET_SIZE: usize = 16;
+/// The amount of characters making up a randomly generated beta-key
+const BETA_KEY_SIZE: usize = 5;
 
 /// Root of all backend-requests
 const BACKEND_ROOT_ROUTE: &str = "/api";
@@ -74,9 +128,24 @@ const FRONTEND_ROOT_PATH: &str = ".\\public";
 const FRONTEND_ROOT_PATH: &str = "./public";
 /// Frontend index-file
 const FRONTEND_INDEX_FILE: &str = "index.html";
+/// Default path to the database-file of the sqlite-backend
+const DEFAULT_SQLITE_PATH: &str = "writeup.db";
+/// Default name of the database inside of the mongodb-server
+const DEFAULT_MONGO_DB_NAME: &str = "test";
 
 /// Simplifies certain behaviour to allow for easier testing and debugging
-fn has_dev_flag() -> bool { env::var("ENVIRONMENT").map_or(false, |env| env.eq("DEVELOPMENT")) }
+fn has_dev_flag() -> bool { env::var("ENVIRONMENT").is_ok_and(|env| env.eq("DEVELOPMENT")) }
+
+/// The kinds of databases writeUp can store its objects in
+#[derive(ValueEnum, Clone, Copy)]
+enum DBType {
+    /// A mongodb-server
+    Mongo,
+    /// An embedded sqlite-database
+    Sqlite,
+    /// A volatile in-memory database
+    Memory
+}
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -87,6 +156,25 @@ struct Args {
     /// Specify the port to be listened to (default: 8080)
     #[clap(short = 'p', long = "port", value_parser)]
     api_port: Option<u16>,
+    /// Specify the kind of database to be used (default: mongo)
+    #[clap(short = 'd', long = "db-type", value_enum)]
+    db_type: Option<DBType>,
+    /// Refuse to start instead of applying pending migrations of the database-schema
+    #[clap(long, action)]
+    no_migrate: bool,
+    #[clap(subcommand)]
+    command: Option<Command>
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Apply all pending migrations of the database-schema and exit
+    Migrate,
+    /// Issue a token allowing a user to set a new password, print it and exit
+    ResetPassword {
+        /// The user whose password is to be reset
+        username: String
+    }
 }
 
 #[actix_rt::main]
@@ -98,51 +186,158 @@ async fn main() -> std::io::Result<()> {
     info!("Starting up writeUp");
 
     info!("Checking for environment-variables");
-    env::var(PASSWD_SECRET_ENV_VAR_KEY).expect("Env-Variable 'PASSWD_SECRET' needs to be set");
-    debug!("Passwd-Secret: {}", env::var(PASSWD_SECRET_ENV_VAR_KEY).unwrap());
-    // Set random secrets for encryption if not predefined
-    if env::var(JWT_SECRET_ENV_VAR_KEY).is_err() {
-        let new_secret: String = rand::thread_rng().sample_iter(&Alphanumeric)
-            .take(SECRET_SIZE).map(char::from).collect();
-        env::set_var(JWT_SECRET_ENV_VAR_KEY, new_secret);
-    }
-    debug!("JWT-Secret: {}", env::var(JWT_SECRET_ENV_VAR_KEY).unwrap());
-    if env::var(SHARE_SECRET_ENV_VAR_KEY).is_err() {
-        let new_secret: String = rand::thread_rng().sample_iter(&Alphanumeric)
-            .take(SECRET_SIZE).map(char::from).collect();
-        env::set_var(SHARE_SECRET_ENV_VAR_KEY, new_secret);
+    // Load all secrets, setting random ones for encryption if not predefined, and report where they came from
+    let jwt_secret_size = env::var(JWT_KEY_FILES_ENV_VAR_KEY).is_err().then_some(SECRET_SIZE);
+    let secrets = [(PASSWD_SECRET_ENV_VAR_KEY, None), ("PASSWD_SECRET_OLD", None), (JWT_SECRET_ENV_VAR_KEY, jwt_secret_size),
+        (SHARE_SECRET_ENV_VAR_KEY, Some(SECRET_SIZE)), (BETA_KEY_ENV_VAR_KEY, Some(BETA_KEY_SIZE)), (DB_PASSWD_ENV_VAR_KEY, None),
+        ("OIDC_CLIENT_SECRET", None), ("LDAP_BIND_PASSWORD", None)];
+    for (name, generated_size) in secrets {
+        let source = match generated_size {
+            Some(size) => load_or_generate_secret(name, size),
+            None => load_secret(name)
+        };
+        match source {
+            Ok(SecretSource::Generated) if name == JWT_SECRET_ENV_VAR_KEY =>
+                warn!("Neither 'JWT_SECRET' nor 'JWT_KEY_FILES' is set, all sessions will end on shutdown"),
+            Ok(source) => info!("Secret '{}': {}", name, source),
+            Err(e) => {
+                error!("Failed to load the secret '{}': {}. Shutting down", name, e);
+                return Ok(());
+            }
+        }
     }
-    debug!("Share-Secret: {}", env::var(SHARE_SECRET_ENV_VAR_KEY).unwrap());
-
-    if env::var("BETA_KEY").is_err() {
-        let new_key: String = rand::thread_rng().sample_iter(&Alphanumeric)
-            .take(5).map(char::from).collect();
-        env::set_var("BETA_KEY", new_key);
+    secret_from_env(PASSWD_SECRET_ENV_VAR_KEY).expect("Env-Variable 'PASSWD_SECRET' needs to be set");
+    // Fail early on malformed hash-parameters
+    let hash_policy = HashPolicy::from_env();
+    debug!("Password-Hashes: {} kiB, {} iterations, {} old secret(s)", hash_policy.memory_size, hash_policy.iterations, hash_policy.old_peppers.len());
+    // Fail early on unreadable keys
+    let keys = Data::new(KeySet::from_env());
+    let secrets = Data::new(AppSecrets::from_env());
+    if let Some(kid) = keys.current().kid() {
+        info!("Signing JWTs using the key '{}'", kid);
     }
-    debug!("Beta-Key: {}", env::var("BETA_KEY").unwrap());
 
     // The port to listen to
-    let api_port;
-    if args.api_port.is_some() { api_port = args.api_port.unwrap(); } else {
-        api_port = env::var("API_PORT").unwrap_or("8080".to_string()).parse::<u16>().unwrap();
-    }
-    // Database-related environment variables
-    let db_uri = env::var("DB_URI").expect("Env-Variable 'DB_URI' needs to be set"); //TODO? Combine the following four vars to one big 'CONFIG_MONGODB_URL'
-    let db_port = env::var("DB_PORT").expect("Env-Variable 'DB_PORT' needs to be set");
-    let db_user = env::var("DB_USER").expect("Env-Variable 'DB_USER' needs to be set");
-    let db_passwd = env::var("DB_PASSWD").expect("Env-Variable 'DB_PASSWD' needs to be set");
+    let api_port = args.api_port.unwrap_or_else(||
+        env::var("API_PORT").unwrap_or_else(|_| "8080".to_string()).parse::<u16>().unwrap());
+    // The kind of database to use
+    let db_type = args.db_type.unwrap_or_else(|| match env::var("DB_TYPE") {
+        Ok(db_type) => DBType::from_str(&db_type, true).expect("Env-Variable 'DB_TYPE' needs to be one of 'mongo', 'sqlite' or 'memory'"),
+        Err(_) => DBType::Mongo
+    });
 
     // Connect to the Database
     info!("Connecting to Database");
-    debug!("Database-Address: {}:{}", db_uri, db_port);
-    debug!("Database-User: {} ({})", db_user, db_passwd);
-    let db = connect_to_database((db_uri, db_port), (db_user, db_passwd)).await;
-    if db.is_err() {
-        error!("Failed to establish a connection to the Database. Shutting down");
+    let db: Arc<dyn Storage> = match db_type {
+        DBType::Mongo => {
+            // Database-related environment variables
+            let db_uri = env::var("DB_URI").expect("Env-Variable 'DB_URI' needs to be set"); //TODO? Combine the following four vars to one big 'CONFIG_MONGODB_URL'
+            let db_port = env::var("DB_PORT").expect("Env-Variable 'DB_PORT' needs to be set");
+            let db_user = env::var("DB_USER").expect("Env-Variable 'DB_USER' needs to be set");
+            let db_passwd = secret_from_env(DB_PASSWD_ENV_VAR_KEY).expect("Env-Variable 'DB_PASSWD' needs to be set");
+            let db_name = env::var("DB_NAME").unwrap_or_else(|_| DEFAULT_MONGO_DB_NAME.to_string());
+            debug!("Database-Address: {}:{}", db_uri, db_port);
+            debug!("Database-User: {}", db_user);
+            debug!("Database-Name: {}", db_name);
+            match MongoStorage::connect((db_uri, db_port), (db_user, db_passwd), &db_name).await {
+                Ok(db) => Arc::new(db),
+                Err(_) => {
+                    error!("Failed to establish a connection to the Database. Shutting down");
+                    return Ok(());
+                }
+            }
+        }
+        DBType::Sqlite => {
+            let db_path = env::var("DB_PATH").unwrap_or_else(|_| DEFAULT_SQLITE_PATH.to_string());
+            debug!("Database-File: {}", db_path);
+            match SqliteStorage::open(&db_path) {
+                Ok(db) => Arc::new(db),
+                Err(_) => {
+                    error!("Failed to open the Database-File. Shutting down");
+                    return Ok(());
+                }
+            }
+        }
+        DBType::Memory => {
+            warn!("Using a volatile in-memory Database. All data will be lost on shutdown");
+            Arc::new(MemoryStorage::new())
+        }
+    };
+
+    // Bring the database-schema up to date
+    info!("Checking the database-schema");
+    let pending = match pending_migrations(db.as_ref()).await {
+        Ok(pending) => pending,
+        Err(DBError::UnsupportedSchemaError(version)) => {
+            error!("The database-schema (version {}) is newer than the one supported (version {}). Shutting down", version, SCHEMA_VERSION);
+            return Ok(());
+        }
+        Err(_) => {
+            error!("Failed to read the version of the database-schema. Shutting down");
+            return Ok(());
+        }
+    };
+    let migrate_only = matches!(args.command, Some(Command::Migrate));
+    if !pending.is_empty() {
+        if args.no_migrate && !migrate_only {
+            error!("{} migration(s) of the database-schema pending, apply them using 'writeUp migrate'. Shutting down", pending.len());
+            return Ok(());
+        }
+        if migrate(db.as_ref()).await.is_err() {
+            error!("Failed to migrate the database-schema. Shutting down");
+            return Ok(());
+        }
+    }
+    info!("Database-schema is up to date (version {})", SCHEMA_VERSION);
+    if migrate_only {
         return Ok(());
     }
-    // Prepare the connection for use by the web-server
-    let data = Data::new(Mutex::new(db.unwrap()));
+
+    // Issue a password-reset token instead of serving requests
+    if let Some(Command::ResetPassword { username }) = &args.command {
+        match db.get_credential(username).await {
+            Ok(_) => match issue_reset_token(db.as_ref(), username).await {
+                Ok((token, _)) => {
+                    info!("Issued a password-reset token for '{}'", username);
+                    println!("Reset-token for '{}' (valid for {} minutes): {}", username, RESET_DURATION_MINUTES, token);
+                }
+                Err(_) => error!("Failed to issue a password-reset token")
+            }
+            Err(DBError::NoDocumentFoundError) => error!("There is no user called '{}'", username),
+            Err(_) => error!("Failed to access the credentials of '{}'", username)
+        }
+        return Ok(());
+    }
+
+    // Choose how to deliver messages to users
+    let mailer: Arc<dyn Mailer> = match env::var("MAIL_OUTBOX") {
+        Ok(path) => {
+            debug!("Mail-Outbox: {}", path);
+            Arc::new(FileMailer::new(path))
+        }
+        Err(_) => Arc::new(LogMailer)
+    };
+    let mailer: Data<dyn Mailer> = Data::from(mailer);
+
+    // Prepare the storage-backend for use by the web-server
+    let data: Data<dyn Storage> = Data::from(db);
+    // Prepare the registry of all live-editing sessions
+    let live_hub = Data::new(LiveHub::default());
+    // Prepare the limits protecting logins and signups
+    let limit_config = RateLimitConfig::from_env();
+    debug!("Rate-Limits: {:?}", limit_config);
+    let limiter = Data::new(RateLimiter::new(limit_config));
+    // Prepare the identity-provider to log in with, if configured
+    let oidc = OidcConfig::from_env().map(Data::new);
+    if let Some(oidc) = &oidc {
+        info!("Enabling single sign-on using '{}'", oidc.issuer);
+    }
+    // Prepare the directory to verify the passwords of users without local credentials, if configured
+    let directory: Option<Data<dyn Directory>> = LdapConfig::from_env().map(|config| {
+        info!("Verifying passwords using the directory at '{}'", config.url);
+        let directory: Arc<dyn Directory> = Arc::new(LdapDirectory::new(config));
+        Data::from(directory)
+    });
 
     // Start the web-server
     info!("Starting up webserver on port {}", api_port);
@@ -157,7 +352,21 @@ async fn main() -> std::io::Result<()> {
                 .custom_request_replace("REQ_SERVICE", |req| if req.path().starts_with(BACKEND_ROOT_ROUTE) { "API" } else { "WEB" }.parse().unwrap())
                 .log_target("writeup::actix"))
             .app_data(data.clone())
-            .app_data(JsonConfig::default().error_handler(web::json_error_handler));
+            .app_data(live_hub.clone())
+            .app_data(mailer.clone())
+            .app_data(limiter.clone())
+            .app_data(keys.clone())
+            .app_data(secrets.clone())
+            .app_data(JsonConfig::default().error_handler(web::json_error_handler))
+            .app_data(QueryConfig::default().error_handler(web::query_error_handler));
+        let app_base = match &oidc {
+            Some(oidc) => app_base.app_data(oidc.clone()),
+            None => app_base
+        };
+        let app_base = match &directory {
+            Some(directory) => app_base.app_data(directory.clone()),
+            None => app_base
+        };
 
         // Register backend-service
         let app_backend = app_base.service(actix_web::web::scope(BACKEND_ROOT_ROUTE).configure(web::handler_config));
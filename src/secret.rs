@@ -0,0 +1,152 @@
+//! Handling of secrets, such as the pepper of password-hashes or the password of the database
+//!
+//! Every secret is read from its environment-variable or, if `<NAME>_FILE` is set instead,
+//! from the file that variable points to (e.g. a Docker secret), see [`load_secret`].
+//! Once read, secrets are wrapped in a [`Secret`], whose `Debug`- and `Display`-output never reveals its content,
+//! so that they don't end up in the log by accident. At startup only where each secret came from is reported.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use crate::{BETA_KEY_ENV_VAR_KEY, SHARE_SECRET_ENV_VAR_KEY};
+
+/// The text shown in place of the content of a secret
+const REDACTED: &str = "[redacted]";
+
+/// A secret value, which is never revealed by its `Debug`- or `Display`-output
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    /// Wraps a secret value
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The secret value
+    pub fn new(value: impl Into<String>) -> Secret {
+        Secret(value.into())
+    }
+
+    /// Returns the secret value, which must not be logged
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Where a secret came from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SecretSource {
+    /// Set directly within the environment-variable
+    Env,
+    /// Read from the file at the given path
+    File(String),
+    /// Generated randomly, as it has not been set
+    Generated,
+    /// Not set at all
+    Unset
+}
+
+impl fmt::Display for SecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretSource::Env => f.write_str("set (environment)"),
+            SecretSource::File(path) => write!(f, "set (file '{}')", path),
+            SecretSource::Generated => f.write_str("generated randomly"),
+            SecretSource::Unset => f.write_str("not set")
+        }
+    }
+}
+
+/// Makes a secret available under its environment-variable, reading it from the file named by `<name>_FILE` if that is set.
+/// A single trailing line-break is removed from the content of the file.
+/// Returns where the secret came from, failing if both variables are set or the file can't be read
+///
+/// # Arguments
+///
+/// * `name` - The name of the environment-variable
+///
+/// # Examples
+///
+/// ```text
+/// PASSWD_SECRET_FILE=/run/secrets/passwd_secret writeUp
+/// ```
+pub fn load_secret(name: &str) -> io::Result<SecretSource> {
+    let file_var = format!("{}_FILE", name);
+    match (env::var_os(name), env::var(&file_var)) {
+        (Some(_), Ok(_)) => Err(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("only one of '{}' and '{}' may be set", name, file_var))),
+        (Some(_), Err(_)) => Ok(SecretSource::Env),
+        (None, Ok(path)) => {
+            let content = fs::read_to_string(&path)
+                .map_err(|e| io::Error::new(e.kind(), format!("'{}' could not be read: {}", path, e)))?;
+            let content = content.strip_suffix('\n').map(|content| content.strip_suffix('\r').unwrap_or(content)).unwrap_or(&content);
+            env::set_var(name, content);
+            Ok(SecretSource::File(path))
+        }
+        (None, Err(_)) => Ok(SecretSource::Unset)
+    }
+}
+
+/// Loads a secret like [`load_secret`], generating a random one if it has not been set
+///
+/// # Arguments
+///
+/// * `name` - The name of the environment-variable
+/// * `size` - The amount of characters making up a generated secret
+pub fn load_or_generate_secret(name: &str, size: usize) -> io::Result<SecretSource> {
+    match load_secret(name)? {
+        SecretSource::Unset => {
+            let secret: String = rand::thread_rng().sample_iter(&Alphanumeric).take(size).map(char::from).collect();
+            env::set_var(name, secret);
+            Ok(SecretSource::Generated)
+        }
+        source => Ok(source)
+    }
+}
+
+/// The secrets needed while serving requests, to be added to the app-data of the web-server
+#[derive(Clone, Debug)]
+pub struct AppSecrets {
+    /// The secret signing invite-codes
+    pub share_secret: Secret,
+    /// The key required to sign up
+    pub beta_key: Secret
+}
+
+impl AppSecrets {
+    /// Reads the secrets from the following environment-variables, which have been loaded using [`load_secret`] before:
+    /// * `SHARE_SECRET` - The secret signing invite-codes
+    /// * `BETA_KEY` - The key required to sign up
+    ///
+    /// Panics if either variable is not set
+    pub fn from_env() -> AppSecrets {
+        AppSecrets {
+            share_secret: secret_from_env(SHARE_SECRET_ENV_VAR_KEY).expect("Env-Variable 'SHARE_SECRET' needs to be set"),
+            beta_key: secret_from_env(BETA_KEY_ENV_VAR_KEY).expect("Env-Variable 'BETA_KEY' needs to be set")
+        }
+    }
+}
+
+/// Reads a secret from its environment-variable, which has been loaded using [`load_secret`] before
+///
+/// # Arguments
+///
+/// * `name` - The name of the environment-variable
+pub fn secret_from_env(name: &str) -> Option<Secret> {
+    env::var(name).ok().map(Secret::new)
+}
@@ -1,38 +1,84 @@
 //! Contains functions and endpoints revolving around authorisation and authentication
 
-use std::env;
-use std::sync::Mutex;
 use actix_web::{post, get, delete, HttpResponse, Responder, web, HttpRequest};
 use actix_web::cookie::{CookieBuilder, SameSite, time::Duration};
-use actix_web::web::Data;
+use actix_web::http::header::{AUTHORIZATION, USER_AGENT};
+use actix_web::middleware::from_fn;
+use actix_web::web::{Data, Path};
 use chrono::Utc;
-use jsonwebtoken::{Algorithm, decode, DecodingKey, encode, EncodingKey, Header, Validation};
+use log::warn;
 use mongodb::bson::doc;
-use mongodb::Database;
-use crate::db_access::{Credential, CREDENTIALS, DBError, get_dbo_by_id, User, USER};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use crate::db_access::{DBError, HashPolicy, is_safe, Session, Storage, TokenScope, User, Verification};
+use crate::directory::{Directory, DirectoryAccount};
 use crate::web::{error::APIError, ResponseObject, ResponseObjectWithPayload};
+use crate::web::auth::json_objects::{SecondFactorRequest, SecondFactorResponse, SessionResponse};
+use crate::web::keys::{get_keys, KeySet};
+use crate::web::limit::{limit_requests, RateLimiter};
+use crate::web::totp::check_second_factor;
 use serde::{Serialize, Deserialize};
-use crate::{has_dev_flag, JWT_SECRET_ENV_VAR_KEY};
+use crate::has_dev_flag;
 
 // JWT-Assets
 /// Time in minutes until a JWT expires
-const JWT_DURATION_MINUTES: i64 = 60;
+const JWT_DURATION_MINUTES: i64 = 15;
 /// Name of the cookie carrying the JWT
 const JWT_TOKEN_COOKIE_NAME: &str = "writeup_jwt";
 
+/// Time in minutes a user has to provide their second factor after their password has been verified
+const CHALLENGE_DURATION_MINUTES: i64 = 5;
+/// Audience of JWTs proving only the password of a user, which are not accepted as a login
+const CHALLENGE_AUDIENCE: &str = "writeup-second-factor";
+
+// Session-Assets
+/// Time in days until a session expires, if it isn't refreshed in the meantime
+const SESSION_DURATION_DAYS: i64 = 30;
+/// Name of the cookie carrying the refresh-token
+const REFRESH_TOKEN_COOKIE_NAME: &str = "writeup_refresh";
+/// Amount of characters making up the identifier of a session
+const SESSION_ID_SIZE: usize = 24;
+/// Amount of characters making up the secret part of a refresh-token
+const REFRESH_SECRET_SIZE: usize = 48;
+/// Time in seconds a session or token has to be unused before its last usage is recorded again
+const LAST_SEEN_INTERVAL_SECONDS: i64 = 60;
+
+// Token-Assets
+/// Scheme of the Authorization-header carrying a personal access-token
+const BEARER_SCHEME: &str = "Bearer ";
+/// Prefix marking a personal access-token, making leaked tokens easy to recognize
+pub const API_TOKEN_PREFIX: &str = "wup_";
+
 /// Struct containing all information to be encoded in the JWT
 #[derive(Debug, Deserialize, Serialize)]
 struct Claims {  // Credits to: https://blog.logrocket.com/jwt-authentication-in-rust/
     /// Username of the user to be authorised
     sub: String,
+    /// Identifier of the session the JWT belongs to
+    sid: String,
     /// Timestamp of JWT-expiration
     exp: usize,
 }
 
+/// Struct containing all information to be encoded in the JWT handed out while the second factor is pending
+#[derive(Debug, Deserialize, Serialize)]
+struct ChallengeClaims {
+    /// Username of the user whose password has been verified
+    sub: String,
+    /// Whether the session to be started should end together with the browser-session
+    session_only: bool,
+    /// Audience distinguishing the challenge from a regular JWT
+    aud: String,
+    /// Timestamp of challenge-expiration
+    exp: usize,
+}
+
 // Response-/Request-Objects
 /// Structs modelling the request- and response-bodies
 mod json_objects {
-    use serde::Deserialize;
+    use chrono::{DateTime, Utc};
+    use serde::{Serialize, Deserialize};
 
     /// Body of an authentication-request
     #[derive(Deserialize)]
@@ -44,20 +90,66 @@ mod json_objects {
         /// Define whether or not to verify the user for longer than a single session
         pub session_only: bool
     }
+
+    /// Body of a response requesting the second factor of the user
+    #[derive(Serialize)]
+    pub struct SecondFactorResponse {
+        /// Indicator that the login requires a second factor (will always be true)
+        pub second_factor_required: bool,
+        /// Proof of the verified password, to be sent along with the second factor
+        pub challenge: String
+    }
+
+    /// Body of a request providing the second factor
+    #[derive(Deserialize)]
+    pub struct SecondFactorRequest {
+        /// The challenge handed out after the password has been verified
+        pub challenge: String,
+        /// A one-time password or recovery-code
+        pub code: String
+    }
+
+    /// Body of a response containing a session of the user
+    #[derive(Serialize)]
+    pub struct SessionResponse {
+        /// The identifier of the session
+        pub session_id: String,
+        /// The device (user-agent) the user logged in from
+        pub device: String,
+        /// The IP-address the session was last used from
+        pub ip: String,
+        /// Timestamp of the login
+        pub created_at: DateTime<Utc>,
+        /// Timestamp of when the session was last used
+        pub last_seen: DateTime<Utc>,
+        /// Whether this is the session the request was made with
+        pub current: bool
+    }
 }
 
-/// ENDPOINT: Takes a set of credentials, verifies them and sets a JWT-cookie as proof
+/// ENDPOINT: Takes a set of credentials, verifies them and starts a new session,
+/// setting a JWT- and a refresh-cookie as proof.
+/// If the user has enabled a second factor, a challenge is returned instead,
+/// which has to be completed at [`verify_second_factor`] to start the session.
+/// Users without local credentials are verified by the [`Directory`], if one has been configured.
+/// Too many failed attempts lock the account temporarily (see [`limit`](crate::web::limit))
 ///
 /// Returns one of the following HttpResponses:
 /// * `200`
-///     - \[COOKIE: JWT\] Credentials could be verified
+///     - \[COOKIE: JWT, REFRESH\] Credentials could be verified
+///     - \[Body: JSON\] Credentials could be verified, the second factor is required
 ///     - **\[11\]** Credentials are incorrect
+/// * `429`
+///     - **\[13\]** \[Retry-After\] Too many requests or the account is locked
 /// * `500`
 ///     - Something went wrong internally (debug)
 ///
 /// # Arguments
 ///
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+/// * `limiter` - The AppData containing the RateLimiter
+/// * `directory` - The AppData containing the Directory, if one has been configured
 /// * `creds` - From JSON generated TokenRequest including the credentials to be checked
 ///
 /// # Examples
@@ -69,9 +161,26 @@ mod json_objects {
 ///         "password": "testPass",
 ///         "session_only": false
 ///     }
-/// => 200 [cookie with JWT is set]
+/// => 200 [cookies with JWT and refresh-token are set]
+///     {
+///         "success": true,
+///         "time": "2022-04-11 12:05:57"
+///     }
+/// ```
+/// ```text
+/// POST-Request at `{api-url}/auth` (valid credentials, second factor enabled)
+///     {
+///         "username": "testUser",
+///         "password": "testPass",
+///         "session_only": false
+///     }
+/// => 200
 ///     {
 ///         "success": true,
+///         "content": {
+///             "second_factor_required": true,
+///             "challenge": "eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzUxMiJ9.eyJzdWIiOiJ0ZXN0VXNlciIsInNlc3Npb25fb25seSI6ZmFsc2V9.R8y1nXb..."
+///         },
 ///         "time": "2022-04-11 12:05:57"
 ///     }
 /// ```
@@ -90,40 +199,239 @@ mod json_objects {
 ///         "time": "2022-04-11 12:05:57"
 ///     }
 /// ```
-#[post("/auth")]
-pub async fn authenticate(db: Data<Mutex<Database>>, creds: web::Json<json_objects::TokenRequest>) -> impl Responder {
+/// ```text
+/// POST-Request at `{api-url}/auth` (account locked after too many failed attempts)
+///     {
+///         "username": "testUser",
+///         "password": "testPass",
+///         "session_only": false
+///     }
+/// => 429 [Retry-After: 60]
+///     {
+///         "success": false,
+///         "code": 13,
+///         "message": "too many attempts, retry in 60 seconds",
+///         "time": "2022-04-11 12:05:57"
+///     }
+/// ```
+#[post("/auth", wrap = "from_fn(limit_requests)")]
+pub async fn authenticate(req: HttpRequest, db: Data<dyn Storage>, limiter: Data<RateLimiter>, directory: Option<Data<dyn Directory>>,
+                          creds: web::Json<json_objects::TokenRequest>) -> impl Responder {
+    // Reject locked accounts before spending any effort on their password
+    if let Err(retry_after) = limiter.check_account(&creds.username, Utc::now()) {
+        return APIError::TooManyAttemptsError(retry_after).gen_response()
+    }
     // Load Credentials for the supposed user
-    match get_dbo_by_id::<Credential>(CREDENTIALS, creds.username.as_str().to_string(), db.get_ref()).await {
-        Ok(cred) => {
-            // Verify their password
-            if cred.verify(creds.password.as_str()) {
-                // Generate a JWT
-                match gen_jwt(creds.username.as_str()) {
-                    Ok(jwt) => {
-                        // Create a cookie with the JWT
-                        let token_cookie_builder = CookieBuilder::new(JWT_TOKEN_COOKIE_NAME, jwt)
-                            .same_site(SameSite::Strict)
-                            .http_only(true)
-                            .secure(!has_dev_flag());
-                        let token_cookie = if !creds.session_only {
-                                token_cookie_builder.max_age(Duration::minutes(JWT_DURATION_MINUTES)).finish()
-                            } else {
-                                token_cookie_builder.finish()
-                            };
-                        let mut response = HttpResponse::Ok().json(ResponseObject::new());
-                        if response.add_cookie(&token_cookie).is_err() {
-                            return APIError::InternalServerError("failed to set authentication-cookie".to_string()).gen_response()} //Cookie couldn't be parsed
-                        response
+    match db.get_credential(&creds.username).await {
+        Ok(mut cred) => {
+            // Verify their password, re-hashing it if its hash is outdated
+            let verification = cred.verify_and_upgrade(creds.password.as_str(), &HashPolicy::from_env());
+            if verification == Verification::Upgraded && db.update_credential(&cred).await.is_err() {
+                warn!("The password-hash of '{}' could not be upgraded", cred._id); // The login still succeeds
+            }
+            if verification != Verification::Invalid {
+                // Ask for the second factor before starting a session
+                if cred.totp_enabled {
+                    return match get_keys(&req).and_then(|keys| gen_challenge(keys, &cred._id, creds.session_only)) {
+                        Ok(challenge) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(
+                            SecondFactorResponse { second_factor_required: true, challenge })),
+                        Err(e) => e.gen_response()
+                    }
+                }
+                limiter.record_success(&cred._id);
+                start_session(&req, db.get_ref(), &cred._id, creds.session_only).await
+            } else {
+                //wrong password
+                limiter.record_failure(&cred._id, Utc::now());
+                APIError::InvalidCredentialsError("wrong credentials".to_string()).gen_response()
+            }
+        }
+        Err(DBError::NoDocumentFoundError) => {
+            // Users without local credentials may be known to the directory
+            if let Some(directory) = directory {
+                match directory.verify(&creds.username, &creds.password).await {
+                    Ok(Some(account)) => {
+                        if let Err(e) = sync_directory_user(db.get_ref(), &creds.username, account).await {
+                            return e.gen_response()
+                        }
+                        limiter.record_success(&creds.username);
+                        return start_session(&req, db.get_ref(), &creds.username, creds.session_only).await
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        warn!("The directory could not verify '{}': {}", creds.username, e);
+                        return APIError::InternalServerError("directory could not be reached".to_string()).gen_response()
                     }
-                    Err(e) => e.gen_response()
                 }
-            } else { APIError::InvalidCredentialsError("wrong credentials".to_string()).gen_response() } //wrong password
+            }
+            //No user with that username has been found, which must be indistinguishable from a wrong password
+            limiter.record_failure(&creds.username, Utc::now());
+            APIError::InvalidCredentialsError("wrong credentials".to_string()).gen_response()
         }
-        Err(DBError::NoDocumentFoundError) => APIError::InvalidCredentialsError("wrong credentials".to_string()).gen_response(), //No user with that username has been found
         Err(_) => APIError::QueryError("can not access credentials".to_string()).gen_response() //Unknown
     }
 }
 
+/// ENDPOINT: Completes a login by verifying the second factor of the user, starting a new session
+/// and setting a JWT- and a refresh-cookie as proof.
+/// Recovery-codes are accepted in place of one-time passwords, but can only be used once
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[COOKIE: JWT, REFRESH\] Second factor could be verified
+///     - **\[11\]** Wrong one-time password or recovery-code
+/// * `400`
+///     - **\[20\]** Invalid payload
+/// * `401`
+///     - **\[10\]** Invalid or expired challenge
+/// * `429`
+///     - **\[13\]** \[Retry-After\] Too many requests or the account is locked
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+/// * `limiter` - The AppData containing the RateLimiter
+/// * `factor_req` - The body of the request parsed to a SecondFactorRequest-object
+///
+/// # Examples
+///
+/// ```text
+/// POST-Request at `{api-url}/auth/totp`
+///     {
+///         "challenge": "eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzUxMiJ9.eyJzdWIiOiJ0ZXN0VXNlciIsInNlc3Npb25fb25seSI6ZmFsc2V9.R8y1nXb...",
+///         "code": "492039"
+///     }
+/// => 200 [cookies with JWT and refresh-token are set]
+///     {
+///         "success": true,
+///         "time": "2022-04-11 12:05:57"
+///     }
+/// ```
+/// ```text
+/// POST-Request at `{api-url}/auth/totp` (wrong one-time password)
+///     {
+///         "challenge": "eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzUxMiJ9.eyJzdWIiOiJ0ZXN0VXNlciIsInNlc3Npb25fb25seSI6ZmFsc2V9.R8y1nXb...",
+///         "code": "123456"
+///     }
+/// => 200
+///     {
+///         "success": false,
+///         "code": 11,
+///         "message": "failed to process credentials: wrong one-time password",
+///         "time": "2022-04-11 12:05:57"
+///     }
+/// ```
+#[post("/auth/totp", wrap = "from_fn(limit_requests)")]
+pub async fn verify_second_factor(req: HttpRequest, db: Data<dyn Storage>, limiter: Data<RateLimiter>, factor_req: web::Json<SecondFactorRequest>) -> impl Responder {
+    // Verify the challenge
+    let keys = match get_keys(&req) {
+        Ok(keys) => keys,
+        Err(e) => return e.gen_response()
+    };
+    let claims = match keys.decode::<ChallengeClaims>(&factor_req.challenge, Some(CHALLENGE_AUDIENCE)) {
+        Some(claims) => claims,
+        None => return APIError::AuthenticationError.gen_response() // Invalid or expired challenge
+    };
+    // Failed one-time passwords count towards the lockout of the account as well
+    if let Err(retry_after) = limiter.check_account(&claims.sub, Utc::now()) {
+        return APIError::TooManyAttemptsError(retry_after).gen_response()
+    }
+    let mut cred = match db.get_credential(&claims.sub).await {
+        Ok(cred) => cred,
+        Err(DBError::NoDocumentFoundError) => return APIError::AuthenticationError.gen_response(), // User removed in the meantime
+        Err(_) => return APIError::QueryError("can not access credentials".to_string()).gen_response()
+    };
+    // Verify the second factor, consuming the used code
+    if cred.totp_enabled && !check_second_factor(&mut cred, &factor_req.code) {
+        limiter.record_failure(&cred._id, Utc::now());
+        return APIError::InvalidCredentialsError("wrong one-time password".to_string()).gen_response()
+    }
+    if db.update_credential(&cred).await.is_err() {
+        return APIError::QueryError("failed to consume the one-time password".to_string()).gen_response()
+    }
+    limiter.record_success(&cred._id);
+    start_session(&req, db.get_ref(), &cred._id, claims.session_only).await
+}
+
+/// ENDPOINT: Exchanges the refresh-token of a session for a new one and a fresh JWT.
+/// Presenting an already exchanged refresh-token revokes the entire session
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[COOKIE: JWT, REFRESH\] Session has been refreshed
+/// * `401`
+///     - **\[10\]** No, invalid, reused or expired refresh-cookie found
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// POST-Request at `{api-url}/auth/refresh` with a cookie containing a valid refresh-token
+/// => 200 [cookies with JWT and refresh-token are replaced]
+///     {
+///         "success": true,
+///         "time": "2022-04-11 12:05:57"
+///     }
+/// ```
+/// ```text
+/// POST-Request at `{api-url}/auth/refresh` with a cookie containing an already used refresh-token
+/// => 401 [session is revoked]
+///     {
+///         "success": false,
+///         "code": 10,
+///         "message": "user is not logged in",
+///         "time": "2022-04-11 12:05:57"
+///     }
+/// ```
+#[post("/auth/refresh")]
+pub async fn refresh(req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    // Split the refresh-token into the session and its secret
+    let (session_id, secret) = match req.cookie(REFRESH_TOKEN_COOKIE_NAME)
+        .and_then(|cookie| cookie.value().split_once('.').map(|(id, secret)| (id.to_string(), secret.to_string()))) {
+        Some(token) => token,
+        None => return APIError::AuthenticationError.gen_response()
+    };
+    if !is_safe(&session_id) {
+        return APIError::AuthenticationError.gen_response()
+    }
+    let session = match db.get_session(&session_id).await {
+        Ok(session) => session,
+        Err(DBError::NoDocumentFoundError) => return APIError::AuthenticationError.gen_response(),
+        Err(_) => return APIError::QueryError("session could not be retrieved from database".to_string()).gen_response()
+    };
+    // Expired sessions and reused refresh-tokens end the session
+    if session.expires_at < Utc::now() || session.refresh_hash.ne(&hash_token(&secret)) {
+        if db.remove_session(&session._id).await.is_err() {
+            return APIError::QueryError("session could not be revoked".to_string()).gen_response()
+        }
+        return APIError::AuthenticationError.gen_response()
+    }
+    // Rotate the refresh-token
+    let now = Utc::now();
+    let (refresh_token, refresh_hash) = gen_refresh_token(&session._id);
+    let refreshed = Session {
+        refresh_hash,
+        ip: get_ip(&req),
+        last_seen: now,
+        expires_at: now + chrono::Duration::days(SESSION_DURATION_DAYS),
+        ..session.clone()
+    };
+    match db.set_session_fields(&refreshed, &session.refresh_hash).await {
+        Ok(_) => gen_session_response(&req, &refreshed, refresh_token, HttpResponse::Ok().json(ResponseObject::new())),
+        Err(DBError::NoDocumentFoundError) => APIError::AuthenticationError.gen_response(), //refreshed or revoked in the meantime
+        Err(_) => APIError::QueryError("session could not be refreshed".to_string()).gen_response()
+    }
+}
+
 /// ENDPOINT: Checks if a user is currently logged in
 ///
 /// Returns one of the following HttpResponses:
@@ -136,7 +444,7 @@ pub async fn authenticate(db: Data<Mutex<Database>>, creds: web::Json<json_objec
 /// # Arguments
 ///
 /// * `req` - The HttpRequest that was made
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `db` - The AppData containing the storage-backend
 ///
 /// # Examples
 ///
@@ -160,8 +468,8 @@ pub async fn authenticate(db: Data<Mutex<Database>>, creds: web::Json<json_objec
 ///     }
 /// ```
 #[get("/auth")]
-pub async fn get_auth_status(req: HttpRequest, db: Data<Mutex<Database>>) -> impl Responder {
-    match get_user_from_request(req, &db).await {
+pub async fn get_auth_status(req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    match get_user_from_request(req, db.get_ref()).await {
         Ok(user) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(
             doc! {"username": user._id})),
         Err(APIError::AuthenticationError) => {
@@ -173,23 +481,26 @@ pub async fn get_auth_status(req: HttpRequest, db: Data<Mutex<Database>>) -> imp
     }
 }
 
-/// ENDPOINT: Removes all verification from the client to effectively log them out
+/// ENDPOINT: Ends the current session and removes all verification from the client to effectively log them out
 ///
 /// Returns one of the following HttpResponses:
 /// * `200`
-///     - \[REMOVAL_COOKIE: JWT\] Valid JWT-cookie found
+///     - \[REMOVAL_COOKIE: JWT, REFRESH\] Valid JWT-cookie found
 /// * `401`
 ///     - **\[10\]** No or invalid JWT-cookie found
+/// * `500`
+///     - Something went wrong internally (debug)
 ///
 /// # Arguments
 ///
 /// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
 ///
 /// # Examples
 ///
 /// ```text
 /// DELETE-Request at `{api-url}/auth` with a cookie containing a valid JWT
-/// => 200 [cookie is removed]
+/// => 200 [cookies are removed]
 ///     {
 ///         "success": true,
 ///         "time": "2022-04-11 12:05:57"
@@ -206,28 +517,282 @@ pub async fn get_auth_status(req: HttpRequest, db: Data<Mutex<Database>>) -> imp
 ///     }
 /// ```
 #[delete("/auth")]
-pub async fn logout(req: HttpRequest) -> impl Responder {
-    match get_user_id_from_request(req) {
-        Ok(_) => gen_logout_response(),
+pub async fn logout(req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    match get_session_from_request(&req, db.get_ref()).await {
+        Ok(session) => match db.remove_session(&session._id).await {
+            Ok(_) => gen_logout_response(),
+            Err(_) => APIError::QueryError("session could not be revoked".to_string()).gen_response()
+        },
         Err(e) => e.gen_response()
     }
 }
 
+/// ENDPOINT: Lists all active sessions of the current user
+///
+/// Returns one of the following HttpResponses:
+/// * `200` [Body: JSON]
+///     - Sessions have been compiled
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/auth/sessions` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": [
+///             {
+///                 "session_id": "dWq8mYc1ZrT4kLx0pVb2NsGe",
+///                 "device": "Mozilla/5.0 (X11; Linux x86_64; rv:104.0) Gecko/20100101 Firefox/104.0",
+///                 "ip": "192.168.178.21",
+///                 "created_at": "2022-04-09T17:01:13.004Z",
+///                 "last_seen": "2022-04-11T12:04:41.872Z",
+///                 "current": true
+///             },
+///             {
+///                 "session_id": "Hj3kPq9sXo2LmZ7aVc4BnR1t",
+///                 "device": "Mozilla/5.0 (Android 12; Mobile; rv:104.0) Gecko/104.0 Firefox/104.0",
+///                 "ip": "192.168.178.35",
+///                 "created_at": "2022-04-10T09:12:45.310Z",
+///                 "last_seen": "2022-04-10T09:30:02.117Z",
+///                 "current": false
+///             }
+///         ],
+///         "time": "2022-04-11 12:05:57"
+///     }
+/// ```
+/// ```text
+/// GET-Request at `{api-url}/auth/sessions` without a cookie containing a JWT
+/// => 401
+///     {
+///         "success": false,
+///         "code": 10,
+///         "message": "user is not logged in",
+///         "time": "2022-04-11 12:05:57"
+///     }
+/// ```
+#[get("/auth/sessions")]
+pub async fn list_sessions(req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let current = match get_session_from_request(&req, db.get_ref()).await {
+        Ok(session) => session,
+        Err(e) => return e.gen_response()
+    };
+    match db.get_sessions(&current.user_id).await {
+        Ok(sessions) => {
+            let now = Utc::now();
+            let sessions: Vec<SessionResponse> = sessions.into_iter()
+                .filter(|session| session.expires_at > now)
+                .map(|session| SessionResponse {
+                    current: session._id.eq(&current._id),
+                    session_id: session._id,
+                    device: session.device,
+                    ip: session.ip,
+                    created_at: session.created_at,
+                    last_seen: session.last_seen
+                }).collect();
+            HttpResponse::Ok().json(ResponseObjectWithPayload::new(sessions))
+        }
+        Err(_) => APIError::QueryError("sessions could not be retrieved from database".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Revokes a session of the current user, logging the device using it out
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - Session has been revoked
+///     - \[REMOVAL_COOKIE: JWT, REFRESH\] The current session has been revoked
+/// * `400`
+///     - **\[21\]** Invalid session-ID
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `404`
+///     - **\[22\]** The user has no session with the given ID
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the to-be-revoked session
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// DELETE-Request at `{api-url}/auth/sessions/Hj3kPq9sXo2LmZ7aVc4BnR1t` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-11 12:05:57"
+///     }
+/// ```
+/// ```text
+/// DELETE-Request at `{api-url}/auth/sessions/Hj3kPq9sXo2LmZ7aVc4BnR1t` (session of another user)
+/// => 404
+///     {
+///         "success": false,
+///         "code": 22,
+///         "message": "requested resource does not exist: session",
+///         "time": "2022-04-11 12:05:57"
+///     }
+/// ```
+#[delete("/auth/sessions/{session_id}")]
+pub async fn revoke_session(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let session_id = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&session_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    let current = match get_session_from_request(&req, db.get_ref()).await {
+        Ok(session) => session,
+        Err(e) => return e.gen_response()
+    };
+    // Only the own sessions can be revoked
+    match db.get_session(&session_id).await {
+        Ok(session) if session.user_id.eq(&current.user_id) => {}
+        Ok(_) | Err(DBError::NoDocumentFoundError) => return APIError::ResourceNotFoundError("session".to_string()).gen_response(),
+        Err(_) => return APIError::QueryError("session could not be retrieved from database".to_string()).gen_response()
+    }
+    if db.remove_session(&session_id).await.is_err() {
+        return APIError::QueryError("session could not be revoked".to_string()).gen_response()
+    }
+    if session_id.eq(&current._id) {
+        gen_logout_response()
+    } else {
+        HttpResponse::Ok().json(ResponseObject::new())
+    }
+}
+
+/// Creates the user of an account verified by the directory on their first login, updating their roles on every other
+///
+/// # Arguments
+///
+/// * `db` - Reference to the storage-backend
+/// * `username` - The name of the user
+/// * `account` - The account verified by the directory
+async fn sync_directory_user(db: &dyn Storage, username: &str, account: DirectoryAccount) -> Result<(), APIError> {
+    match db.get_user(username).await {
+        Ok(user) if user.roles == account.roles => Ok(()),
+        Ok(_) => db.set_roles(username, &account.roles).await
+            .map_err(|_| APIError::QueryError("roles could not be updated".to_string())),
+        Err(DBError::NoDocumentFoundError) => {
+            if !is_safe(username) {
+                return Err(APIError::InvalidIDError)
+            }
+            let user = User { _id: username.to_string(), allowances: Vec::new(), connections: Vec::new(), roles: account.roles };
+            db.insert_user(&user).await.map_err(|_| APIError::QueryError("user could not be created".to_string()))
+        }
+        Err(_) => Err(APIError::QueryError("user could not be retrieved".to_string()))
+    }
+}
+
+/// Starts a new session for a user whose credentials have been verified,
+/// returning a Response carrying the proof of it
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `db` - Reference to the storage-backend
+/// * `user_id` - The identifier of the user
+/// * `session_only` - Whether the session should end together with the browser-session
+async fn start_session(req: &HttpRequest, db: &dyn Storage, user_id: &str, session_only: bool) -> HttpResponse {
+    start_session_with(req, db, user_id, session_only, HttpResponse::Ok().json(ResponseObject::new())).await
+}
+
+/// Starts a new session for a user whose credentials have been verified,
+/// adding the proof of it to the given Response
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `db` - Reference to the storage-backend
+/// * `user_id` - The identifier of the user
+/// * `session_only` - Whether the session should end together with the browser-session
+/// * `response` - The Response to be returned once the session has been started
+pub async fn start_session_with(req: &HttpRequest, db: &dyn Storage, user_id: &str, session_only: bool, response: HttpResponse) -> HttpResponse {
+    let now = Utc::now();
+    let session_id: String = rand::thread_rng().sample_iter(&Alphanumeric)
+        .take(SESSION_ID_SIZE).map(char::from).collect();
+    let (refresh_token, refresh_hash) = gen_refresh_token(&session_id);
+    let session = Session {
+        _id: session_id,
+        user_id: user_id.to_string(),
+        refresh_hash,
+        device: get_device(req),
+        ip: get_ip(req),
+        session_only,
+        created_at: now,
+        last_seen: now,
+        expires_at: now + chrono::Duration::days(SESSION_DURATION_DAYS)
+    };
+    if db.insert_session(&session).await.is_err() {
+        return APIError::QueryError("session could not be created".to_string()).gen_response()
+    }
+    gen_session_response(req, &session, refresh_token, response)
+}
+
+/// Adds a fresh JWT and the given refresh-token of a session to a Response
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `session` - The session to be proven
+/// * `refresh_token` - The refresh-token currently valid for the session
+/// * `response` - The Response to carry the tokens
+fn gen_session_response(req: &HttpRequest, session: &Session, refresh_token: String, mut response: HttpResponse) -> HttpResponse {
+    // Generate a JWT
+    let jwt = match get_keys(req).and_then(|keys| gen_jwt(keys, &session.user_id, &session._id)) {
+        Ok(jwt) => jwt,
+        Err(e) => return e.gen_response()
+    };
+    // Create a cookie for each token
+    for (name, value, max_age) in [(JWT_TOKEN_COOKIE_NAME, jwt, Duration::minutes(JWT_DURATION_MINUTES)),
+                                   (REFRESH_TOKEN_COOKIE_NAME, refresh_token, Duration::days(SESSION_DURATION_DAYS))] {
+        let cookie_builder = CookieBuilder::new(name, value)
+            .same_site(SameSite::Strict)
+            .http_only(true)
+            .secure(!has_dev_flag());
+        let cookie = if !session.session_only {
+                cookie_builder.max_age(max_age).finish()
+            } else {
+                cookie_builder.finish()
+            };
+        if response.add_cookie(&cookie).is_err() {
+            return APIError::InternalServerError("failed to set authentication-cookie".to_string()).gen_response() //Cookie couldn't be parsed
+        }
+    }
+    response
+}
+
 #[allow(unused_must_use)]
 /// Creates a Response that revokes any form of login-verification
 pub fn gen_logout_response() -> HttpResponse {
     let mut resp = HttpResponse::Ok().json(ResponseObject::new());
-    resp.add_removal_cookie(&CookieBuilder::new(JWT_TOKEN_COOKIE_NAME, "-1")
-        .same_site(SameSite::Strict).http_only(true).finish());
+    for name in [JWT_TOKEN_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME] {
+        resp.add_removal_cookie(&CookieBuilder::new(name, "-1")
+            .same_site(SameSite::Strict).http_only(true).finish());
+    }
     resp
 }
 
-/// Creates a JWT with the username as its payload
+/// Creates a JWT with the username and the session as its payload
 ///
 /// # Arguments
 ///
+/// * `keys` - The keys signing the JWT
 /// * `uid` - The username to save
-fn gen_jwt(uid: &str) -> Result<String, APIError> {
+/// * `sid` - The identifier of the session
+fn gen_jwt(keys: &KeySet, uid: &str, sid: &str) -> Result<String, APIError> {
     // Set all required values
     let expiration = Utc::now()
         .checked_add_signed(chrono::Duration::minutes(JWT_DURATION_MINUTES))
@@ -235,43 +800,192 @@ fn gen_jwt(uid: &str) -> Result<String, APIError> {
         .timestamp();
     let claims = Claims {
         sub: uid.to_owned(),
+        sid: sid.to_owned(),
         exp: expiration as usize
     };
-    let header = Header::new(Algorithm::HS512);
-
     // Generate the JWT
-    encode(&header, &claims, &EncodingKey::from_secret(env::var(JWT_SECRET_ENV_VAR_KEY).unwrap().as_bytes()))
+    keys.encode(&claims)
         .map_err(|_| APIError::InternalServerError("jwt-token creation failed".to_string()))
 }
 
-/// Retrieves the username of the current user using the JWT-cookie in a HttpRequest
+/// Creates a JWT proving the password of a user has been verified, while their second factor is still pending
+///
+/// # Arguments
+///
+/// * `keys` - The keys signing the JWT
+/// * `uid` - The username to save
+/// * `session_only` - Whether the session to be started should end together with the browser-session
+fn gen_challenge(keys: &KeySet, uid: &str, session_only: bool) -> Result<String, APIError> {
+    let expiration = Utc::now()
+        .checked_add_signed(chrono::Duration::minutes(CHALLENGE_DURATION_MINUTES))
+        .expect("Not a valid timestamp")
+        .timestamp();
+    let claims = ChallengeClaims {
+        sub: uid.to_owned(),
+        session_only,
+        aud: CHALLENGE_AUDIENCE.to_string(),
+        exp: expiration as usize
+    };
+    keys.encode(&claims)
+        .map_err(|_| APIError::InternalServerError("challenge creation failed".to_string()))
+}
+
+/// Creates a new refresh-token for a session, returning it together with the hash to be stored
+///
+/// # Arguments
+///
+/// * `session_id` - The identifier of the session
+fn gen_refresh_token(session_id: &str) -> (String, String) {
+    let secret: String = rand::thread_rng().sample_iter(&Alphanumeric)
+        .take(REFRESH_SECRET_SIZE).map(char::from).collect();
+    (format!("{}.{}", session_id, secret), hash_token(&secret))
+}
+
+/// Hashes a randomly generated token, so it can be stored without granting access on its own.
+/// As these tokens are long and random, a single fast hash suffices
+///
+/// # Arguments
+///
+/// * `token` - The token to be hashed
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Describes the device a request was made from using its user-agent
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+fn get_device(req: &HttpRequest) -> String {
+    req.headers().get(USER_AGENT).and_then(|agent| agent.to_str().ok())
+        .unwrap_or("unknown").to_string()
+}
+
+/// Returns the IP-address a request was made from
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+pub fn get_ip(req: &HttpRequest) -> String {
+    req.peer_addr().map_or_else(|| "unknown".to_string(), |addr| addr.ip().to_string())
+}
+
+/// Retrieves the session a request was made with using the JWT-cookie in a HttpRequest.
+/// Revoked and expired sessions are rejected, personal access-tokens are not considered at all
 ///
 /// # Arguments
 ///
 /// * `req` - HttpRequest from which the cookie and therefore the JWT gets extracted
-pub fn get_user_id_from_request(req: HttpRequest) -> Result<String, APIError> { //TODO Make private
-    match req.cookie(JWT_TOKEN_COOKIE_NAME) {
-        Some(cookie) => decode::<Claims>(cookie.value(),
-                                         &DecodingKey::from_secret(env::var(JWT_SECRET_ENV_VAR_KEY).unwrap().as_bytes()),
-                                         &Validation::new(Algorithm::HS512))
-            .map(|dec|dec.claims.sub).map_err(|_|APIError::AuthenticationError), // Invalid JWT
-        None => Err(APIError::AuthenticationError) // No JWT-cookie
+/// * `db` - Reference to the storage-backend
+pub async fn get_session_from_request(req: &HttpRequest, db: &dyn Storage) -> Result<Session, APIError> {
+    // Verify jwt
+    let claims = match req.cookie(JWT_TOKEN_COOKIE_NAME) {
+        Some(cookie) => get_keys(req)?.decode::<Claims>(cookie.value(), None)
+            .ok_or(APIError::AuthenticationError)?, // Invalid JWT
+        None => return Err(APIError::AuthenticationError) // No JWT-cookie
+    };
+    // Check whether the session is still active
+    let session = match db.get_session(&claims.sid).await {
+        Ok(session) => session,
+        Err(DBError::NoDocumentFoundError) => return Err(APIError::AuthenticationError), // Revoked session
+        Err(_) => return Err(APIError::QueryError("session could not be retrieved from database".to_string()))
+    };
+    let now = Utc::now();
+    if session.user_id.ne(&claims.sub) || session.expires_at < now {
+        return Err(APIError::AuthenticationError)
+    }
+    // Recording the usage is not worth failing the request over
+    if now - session.last_seen > chrono::Duration::seconds(LAST_SEEN_INTERVAL_SECONDS) {
+        let _ = db.touch_session(&session._id, now).await;
     }
+    Ok(session)
 }
 
+/// Returns the scope a personal access-token needs to access the endpoint a request was made at.
+/// Endpoints without a scope can only be accessed using a session
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+fn get_required_scope(req: &HttpRequest) -> Option<TokenScope> {
+    match req.match_name()? {
+        "list_notes" | "search_notes" | "get_note" | "list_revisions" | "get_revision" | "diff_revision"
+        | "list_folders" | "get_folder" => Some(TokenScope::NotesRead),
+        "add_note" | "update_note" | "remove_note" | "live_note" | "restore_revision"
+        | "add_folder" | "update_folder" | "remove_folder" | "move_note" => Some(TokenScope::NotesWrite),
+        "get_relation_code" | "create_relation" | "remove_relation" | "update_allowances"
+        | "add_link" | "list_links" | "remove_link" | "update_group_allowances" | "add_group" | "list_groups" | "get_group"
+        | "remove_group" | "add_member" | "remove_member" | "share_folder"
+        | "offer_transfer" | "list_transfers" | "accept_transfer" | "cancel_transfer" => Some(TokenScope::ShareManage),
+        _ => None
+    }
+}
 
-/// Retrieves a User-object from the DB using the JWT-cookie in a HttpRequest
+/// Retrieves the username of the user a personal access-token belongs to,
+/// given it grants access to the endpoint the request was made at
 ///
 /// # Arguments
 ///
-/// * `req` - HttpRequest from which the cookie and therefore the JWT gets extracted
-/// * `db` - Reference to a Mutex-secured Database-connection
-pub async fn get_user_from_request(req: HttpRequest, db: &Mutex<Database>) -> Result<User,APIError> {
+/// * `req` - The HttpRequest that was made
+/// * `token` - The personal access-token taken from the request
+/// * `db` - Reference to the storage-backend
+async fn get_user_id_from_token(req: &HttpRequest, token: &str, db: &dyn Storage) -> Result<String, APIError> {
+    // Split the token into its identifier and secret
+    let (token_id, secret) = token.strip_prefix(API_TOKEN_PREFIX).and_then(|token| token.split_once('.'))
+        .ok_or(APIError::AuthenticationError)?;
+    if !is_safe(token_id) {
+        return Err(APIError::AuthenticationError)
+    }
+    let token = match db.get_token(token_id).await {
+        Ok(token) => token,
+        Err(DBError::NoDocumentFoundError) => return Err(APIError::AuthenticationError), // Revoked token
+        Err(_) => return Err(APIError::QueryError("token could not be retrieved from database".to_string()))
+    };
+    let now = Utc::now();
+    if token.token_hash.ne(&hash_token(secret)) || token.expires_at.is_some_and(|expires_at| expires_at < now) {
+        return Err(APIError::AuthenticationError)
+    }
+    // Check whether the token may access the endpoint
+    match get_required_scope(req) {
+        Some(scope) if token.scopes.contains(&scope) => {}
+        _ => return Err(APIError::NoPermissionError)
+    }
+    // Recording the usage is not worth failing the request over
+    if token.last_used.is_none_or(|last_used| now - last_used > chrono::Duration::seconds(LAST_SEEN_INTERVAL_SECONDS)) {
+        let _ = db.touch_token(&token._id, now).await;
+    }
+    Ok(token.user_id)
+}
+
+/// Retrieves the username of the current user using either a personal access-token
+/// from the Authorization-header or the JWT-cookie in a HttpRequest
+///
+/// # Arguments
+///
+/// * `req` - HttpRequest from which the token or the cookie and therefore the JWT gets extracted
+/// * `db` - Reference to the storage-backend
+pub async fn get_user_id_from_request(req: HttpRequest, db: &dyn Storage) -> Result<String, APIError> { //TODO Make private
+    let bearer = req.headers().get(AUTHORIZATION).and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix(BEARER_SCHEME));
+    match bearer {
+        Some(token) => get_user_id_from_token(&req, token.trim(), db).await,
+        None => get_session_from_request(&req, db).await.map(|session| session.user_id)
+    }
+}
+
+
+/// Retrieves a User-object from the DB using either a personal access-token or the JWT-cookie in a HttpRequest
+///
+/// # Arguments
+///
+/// * `req` - HttpRequest from which the token or the cookie and therefore the JWT gets extracted
+/// * `db` - Reference to the storage-backend
+pub async fn get_user_from_request(req: HttpRequest, db: &dyn Storage) -> Result<User,APIError> {
     // Verify jwt
-    match get_user_id_from_request(req) {
+    match get_user_id_from_request(req, db).await {
         Ok(user_id) => {
             // Extract the user
-            match get_dbo_by_id::<User>(USER, user_id, db).await {
+            match db.get_user(&user_id).await {
                 Ok(user) => Ok(user),
                 Err(DBError::NoDocumentFoundError) => Err(APIError::AuthenticationError),
                 Err(_) => Err(APIError::QueryError("user could not be retrieved from database".to_string()))
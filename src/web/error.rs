@@ -6,10 +6,13 @@
 //!     * **\[10\]** `AuthenticationError` - Occurs when accessing a secured endpoint without prior authentication
 //!     * **\[11\]** `InvalidCredentialsError` - Occurs when a given set of credentials can not be processed
 //!     * **\[12\]** `NoPermissionError` - Occurs when accessing a secured endpoint without sufficient authorization
+//!     * **\[13\]** `TooManyAttemptsError` - Occurs when exceeding a rate-limit or trying to log into a locked account
 //!
 //! + Formal
 //!     * **\[20\]** `InvalidPayloadError` - Occurs when a given payload does not match with the endpoints expectations
 //!     * **\[21\]** `InvalidIDError` - Occurs when a given ID contains invalid character
+//!     * **\[22\]** `ResourceNotFoundError` - Occurs when requesting a resource that does not exist
+//!     * **\[23\]** `VersionConflictError` - Occurs when modifying a resource based on an outdated version of it
 //!     * **\[24\]** `InvalidInstructionsError` - Occurs when issuing an instruction that is invalid in context
 //!     * **\[27\]** `InvalidInviteError` - Occurs when accessing a secured endpoint without prior authentication
 //!
@@ -19,8 +22,10 @@
 //!     * **\[55\]** `DBInconsistencyError` - Occurs whenever an inconsistency within the database is discovered
 
 use actix_web::{HttpResponse, HttpResponseBuilder};
+use actix_web::http::header::{ETAG, RETRY_AFTER};
 use thiserror::Error;
 use serde::Serialize;
+use crate::web::note::gen_etag;
 use crate::web::TIME_FORMAT;
 
 /// Struct modelling the response-body of an error
@@ -32,11 +37,15 @@ struct ErrorResponse {
     code: i8,
     /// Specific description of what went wrong
     message: String,
+    /// Further information on what went wrong, specific to the endpoint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
     /// Timestamp of when the response was created
     time: String
 }
 
 /// Error-types that can appear in processing requests
+#[allow(clippy::enum_variant_names)]
 #[derive(Error, Debug)]
 pub enum APIError {
     // auth-based error
@@ -49,6 +58,9 @@ pub enum APIError {
     /// An error that occurs when attempting something the user is not allowed to
     #[error("no permission")]
     NoPermissionError,
+    /// An error that occurs when making too many requests or failed logins in a short amount of time
+    #[error("too many attempts, retry in {0} seconds")]
+    TooManyAttemptsError(i64),
 
     // formal error
     /// An error that occurs when the payload of a request doesn't fit what the endpoint expects
@@ -57,6 +69,12 @@ pub enum APIError {
     /// An error that occurs when trying to access a resource with an invalid id
     #[error("requested id contains forbidden character")]
     InvalidIDError,
+    /// An error that occurs when trying to access a resource that does not exist
+    #[error("requested resource does not exist: {0}")]
+    ResourceNotFoundError(String),
+    /// An error that occurs when trying to modify a resource that has been modified in the meantime
+    #[error("resource has been modified in the meantime (current version: {0})")]
+    VersionConflictError(u64),
     /// An error that occurs when given an instruction that is invalid in context
     #[error("invalid instruction: {0}")]
     InvalidInstructionsError(String),
@@ -84,9 +102,12 @@ impl APIError {
             APIError::AuthenticationError => (HttpResponse::Unauthorized(),10),
             APIError::InvalidCredentialsError(_) => (HttpResponse::Ok(),11),
             APIError::NoPermissionError => (HttpResponse::Forbidden(),12),
+            APIError::TooManyAttemptsError(_) => (HttpResponse::TooManyRequests(),13),
             // formal error
             APIError::InvalidPayloadError => (HttpResponse::BadRequest(),20),
             APIError::InvalidIDError => (HttpResponse::BadRequest(),21),
+            APIError::ResourceNotFoundError(_) => (HttpResponse::NotFound(),22),
+            APIError::VersionConflictError(_) => (HttpResponse::PreconditionFailed(),23),
             APIError::InvalidInstructionsError(_) => (HttpResponse::Ok(),24),
             APIError::InvalidInviteError => (HttpResponse::Ok(),27),
             // internal error
@@ -98,11 +119,38 @@ impl APIError {
 
     /// Creates a HttpResponse representing itself
     pub fn gen_response(&self) -> HttpResponse {
+        self.gen_response_with(None)
+    }
+
+    /// Creates a HttpResponse representing itself, telling the client in detail what went wrong
+    ///
+    /// # Arguments
+    ///
+    /// * `details` - Further information to be returned alongside the error
+    pub fn gen_response_with_details<T: Serialize>(&self, details: T) -> HttpResponse {
+        self.gen_response_with(serde_json::to_value(details).ok())
+    }
+
+    /// Creates a HttpResponse representing itself, with optional details
+    ///
+    /// # Arguments
+    ///
+    /// * `details` - Further information to be returned alongside the error
+    fn gen_response_with(&self, details: Option<serde_json::Value>) -> HttpResponse {
         let (mut response_builder, error_code) = self.get_response_information();
+        // Let the client know which version it has to base its changes on
+        if let APIError::VersionConflictError(version) = self {
+            response_builder.insert_header((ETAG, gen_etag(*version)));
+        }
+        // Let the client know when to try again
+        if let APIError::TooManyAttemptsError(seconds) = self {
+            response_builder.insert_header((RETRY_AFTER, seconds.to_string()));
+        }
         response_builder.json(ErrorResponse {
             success: false,
             code: error_code,
             message: self.to_string(),
+            details,
             time: chrono::Local::now().format(TIME_FORMAT).to_string()
         })
     }
@@ -0,0 +1,649 @@
+//! Endpoints regarding folders, which arrange the notes of a user in a hierarchy
+//!
+//! A folder contains notes and other folders of the same owner. Sharing a folder gives a user access to every note inside of it,
+//! including those inside of its sub-folders, unless the note itself has been shared with them, which always takes precedence.
+//! Removing a folder keeps its contents, moving them into the folder that contained it.
+
+use std::collections::HashMap;
+use actix_web::{get, put, post, delete, Responder, HttpRequest, HttpResponse, web};
+use actix_web::web::{Data, Path};
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use crate::db_access::{AllowanceLevel, DBError, Folder, get_folder_level, is_safe, Storage, User};
+use crate::web::auth::get_user_from_request;
+use crate::web::error::APIError;
+use crate::web::folder::json_objects::{FolderRequest, FolderResponse, FolderShareRequest, MoveNoteRequest};
+use crate::web::note::get_allow_level_for_note;
+use crate::web::{ResponseObject, ResponseObjectWithPayload};
+
+/// Amount of characters making up the identifier of a folder
+const FOLDER_ID_SIZE: usize = 16;
+/// Maximum amount of characters in the name of a folder
+const MAX_FOLDER_NAME_LENGTH: usize = 64;
+
+// Response-/Request-Objects
+/// Structs modelling the request- and response-bodies
+mod json_objects {
+    use chrono::{DateTime, Utc};
+    use serde::{Serialize, Deserialize};
+    use crate::db_access::{AllowanceLevel, Folder, FolderShare};
+
+    /// Body of a request for a new or altered folder
+    #[derive(Deserialize)]
+    pub struct FolderRequest {
+        /// The name of the folder
+        pub name: String,
+        /// The folder to place the folder into (top-level if not set)
+        pub parent_id: Option<String>
+    }
+
+    /// Body of a request sharing a folder with a user
+    #[derive(Deserialize)]
+    pub struct FolderShareRequest {
+        /// User the folder is to be shared with
+        pub user_id: String,
+        /// The level of access being given to the user
+        pub allowance: AllowanceLevel
+    }
+
+    /// Body of a request moving a note into a folder
+    #[derive(Deserialize)]
+    pub struct MoveNoteRequest {
+        /// The folder to move the note into (none if not set)
+        pub folder_id: Option<String>
+    }
+
+    /// Body of a response containing a folder
+    #[derive(Serialize)]
+    pub struct FolderResponse {
+        /// The identifier of the folder
+        pub folder_id: String,
+        /// The name of the folder
+        pub name: String,
+        /// The owner of the folder and all notes inside of it
+        pub owner_id: String,
+        /// The folder containing this one
+        pub parent_id: Option<String>,
+        /// The folders directly inside of this one
+        pub folders: Vec<String>,
+        /// The notes directly inside of the folder
+        pub notes: Vec<String>,
+        /// The level of access the user has regarding the folder
+        pub allowance: AllowanceLevel,
+        /// The users the folder is shared with (only visible to the owner)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub shares: Option<Vec<FolderShare>>,
+        /// Timestamp of the creation
+        pub created_at: DateTime<Utc>
+    }
+    impl FolderResponse {
+        /// Creates the response describing a folder
+        ///
+        /// # Arguments
+        ///
+        /// * `folder` - The folder to be described
+        /// * `allowance` - The level of access the user has regarding the folder
+        /// * `folders` - The folders directly inside of it
+        pub fn new(folder: Folder, allowance: AllowanceLevel, folders: Vec<String>) -> Self {
+            FolderResponse {
+                folder_id: folder._id,
+                name: folder.name,
+                owner_id: folder.owner_id,
+                parent_id: folder.parent_id,
+                folders,
+                notes: folder.notes,
+                allowance,
+                shares: if allowance == AllowanceLevel::Owner { Some(folder.shares) } else { None },
+                created_at: folder.created_at
+            }
+        }
+    }
+}
+
+/// A folder the current user has access to
+struct FolderAccess {
+    /// The current user
+    user: User,
+    /// The folder in question
+    folder: Folder,
+    /// The level of access the user has regarding the folder
+    level: AllowanceLevel,
+    /// All folders of the owner, mapped by their identifier
+    folders: HashMap<String, Folder>
+}
+
+/// ENDPOINT: Creates a new folder owned by the current user
+///
+/// Returns one of the following HttpResponses:
+/// * `201`
+///     - \[Body: JSON\] Folder was created successfully
+/// * `400`
+///     - **\[20\]** The name is empty or too long
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** The parent is owned by someone else
+/// * `404`
+///     - **\[22\]** The parent does not exist or is not accessible
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `folder_req` - The body of the request parsed to a FolderRequest-object
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// POST-Request at `{api-url}/folders` with a cookie containing a valid JWT
+///     {
+///         "name": "Projects",
+///         "parent_id": null
+///     }
+/// => 201
+///     {
+///         "success": true,
+///         "content": {
+///             "folder_id": "Qm3vT8pLx1ZcR5nW",
+///             "name": "Projects",
+///             "owner_id": "testUser",
+///             "parent_id": null,
+///             "folders": [],
+///             "notes": [],
+///             "allowance": "Owner",
+///             "shares": [],
+///             "created_at": "2022-04-11T12:20:28.120Z"
+///         },
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+#[post("/folders")]
+pub async fn add_folder(req: HttpRequest, folder_req: web::Json<FolderRequest>, db: Data<dyn Storage>) -> impl Responder {
+    let folder_req = folder_req.into_inner();
+    let name = match check_name(&folder_req.name) {
+        Ok(name) => name,
+        Err(e) => return e.gen_response()
+    };
+    let user = match &folder_req.parent_id {
+        // Folders can only be placed inside of folders of the same owner
+        Some(parent_id) => match get_folder_access(parent_id, req, db.get_ref()).await {
+            Ok(access) if access.level == AllowanceLevel::Owner => access.user,
+            Ok(_) => return APIError::NoPermissionError.gen_response(),
+            Err(e) => return e.gen_response()
+        },
+        None => match get_user_from_request(req, db.get_ref()).await {
+            Ok(user) => user,
+            Err(e) => return e.gen_response()
+        }
+    };
+    let folder = Folder {
+        _id: rand::thread_rng().sample_iter(&Alphanumeric).take(FOLDER_ID_SIZE).map(char::from).collect(),
+        name,
+        owner_id: user._id,
+        parent_id: folder_req.parent_id,
+        notes: Vec::new(),
+        shares: Vec::new(),
+        created_at: Utc::now()
+    };
+    match db.insert_folder(&folder).await {
+        Ok(_) => HttpResponse::Created().json(ResponseObjectWithPayload::new(FolderResponse::new(folder, AllowanceLevel::Owner, Vec::new()))),
+        Err(_) => APIError::QueryError("folder could not be created".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Lists all folders the current user owns or has been given access to,
+/// including the folders inside of those shared with them
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] Folders have been compiled
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/folders` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": [
+///             {
+///                 "folder_id": "Wd2nK7sPb4YxM9tR",
+///                 "name": "Shared Projects",
+///                 "owner_id": "otherUser",
+///                 "parent_id": null,
+///                 "folders": [],
+///                 "notes": ["7254fa970b62u3ag62dr4d3l"],
+///                 "allowance": "Read",
+///                 "created_at": "2022-04-11T12:20:28.120Z"
+///             }
+///         ],
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[get("/folders")]
+pub async fn list_folders(req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let user = match get_user_from_request(req, db.get_ref()).await {
+        Ok(user) => user,
+        Err(e) => return e.gen_response()
+    };
+    // Compile the folders of the user and of everyone sharing a folder with them
+    let mut owners: Vec<String> = match db.get_shared_folders(&user._id).await {
+        Ok(shared) => shared.into_iter().map(|folder| folder.owner_id).collect(),
+        Err(_) => return APIError::QueryError("folders could not be retrieved from database".to_string()).gen_response()
+    };
+    owners.sort();
+    owners.dedup();
+    owners.insert(0, user._id.clone());
+    let mut response = Vec::new();
+    for owner_id in &owners {
+        let folders = match db.get_folders(owner_id).await {
+            Ok(folders) => folders,
+            Err(_) => return APIError::QueryError("folders could not be retrieved from database".to_string()).gen_response()
+        };
+        let by_id: HashMap<String, Folder> = folders.iter().map(|folder| (folder._id.clone(), folder.clone())).collect();
+        for folder in folders {
+            if let Some(level) = get_folder_level(&folder._id, &user._id, &by_id) {
+                let children = get_children(&folder._id, &by_id);
+                response.push(FolderResponse::new(folder, level, children));
+            }
+        }
+    }
+    HttpResponse::Ok().json(ResponseObjectWithPayload::new(response))
+}
+
+/// ENDPOINT: Returns a folder the current user owns or has been given access to
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] The folder has been found
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `404`
+///     - **\[22\]** The folder does not exist or is not accessible
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the folder
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/folders/Qm3vT8pLx1ZcR5nW` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": {
+///             "folder_id": "Qm3vT8pLx1ZcR5nW",
+///             "name": "Projects",
+///             "owner_id": "testUser",
+///             "parent_id": null,
+///             "folders": ["Hx6bN2wQe8RtY4kL"],
+///             "notes": ["7254fa970b62u3ag62dr4d3l"],
+///             "allowance": "Owner",
+///             "shares": [
+///                 {
+///                     "user_id": "otherUser",
+///                     "level": "Read"
+///                 }
+///             ],
+///             "created_at": "2022-04-11T12:20:28.120Z"
+///         },
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[get("/folders/{folder_id}")]
+pub async fn get_folder(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    match get_folder_access(&path.into_inner(), req, db.get_ref()).await {
+        Ok(access) => {
+            let children = get_children(&access.folder._id, &access.folders);
+            HttpResponse::Ok().json(ResponseObjectWithPayload::new(FolderResponse::new(access.folder, access.level, children)))
+        }
+        Err(e) => e.gen_response()
+    }
+}
+
+/// ENDPOINT: Renames a folder owned by the current user and moves it into another one
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - Folder has been updated
+///     - **\[24\]** Invalid instruction (the folder would be placed inside of itself)
+/// * `400`
+///     - **\[20\]** The name is empty or too long
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** The folder or the new parent is owned by someone else
+/// * `404`
+///     - **\[22\]** The folder or the new parent does not exist or is not accessible
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the folder
+/// * `req` - The HttpRequest that was made
+/// * `folder_req` - The body of the request parsed to a FolderRequest-object
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// PUT-Request at `{api-url}/folders/Hx6bN2wQe8RtY4kL` with a cookie containing a valid JWT
+///     {
+///         "name": "Archive",
+///         "parent_id": "Qm3vT8pLx1ZcR5nW"
+///     }
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+/// ```text
+/// PUT-Request at `{api-url}/folders/Qm3vT8pLx1ZcR5nW` with a cookie containing a valid JWT [Hx6bN2wQe8RtY4kL is inside of Qm3vT8pLx1ZcR5nW]
+///     {
+///         "name": "Projects",
+///         "parent_id": "Hx6bN2wQe8RtY4kL"
+///     }
+/// => 200
+///     {
+///         "success": false,
+///         "code": 24,
+///         "message": "invalid instruction: folder can't be moved inside of itself",
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[put("/folders/{folder_id}")]
+pub async fn update_folder(path: Path<String>, req: HttpRequest, folder_req: web::Json<FolderRequest>, db: Data<dyn Storage>) -> impl Responder {
+    let folder_req = folder_req.into_inner();
+    let name = match check_name(&folder_req.name) {
+        Ok(name) => name,
+        Err(e) => return e.gen_response()
+    };
+    let access = match get_folder_access(&path.into_inner(), req.clone(), db.get_ref()).await {
+        Ok(access) if access.level == AllowanceLevel::Owner => access,
+        Ok(_) => return APIError::NoPermissionError.gen_response(),
+        Err(e) => return e.gen_response()
+    };
+    if let Some(parent_id) = &folder_req.parent_id {
+        // Folders can only be placed inside of folders of the same owner
+        match get_folder_access(parent_id, req, db.get_ref()).await {
+            Ok(parent) if parent.level == AllowanceLevel::Owner => {}
+            Ok(_) => return APIError::NoPermissionError.gen_response(),
+            Err(e) => return e.gen_response()
+        }
+        // Walk up from the new parent, which must not lead to the folder itself
+        let mut current = Some(parent_id);
+        for _ in 0..access.folders.len() {
+            match current {
+                Some(id) if id.eq(&access.folder._id) =>
+                    return APIError::InvalidInstructionsError("folder can't be moved inside of itself".to_string()).gen_response(),
+                Some(id) => current = access.folders.get(id).and_then(|folder| folder.parent_id.as_ref()),
+                None => break
+            }
+        }
+    }
+    match db.update_folder(&access.folder._id, &name, folder_req.parent_id.as_deref()).await {
+        Ok(_) => HttpResponse::Ok().json(ResponseObject::new()),
+        Err(_) => APIError::QueryError("folder could not be updated".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Removes a folder owned by the current user.
+/// Its notes and folders are moved into the folder that contained it
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - Folder has been removed
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** The folder is owned by someone else
+/// * `404`
+///     - **\[22\]** The folder does not exist or is not accessible
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the folder
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// DELETE-Request at `{api-url}/folders/Qm3vT8pLx1ZcR5nW` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[delete("/folders/{folder_id}")]
+pub async fn remove_folder(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let access = match get_folder_access(&path.into_inner(), req, db.get_ref()).await {
+        Ok(access) if access.level == AllowanceLevel::Owner => access,
+        Ok(_) => return APIError::NoPermissionError.gen_response(),
+        Err(e) => return e.gen_response()
+    };
+    // The contents are handed over to the parent by the storage-backend
+    match db.remove_folder(&access.folder._id).await {
+        Ok(_) => HttpResponse::Ok().json(ResponseObject::new()),
+        Err(_) => APIError::QueryError("folder could not be removed".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Takes a list of users and their level of access and shares a folder owned by the current user accordingly.
+/// Every note inside of the folder inherits the level, unless it has been shared with the user itself.
+/// The whole list is checked before anything is changed, and either all shares are updated or none of them
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - All Shares have been updated
+///     - **\[24\]** Invalid instruction (a user is not connected with the owner)
+/// * `400`
+///     - **\[20\]** A user is listed twice or is to become owner of the folder
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** The folder is owned by someone else
+/// * `404`
+///     - **\[22\]** The folder does not exist or is not accessible
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the folder
+/// * `req` - The HttpRequest that was made
+/// * `share_req` - The body of the request parsed to a Vector containing FolderShareRequest-objects
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// PUT-Request at `{api-url}/folders/Qm3vT8pLx1ZcR5nW/share` with a cookie containing a valid JWT
+///     [
+///         {
+///             "user_id": "otherUser",
+///             "allowance": "Read"
+///         }
+///     ]
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[put("/folders/{folder_id}/share")]
+pub async fn share_folder(path: Path<String>, req: HttpRequest, share_req: web::Json<Vec<FolderShareRequest>>, db: Data<dyn Storage>) -> impl Responder {
+    let access = match get_folder_access(&path.into_inner(), req, db.get_ref()).await {
+        Ok(access) if access.level == AllowanceLevel::Owner => access,
+        Ok(_) => return APIError::NoPermissionError.gen_response(),
+        Err(e) => return e.gen_response()
+    };
+    // Check all changes before applying any of them
+    let share_req = share_req.into_inner();
+    for (i, share) in share_req.iter().enumerate() {
+        if share.allowance == AllowanceLevel::Owner || share_req[..i].iter().any(|other| other.user_id.eq(&share.user_id)) {
+            return APIError::InvalidPayloadError.gen_response()
+        }
+        if !access.user.connections.contains(&share.user_id) {
+            return APIError::InvalidInstructionsError("user don't share a connection".to_string()).gen_response()
+        }
+    }
+    let changes: Vec<(String, AllowanceLevel)> = share_req.into_iter().map(|share| (share.user_id, share.allowance)).collect();
+    if db.set_folder_shares(&access.folder._id, &changes).await.is_err() {
+        return APIError::QueryError("shares could not be updated".to_string()).gen_response()
+    }
+    HttpResponse::Ok().json(ResponseObject::new())
+}
+
+/// ENDPOINT: Moves a note owned by the current user into one of their folders, or out of any folder
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - Note has been moved
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** The note or the folder is owned by someone else
+/// * `404`
+///     - **\[22\]** The folder does not exist or is not accessible
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the note
+/// * `req` - The HttpRequest that was made
+/// * `move_req` - The body of the request parsed to a MoveNoteRequest-object
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// PUT-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/folder` with a cookie containing a valid JWT
+///     {
+///         "folder_id": "Qm3vT8pLx1ZcR5nW"
+///     }
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[put("/note/{note_id}/folder")]
+pub async fn move_note(path: Path<String>, req: HttpRequest, move_req: web::Json<MoveNoteRequest>, db: Data<dyn Storage>) -> impl Responder {
+    let note_id = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&note_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    // Only the owner may arrange a note, and only inside of their own folders
+    match get_allow_level_for_note(&note_id, req.clone(), db.get_ref()).await {
+        Ok(AllowanceLevel::Owner) => {}
+        Ok(_) => return APIError::NoPermissionError.gen_response(),
+        Err(e) => return e.gen_response()
+    }
+    let folder_id = move_req.into_inner().folder_id;
+    if let Some(folder_id) = &folder_id {
+        match get_folder_access(folder_id, req, db.get_ref()).await {
+            Ok(access) if access.level == AllowanceLevel::Owner => {}
+            Ok(_) => return APIError::NoPermissionError.gen_response(),
+            Err(e) => return e.gen_response()
+        }
+    }
+    // The note only gets added once it has been taken out of its former folder
+    let result = match &folder_id {
+        Some(folder_id) => match db.pull_folder_note(&note_id).await {
+            Ok(_) => db.add_folder_note(folder_id, &note_id).await,
+            Err(e) => Err(e)
+        },
+        None => db.pull_folder_note(&note_id).await
+    };
+    match result {
+        Ok(_) => HttpResponse::Ok().json(ResponseObject::new()),
+        Err(_) => APIError::QueryError("note could not be moved".to_string()).gen_response()
+    }
+}
+
+/// Checks the name of a folder, returning it without surrounding whitespace
+///
+/// # Arguments
+///
+/// * `name` - The name to be checked
+fn check_name(name: &str) -> Result<String, APIError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_FOLDER_NAME_LENGTH {
+        return Err(APIError::InvalidPayloadError)
+    }
+    Ok(name.to_string())
+}
+
+/// Returns the identifiers of all folders directly inside of a folder, ordered by their creation
+///
+/// # Arguments
+///
+/// * `folder_id` - The identifier of the folder
+/// * `folders` - All folders of the owner, mapped by their identifier
+fn get_children(folder_id: &str, folders: &HashMap<String, Folder>) -> Vec<String> {
+    let mut children: Vec<&Folder> = folders.values().filter(|folder| folder.parent_id.as_deref() == Some(folder_id)).collect();
+    children.sort_by_key(|folder| folder.created_at);
+    children.into_iter().map(|folder| folder._id.clone()).collect()
+}
+
+/// Looks up a folder along with the level of access the current user has regarding it.
+/// Folders the user has no access to are reported as nonexistent
+///
+/// # Arguments
+///
+/// * `folder_id` - The identifier of the folder
+/// * `req` - The HttpRequest that was made
+/// * `db` - A reference to the storage-backend
+async fn get_folder_access(folder_id: &str, req: HttpRequest, db: &dyn Storage) -> Result<FolderAccess, APIError> {
+    // Check for potential injection-attempt
+    if !is_safe(folder_id) {
+        return Err(APIError::InvalidIDError)
+    }
+    let user = get_user_from_request(req, db).await?;
+    let folder = match db.get_folder(folder_id).await {
+        Ok(folder) => folder,
+        Err(DBError::NoDocumentFoundError) => return Err(APIError::ResourceNotFoundError("folder".to_string())),
+        Err(_) => return Err(APIError::QueryError("folder could not be retrieved from database".to_string()))
+    };
+    let folders: HashMap<String, Folder> = db.get_folders(&folder.owner_id).await
+        .map_err(|_| APIError::QueryError("folders could not be retrieved from database".to_string()))?
+        .into_iter().map(|folder| (folder._id.clone(), folder)).collect();
+    match get_folder_level(folder_id, &user._id, &folders) {
+        Some(level) => Ok(FolderAccess { user, folder, level, folders }),
+        None => Err(APIError::ResourceNotFoundError("folder".to_string()))
+    }
+}
@@ -0,0 +1,473 @@
+//! Endpoints regarding groups, which allow sharing a note with several users at once
+//!
+//! A group is created and managed by its owner, who may only add users they are connected with.
+//! Notes are shared with a group using [`update_group_allowances`](crate::web::share::update_group_allowances),
+//! every member then has the level of access given to the group, unless they have been given a higher one directly.
+
+use actix_web::{get, post, delete, Responder, HttpRequest, HttpResponse, web};
+use actix_web::web::{Data, Path};
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use crate::db_access::{AllowanceLevel, DBError, Group, is_safe, Storage, User};
+use crate::web::auth::get_user_from_request;
+use crate::web::error::APIError;
+use crate::web::group::json_objects::{GroupRequest, GroupResponse, MemberRequest};
+use crate::web::{ResponseObject, ResponseObjectWithPayload};
+
+/// Amount of characters making up the identifier of a group
+const GROUP_ID_SIZE: usize = 16;
+/// Maximum amount of characters in the name of a group
+const MAX_GROUP_NAME_LENGTH: usize = 64;
+
+// Response-/Request-Objects
+/// Structs modelling the request- and response-bodies
+mod json_objects {
+    use chrono::{DateTime, Utc};
+    use serde::{Serialize, Deserialize};
+    use crate::db_access::{Allowance, Group};
+
+    /// Body of a request for a new group
+    #[derive(Deserialize)]
+    pub struct GroupRequest {
+        /// The name of the group
+        pub name: String
+    }
+
+    /// Body of a request adding a member to a group
+    #[derive(Deserialize)]
+    pub struct MemberRequest {
+        /// The user to be added
+        pub user_id: String
+    }
+
+    /// Body of a response containing a group
+    #[derive(Serialize)]
+    pub struct GroupResponse {
+        /// The identifier of the group
+        pub group_id: String,
+        /// The name of the group
+        pub name: String,
+        /// The user managing the group
+        pub owner_id: String,
+        /// All members of the group, including its owner
+        pub members: Vec<String>,
+        /// All notes shared with the group
+        pub allowances: Vec<Allowance>,
+        /// Timestamp of the creation
+        pub created_at: DateTime<Utc>
+    }
+    impl From<Group> for GroupResponse {
+        fn from(group: Group) -> Self {
+            GroupResponse {
+                group_id: group._id,
+                name: group.name,
+                owner_id: group.owner_id,
+                members: group.members,
+                allowances: group.allowances,
+                created_at: group.created_at
+            }
+        }
+    }
+}
+
+/// ENDPOINT: Creates a new group, managed by the current user
+///
+/// Returns one of the following HttpResponses:
+/// * `201`
+///     - \[Body: JSON\] Group was created successfully
+/// * `400`
+///     - **\[20\]** The name is empty or too long
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `group_req` - The body of the request parsed to a GroupRequest-object
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// POST-Request at `{api-url}/groups` with a cookie containing a valid JWT
+///     {
+///         "name": "Team"
+///     }
+/// => 201
+///     {
+///         "success": true,
+///         "content": {
+///             "group_id": "Zt4cW9qKa2LmN0xB",
+///             "name": "Team",
+///             "owner_id": "testUser",
+///             "members": ["testUser"],
+///             "allowances": [],
+///             "created_at": "2022-04-11T12:20:28.120Z"
+///         },
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+#[post("/groups")]
+pub async fn add_group(req: HttpRequest, group_req: web::Json<GroupRequest>, db: Data<dyn Storage>) -> impl Responder {
+    let name = group_req.into_inner().name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_GROUP_NAME_LENGTH {
+        return APIError::InvalidPayloadError.gen_response()
+    }
+    let user = match get_user_from_request(req, db.get_ref()).await {
+        Ok(user) => user,
+        Err(e) => return e.gen_response()
+    };
+    let group = Group {
+        _id: rand::thread_rng().sample_iter(&Alphanumeric).take(GROUP_ID_SIZE).map(char::from).collect(),
+        name,
+        owner_id: user._id.clone(),
+        members: vec![user._id],
+        allowances: Vec::new(),
+        created_at: Utc::now()
+    };
+    match db.insert_group(&group).await {
+        Ok(_) => HttpResponse::Created().json(ResponseObjectWithPayload::new(GroupResponse::from(group))),
+        Err(_) => APIError::QueryError("group could not be created".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Lists all groups the current user is a member of
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] Groups have been compiled
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/groups` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": [
+///             {
+///                 "group_id": "Zt4cW9qKa2LmN0xB",
+///                 "name": "Team",
+///                 "owner_id": "otherUser",
+///                 "members": ["otherUser", "testUser"],
+///                 "allowances": [
+///                     {
+///                         "note_id": "7254fa970b62u3ag62dr4d3l",
+///                         "level": "Read"
+///                     }
+///                 ],
+///                 "created_at": "2022-04-11T12:20:28.120Z"
+///             }
+///         ],
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[get("/groups")]
+pub async fn list_groups(req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let user = match get_user_from_request(req, db.get_ref()).await {
+        Ok(user) => user,
+        Err(e) => return e.gen_response()
+    };
+    match db.get_groups(&user._id).await {
+        Ok(groups) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(
+            groups.into_iter().map(GroupResponse::from).collect::<Vec<GroupResponse>>())),
+        Err(_) => APIError::QueryError("groups could not be retrieved from database".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Returns a group the current user is a member of
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] The group has been found
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `404`
+///     - **\[22\]** The group does not exist or the user is not a member of it
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the group
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/groups/Zt4cW9qKa2LmN0xB` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": {
+///             "group_id": "Zt4cW9qKa2LmN0xB",
+///             "name": "Team",
+///             "owner_id": "testUser",
+///             "members": ["testUser", "otherUser"],
+///             "allowances": [],
+///             "created_at": "2022-04-11T12:20:28.120Z"
+///         },
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+/// ```text
+/// GET-Request at `{api-url}/groups/Zt4cW9qKa2LmN0xB` with a cookie containing a valid JWT [user is not a member]
+/// => 404
+///     {
+///         "success": false,
+///         "code": 22,
+///         "message": "requested resource does not exist: group",
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[get("/groups/{group_id}")]
+pub async fn get_group(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    match get_group_of_member(&path.into_inner(), req, db.get_ref()).await {
+        Ok((_, group)) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(GroupResponse::from(group))),
+        Err(e) => e.gen_response()
+    }
+}
+
+/// ENDPOINT: Removes a group owned by the current user, revoking everything shared with it
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - Group has been removed
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** The user is a member, but not the owner of the group
+/// * `404`
+///     - **\[22\]** The group does not exist or the user is not a member of it
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the group
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// DELETE-Request at `{api-url}/groups/Zt4cW9qKa2LmN0xB` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[delete("/groups/{group_id}")]
+pub async fn remove_group(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let (user, group) = match get_group_of_member(&path.into_inner(), req, db.get_ref()).await {
+        Ok(group) => group,
+        Err(e) => return e.gen_response()
+    };
+    if !group.owner_id.eq(&user._id) {
+        return APIError::NoPermissionError.gen_response()
+    }
+    match db.remove_group(&group._id).await {
+        Ok(_) => HttpResponse::Ok().json(ResponseObject::new()),
+        Err(_) => APIError::QueryError("group could not be removed".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Adds a user the current user is connected with to a group owned by the current user
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - Member has been added
+///     - **\[24\]** Invalid instruction (user is not connected with the owner, user already is a member)
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** The user is a member, but not the owner of the group
+/// * `404`
+///     - **\[22\]** The group does not exist or the user is not a member of it
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the group
+/// * `req` - The HttpRequest that was made
+/// * `member_req` - The body of the request parsed to a MemberRequest-object
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// POST-Request at `{api-url}/groups/Zt4cW9qKa2LmN0xB/members` with a cookie containing a valid JWT
+///     {
+///         "user_id": "otherUser"
+///     }
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+/// ```text
+/// POST-Request at `{api-url}/groups/Zt4cW9qKa2LmN0xB/members` with a cookie containing a valid JWT [otherUser is not connected to this user]
+///     {
+///         "user_id": "otherUser"
+///     }
+/// => 200
+///     {
+///         "success": false,
+///         "code": 24,
+///         "message": "invalid instruction: user don't share a connection",
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[post("/groups/{group_id}/members")]
+pub async fn add_member(path: Path<String>, req: HttpRequest, member_req: web::Json<MemberRequest>, db: Data<dyn Storage>) -> impl Responder {
+    let member_id = member_req.into_inner().user_id;
+    if !is_safe(&member_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    let (user, group) = match get_group_of_member(&path.into_inner(), req, db.get_ref()).await {
+        Ok(group) => group,
+        Err(e) => return e.gen_response()
+    };
+    if !group.owner_id.eq(&user._id) {
+        return APIError::NoPermissionError.gen_response()
+    }
+    if group.members.contains(&member_id) {
+        return APIError::InvalidInstructionsError("user already is a member".to_string()).gen_response()
+    }
+    // Only users connected with the owner can be added
+    if !user.connections.contains(&member_id) {
+        return APIError::InvalidInstructionsError("user don't share a connection".to_string()).gen_response()
+    }
+    match db.add_member(&group._id, &member_id).await {
+        Ok(_) => HttpResponse::Ok().json(ResponseObject::new()),
+        Err(_) => APIError::QueryError("member could not be added".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Removes a member from a group, which loses access to the notes the member shared with it.
+/// The owner may remove any other member, everyone else may only leave the group themselves
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - Member has been removed
+///     - **\[24\]** Invalid instruction (the owner can't leave their own group, user is not a member)
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** A member other than the owner tries to remove someone else
+/// * `404`
+///     - **\[22\]** The group does not exist or the user is not a member of it
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the group and of the to-be-removed member
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// DELETE-Request at `{api-url}/groups/Zt4cW9qKa2LmN0xB/members/otherUser` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+/// ```text
+/// DELETE-Request at `{api-url}/groups/Zt4cW9qKa2LmN0xB/members/testUser` with a cookie containing a valid JWT [testUser owns the group]
+/// => 200
+///     {
+///         "success": false,
+///         "code": 24,
+///         "message": "invalid instruction: the owner can't leave the group",
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[delete("/groups/{group_id}/members/{user_id}")]
+pub async fn remove_member(path: Path<(String, String)>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let (group_id, member_id) = path.into_inner();
+    if !is_safe(&member_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    let (user, group) = match get_group_of_member(&group_id, req, db.get_ref()).await {
+        Ok(group) => group,
+        Err(e) => return e.gen_response()
+    };
+    if !group.owner_id.eq(&user._id) && !member_id.eq(&user._id) {
+        return APIError::NoPermissionError.gen_response()
+    }
+    if group.owner_id.eq(&member_id) {
+        return APIError::InvalidInstructionsError("the owner can't leave the group".to_string()).gen_response()
+    }
+    if !group.members.contains(&member_id) {
+        return APIError::InvalidInstructionsError("user is not a member".to_string()).gen_response()
+    }
+    match pull_member_and_notes(&group, &member_id, db.get_ref()).await {
+        Ok(_) => HttpResponse::Ok().json(ResponseObject::new()),
+        Err(_) => APIError::QueryError("member could not be removed".to_string()).gen_response()
+    }
+}
+
+/// Removes a member from a group, revoking the allowances of the group regarding the notes the member owns
+///
+/// # Arguments
+///
+/// * `group` - The group to be left
+/// * `member_id` - The identifier of the member
+/// * `db` - A reference to the storage-backend
+pub async fn pull_member_and_notes(group: &Group, member_id: &str, db: &dyn Storage) -> Result<(), DBError> {
+    let member = db.get_user(member_id).await?;
+    let owned = |note_id: &str| member.allowances.iter().any(|own| own.note_id.eq(note_id) && own.level.eq(&AllowanceLevel::Owner));
+    for allowance in group.allowances.iter().filter(|allowance| owned(&allowance.note_id)) {
+        db.pull_group_allowance(&group._id, &allowance.note_id).await?;
+    }
+    db.pull_member(&group._id, member_id).await
+}
+
+/// Looks up a group the current user is a member of, returning the user alongside it.
+/// Groups of which the user is not a member are reported as nonexistent
+///
+/// # Arguments
+///
+/// * `group_id` - The identifier of the group
+/// * `req` - The HttpRequest that was made
+/// * `db` - A reference to the storage-backend
+pub async fn get_group_of_member(group_id: &str, req: HttpRequest, db: &dyn Storage) -> Result<(User, Group), APIError> {
+    // Check for potential injection-attempt
+    if !is_safe(group_id) {
+        return Err(APIError::InvalidIDError)
+    }
+    let user = get_user_from_request(req, db).await?;
+    match db.get_group(group_id).await {
+        Ok(group) if group.members.contains(&user._id) => Ok((user, group)),
+        Ok(_) | Err(DBError::NoDocumentFoundError) => Err(APIError::ResourceNotFoundError("group".to_string())),
+        Err(_) => Err(APIError::QueryError("group could not be retrieved from database".to_string()))
+    }
+}
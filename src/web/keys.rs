@@ -0,0 +1,297 @@
+//! Keys signing and verifying all JWTs issued by writeUp
+//!
+//! By default JWTs are signed using HS512 with the shared secret `JWT_SECRET`, so they can only be verified by writeUp itself.
+//! Alternatively private keys (Ed25519 or RSA) can be loaded from PEM-files, see [`KeySet::from_env`].
+//! Their public keys are published at [`get_jwks`], allowing other services to verify the JWTs as well,
+//! and every JWT names the key it has been signed with in its `kid`-header.
+//!
+//! The first key signs all new JWTs, while all further ones are only used to verify JWTs issued before.
+//! To rotate keys, add the new key in front and remove the previous one once its JWTs have expired.
+//! `JWT_SECRET` is still accepted for verification alongside the keys if set, so configuring keys doesn't end all sessions.
+//!
+//! Only JWTs proving a session carry a `sid`-claim, all others carry an `aud`-claim and must not be accepted as a login.
+
+use std::env;
+use std::fs;
+use actix_web::{get, HttpRequest, HttpResponse, Responder};
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::web::Data;
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{Algorithm, decode, decode_header, DecodingKey, encode, EncodingKey, Header, Validation};
+use ring::signature::{Ed25519KeyPair, KeyPair, RsaKeyPair};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use crate::JWT_SECRET_ENV_VAR_KEY;
+use crate::secret::secret_from_env;
+use crate::web::error::APIError;
+use crate::web::keys::json_objects::{Jwk, JwkSet};
+
+/// The name of the environment-variable containing the paths to the key-files
+pub const JWT_KEY_FILES_ENV_VAR_KEY: &str = "JWT_KEY_FILES";
+/// Time in seconds other services may cache the published keys for
+const JWKS_MAX_AGE_SECONDS: u32 = 300;
+
+// Response-/Request-Objects
+/// Structs modelling the request- and response-bodies
+mod json_objects {
+    use serde::Serialize;
+
+    /// Body of a response containing the published keys (RFC 7517)
+    #[derive(Serialize)]
+    pub struct JwkSet<'a> {
+        /// All public keys verifying JWTs
+        pub keys: Vec<&'a Jwk>
+    }
+
+    /// A public key verifying JWTs
+    #[derive(Clone, Serialize)]
+    pub struct Jwk {
+        /// The type of the key (`OKP` or `RSA`)
+        pub kty: &'static str,
+        /// The intended use of the key, always `sig`
+        #[serde(rename = "use")]
+        pub usage: &'static str,
+        /// The algorithm the key is used with (`EdDSA` or `RS256`)
+        pub alg: &'static str,
+        /// The identifier of the key
+        pub kid: String,
+        /// The curve of the key *[OKP only]*
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub crv: Option<&'static str>,
+        /// The public key *[OKP only]*
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub x: Option<String>,
+        /// The modulus of the key *[RSA only]*
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub n: Option<String>,
+        /// The public exponent of the key *[RSA only]*
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub e: Option<String>
+    }
+}
+
+/// A key signing JWTs, together with the key verifying them
+pub struct SigningKey {
+    /// The identifier of the key, set for public keys only
+    kid: Option<String>,
+    /// The algorithm the key is used with
+    alg: Algorithm,
+    /// The key signing JWTs
+    encoding: EncodingKey,
+    /// The key verifying JWTs
+    decoding: DecodingKey,
+    /// The public key to be published, if it may be
+    jwk: Option<Jwk>
+}
+
+impl SigningKey {
+    /// Creates a key signing JWTs using HS512 with a shared secret, which is never published
+    ///
+    /// # Arguments
+    ///
+    /// * `secret` - The shared secret
+    pub fn from_secret(secret: &str) -> SigningKey {
+        SigningKey {
+            kid: None,
+            alg: Algorithm::HS512,
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            jwk: None
+        }
+    }
+
+    /// Loads a private key from a PEM-file, which is either a PKCS#8-encoded Ed25519-key (signing using EdDSA)
+    /// or a PKCS#1- or PKCS#8-encoded RSA-key of at least 2048 bits (signing using RS256).
+    /// Returns None if the file contains neither.
+    /// The identifier of the key is its JWK-thumbprint (RFC 7638)
+    ///
+    /// # Arguments
+    ///
+    /// * `pem` - The content of the PEM-file
+    ///
+    /// # Examples
+    ///
+    /// ```sh
+    /// openssl genpkey -algorithm ed25519 -out jwt-key.pem
+    /// ```
+    pub fn from_pem(pem: &[u8]) -> Option<SigningKey> {
+        let der = pem::parse(pem).ok()?;
+        let ed_pair = match der.tag.as_str() {
+            "PRIVATE KEY" => Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der.contents).ok(),
+            "RSA PRIVATE KEY" => None,
+            _ => return None
+        };
+        if let Some(pair) = ed_pair {
+            let x = BASE64URL_NOPAD.encode(pair.public_key().as_ref());
+            let kid = thumbprint(&format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, x));
+            return Some(SigningKey {
+                kid: Some(kid.clone()),
+                alg: Algorithm::EdDSA,
+                encoding: EncodingKey::from_ed_der(&der.contents),
+                decoding: DecodingKey::from_ed_der(pair.public_key().as_ref()),
+                jwk: Some(Jwk { kty: "OKP", usage: "sig", alg: "EdDSA", kid, crv: Some("Ed25519"), x: Some(x), n: None, e: None })
+            })
+        }
+        let rsa_pair = match der.tag.as_str() {
+            "PRIVATE KEY" => RsaKeyPair::from_pkcs8(&der.contents),
+            _ => RsaKeyPair::from_der(&der.contents)
+        }.ok()?;
+        let modulus = rsa_pair.public_key().modulus();
+        let exponent = rsa_pair.public_key().exponent();
+        let (modulus, exponent) = (modulus.big_endian_without_leading_zero(), exponent.big_endian_without_leading_zero());
+        let (n, e) = (BASE64URL_NOPAD.encode(modulus), BASE64URL_NOPAD.encode(exponent));
+        let kid = thumbprint(&format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n));
+        Some(SigningKey {
+            kid: Some(kid.clone()),
+            alg: Algorithm::RS256,
+            encoding: EncodingKey::from_rsa_pem(pem).ok()?,
+            decoding: DecodingKey::from_rsa_raw_components(modulus, exponent),
+            jwk: Some(Jwk { kty: "RSA", usage: "sig", alg: "RS256", kid, crv: None, x: None, n: Some(n), e: Some(e) })
+        })
+    }
+
+    /// Returns the identifier of the key, if it has one
+    pub fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+}
+
+/// Returns the JWK-thumbprint (RFC 7638) of a public key
+///
+/// # Arguments
+///
+/// * `members` - The required members of the JWK in lexicographic order, without whitespace
+fn thumbprint(members: &str) -> String {
+    BASE64URL_NOPAD.encode(&Sha256::digest(members.as_bytes()))
+}
+
+/// All keys signing and verifying JWTs, to be added to the app-data of the web-server
+pub struct KeySet {
+    /// The key signing all new JWTs
+    current: SigningKey,
+    /// The keys still accepted for verification
+    previous: Vec<SigningKey>
+}
+
+impl KeySet {
+    /// Creates a new KeySet
+    ///
+    /// # Arguments
+    ///
+    /// * `current` - The key signing all new JWTs
+    /// * `previous` - The keys still accepted for verification
+    pub fn new(current: SigningKey, previous: Vec<SigningKey>) -> KeySet {
+        KeySet { current, previous }
+    }
+
+    /// Reads the keys from the following environment-variables:
+    /// * `JWT_KEY_FILES` - Comma-separated paths to PEM-files containing private keys, the first one signing all new JWTs
+    ///   *[default: none]* (see [`SigningKey::from_pem`])
+    /// * `JWT_SECRET` - The secret signing all new JWTs if no key-files are set, otherwise only used for verification
+    ///
+    /// Panics if a key-file can't be read or neither variable is set
+    pub fn from_env() -> KeySet {
+        let secret = secret_from_env(JWT_SECRET_ENV_VAR_KEY).map(|secret| SigningKey::from_secret(secret.expose()));
+        let paths = match env::var(JWT_KEY_FILES_ENV_VAR_KEY) {
+            Ok(paths) => paths,
+            Err(_) => return KeySet::new(secret.expect("Env-Variable 'JWT_SECRET' needs to be set"), Vec::new())
+        };
+        let mut keys: Vec<SigningKey> = paths.split(',').map(str::trim).filter(|path| !path.is_empty()).map(|path| {
+            let pem = fs::read(path).unwrap_or_else(|e| panic!("Key-File '{}' could not be read: {}", path, e));
+            SigningKey::from_pem(&pem).unwrap_or_else(|| panic!("Key-File '{}' needs to contain a private Ed25519- or RSA-key", path))
+        }).collect();
+        if keys.is_empty() {
+            panic!("Env-Variable 'JWT_KEY_FILES' needs to contain at least one path");
+        }
+        keys.extend(secret);
+        let current = keys.remove(0);
+        KeySet::new(current, keys)
+    }
+
+    /// Returns the key signing all new JWTs
+    pub fn current(&self) -> &SigningKey {
+        &self.current
+    }
+
+    /// Signs a new JWT using the current key
+    ///
+    /// # Arguments
+    ///
+    /// * `claims` - The payload of the JWT
+    pub fn encode<T: Serialize>(&self, claims: &T) -> jsonwebtoken::errors::Result<String> {
+        let mut header = Header::new(self.current.alg);
+        header.kid = self.current.kid.clone();
+        encode(&header, claims, &self.current.encoding)
+    }
+
+    /// Verifies a JWT using the key named in its header, returning its payload.
+    /// Returns None if the JWT is invalid, has expired, has been signed by an unknown key or is meant for another audience
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The JWT to be verified
+    /// * `audience` - The audience the JWT needs to be meant for, if any
+    pub fn decode<T: DeserializeOwned>(&self, token: &str, audience: Option<&str>) -> Option<T> {
+        let header = decode_header(token).ok()?;
+        // The algorithm is dictated by the key, never by the JWT
+        let key = std::iter::once(&self.current).chain(&self.previous)
+            .find(|key| key.kid == header.kid && key.alg == header.alg)?;
+        let mut validation = Validation::new(key.alg);
+        if let Some(audience) = audience {
+            validation.set_audience(&[audience]);
+        }
+        decode::<T>(token, &key.decoding, &validation).ok().map(|data| data.claims)
+    }
+
+    /// Returns all public keys, which may be published
+    pub fn jwks(&self) -> JwkSet<'_> {
+        JwkSet { keys: std::iter::once(&self.current).chain(&self.previous).filter_map(|key| key.jwk.as_ref()).collect() }
+    }
+}
+
+/// Returns the KeySet added to the app-data of the web-server
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+pub fn get_keys(req: &HttpRequest) -> Result<&KeySet, APIError> {
+    req.app_data::<Data<KeySet>>().map(|keys| keys.get_ref())
+        .ok_or_else(|| APIError::InternalServerError("signing-keys are missing".to_string()))
+}
+
+/// ENDPOINT: Returns the public keys verifying the JWTs issued by writeUp as a JWK-Set (RFC 7517).
+/// The set is empty if JWTs are signed using a shared secret
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] \[Cache-Control\] The public keys
+///
+/// # Arguments
+///
+/// * `keys` - The AppData containing the KeySet
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/.well-known/jwks.json`
+/// => 200
+///     {
+///         "keys": [
+///             {
+///                 "kty": "OKP",
+///                 "use": "sig",
+///                 "alg": "EdDSA",
+///                 "kid": "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k",
+///                 "crv": "Ed25519",
+///                 "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"
+///             }
+///         ]
+///     }
+/// ```
+#[get("/.well-known/jwks.json")]
+pub async fn get_jwks(keys: Data<KeySet>) -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(JWKS_MAX_AGE_SECONDS)]))
+        .json(keys.jwks())
+}
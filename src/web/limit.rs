@@ -0,0 +1,268 @@
+//! Protection of the endpoints handling credentials against brute-force and denial-of-service attempts
+//!
+//! Two kinds of limits are enforced, both answering with a [`TooManyAttemptsError`](APIError::TooManyAttemptsError)
+//! and a `Retry-After`-header once exceeded:
+//! * Per IP-address, the [`limit_requests`]-middleware only lets a certain amount of requests pass within a time-window.
+//!   It wraps all endpoints verifying credentials or creating new ones, each of which runs a costly password-hash,
+//!   as well as those verifying one-time passwords.
+//!   Behind a reverse-proxy all requests share the address of the proxy, unless a header carrying the address of the client is configured.
+//! * Per account, failed logins, passwords and one-time passwords are counted. Once too many have failed in a row, the account is locked temporarily,
+//!   with the lockout doubling with each further failure. Locked accounts are rejected before their password is checked.
+//!
+//! All limits are kept in memory, see [`RateLimitConfig::from_env`] on how to configure them.
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::{Error, HttpRequest};
+use actix_web::middleware::Next;
+use actix_web::web::Data;
+use chrono::{DateTime, Duration, MAX_DATETIME, Utc};
+use crate::web::auth::get_ip;
+use crate::web::error::APIError;
+
+/// Amount of tracked IP-addresses or accounts above which stale entries are dropped
+const PRUNE_THRESHOLD: usize = 10_000;
+/// Upper bound of all durations that can be configured in seconds (one year)
+const MAX_LIMIT_SECONDS: i64 = 365 * 24 * 60 * 60;
+
+/// The limits to be enforced by a [`RateLimiter`]
+#[derive(Clone, Debug)]
+pub struct RateLimitConfig {
+    /// Amount of requests an IP-address may make within a window
+    pub requests_per_window: u32,
+    /// Length of a window in seconds
+    pub window_seconds: i64,
+    /// Amount of failed logins in a row after which an account gets locked
+    pub lockout_threshold: u32,
+    /// Duration of the first lockout in seconds, doubling with each further failure
+    pub lockout_seconds: i64,
+    /// Upper bound of the duration of a lockout in seconds
+    pub max_lockout_seconds: i64,
+    /// Name of the header a trusted reverse-proxy puts the IP-address of the client into, if any
+    pub ip_header: Option<String>
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            requests_per_window: 30,
+            window_seconds: 60,
+            lockout_threshold: 5,
+            lockout_seconds: 30,
+            max_lockout_seconds: 3600,
+            ip_header: None
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Reads the limits from the following environment-variables, using the defaults for all that are not set:
+    /// * `RATE_LIMIT_REQUESTS` - Amount of requests per IP-address and window *[default: 30]*
+    /// * `RATE_LIMIT_WINDOW` - Length of a window in seconds (at most a year) *[default: 60]*
+    /// * `RATE_LIMIT_IP_HEADER` - Header a trusted reverse-proxy sets to the IP-address of the client (e.g. `X-Forwarded-For`, using its last address).
+    ///   Only to be set if all requests pass the proxy, as clients could pick their own address otherwise *[default: the address of the connection]*
+    /// * `LOCKOUT_THRESHOLD` - Amount of failed attempts in a row until an account gets locked *[default: 5]*
+    /// * `LOCKOUT_SECONDS` - Duration of the first lockout in seconds (at most a year) *[default: 30]*
+    /// * `LOCKOUT_MAX_SECONDS` - Upper bound of the duration of a lockout in seconds (at most a year) *[default: 3600]*
+    ///
+    /// Panics if a variable is not a positive number within its bounds
+    pub fn from_env() -> RateLimitConfig {
+        let default = RateLimitConfig::default();
+        RateLimitConfig {
+            requests_per_window: read_limit("RATE_LIMIT_REQUESTS", default.requests_per_window, u32::MAX),
+            window_seconds: read_limit("RATE_LIMIT_WINDOW", default.window_seconds, MAX_LIMIT_SECONDS),
+            lockout_threshold: read_limit("LOCKOUT_THRESHOLD", default.lockout_threshold, u32::MAX),
+            lockout_seconds: read_limit("LOCKOUT_SECONDS", default.lockout_seconds, MAX_LIMIT_SECONDS),
+            max_lockout_seconds: read_limit("LOCKOUT_MAX_SECONDS", default.max_lockout_seconds, MAX_LIMIT_SECONDS),
+            ip_header: env::var("RATE_LIMIT_IP_HEADER").ok().filter(|header| !header.trim().is_empty())
+        }
+    }
+}
+
+/// Reads a single limit from an environment-variable, panicking if it is not a positive number of at most the given maximum
+///
+/// # Arguments
+///
+/// * `key` - The name of the environment-variable
+/// * `default` - The value to be used if the variable is not set
+/// * `max` - The largest accepted value
+fn read_limit<T: std::str::FromStr + PartialOrd + From<u8> + std::fmt::Display>(key: &str, default: T, max: T) -> T {
+    match env::var(key) {
+        Ok(value) => match value.trim().parse() {
+            Ok(limit) if limit >= T::from(1) && limit <= max => limit,
+            _ => panic!("Env-Variable '{}' needs to be a positive number of at most {}", key, max)
+        },
+        Err(_) => default
+    }
+}
+
+/// Adds an amount of seconds to a point in time, saturating instead of overflowing
+///
+/// # Arguments
+///
+/// * `time` - The point in time
+/// * `seconds` - The amount of seconds to be added
+fn add_seconds(time: DateTime<Utc>, seconds: i64) -> DateTime<Utc> {
+    time.checked_add_signed(Duration::milliseconds(seconds.saturating_mul(1000))).unwrap_or(MAX_DATETIME)
+}
+
+/// The requests of an IP-address within the current window
+struct Window {
+    /// Start of the window
+    start: DateTime<Utc>,
+    /// Amount of requests made since
+    count: u32
+}
+
+/// The failed logins of an account
+struct Failures {
+    /// Amount of failed logins in a row
+    count: u32,
+    /// Timestamp of the last failed login
+    last: DateTime<Utc>,
+    /// Timestamp until which the account is locked
+    locked_until: Option<DateTime<Utc>>
+}
+
+/// Keeps track of requests and failed logins, to be added to the app-data of the web-server
+pub struct RateLimiter {
+    /// The limits to be enforced
+    config: RateLimitConfig,
+    /// The current window of all IP-addresses
+    windows: Mutex<HashMap<String, Window>>,
+    /// The failed logins of all accounts
+    failures: Mutex<HashMap<String, Failures>>
+}
+
+impl RateLimiter {
+    /// Creates a new RateLimiter enforcing the given limits
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The limits to be enforced
+    pub fn new(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter { config, windows: Mutex::new(HashMap::new()), failures: Mutex::new(HashMap::new()) }
+    }
+
+    /// Counts a request of an IP-address.
+    /// Returns the amount of seconds until the next request will be accepted, if the limit has been reached
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - The IP-address the request was made from
+    /// * `now` - The current time
+    pub fn hit_ip(&self, ip: &str, now: DateTime<Utc>) -> Result<(), i64> {
+        let window_seconds = self.config.window_seconds;
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() > PRUNE_THRESHOLD {
+            windows.retain(|_, window| add_seconds(window.start, window_seconds) > now);
+        }
+        let window = windows.entry(ip.to_string()).or_insert(Window { start: now, count: 0 });
+        if add_seconds(window.start, window_seconds) <= now {
+            *window = Window { start: now, count: 0 };
+        }
+        if window.count >= self.config.requests_per_window {
+            return Err(seconds_until(add_seconds(window.start, window_seconds), now))
+        }
+        window.count += 1;
+        Ok(())
+    }
+
+    /// Checks whether an account is currently locked.
+    /// Returns the amount of seconds until the lockout ends, if it is
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the account
+    /// * `now` - The current time
+    pub fn check_account(&self, user_id: &str, now: DateTime<Utc>) -> Result<(), i64> {
+        match self.failures.lock().unwrap().get(user_id).and_then(|failures| failures.locked_until) {
+            Some(locked_until) if locked_until > now => Err(seconds_until(locked_until, now)),
+            _ => Ok(())
+        }
+    }
+
+    /// Records a failed login of an account, locking it if there have been too many in a row.
+    /// Failures are forgotten once the account has not seen one for as long as the longest lockout
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the account
+    /// * `now` - The current time
+    pub fn record_failure(&self, user_id: &str, now: DateTime<Utc>) {
+        let max_lockout = self.config.max_lockout_seconds;
+        let mut failures = self.failures.lock().unwrap();
+        if failures.len() > PRUNE_THRESHOLD {
+            failures.retain(|_, failures| add_seconds(failures.last, max_lockout) > now);
+        }
+        let entry = failures.entry(user_id.to_string()).or_insert(Failures { count: 0, last: now, locked_until: None });
+        if add_seconds(entry.last, max_lockout) <= now {
+            entry.count = 0;
+        }
+        entry.count += 1;
+        entry.last = now;
+        if entry.count >= self.config.lockout_threshold {
+            // Exponential back-off, capped at the longest lockout
+            let exponent = (entry.count - self.config.lockout_threshold).min(31);
+            let lockout = self.config.lockout_seconds.saturating_mul(1 << exponent).min(self.config.max_lockout_seconds);
+            entry.locked_until = Some(add_seconds(now, lockout));
+        }
+    }
+
+    /// Returns the IP-address a request was made from, as reported by the trusted reverse-proxy if configured
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - The HttpRequest that was made
+    pub fn client_ip(&self, req: &HttpRequest) -> String {
+        // The proxy appends the address it has been connected from to whatever the client sent
+        let forwarded = self.config.ip_header.as_ref()
+            .and_then(|header| req.headers().get(header.as_str()))
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').map(str::trim).find(|ip| !ip.is_empty()));
+        match forwarded {
+            Some(ip) => ip.to_string(),
+            None => get_ip(req)
+        }
+    }
+
+    /// Forgets all failed logins of an account after a successful one
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The identifier of the account
+    pub fn record_success(&self, user_id: &str) {
+        self.failures.lock().unwrap().remove(user_id);
+    }
+}
+
+/// Returns the amount of whole seconds (at least one) from now until the given time
+///
+/// # Arguments
+///
+/// * `until` - The time to count to
+/// * `now` - The current time
+fn seconds_until(until: DateTime<Utc>, now: DateTime<Utc>) -> i64 {
+    let millis = (until - now).num_milliseconds();
+    ((millis + 999) / 1000).max(1)
+}
+
+/// MIDDLEWARE: Rejects a request once its IP-address has exceeded its amount of requests within the current window.
+/// Requests pass unlimited if no [`RateLimiter`] has been added to the app-data
+///
+/// # Arguments
+///
+/// * `req` - The ServiceRequest that was made
+/// * `next` - The wrapped service
+pub async fn limit_requests(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Some(limiter) = req.app_data::<Data<RateLimiter>>() {
+        if let Err(retry_after) = limiter.hit_ip(&limiter.client_ip(req.request()), Utc::now()) {
+            let (req, _) = req.into_parts();
+            return Ok(ServiceResponse::new(req, APIError::TooManyAttemptsError(retry_after).gen_response()).map_into_right_body())
+        }
+    }
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
@@ -0,0 +1,414 @@
+//! Endpoints regarding public links, which let anyone knowing them read a note without logging in
+//!
+//! Just like personal access-tokens, a link is handed out exactly once upon its creation, only a hash of it is stored.
+//! Links are created by the owner of a note and may expire, be protected by a password
+//! (sent inside of an `X-Share-Password`-header) or only allow a certain amount of views.
+//! Removing a note removes all of its links as well.
+
+use actix_web::{get, post, delete, Responder, HttpRequest, HttpResponse, web};
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::middleware::from_fn;
+use actix_web::web::{Data, Path};
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use crate::db_access::{AllowanceLevel, DBError, is_safe, ShareLink, Storage};
+use crate::web::auth::{get_user_id_from_request, hash_token};
+use crate::web::error::APIError;
+use crate::web::limit::limit_requests;
+use crate::web::link::json_objects::{CreatedLinkResponse, LinkRequest, LinkResponse, PublicNoteResponse};
+use crate::web::note::get_allow_level_for_note;
+use crate::web::{ResponseObject, ResponseObjectWithPayload};
+
+/// Amount of characters making up the identifier of a link
+const LINK_ID_SIZE: usize = 16;
+/// Amount of characters making up the secret part of a link
+const LINK_SECRET_SIZE: usize = 40;
+/// The header carrying the password of a protected link
+pub const SHARE_PASSWORD_HEADER: &str = "X-Share-Password";
+
+// Response-/Request-Objects
+/// Structs modelling the request- and response-bodies
+mod json_objects {
+    use chrono::{DateTime, Utc};
+    use serde::{Serialize, Deserialize};
+    use crate::db_access::{Note, ShareLink};
+
+    /// Body of a request for a new link
+    #[derive(Deserialize)]
+    pub struct LinkRequest {
+        /// Amount of days until the link expires (never if not set)
+        pub expires_in_days: Option<u32>,
+        /// The password protecting the link (unprotected if not set)
+        pub password: Option<String>,
+        /// The amount of views after which the link expires (unlimited if not set)
+        pub max_views: Option<u32>
+    }
+
+    /// Body of a response containing a link without its secret
+    #[derive(Serialize)]
+    pub struct LinkResponse {
+        /// The identifier of the link
+        pub link_id: String,
+        /// The user who created the link
+        pub created_by: String,
+        /// Whether a password has to be supplied to use the link
+        pub protected: bool,
+        /// Timestamp of the creation
+        pub created_at: DateTime<Utc>,
+        /// Timestamp after which the link is no longer accepted
+        pub expires_at: Option<DateTime<Utc>>,
+        /// The amount of views after which the link is no longer accepted
+        pub max_views: Option<u32>,
+        /// The amount of times the note has been viewed using the link
+        pub views: u32
+    }
+    impl From<ShareLink> for LinkResponse {
+        fn from(link: ShareLink) -> Self {
+            LinkResponse {
+                protected: link.is_protected(),
+                link_id: link._id,
+                created_by: link.created_by,
+                created_at: link.created_at,
+                expires_at: link.expires_at,
+                max_views: link.max_views,
+                views: link.views
+            }
+        }
+    }
+
+    /// Body of a response containing a newly created link including its secret
+    #[derive(Serialize)]
+    pub struct CreatedLinkResponse {
+        /// The token to be appended to the public URL
+        pub token: String,
+        /// Everything else known about the link
+        #[serde(flatten)]
+        pub info: LinkResponse
+    }
+
+    /// Body of a response containing a note viewed using a link
+    #[derive(Serialize)]
+    pub struct PublicNoteResponse {
+        /// The title
+        pub title: String,
+        /// The actual note
+        pub content: String,
+        /// The tags associated with the note
+        pub tags: Vec<String>,
+        /// Timestamp of when the note was last modified
+        pub updated_at: DateTime<Utc>
+    }
+    impl From<Note> for PublicNoteResponse {
+        fn from(note: Note) -> Self {
+            PublicNoteResponse { title: note.title, content: note.content, tags: note.tags, updated_at: note.updated_at }
+        }
+    }
+}
+
+/// ENDPOINT: Creates a new public link to a note owned by the current user.
+/// The token of the link is only ever returned by this endpoint
+///
+/// Returns one of the following HttpResponses:
+/// * `201`
+///     - \[Body: JSON\] Link was created successfully
+/// * `400`
+///     - **\[20\]** An empty password, an expiration or maximum of zero, or an expiration too far in the future
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** The user does not own the note
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the note
+/// * `req` - The HttpRequest that was made
+/// * `link_req` - The body of the request parsed to a LinkRequest-object
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// POST-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/links` with a cookie containing a valid JWT
+///     {
+///         "expires_in_days": 7,
+///         "password": "linkPass",
+///         "max_views": 100
+///     }
+/// => 201
+///     {
+///         "success": true,
+///         "content": {
+///             "token": "p8Vb2kLx0QaZ7mWc.Hr3wN9sUyZ6dMi2AqL1bXn8tKe4Gc0RfSu7VpYjW",
+///             "link_id": "p8Vb2kLx0QaZ7mWc",
+///             "created_by": "testUser",
+///             "protected": true,
+///             "created_at": "2022-04-11T12:20:28.120Z",
+///             "expires_at": "2022-04-18T12:20:28.120Z",
+///             "max_views": 100,
+///             "views": 0
+///         },
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+/// ```text
+/// POST-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/links` to a note shared with the current user
+///     {}
+/// => 403
+///     {
+///         "success": false,
+///         "code": 12,
+///         "message": "no permission",
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+#[post("/note/{note_id}/links")]
+pub async fn add_link(path: Path<String>, req: HttpRequest, link_req: web::Json<LinkRequest>, db: Data<dyn Storage>) -> impl Responder {
+    let note_id = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&note_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    let link_req = link_req.into_inner();
+    if link_req.expires_in_days == Some(0) || link_req.max_views == Some(0) || link_req.password.as_deref() == Some("") {
+        return APIError::InvalidPayloadError.gen_response()
+    }
+    // Only the owner may publish a note
+    match get_allow_level_for_note(&note_id, req.clone(), db.get_ref()).await {
+        Ok(AllowanceLevel::Owner) => {}
+        Ok(_) => return APIError::NoPermissionError.gen_response(),
+        Err(e) => return e.gen_response()
+    }
+    let user_id = match get_user_id_from_request(req, db.get_ref()).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e.gen_response()
+    };
+    // Generate the link
+    let mut rng = rand::thread_rng();
+    let link_id: String = (&mut rng).sample_iter(&Alphanumeric).take(LINK_ID_SIZE).map(char::from).collect();
+    let secret: String = (&mut rng).sample_iter(&Alphanumeric).take(LINK_SECRET_SIZE).map(char::from).collect();
+    let mut link = ShareLink::new(link_id.clone(), note_id, user_id, hash_token(&secret), link_req.password.as_deref());
+    link.expires_at = match link_req.expires_in_days.map(|days| link.created_at.checked_add_signed(chrono::Duration::days(days as i64))) {
+        Some(None) => return APIError::InvalidPayloadError.gen_response(), // Beyond what can be represented
+        expires_at => expires_at.flatten()
+    };
+    link.max_views = link_req.max_views;
+    match db.insert_link(&link).await {
+        Ok(_) => HttpResponse::Created().json(ResponseObjectWithPayload::new(CreatedLinkResponse {
+            token: format!("{}.{}", link_id, secret),
+            info: link.into()
+        })),
+        Err(_) => APIError::QueryError("link could not be created".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Lists all public links to a note owned by the current user, without their secrets
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] Links have been compiled
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** The user does not own the note
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the note
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/links` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": [
+///             {
+///                 "link_id": "p8Vb2kLx0QaZ7mWc",
+///                 "created_by": "testUser",
+///                 "protected": true,
+///                 "created_at": "2022-04-11T12:20:28.120Z",
+///                 "expires_at": "2022-04-18T12:20:28.120Z",
+///                 "max_views": 100,
+///                 "views": 12
+///             }
+///         ],
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[get("/note/{note_id}/links")]
+pub async fn list_links(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let note_id = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&note_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    match get_allow_level_for_note(&note_id, req, db.get_ref()).await {
+        Ok(AllowanceLevel::Owner) => match db.get_links(&note_id).await {
+            Ok(links) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(
+                links.into_iter().map(LinkResponse::from).collect::<Vec<LinkResponse>>())),
+            Err(_) => APIError::QueryError("links could not be retrieved from database".to_string()).gen_response()
+        },
+        Ok(_) => APIError::NoPermissionError.gen_response(),
+        Err(e) => e.gen_response()
+    }
+}
+
+/// ENDPOINT: Revokes a public link to a note owned by the current user
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - Link has been revoked
+/// * `400`
+///     - **\[21\]** Invalid note- or link-ID
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** The user does not own the note
+/// * `404`
+///     - **\[22\]** The note has no link with the given ID
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the note and of the to-be-revoked link
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// DELETE-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/links/p8Vb2kLx0QaZ7mWc` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+/// ```text
+/// DELETE-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/links/p8Vb2kLx0QaZ7mWc` (link to another note)
+/// => 404
+///     {
+///         "success": false,
+///         "code": 22,
+///         "message": "requested resource does not exist: link",
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[delete("/note/{note_id}/links/{link_id}")]
+pub async fn remove_link(path: Path<(String, String)>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let (note_id, link_id) = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&note_id) || !is_safe(&link_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    match get_allow_level_for_note(&note_id, req, db.get_ref()).await {
+        Ok(AllowanceLevel::Owner) => {}
+        Ok(_) => return APIError::NoPermissionError.gen_response(),
+        Err(e) => return e.gen_response()
+    }
+    // Only links to the given note can be revoked
+    match db.get_link(&link_id).await {
+        Ok(link) if link.note_id.eq(&note_id) => {}
+        Ok(_) | Err(DBError::NoDocumentFoundError) => return APIError::ResourceNotFoundError("link".to_string()).gen_response(),
+        Err(_) => return APIError::QueryError("link could not be retrieved from database".to_string()).gen_response()
+    }
+    match db.remove_link(&link_id).await {
+        Ok(_) => HttpResponse::Ok().json(ResponseObject::new()),
+        Err(_) => APIError::QueryError("link could not be revoked".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Returns the note a public link leads to, without requiring a login.
+/// Every successful request counts as a view of the link
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] The link is valid
+///     - **\[11\]** Missing or wrong password of a protected link
+/// * `404`
+///     - **\[22\]** Unknown, revoked, expired or exhausted link
+/// * `429`
+///     - **\[13\]** \[Retry-After\] Too many requests
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the token of the link
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/public/p8Vb2kLx0QaZ7mWc.Hr3wN9sUyZ6dMi2AqL1bXn8tKe4Gc0RfSu7VpYjW` with the header `X-Share-Password: linkPass`
+/// => 200
+///     {
+///         "success": true,
+///         "content": {
+///             "title": "Test-Note",
+///             "content": "This is a Test-Note",
+///             "tags": ["test"],
+///             "updated_at": "2022-04-11T12:20:28.120Z"
+///         },
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+/// ```text
+/// GET-Request at `{api-url}/public/p8Vb2kLx0QaZ7mWc.Hr3wN9sUyZ6dMi2AqL1bXn8tKe4Gc0RfSu7VpYjW` without a password
+/// => 200
+///     {
+///         "success": false,
+///         "code": 11,
+///         "message": "failed to process credentials: password required",
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[get("/public/{token}", wrap = "from_fn(limit_requests)")]
+pub async fn get_public_note(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let token = path.into_inner();
+    let not_found = || APIError::ResourceNotFoundError("link".to_string()).gen_response();
+    // Find the link, without revealing whether it exists at all
+    let (link_id, secret) = match token.split_once('.') {
+        Some((link_id, secret)) if is_safe(link_id) => (link_id, secret),
+        _ => return not_found()
+    };
+    let link = match db.get_link(link_id).await {
+        Ok(link) if link.token_hash.eq(&hash_token(secret)) => link,
+        Ok(_) | Err(DBError::NoDocumentFoundError) => return not_found(),
+        Err(_) => return APIError::QueryError("link could not be retrieved from database".to_string()).gen_response()
+    };
+    if link.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return not_found()
+    }
+    // Check the password of protected links
+    let passwd = req.headers().get(SHARE_PASSWORD_HEADER).and_then(|header| header.to_str().ok());
+    if !link.verify(passwd) {
+        let reason = if passwd.is_some() { "wrong password" } else { "password required" };
+        return APIError::InvalidCredentialsError(reason.to_string()).gen_response()
+    }
+    // Count the view, unless the link is exhausted
+    match db.count_view(link_id).await {
+        Ok(_) => {}
+        Err(DBError::NoDocumentFoundError) => return not_found(),
+        Err(_) => return APIError::QueryError("view could not be counted".to_string()).gen_response()
+    }
+    match db.get_note(&link.note_id).await {
+        Ok(note) => HttpResponse::Ok().insert_header(CacheControl(vec![CacheDirective::NoStore]))
+            .json(ResponseObjectWithPayload::new(PublicNoteResponse::from(note))),
+        Err(DBError::NoDocumentFoundError) => not_found(),
+        Err(_) => APIError::QueryError("note could not be retrieved from database".to_string()).gen_response()
+    }
+}
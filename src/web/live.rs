@@ -0,0 +1,663 @@
+//! Real-time collaborative editing of notes over WebSocket
+//!
+//! All editors connected to the same note share a session holding the current content of the note.
+//! Edits are exchanged as operations on that content, concurrent operations are merged using
+//! operational transformation. Every accepted operation is persisted to the note right away.
+//!
+//! # Protocol
+//!
+//! All messages are JSON-encoded text-frames. Positions and lengths count characters (unicode scalar values).
+//!
+//! + Client -> Server:
+//!     * `{"rev": 4, "op": {"type": "insert", "pos": 12, "text": "abc"}}` - Insert text based on revision 4
+//!     * `{"rev": 4, "op": {"type": "delete", "pos": 12, "len": 3}}` - Delete text based on revision 4
+//!
+//! + Server -> Client:
+//!     * `{"type": "snapshot", "rev": 4, "title": ..., "content": ..., "tags": [...], "allowance": "ReadWrite"}` -
+//!       The entire note, sent on connect and whenever the client has to resynchronize
+//!     * `{"type": "op", "rev": 5, "author_id": "testUser", "op": {...}}` - An operation of another editor
+//!     * `{"type": "ack", "rev": 5}` - The own operation was accepted and is part of the given revision
+//!     * `{"type": "error", "message": "..."}` - The own operation was rejected, e.g. because the access has been revoked in the meantime
+//!
+//! The access of every client is checked again before it receives a snapshot or an operation.
+//! Clients no longer allowed to read the note receive a final error and get disconnected.
+//! Only the most recent operations are kept to merge concurrent ones, clients lagging further behind have to resynchronize
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use actix_web::{get, Responder, HttpRequest, web::{Data, Path, Payload}};
+use actix_ws::{Message, MessageStream, Session};
+use chrono::Utc;
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::lock::Mutex as AsyncMutex;
+use futures::StreamExt;
+use log::warn;
+use serde::{Serialize, Deserialize};
+use crate::db_access::{AllowanceLevel, DBError, is_safe, Note, Storage};
+use crate::web::error::APIError;
+use crate::web::auth::get_user_id_from_request;
+use crate::web::note::{gen_revision, get_allow_level_for_note, get_allow_level_of_user};
+
+/// The maximum amount of operations a document keeps to transform older ones against
+pub const MAX_HISTORY_LENGTH: usize = 1_000;
+
+/// A modification of the content of a note
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Operation {
+    /// Inserts text in front of the character at the given position
+    Insert {
+        /// The position to insert at
+        pos: usize,
+        /// The text to be inserted
+        text: String
+    },
+    /// Deletes a range of characters
+    Delete {
+        /// The position of the first deleted character
+        pos: usize,
+        /// The amount of deleted characters
+        len: usize
+    }
+}
+
+impl Operation {
+    /// Checks whether applying the operation would leave the content unchanged
+    fn is_noop(&self) -> bool {
+        match self {
+            Operation::Insert { text, .. } => text.is_empty(),
+            Operation::Delete { len, .. } => *len == 0
+        }
+    }
+
+    /// Transforms the operation to apply after another operation based on the same content.
+    /// Returns the (possibly split up) operations having the same intent,
+    /// or an InvalidOperation-error if a position exceeds what can be represented
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The concurrent operation
+    /// * `first` - Whether this operation wins ties (two inserts at the same position)
+    fn transform(&self, other: &Operation, first: bool) -> Result<Vec<Operation>, LiveError> {
+        let transformed = match (self, other) {
+            (Operation::Insert { pos, text }, Operation::Insert { pos: other_pos, text: other_text }) => {
+                let shifted = *other_pos < *pos || (*other_pos == *pos && !first);
+                vec![Operation::Insert { pos: if shifted { add(*pos, other_text.chars().count())? } else { *pos }, text: text.clone() }]
+            }
+            (Operation::Insert { pos, text }, Operation::Delete { pos: other_pos, len: other_len }) => {
+                let pos = if *pos <= *other_pos {
+                    *pos
+                } else if *pos >= add(*other_pos, *other_len)? {
+                    pos - other_len
+                } else {
+                    *other_pos // The surrounding text is gone
+                };
+                vec![Operation::Insert { pos, text: text.clone() }]
+            }
+            (Operation::Delete { pos, len }, Operation::Insert { pos: other_pos, text: other_text }) => {
+                let inserted = other_text.chars().count();
+                if *other_pos <= *pos {
+                    vec![Operation::Delete { pos: add(*pos, inserted)?, len: *len }]
+                } else if *other_pos >= add(*pos, *len)? {
+                    vec![Operation::Delete { pos: *pos, len: *len }]
+                } else {
+                    // Keep the inserted text by deleting around it
+                    let before = other_pos - pos;
+                    vec![Operation::Delete { pos: *pos, len: before }, Operation::Delete { pos: add(*pos, inserted)?, len: len - before }]
+                }
+            }
+            (Operation::Delete { pos, len }, Operation::Delete { pos: other_pos, len: other_len }) => {
+                let (end, other_end) = (add(*pos, *len)?, add(*other_pos, *other_len)?);
+                if other_end <= *pos {
+                    vec![Operation::Delete { pos: pos - other_len, len: *len }]
+                } else if *other_pos >= end {
+                    vec![Operation::Delete { pos: *pos, len: *len }]
+                } else {
+                    // Do not delete what is already gone
+                    let overlap = end.min(other_end) - pos.max(other_pos);
+                    vec![Operation::Delete { pos: *pos.min(other_pos), len: len - overlap }]
+                }
+            }
+        };
+        Ok(transformed.into_iter().filter(|op| !op.is_noop()).collect())
+    }
+
+    /// Applies the operation to a text
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The text to be modified
+    fn apply_to(&self, content: &mut String) -> Result<(), LiveError> {
+        // Maps a character-position to its byte-offset
+        let offset = |content: &String, pos: usize| match content.char_indices().nth(pos) {
+            Some((offset, _)) => Ok(offset),
+            None if pos == content.chars().count() => Ok(content.len()),
+            None => Err(LiveError::InvalidOperation)
+        };
+        match self {
+            Operation::Insert { pos, text } => {
+                let start = offset(content, *pos)?;
+                content.insert_str(start, text);
+            }
+            Operation::Delete { pos, len } => {
+                let (start, end) = (offset(content, *pos)?, offset(content, add(*pos, *len)?)?);
+                content.replace_range(start..end, "");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Adds a length to a position, failing with an InvalidOperation-error instead of overflowing
+///
+/// # Arguments
+///
+/// * `pos` - The position
+/// * `len` - The length to be added
+fn add(pos: usize, len: usize) -> Result<usize, LiveError> {
+    pos.checked_add(len).ok_or(LiveError::InvalidOperation)
+}
+
+/// Transforms two sequences of operations based on the same content against each other.
+/// Returns both sequences, each to be applied after the other one (`others` wins ties)
+///
+/// # Arguments
+///
+/// * `ops` - The first sequence
+/// * `others` - The second sequence
+fn transform_all(ops: &[Operation], others: &[Operation]) -> Result<(Vec<Operation>, Vec<Operation>), LiveError> {
+    Ok(match (ops, others) {
+        ([], _) | (_, []) => (ops.to_vec(), others.to_vec()),
+        ([op], [other]) => (op.transform(other, false)?, other.transform(op, true)?),
+        ([op, rest @ ..], _) if !rest.is_empty() => {
+            let (op, others) = transform_all(std::slice::from_ref(op), others)?;
+            let (rest, others) = transform_all(rest, &others)?;
+            ([op, rest].concat(), others)
+        }
+        (_, [other, rest @ ..]) => {
+            let (ops, other) = transform_all(ops, std::slice::from_ref(other))?;
+            let (ops, rest) = transform_all(&ops, rest)?;
+            (ops, [other, rest].concat())
+        }
+    })
+}
+
+/// Errors that can appear when applying an operation to a document
+#[derive(Debug, PartialEq, Eq)]
+pub enum LiveError {
+    /// The operation is based on a revision no longer (or not yet) known
+    UnknownRevision,
+    /// The operation does not fit the content
+    InvalidOperation
+}
+
+/// The content of a note together with the history of all operations applied to it
+#[derive(Clone)]
+pub struct LiveDocument {
+    /// The current content
+    content: String,
+    /// The current revision
+    rev: u64,
+    /// The revision the history starts at
+    history_start: u64,
+    /// The operations applied since the start of the history, one per revision (at most [`MAX_HISTORY_LENGTH`])
+    history: Vec<Operation>
+}
+
+impl LiveDocument {
+    /// Creates a new document without any history
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The initial content
+    /// * `rev` - The initial revision
+    pub fn new(content: String, rev: u64) -> LiveDocument {
+        LiveDocument { content, rev, history_start: rev, history: Vec::new() }
+    }
+
+    /// Returns the current content
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Returns the current revision
+    pub fn rev(&self) -> u64 {
+        self.rev
+    }
+
+    /// Applies an operation based on an older revision, transforming it against all operations
+    /// applied since. Returns the operations actually applied (one revision each).
+    /// Operations based on revisions which have been dropped from the history are rejected as unknown
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - The revision the operation is based on
+    /// * `op` - The operation to be applied
+    pub fn apply(&mut self, base: u64, op: Operation) -> Result<Vec<Operation>, LiveError> {
+        if base < self.history_start || base > self.rev {
+            return Err(LiveError::UnknownRevision)
+        }
+        let concurrent = &self.history[(base - self.history_start) as usize..];
+        let (ops, _) = transform_all(&[op], concurrent)?;
+        let ops: Vec<Operation> = ops.into_iter().filter(|op| !op.is_noop()).collect();
+        // Only modify the content if all operations fit
+        let mut content = self.content.clone();
+        for op in &ops {
+            op.apply_to(&mut content)?;
+        }
+        self.content = content;
+        self.rev += ops.len() as u64;
+        self.history.extend(ops.iter().cloned());
+        // Forget the oldest operations
+        let excess = self.history.len().saturating_sub(MAX_HISTORY_LENGTH);
+        self.history.drain(..excess);
+        self.history_start += excess as u64;
+        Ok(ops)
+    }
+}
+
+/// A message sent by a client
+#[derive(Deserialize)]
+struct ClientMessage {
+    /// The revision the operation is based on
+    rev: u64,
+    /// The operation to be applied
+    op: Operation
+}
+
+/// A message sent to a client
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    /// The entire note
+    Snapshot { rev: u64, title: &'a str, content: &'a str, tags: &'a [String], allowance: AllowanceLevel },
+    /// An operation of another editor
+    Op { rev: u64, author_id: &'a str, op: &'a Operation },
+    /// The own operation was accepted
+    Ack { rev: u64 },
+    /// The own operation was rejected
+    Error { message: &'a str }
+}
+
+/// A client connected to a session
+struct LiveClient {
+    /// The user behind the client
+    user_id: String,
+    /// The level of access the client has regarding the note
+    allowance: AllowanceLevel,
+    /// The queue of messages to be sent to the client
+    sender: UnboundedSender<String>
+}
+
+/// All editors of a note and its shared state
+struct LiveSession {
+    /// The content currently edited
+    document: LiveDocument,
+    /// The note as it was last persisted
+    note: Note,
+    /// Whether the state before the session was kept as a revision already
+    revision_recorded: bool,
+    /// All connected clients by their identifier
+    clients: HashMap<u64, LiveClient>
+}
+
+impl LiveSession {
+    /// Creates a new session editing the given note
+    ///
+    /// # Arguments
+    ///
+    /// * `note` - The note to be edited
+    fn new(note: Note) -> LiveSession {
+        LiveSession { document: LiveDocument::new(note.content.clone(), 0), note, revision_recorded: false, clients: HashMap::new() }
+    }
+
+    /// Queues a message for a single client
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - The identifier of the client
+    /// * `msg` - The message to be sent
+    fn send(&self, client_id: u64, msg: &ServerMessage) {
+        if let (Some(client), Ok(msg)) = (self.clients.get(&client_id), serde_json::to_string(msg)) {
+            let _ = client.sender.unbounded_send(msg); // The client is disconnecting anyway
+        }
+    }
+
+    /// Queues a snapshot of the current state for a single client
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - The identifier of the client
+    fn send_snapshot(&self, client_id: u64) {
+        if let Some(client) = self.clients.get(&client_id) {
+            self.send(client_id, &ServerMessage::Snapshot {
+                rev: self.document.rev(),
+                title: &self.note.title,
+                content: self.document.content(),
+                tags: &self.note.tags,
+                allowance: client.allowance
+            })
+        }
+    }
+
+    /// Checks the current access of all clients except for one, disconnecting those no longer allowed to read the note
+    ///
+    /// # Arguments
+    ///
+    /// * `except` - The identifier of the client whose access has been checked already, if any
+    /// * `note_id` - The identifier of the note
+    /// * `db` - A reference to the storage-backend
+    async fn verify_clients(&mut self, except: Option<u64>, note_id: &str, db: &dyn Storage) {
+        let clients: Vec<(u64, String)> = self.clients.iter()
+            .filter(|(client_id, _)| Some(**client_id) != except)
+            .map(|(client_id, client)| (*client_id, client.user_id.clone()))
+            .collect();
+        let mut levels: HashMap<String, AllowanceLevel> = HashMap::new();
+        for (client_id, user_id) in clients {
+            let allowance = match levels.get(&user_id) {
+                Some(allowance) => *allowance,
+                None => {
+                    // Clients whose access can not be verified are treated as unauthorized, they may reconnect
+                    let allowance = get_current_allowance(&user_id, note_id, db).await.unwrap_or(AllowanceLevel::Forbidden);
+                    levels.insert(user_id, allowance);
+                    allowance
+                }
+            };
+            if allowance < AllowanceLevel::Read {
+                // Dropping the queue closes the connection once the error has been sent
+                self.send(client_id, &ServerMessage::Error { message: "no permission" });
+                self.clients.remove(&client_id);
+            } else if let Some(client) = self.clients.get_mut(&client_id) {
+                client.allowance = allowance;
+            }
+        }
+    }
+
+    /// Replaces the edited content with the given note, letting all clients still allowed to read it resynchronize
+    ///
+    /// # Arguments
+    ///
+    /// * `note` - The current state of the note
+    /// * `note_id` - The identifier of the note
+    /// * `db` - A reference to the storage-backend
+    async fn reset(&mut self, note: Note, note_id: &str, db: &dyn Storage) {
+        self.document = LiveDocument::new(note.content.clone(), self.document.rev() + 1);
+        self.note = note;
+        self.revision_recorded = false;
+        self.verify_clients(None, note_id, db).await;
+        let client_ids: Vec<u64> = self.clients.keys().copied().collect();
+        for client_id in client_ids {
+            self.send_snapshot(client_id)
+        }
+    }
+
+    /// Applies and persists an operation of a client and distributes it to all other clients
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - The identifier of the client
+    /// * `user_id` - The user behind the client
+    /// * `msg` - The message sent by the client
+    /// * `note_id` - The identifier of the note
+    /// * `db` - A reference to the storage-backend
+    async fn handle(&mut self, client_id: u64, user_id: &str, msg: ClientMessage, note_id: &str, db: &dyn Storage) {
+        // Clients disconnected in the meantime are ignored
+        if !self.clients.contains_key(&client_id) {
+            return
+        }
+        // The access may have changed since connecting
+        let allowance = match get_current_allowance(user_id, note_id, db).await {
+            Ok(allowance) => allowance,
+            Err(_) => return self.send(client_id, &ServerMessage::Error { message: "permission could not be verified" })
+        };
+        if let Some(client) = self.clients.get_mut(&client_id) {
+            client.allowance = allowance;
+        }
+        // Read-only editors only receive updates
+        match allowance {
+            AllowanceLevel::ReadWrite | AllowanceLevel::Owner => {}
+            _ => return self.send(client_id, &ServerMessage::Error { message: "no permission" })
+        }
+        let mut document = self.document.clone();
+        let ops = match document.apply(msg.rev, msg.op) {
+            Ok(ops) => ops,
+            Err(LiveError::UnknownRevision) => {
+                self.send(client_id, &ServerMessage::Error { message: "unknown revision" });
+                return self.send_snapshot(client_id)
+            }
+            Err(LiveError::InvalidOperation) => return self.send(client_id, &ServerMessage::Error { message: "invalid operation" })
+        };
+        if !ops.is_empty() {
+            // Persist the merged content, keeping the state before the session as a revision
+            let revision = (!self.revision_recorded).then(|| gen_revision(note_id, &self.note, user_id));
+            let update = Note {
+                content: document.content().to_string(),
+                updated_at: Utc::now(),
+                last_editor_id: user_id.to_string(),
+                ..self.note.clone()
+            };
+            match db.set_note_fields(note_id, &update, update.version, revision.as_ref()).await {
+                Ok(version) => {
+                    self.note = Note { version, ..update };
+                    self.revision_recorded = true;
+                }
+                Err(DBError::VersionMismatchError(_)) => {
+                    // The note was modified outside of the session, start over from its current state
+                    self.send(client_id, &ServerMessage::Error { message: "note has been modified in the meantime" });
+                    return match db.get_note(note_id).await {
+                        Ok(note) => self.reset(note, note_id, db).await,
+                        Err(_) => warn!("Live-session of note {} could not be resynchronized", note_id)
+                    }
+                }
+                Err(_) => return self.send(client_id, &ServerMessage::Error { message: "note could not be saved" })
+            }
+        }
+        // Distribute the applied operations to all clients still allowed to read the note
+        let first_rev = self.document.rev() + 1;
+        self.document = document;
+        self.verify_clients(Some(client_id), note_id, db).await;
+        for (client, _) in self.clients.iter().filter(|(other_id, _)| **other_id != client_id) {
+            for (rev, op) in (first_rev..).zip(&ops) {
+                self.send(*client, &ServerMessage::Op { rev, author_id: user_id, op });
+            }
+        }
+        self.send(client_id, &ServerMessage::Ack { rev: self.document.rev() })
+    }
+}
+
+/// Determines the current level of access a user has regarding a note, users removed in the meantime having none
+///
+/// # Arguments
+///
+/// * `user_id` - The identifier of the user
+/// * `note_id` - The identifier of the note
+/// * `db` - A reference to the storage-backend
+async fn get_current_allowance(user_id: &str, note_id: &str, db: &dyn Storage) -> Result<AllowanceLevel, APIError> {
+    match db.get_user(user_id).await {
+        Ok(user) => match get_allow_level_of_user(note_id, &user, db).await {
+            Err(APIError::NoPermissionError) => Ok(AllowanceLevel::Forbidden),
+            result => result
+        },
+        Err(DBError::NoDocumentFoundError) => Ok(AllowanceLevel::Forbidden),
+        Err(_) => Err(APIError::QueryError("permission could not be verified".to_string()))
+    }
+}
+
+/// A session shared by all clients editing the same note
+type SharedSession = Arc<AsyncMutex<LiveSession>>;
+
+/// Registry of all active live-sessions, to be added to the app-data of the web-server
+#[derive(Default)]
+pub struct LiveHub {
+    /// All sessions by the identifier of their note, together with the amount of connected clients
+    sessions: Mutex<HashMap<String, (SharedSession, usize)>>,
+    /// The identifier assigned to the next client
+    next_client_id: AtomicU64
+}
+
+impl LiveHub {
+    /// Returns the session of a note (creating it if necessary) and registers a new client for it
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    /// * `note` - The current state of the note, used if a new session has to be created
+    fn join(&self, note_id: &str, note: Note) -> (SharedSession, u64) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let entry = sessions.entry(note_id.to_string())
+            .or_insert_with(|| (Arc::new(AsyncMutex::new(LiveSession::new(note))), 0));
+        entry.1 += 1;
+        (entry.0.clone(), self.next_client_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Unregisters a client, closing the session of the note if it was the last one
+    ///
+    /// # Arguments
+    ///
+    /// * `note_id` - The identifier of the note
+    fn leave(&self, note_id: &str) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(entry) = sessions.get_mut(note_id) {
+            entry.1 -= 1;
+            if entry.1 == 0 {
+                sessions.remove(note_id);
+            }
+        }
+    }
+}
+
+/// ENDPOINT: Opens a WebSocket-connection to collaboratively edit a note in real-time (see [module-level documentation](self))
+///
+/// Returns one of the following HttpResponses:
+/// * `101`
+///     - Connection was upgraded to a WebSocket
+/// * `200`
+///     - **\[24\]** Request is no WebSocket-handshake
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** Insufficient access-level (no read-access)
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the note
+/// * `req` - The HttpRequest that was made
+/// * `body` - The stream of incoming data
+/// * `db` - The AppData containing the storage-backend
+/// * `hub` - The AppData containing all live-sessions
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/live` with a cookie containing a valid JWT and WebSocket-headers
+/// => 101
+/// <= {"type": "snapshot", "rev": 0, "title": "Test-Note", "content": "Hello", "tags": ["Test"], "allowance": "Owner"}
+/// => {"rev": 0, "op": {"type": "insert", "pos": 5, "text": " World"}}
+/// <= {"type": "ack", "rev": 1}
+/// ```
+/// ```text
+/// GET-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/live` without a cookie containing a JWT
+/// => 401
+///     {
+///         "success": false,
+///         "code": 10,
+///         "message": "user is not logged in",
+///         "time": "2022-04-11 12:20:19"
+///     }
+/// ```
+#[get("/note/{note_id}/live")]
+pub async fn live_note(path: Path<String>, req: HttpRequest, body: Payload, db: Data<dyn Storage>, hub: Data<LiveHub>) -> impl Responder {
+    let note_id = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&note_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    // Check if the user has clearance to view this note
+    let allowance = match get_allow_level_for_note(&note_id, req.clone(), db.get_ref()).await {
+        Ok(allowance) => allowance,
+        Err(e) => return e.gen_response()
+    };
+    let user_id = match get_user_id_from_request(req.clone(), db.get_ref()).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e.gen_response()
+    };
+    let note = match db.get_note(&note_id).await {
+        Ok(note) => note,
+        Err(DBError::NoDocumentFoundError) => return APIError::DBInconsistencyError(user_id, note_id).gen_response(), //user has allowance for a nonexisting note
+        Err(_) => return APIError::QueryError("failed to retrieve note".to_string()).gen_response() //unknown
+    };
+    // Upgrade the connection and hand it over to the session
+    match actix_ws::handle(&req, body) {
+        Ok((response, session, stream)) => {
+            actix_rt::spawn(run_client(note_id, note, user_id, allowance, session, stream, db, hub));
+            response
+        }
+        Err(_) => APIError::InvalidInstructionsError("endpoint requires a websocket-connection".to_string()).gen_response()
+    }
+}
+
+/// Connects a client to the session of a note until the connection gets closed
+///
+/// # Arguments
+///
+/// * `note_id` - The identifier of the note
+/// * `note` - The current state of the note
+/// * `user_id` - The user behind the client
+/// * `allowance` - The level of access the user has regarding the note
+/// * `session` - The WebSocket-session used to send messages
+/// * `stream` - The stream of incoming messages
+/// * `db` - The storage-backend
+/// * `hub` - The registry of all live-sessions
+#[allow(clippy::too_many_arguments)]
+async fn run_client(note_id: String, note: Note, user_id: String, allowance: AllowanceLevel, session: Session,
+                    mut stream: MessageStream, db: Data<dyn Storage>, hub: Data<LiveHub>) {
+    // Forward all queued messages to the client in order
+    let (sender, mut receiver) = unbounded::<String>();
+    let mut outgoing = session.clone();
+    actix_rt::spawn(async move {
+        while let Some(msg) = receiver.next().await {
+            if outgoing.text(msg).await.is_err() {
+                break
+            }
+        }
+        let _ = outgoing.close(None).await;
+    });
+
+    // Join the session
+    let (live, client_id) = hub.join(&note_id, note);
+    {
+        let mut live = live.lock().await;
+        live.clients.insert(client_id, LiveClient { user_id: user_id.clone(), allowance, sender });
+        live.send_snapshot(client_id);
+    }
+
+    // Process all incoming messages
+    let mut session = session;
+    while let Some(Ok(msg)) = stream.next().await {
+        match msg {
+            Message::Text(text) => {
+                let mut live = live.lock().await;
+                match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(msg) => live.handle(client_id, &user_id, msg, &note_id, db.get_ref()).await,
+                    Err(_) => live.send(client_id, &ServerMessage::Error { message: "message could not be parsed" })
+                }
+                // Stop listening once the client has been disconnected for lack of access
+                if !live.clients.contains_key(&client_id) {
+                    break
+                }
+            }
+            Message::Ping(bytes) if session.pong(&bytes).await.is_err() => break,
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+
+    // Leave the session, which also ends the forwarding of messages
+    live.lock().await.clients.remove(&client_id);
+    hub.leave(&note_id);
+}
@@ -4,51 +4,210 @@
 //!
 //! + System:
 //!     * `GET /system`             - Get System Information [[`return_system_status`]]
+//!     * `GET /.well-known/jwks.json` - Get the public keys verifying all JWTs [[`get_jwks`](keys::get_jwks)]
 //!
 //! + Authorisation:
 //!     * `POST /auth`              - Login [[`authenticate`](auth::authenticate)]
 //!     * `GET /auth`               - Get login-status [[`get_auth_status`](auth::get_auth_status)]
 //!     * `DELETE /auth`            - Logout [[`logout`](auth::logout)]
+//!     * `POST /auth/totp`         - Complete a login using the second factor [[`verify_second_factor`](auth::verify_second_factor)]
+//!     * `GET /auth/oidc`          - Login using the identity-provider or link an account of it [[`start_login`](oidc::start_login)]
+//!     * `GET /auth/oidc/callback` - Complete a login using the identity-provider [[`complete_login`](oidc::complete_login)]
+//!     * `POST /auth/refresh`      - Refresh the current session [[`refresh`](auth::refresh)]
+//!     * `GET /auth/sessions`      - List all active sessions [[`list_sessions`](auth::list_sessions)]
+//!     * `DELETE /auth/sessions/{session_id}` - Revoke a session [[`revoke_session`](auth::revoke_session)]
 //!
 //! + Notes:
 //!     * `GET /notes`              - List of all available notes [[`list_notes`]]
+//!     * `GET /notes/search`       - Search through all available notes [[`search_notes`](search::search_notes)]
 //!
 //!     * `POST /note`              - Add a note [[`add_note`](note::add_note)]
 //!     * `GET /note/{note_id}`     - Get a note [[`get_note`](note::get_note)]
 //!     * `PUT /note/{note_id}`     - Update a note [[`update_note`](note::update_note)]
 //!     * `DELETE /note/{note_id}`  - Remove a note [[`remove_note`](note::remove_note)]
+//!     * `GET /note/{note_id}/live` - Edit a note collaboratively over WebSocket [[`live_note`](live::live_note)]
+//!
+//! + Folders:
+//!     * `POST /folders`           - Create a folder [[`add_folder`](folder::add_folder)]
+//!     * `GET /folders`            - List all accessible folders [[`list_folders`](folder::list_folders)]
+//!     * `GET /folders/{folder_id}` - Get a folder [[`get_folder`](folder::get_folder)]
+//!     * `PUT /folders/{folder_id}` - Rename or move a folder [[`update_folder`](folder::update_folder)]
+//!     * `DELETE /folders/{folder_id}` - Remove a folder, keeping its contents [[`remove_folder`](folder::remove_folder)]
+//!     * `PUT /folders/{folder_id}/share` - Update other users access-rights regarding the folder [[`share_folder`](folder::share_folder)]
+//!     * `PUT /note/{note_id}/folder` - Move a note into a folder [[`move_note`](folder::move_note)]
+//!
+//! + Revisions:
+//!     * `GET /note/{note_id}/revisions`                   - List all revisions of a note [[`list_revisions`](revision::list_revisions)]
+//!     * `GET /note/{note_id}/revisions/{rev}`             - Get a revision [[`get_revision`](revision::get_revision)]
+//!     * `GET /note/{note_id}/revisions/{rev}/diff`        - Compare a revision with another one or the current note [[`diff_revision`](revision::diff_revision)]
+//!     * `POST /note/{note_id}/revisions/{rev}/restore`    - Restore a note to a revision [[`restore_revision`](revision::restore_revision)]
+//!
+//! + Links:
+//!     * `POST /note/{note_id}/links`                      - Create a public link to a note [[`add_link`](link::add_link)]
+//!     * `GET /note/{note_id}/links`                       - List all public links to a note [[`list_links`](link::list_links)]
+//!     * `DELETE /note/{note_id}/links/{link_id}`          - Revoke a public link [[`remove_link`](link::remove_link)]
+//!     * `GET /public/{token}`                             - Read a note using a public link, without a login [[`get_public_note`](link::get_public_note)]
+//!
+//! + Transfers:
+//!     * `POST /note/{note_id}/transfer`                   - Offer a note to another user [[`offer_transfer`](transfer::offer_transfer)]
+//!     * `POST /note/{note_id}/transfer/accept`            - Accept the offer of a note, becoming its owner [[`accept_transfer`](transfer::accept_transfer)]
+//!     * `DELETE /note/{note_id}/transfer`                 - Withdraw or decline the offer of a note [[`cancel_transfer`](transfer::cancel_transfer)]
+//!     * `GET /transfers`                                  - List all pending offers made by or to the user [[`list_transfers`](transfer::list_transfers)]
 //!
 //! + User:
 //!     * `POST /user`              - Create a new user [[`add_user`](user::add_user)]
 //!     * `GET /user`               - Get current user [[`get_user`](user::get_user)]
 //!     * `DELETE /user`            - Delete the current user and logout [[`remove_user`](user::remove_user)]
+//!     * `POST /user/tokens`       - Create a personal access-token [[`add_token`](token::add_token)]
+//!     * `GET /user/tokens`        - List all personal access-tokens [[`list_tokens`](token::list_tokens)]
+//!     * `DELETE /user/tokens/{token_id}` - Revoke a personal access-token [[`remove_token`](token::remove_token)]
+//!     * `POST /user/totp`         - Start the enrolment of a second factor [[`start_enrolment`](totp::start_enrolment)]
+//!     * `PUT /user/totp`          - Confirm the enrolment of a second factor [[`confirm_enrolment`](totp::confirm_enrolment)]
+//!     * `DELETE /user/totp`       - Disable the second factor [[`disable_second_factor`](totp::disable_second_factor)]
+//!     * `PUT /user/password`      - Change the password [[`change_password`](password::change_password)]
+//!     * `POST /user/password/reset` - Request a password-reset token [[`request_reset`](password::request_reset)]
+//!     * `PUT /user/password/reset` - Set a new password using a reset-token [[`reset_password`](password::reset_password)]
 //!
 //! + Shares:
 //!     * `GET /share`              - Generate an invite code [[`get_relation_code`](share::get_relation_code)]
 //!     * `POST /share`             - Use an invite code to create a relation between two user [[`create_relation`](share::create_relation)]
 //!     * `DELETE /share/{user_id}` - Remove the relation between two user [[`remove_relation`](share::remove_relation)]
 //!     * `PUT /share/{note_id}`    - Update other users access-rights regarding the note [[`update_allowances`](share::update_allowances)]
+//!     * `PUT /share/{note_id}/groups` - Update the access-rights of groups regarding the note [[`update_group_allowances`](share::update_group_allowances)]
+//!
+//! + Groups:
+//!     * `POST /groups`            - Create a group [[`add_group`](group::add_group)]
+//!     * `GET /groups`             - List all groups of the user [[`list_groups`](group::list_groups)]
+//!     * `GET /groups/{group_id}`  - Get a group [[`get_group`](group::get_group)]
+//!     * `DELETE /groups/{group_id}` - Remove a group [[`remove_group`](group::remove_group)]
+//!     * `POST /groups/{group_id}/members` - Add a member to a group [[`add_member`](group::add_member)]
+//!     * `DELETE /groups/{group_id}/members/{user_id}` - Remove a member from a group [[`remove_member`](group::remove_member)]
 //!
 //! For a list of Error-Responses have a look at [[`error`](mod@error)]
 
 mod note;
+mod folder;
+mod live;
+mod revision;
+mod link;
+mod transfer;
+mod search;
 mod user;
+mod token;
+mod totp;
+mod oidc;
+mod keys;
+mod password;
+mod limit;
 mod share;
+mod group;
 mod error;
 mod auth;
+#[cfg(test)]
+mod tests;
 
-use std::env;
-use std::sync::Mutex;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use chrono::SecondsFormat;
 use serde::Serialize;
-use actix_web::{get, HttpRequest, HttpResponse, Responder, web::{ServiceConfig, Data}};
-use actix_web::error::JsonPayloadError;
-use mongodb::{bson::doc, Database};
-use crate::db_access::{AllowanceLevel, DBError, get_dbo_by_id, Note, NOTES};
+use actix_web::{get, HttpRequest, HttpResponse, Responder, web::{ServiceConfig, Data, Query}};
+use actix_web::error::{JsonPayloadError, QueryPayloadError};
+use mongodb::bson::doc;
+use crate::db_access::{Allowance, DBError, get_effective_allowances, Note, NoteSummary, Storage};
 use crate::web::auth::get_user_from_request;
 use crate::web::error::APIError;
+use crate::web::json_objects::{ListRequest, ListResponse, ReducedNoteResponse, SortField, SortOrder};
+pub use crate::web::limit::{RateLimitConfig, RateLimiter};
+pub use crate::web::keys::{JWT_KEY_FILES_ENV_VAR_KEY, KeySet};
+pub use crate::web::live::LiveHub;
+pub use crate::web::oidc::OidcConfig;
+pub use crate::web::password::{issue_reset_token, RESET_DURATION_MINUTES};
 
 /// The format used to display time in
 pub const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+/// The amount of notes listed per page if not requested otherwise
+const DEFAULT_PAGE_SIZE: usize = 50;
+/// The maximum amount of notes listed per page
+const MAX_PAGE_SIZE: usize = 200;
+
+// Response-/Request-Objects
+/// Structs modelling the request- and response-bodies
+mod json_objects {
+    use chrono::{DateTime, Utc};
+    use serde::{Serialize, Deserialize};
+    use crate::db_access::AllowanceLevel;
+
+    /// The fields a list of notes can be sorted by
+    #[derive(Deserialize, Copy, Clone)]
+    #[serde(rename_all = "lowercase")]
+    pub enum SortField {
+        /// Alphabetically by title (case-insensitive)
+        Title,
+        /// By the time the notes were created
+        Created,
+        /// By the time the notes were last modified
+        Modified
+    }
+
+    /// The directions a list of notes can be sorted in
+    #[derive(Deserialize, Copy, Clone)]
+    #[serde(rename_all = "lowercase")]
+    pub enum SortOrder {
+        /// Ascending
+        Asc,
+        /// Descending
+        Desc
+    }
+
+    /// Query-parameters of a request for a page of the note-list
+    #[derive(Deserialize)]
+    pub struct ListRequest {
+        /// The maximum amount of notes on the page
+        pub limit: Option<usize>,
+        /// The position after which the page starts
+        pub cursor: Option<String>,
+        /// The field to sort by
+        pub sort: Option<SortField>,
+        /// The direction to sort in
+        pub order: Option<SortOrder>,
+        /// A tag every listed note has to be tagged with
+        pub tag: Option<String>,
+        /// The user every listed note has to be owned by
+        pub owner: Option<String>,
+        /// The level of access the user has to have regarding every listed note
+        pub level: Option<AllowanceLevel>
+    }
+
+    /// Response-body containing a limited amount of information on a note
+    #[derive(Serialize)]
+    pub struct ReducedNoteResponse {
+        /// The note's identifier
+        pub note_id: String,
+        /// The note's title
+        pub title: String,
+        /// The tags associated with the note
+        pub tags: Vec<String>,
+        /// The level of access the current user has regarding this note
+        pub allowance: AllowanceLevel,
+        /// The current version of the note
+        pub version: u64,
+        /// Timestamp of when the note was created
+        pub created_at: DateTime<Utc>,
+        /// Timestamp of when the note was last modified
+        pub updated_at: DateTime<Utc>,
+        /// The user who last modified the note
+        pub last_editor_id: String
+    }
+
+    /// Response-body containing a page of the note-list
+    #[derive(Serialize)]
+    pub struct ListResponse {
+        /// The notes on this page
+        pub notes: Vec<ReducedNoteResponse>,
+        /// The cursor to request the next page with (none if this is the last page)
+        pub next_cursor: Option<String>
+    }
+}
 
 /// Basic Response with no additional information to be returned
 #[derive(Serialize)]
@@ -94,6 +253,11 @@ pub fn json_error_handler(err:JsonPayloadError, _req: &HttpRequest) -> actix_web
     actix_web::error::InternalError::from_response(err, APIError::InvalidPayloadError.gen_response()).into()
 }
 
+/// Converts web-server internal query-conversion-error to one conforming to the rest of the responses
+pub fn query_error_handler(err: QueryPayloadError, _req: &HttpRequest) -> actix_web::error::Error {
+    actix_web::error::InternalError::from_response(err, APIError::InvalidPayloadError.gen_response()).into()
+}
+
 /// Configures the web-server to add all endpoints
 ///
 /// # Arguments
@@ -119,24 +283,73 @@ pub fn json_error_handler(err:JsonPayloadError, _req: &HttpRequest) -> actix_web
 pub fn handler_config(cfg: &mut ServiceConfig) {
     // Add all special handler
     cfg.service(return_system_status)
+        .service(keys::get_jwks)
         .service(auth::authenticate)
         .service(auth::get_auth_status)
         .service(list_notes)
-        .service(auth::logout);
+        .service(search::search_notes)
+        .service(auth::logout)
+        .service(auth::verify_second_factor)
+        .service(oidc::start_login)
+        .service(oidc::complete_login)
+        .service(auth::refresh)
+        .service(auth::list_sessions)
+        .service(auth::revoke_session);
     // Add all note-related handler
     cfg.service(note::add_note)
         .service(note::get_note)
         .service(note::update_note)
-        .service(note::remove_note);
+        .service(note::remove_note)
+        .service(live::live_note);
+    // Add all folder-related handler
+    cfg.service(folder::add_folder)
+        .service(folder::list_folders)
+        .service(folder::get_folder)
+        .service(folder::update_folder)
+        .service(folder::remove_folder)
+        .service(folder::share_folder)
+        .service(folder::move_note);
+    // Add all revision-related handler
+    cfg.service(revision::list_revisions)
+        .service(revision::get_revision)
+        .service(revision::diff_revision)
+        .service(revision::restore_revision);
+
+    cfg.service(link::add_link)
+        .service(link::list_links)
+        .service(link::remove_link)
+        .service(link::get_public_note);
+    // Add all transfer-related handler
+    cfg.service(transfer::offer_transfer)
+        .service(transfer::list_transfers)
+        .service(transfer::accept_transfer)
+        .service(transfer::cancel_transfer);
     // Add all user-related handler
     cfg.service(user::add_user)
         .service(user::get_user)
-        .service(user::remove_user);
+        .service(user::remove_user)
+        .service(token::add_token)
+        .service(token::list_tokens)
+        .service(token::remove_token)
+        .service(totp::start_enrolment)
+        .service(totp::confirm_enrolment)
+        .service(totp::disable_second_factor)
+        .service(password::change_password)
+        .service(password::request_reset)
+        .service(password::reset_password);
     // Add all share-related handler
     cfg.service(share::get_relation_code)
         .service(share::create_relation)
         .service(share::remove_relation)
-        .service(share::update_allowances);
+        .service(share::update_allowances)
+        .service(share::update_group_allowances);
+    // Add all group-related handler
+    cfg.service(group::add_group)
+        .service(group::list_groups)
+        .service(group::get_group)
+        .service(group::remove_group)
+        .service(group::add_member)
+        .service(group::remove_member);
 }
 
 /// ENDPOINT: Returns information on the system currently running.
@@ -145,6 +358,11 @@ pub fn handler_config(cfg: &mut ServiceConfig) {
 /// * `200` [Body: JSON]
 ///     - System-information could be compiled
 ///
+/// # Arguments
+///
+/// * `db` - The AppData containing the storage-backend
+/// * `oidc` - The AppData containing the identity-provider, if one has been configured
+///
 /// # Examples
 ///
 /// ```text
@@ -157,29 +375,136 @@ pub fn handler_config(cfg: &mut ServiceConfig) {
 ///             "version": "0.4.5",
 ///             "db": {
 ///                 "type": "mongo",
-///                 "version": "1.0"
-///             }
+///                 "version": "5.0.9"
+///             },
+///             "sso": false
 ///         },
 ///         "time": "2022-07-30 17:53:32"
 ///     }
 /// ```
 #[get("/system")]
-async fn return_system_status() -> impl Responder {
-    return HttpResponse::Ok().json(ResponseObjectWithPayload::new(doc! {
+async fn return_system_status(db: Data<dyn Storage>, oidc: Option<Data<OidcConfig>>) -> impl Responder {
+    let db_info = db.get_info();
+    HttpResponse::Ok().json(ResponseObjectWithPayload::new(doc! {
         "application": env!("CARGO_PKG_NAME").to_string(),
         "version": env!("CARGO_PKG_VERSION").to_string(),
         "db": {
-            "type": "mongo", //TODO Read from env-var
-            "version": "1.0"
-        }
+            "type": db_info.db_type,
+            "version": db_info.version
+        },
+        "sso": oidc.is_some()
     }))
 }
 
-/// ENDPOINT: Compiles a list of all notes the current user has access to.
+/// Looks up all notes behind the given allowances with a single query
+///
+/// # Arguments
+///
+/// * `user_id` - The identifier of the user the allowances belong to
+/// * `allowances` - The allowances whose notes are to be read
+/// * `db` - The storage-backend to read the notes from
+pub(crate) async fn get_allowed_notes(user_id: &str, allowances: Vec<Allowance>, db: &dyn Storage) -> Result<Vec<(Allowance, Note)>, APIError> {
+    let note_ids: Vec<String> = allowances.iter().map(|allowance| allowance.note_id.clone()).collect();
+    pair_with_allowances(user_id, allowances, db.get_notes(&note_ids).await)
+}
+
+/// Looks up the summaries of all notes behind the given allowances with a single query, leaving out their content
+///
+/// # Arguments
+///
+/// * `user_id` - The identifier of the user the allowances belong to
+/// * `allowances` - The allowances whose notes are to be read
+/// * `db` - The storage-backend to read the notes from
+async fn get_allowed_summaries(user_id: &str, allowances: Vec<Allowance>, db: &dyn Storage) -> Result<Vec<(Allowance, NoteSummary)>, APIError> {
+    let note_ids: Vec<String> = allowances.iter().map(|allowance| allowance.note_id.clone()).collect();
+    pair_with_allowances(user_id, allowances, db.get_note_summaries(&note_ids).await)
+}
+
+/// Pairs every allowance with the note it refers to
+///
+/// # Arguments
+///
+/// * `user_id` - The identifier of the user the allowances belong to
+/// * `allowances` - The allowances to be paired
+/// * `notes` - The result of reading the notes behind the allowances, paired with their identifiers
+fn pair_with_allowances<T>(user_id: &str, allowances: Vec<Allowance>, notes: Result<Vec<(String, T)>, DBError>) -> Result<Vec<(Allowance, T)>, APIError> {
+    let mut notes: HashMap<String, T> = notes
+        .map_err(|_| APIError::QueryError("failed to retrieve notes".to_string()))?
+        .into_iter().collect();
+    allowances.into_iter().map(|allowance| match notes.remove(&allowance.note_id) {
+        Some(note) => Ok((allowance, note)),
+        None => Err(APIError::DBInconsistencyError(user_id.to_string(), allowance.note_id)) //user has allowance for a nonexisting note
+    }).collect()
+}
+
+/// Compares two note-identifiers in the order they were assigned in.
+/// Every storage-backend assigns identifiers of equal length in ascending order (longer ones being newer)
+///
+/// # Arguments
+///
+/// * `a` - The first identifier
+/// * `b` - The second identifier
+fn cmp_note_ids(a: &str, b: &str) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// Returns the value a note is sorted by in a list
+///
+/// # Arguments
+///
+/// * `sort` - The field the list is sorted by
+/// * `note` - The note to get the value of
+fn sort_key(sort: SortField, note: &NoteSummary) -> String {
+    match sort {
+        SortField::Title => note.title.to_lowercase(),
+        // Timestamps of a fixed length sort chronologically as text
+        SortField::Created => note.created_at.to_rfc3339_opts(SecondsFormat::Nanos, true),
+        SortField::Modified => note.updated_at.to_rfc3339_opts(SecondsFormat::Nanos, true)
+    }
+}
+
+/// Encodes the position of a note inside of a sorted list into an opaque cursor
+///
+/// # Arguments
+///
+/// * `key` - The value the note is sorted by
+/// * `note_id` - The identifier of the note
+fn encode_cursor(key: &str, note_id: &str) -> String {
+    serde_json::to_vec(&(key, note_id)).unwrap_or_default().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodes a cursor created by [`encode_cursor`], returning nothing if it is malformed
+///
+/// # Arguments
+///
+/// * `cursor` - The cursor to be decoded
+fn decode_cursor(cursor: &str) -> Option<(String, String)> {
+    if !cursor.len().is_multiple_of(2) || !cursor.is_ascii() {
+        return None
+    }
+    let bytes = (0..cursor.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// ENDPOINT: Compiles a sorted and filtered list of the notes the current user has access to.
+/// The list is split into pages, each of them referencing the next one by a cursor.
+///
+/// Query-parameters (all optional):
+/// * `limit` - The maximum amount of notes per page (default: 50, at most 200)
+/// * `cursor` - The `next_cursor` of the previous page
+/// * `sort` - The field to sort by: `title`, `created` (default) or `modified`
+/// * `order` - The direction to sort in: `asc` (default) or `desc`
+/// * `tag` - Only list notes tagged with this tag (case-insensitive)
+/// * `owner` - Only list notes owned by this user
+/// * `level` - Only list notes the user has this level of access to (e.g. `Owner`)
 ///
 /// Returns one of the following HttpResponses:
 /// * `200`
 ///     - \[Body: JSON\] List could be compiled
+/// * `400`
+///     - **\[20\]** Query-parameters or cursor are malformed
 /// * `401`
 ///     - **\[10\]** No user could be verified
 /// * `500`
@@ -187,36 +512,48 @@ async fn return_system_status() -> impl Responder {
 ///
 /// # Arguments
 ///
+/// * `query` - The query-parameters describing the requested page
 /// * `req` - The HttpRequest that was made
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `db` - The AppData containing the storage-backend
 ///
 /// # Examples
 ///
 /// ```text
-/// GET-Request at `{api-url}/notes` with a cookie containing a valid JWT
+/// GET-Request at `{api-url}/notes?sort=title&tag=note&limit=2` with a cookie containing a valid JWT
 /// => 200
 ///     {
 ///         "success": true,
-///         "content": [
-///             {
-///                 "note_id": "7254fa970b62u3ag62dr4d3l",
-///                 "title": "Test-Note",
-///                 "tags": [
-///                     "Test",
-///                     "Note"
-///                 ],
-///                 "allowance": "Owner"
-///             },
-///             {
-///                 "note_id": "7354fa9uu782u3ag62t54d3l",
-///                 "title": "Note, but this time different",
-///                 "tags": [
-///                     "Note",
-///                     "Different"
-///                 ],
-///                 "allowance": "Read"
-///             }
-///         ],
+///         "content": {
+///             "notes": [
+///                 {
+///                     "note_id": "7354fa9uu782u3ag62t54d3l",
+///                     "title": "Note, but this time different",
+///                     "tags": [
+///                         "Note",
+///                         "Different"
+///                     ],
+///                     "allowance": "Read",
+///                     "version": 0,
+///                     "created_at": "2022-04-10T09:12:45.310Z",
+///                     "updated_at": "2022-04-10T09:12:45.310Z",
+///                     "last_editor_id": "otherUser"
+///                 },
+///                 {
+///                     "note_id": "7254fa970b62u3ag62dr4d3l",
+///                     "title": "Test-Note",
+///                     "tags": [
+///                         "Test",
+///                         "Note"
+///                     ],
+///                     "allowance": "Owner",
+///                     "version": 3,
+///                     "created_at": "2022-04-09T17:01:13.004Z",
+///                     "updated_at": "2022-04-11T11:58:41.872Z",
+///                     "last_editor_id": "testUser"
+///                 }
+///             ],
+///             "next_cursor": "5b22746573742d6e6f7465222c2237323534666139373062363275336167363264723464336c225d"
+///         },
 ///         "time": "2022-04-11 12:00:05"
 ///     }
 /// ```
@@ -230,40 +567,68 @@ async fn return_system_status() -> impl Responder {
 ///     }
 /// ```
 #[get("/notes")]
-async fn list_notes(req: HttpRequest, db: Data<Mutex<Database>>) -> impl Responder {
-    // Define Response-Object
-    /// Response-body containing a limited amount of information on a note
-    #[derive(Serialize)]
-    struct ReducedNoteResponse {
-        /// The note's identifier
-        note_id: String,
-        /// The note's title
-        title: String,
-        /// The tags associated with the note
-        tags: Vec<String>,
-        /// The level of access the current user has regarding this note
-        allowance: AllowanceLevel
-    }
+async fn list_notes(query: Query<ListRequest>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let query = query.into_inner();
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let sort = query.sort.unwrap_or(SortField::Created);
+    let cursor = match query.cursor.as_deref().map(decode_cursor) {
+        Some(None) => return APIError::InvalidPayloadError.gen_response(),
+        Some(Some(cursor)) => Some(cursor),
+        None => None
+    };
+    let tag = query.tag.map(|tag| tag.to_lowercase());
 
     match get_user_from_request(req, db.get_ref()).await {
         Ok(user) => {
-            let mut response_vector = Vec::new();
-            for allowance in user.allowances {
-                // Read all allowed notes and create response-objects
-                match get_dbo_by_id::<Note>(NOTES, allowance.note_id.clone(), db.get_ref()).await {
-                    Ok(note) => response_vector.push(ReducedNoteResponse {
-                        note_id: allowance.note_id,
-                        title: note.title,
-                        tags: note.tags,
-                        allowance: allowance.level
-                    }),
-                    Err(DBError::NoDocumentFoundError) => return APIError::DBInconsistencyError(
-                                user._id, allowance.note_id).gen_response(), //user has allowance for a nonexisting note
-                    Err(_) => {} //unknown
+            // Read all allowed notes at once without their content, including those shared with a group of the user
+            let allowances = match get_effective_allowances(&user, db.get_ref()).await {
+                Ok(allowances) => allowances,
+                Err(_) => return APIError::QueryError("groups could not be retrieved from database".to_string()).gen_response()
+            };
+            let allowances = allowances.into_iter()
+                .filter(|allowance| query.level.is_none_or(|level| allowance.level == level))
+                .collect();
+            let notes = match get_allowed_summaries(&user._id, allowances, db.get_ref()).await {
+                Ok(notes) => notes,
+                Err(e) => return e.gen_response()
+            };
+            // Filter and sort the notes
+            let mut entries: Vec<(String, Allowance, NoteSummary)> = notes.into_iter()
+                .filter(|(_, note)| query.owner.as_ref().is_none_or(|owner| note.owner_id.eq(owner)))
+                .filter(|(_, note)| tag.as_ref().is_none_or(|tag| note.tags.iter().any(|t| t.to_lowercase().eq(tag))))
+                .map(|(allowance, note)| (sort_key(sort, &note), allowance, note))
+                .collect();
+            let order = query.order.unwrap_or(SortOrder::Asc);
+            let cmp = |a_key: &str, a_id: &str, b_key: &str, b_id: &str| {
+                let ordering = a_key.cmp(b_key).then_with(|| cmp_note_ids(a_id, b_id));
+                match order {
+                    SortOrder::Asc => ordering,
+                    SortOrder::Desc => ordering.reverse()
                 }
-            }
-            // Return the compiled list of notes
-            HttpResponse::Ok().json(ResponseObjectWithPayload::new(response_vector))
+            };
+            entries.sort_by(|(a_key, a, _), (b_key, b, _)| cmp(a_key, &a.note_id, b_key, &b.note_id));
+            // Skip everything up to and including the cursor
+            let start = match &cursor {
+                Some((key, note_id)) => entries.partition_point(|(e_key, e, _)| cmp(e_key, &e.note_id, key, note_id) != Ordering::Greater),
+                None => 0
+            };
+            let page: Vec<(String, Allowance, NoteSummary)> = entries.into_iter().skip(start).take(limit + 1).collect();
+            let next_cursor = match page.get(limit) {
+                Some(_) => page.get(limit - 1).map(|(key, allowance, _)| encode_cursor(key, &allowance.note_id)),
+                None => None
+            };
+            // Return the compiled page of notes
+            let notes = page.into_iter().take(limit).map(|(_, allowance, note)| ReducedNoteResponse {
+                note_id: allowance.note_id,
+                title: note.title,
+                tags: note.tags,
+                allowance: allowance.level,
+                version: note.version,
+                created_at: note.created_at,
+                updated_at: note.updated_at,
+                last_editor_id: note.last_editor_id
+            }).collect();
+            HttpResponse::Ok().json(ResponseObjectWithPayload::new(ListResponse { notes, next_cursor }))
         }
         Err(e) => e.gen_response()
     }
@@ -1,10 +1,9 @@
 //! Endpoints regarding note-objects and their manipulation
 
-use std::sync::Mutex;
 use actix_web::{get, put, delete, post, Responder, HttpRequest, HttpResponse, web::{Data, Path}, web};
-use mongodb::bson::doc;
-use mongodb::Database;
-use crate::db_access::{AllowanceLevel, DBError, del_dbo_by_id, get_dbo_by_id, insert_dbo, is_safe, Note, NOTES, update_dbo_by_id, User, USER};
+use actix_web::http::header::{ETAG, IF_MATCH};
+use chrono::Utc;
+use crate::db_access::{Allowance, AllowanceLevel, DBError, get_effective_allowances, is_safe, Note, Revision, Storage, User};
 use crate::web::error::APIError;
 use crate::web::auth::{get_user_from_request, get_user_id_from_request};
 use crate::web::note::json_objects::{NoteRequest, NoteResponse};
@@ -12,7 +11,8 @@ use crate::web::{ResponseObject, ResponseObjectWithPayload};
 
 // Response-/Request-Objects
 /// Structs modelling the request- and response-bodies
-mod json_objects {
+pub(crate) mod json_objects {
+    use chrono::Utc;
     use serde::{Serialize, Deserialize};
     use crate::db_access::{AllowanceLevel, Note};
 
@@ -27,13 +27,23 @@ mod json_objects {
         pub tags: Vec<String>
     }
     impl NoteRequest {
-        /// Converts the Request-body into an actual Note-object
+        /// Converts the Request-body into an actual Note-object, created just now by its owner
         ///
         /// # Arguments
         ///
         /// * `owner_id` - The user that owns the note
-        pub fn to_note(self, owner_id: &str) -> Note {
-            Note { title: self.title, content: self.content, owner_id: owner_id.to_string(), tags: self.tags }
+        pub fn into_note(self, owner_id: &str) -> Note {
+            let now = Utc::now();
+            Note {
+                title: self.title,
+                content: self.content,
+                owner_id: owner_id.to_string(),
+                tags: self.tags,
+                version: 0,
+                created_at: now,
+                updated_at: now,
+                last_editor_id: owner_id.to_string()
+            }
         }
     }
 
@@ -63,7 +73,7 @@ mod json_objects {
 ///
 /// * `req` - The HttpRequest that was made
 /// * `note_req` - The body of the request parsed to a NoteRequest-object
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `db` - The AppData containing the storage-backend
 ///
 /// # Examples
 ///
@@ -86,7 +96,11 @@ mod json_objects {
 ///                 "tags": [
 ///                     "Test",
 ///                     "Note"
-///                 ]
+///                 ],
+///                 "version": 0,
+///                 "created_at": "2022-04-11T12:20:28.120Z",
+///                 "updated_at": "2022-04-11T12:20:28.120Z",
+///                 "last_editor_id": "testUser"
 ///             },
 ///             "allowance": "Owner"
 ///         },
@@ -109,20 +123,18 @@ mod json_objects {
 ///     }
 /// ```
 #[post("/note")]
-pub async fn add_note(req: HttpRequest, note_req: web::Json<NoteRequest>, db: Data<Mutex<Database>>) -> impl Responder {
+pub async fn add_note(req: HttpRequest, note_req: web::Json<NoteRequest>, db: Data<dyn Storage>) -> impl Responder {
     let note_req = note_req.into_inner();
     // Grab the user to add a note to
-    match get_user_from_request(req, &db).await {
+    match get_user_from_request(req, db.get_ref()).await {
         Ok(user) => {
             // Add the new note to the db
-            let note = note_req.to_note(&user._id);
-            match insert_dbo::<Note>(NOTES, &note, db.get_ref()).await {
-                Ok(res) => {
+            let note = note_req.into_note(&user._id);
+            match db.insert_note(&note).await {
+                Ok(note_id) => {
                     // Add an allowance to the user
-                    let note_id = res.inserted_id.as_object_id().unwrap().to_string();
-                    match update_dbo_by_id::<User>(USER, user._id,
-                                                   doc! {"$push": {"allowances": {"note_id": &note_id, "level": "Owner"}}},
-                                                   db.get_ref()).await {
+                    match db.add_allowance(&user._id,
+                                           &Allowance { note_id: note_id.clone(), level: AllowanceLevel::Owner }).await {
                         Ok(_res) => HttpResponse::Created() // Return the created note
                             .json(ResponseObjectWithPayload::new(NoteResponse { note_id, note, allowance: AllowanceLevel::Owner})), //TODO? Re-fetch object instead of putting together
                         Err(_) => APIError::QueryError("note could not be linked to user-account".to_string()).gen_response() //unknown
@@ -135,7 +147,8 @@ pub async fn add_note(req: HttpRequest, note_req: web::Json<NoteRequest>, db: Da
     }
 }
 
-/// ENDPOINT: Returns a note from the database using it's identifier
+/// ENDPOINT: Returns a note from the database using it's identifier.
+/// The current version of the note is returned as the `ETag`-header as well
 ///
 /// Returns one of the following HttpResponses:
 /// * `200`
@@ -153,7 +166,7 @@ pub async fn add_note(req: HttpRequest, note_req: web::Json<NoteRequest>, db: Da
 ///
 /// * `path` - A Path-object containing the id of the to-be-returned note
 /// * `req` - The HttpRequest that was made
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `db` - The AppData containing the storage-backend
 ///
 /// # Examples
 ///
@@ -171,7 +184,11 @@ pub async fn add_note(req: HttpRequest, note_req: web::Json<NoteRequest>, db: Da
 ///                 "tags": [
 ///                     "Test",
 ///                     "Note"
-///                 ]
+///                 ],
+///                 "version": 0,
+///                 "created_at": "2022-04-11T12:20:28.120Z",
+///                 "updated_at": "2022-04-11T12:20:28.120Z",
+///                 "last_editor_id": "testUser"
 ///             },
 ///             "allowance": "Owner"
 ///         },
@@ -209,7 +226,7 @@ pub async fn add_note(req: HttpRequest, note_req: web::Json<NoteRequest>, db: Da
 ///     }
 /// ```
 #[get("/note/{note_id}")]
-pub async fn get_note(path: Path<String>, req: HttpRequest, db: Data<Mutex<Database>>) -> impl Responder {
+pub async fn get_note(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
     let note_id = path.into_inner();
     // Check for potential injection-attempt
     if !is_safe(&note_id) {
@@ -219,10 +236,13 @@ pub async fn get_note(path: Path<String>, req: HttpRequest, db: Data<Mutex<Datab
     match get_allow_level_for_note(&note_id, req.clone(), db.get_ref()).await {
         Ok(allowance) => {
             // Get note and return it
-            match get_dbo_by_id::<Note>(NOTES, note_id.clone(), db.get_ref()).await {
-                Ok(note) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(NoteResponse { note_id, note, allowance})),
-                Err(DBError::NoDocumentFoundError) => APIError::DBInconsistencyError(
-                    get_user_id_from_request(req).unwrap(), note_id).gen_response(), //user has allowance for a nonexisting note
+            match db.get_note(&note_id).await {
+                Ok(note) => HttpResponse::Ok().insert_header((ETAG, gen_etag(note.version)))
+                    .json(ResponseObjectWithPayload::new(NoteResponse { note_id, note, allowance})),
+                Err(DBError::NoDocumentFoundError) => match get_user_id_from_request(req, db.get_ref()).await {
+                    Ok(user_id) => APIError::DBInconsistencyError(user_id, note_id).gen_response(), //user has allowance for a nonexisting note
+                    Err(e) => e.gen_response()
+                },
                 Err(_) => APIError::QueryError("failed to retrieve note".to_string()).gen_response() //unknown
             }
         }
@@ -230,7 +250,11 @@ pub async fn get_note(path: Path<String>, req: HttpRequest, db: Data<Mutex<Datab
     }
 }
 
-/// ENDPOINT: Takes a note and updates its counterpart in the database with its own values
+/// ENDPOINT: Takes a note and updates its counterpart in the database with its own values.
+/// The previous state of the note is kept as a new revision (see [`revision`](crate::web::revision))
+///
+/// To not overwrite modifications of others, the version the update is based on can be passed
+/// as the `If-Match`-header (as returned in the `ETag`-header of [`get_note`]).
 ///
 /// Returns one of the following HttpResponses:
 /// * `200`
@@ -241,6 +265,8 @@ pub async fn get_note(path: Path<String>, req: HttpRequest, db: Data<Mutex<Datab
 ///     - **\[10\]** Missing or invalid JWT
 /// * `403`
 ///     - **\[12\]** Insufficient access-level (no write-access)
+/// * `412`
+///     - **\[23\]** Note has been modified since the given version
 /// * `500`
 ///     - Something went wrong internally (debug)
 ///
@@ -249,7 +275,7 @@ pub async fn get_note(path: Path<String>, req: HttpRequest, db: Data<Mutex<Datab
 /// * `path` - A Path-object containing the id of the to-be-deleted note
 /// * `req` - The HttpRequest that was made
 /// * `note_req` - The body of the request parsed to a NoteRequest-object
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `db` - The AppData containing the storage-backend
 ///
 /// # Examples
 ///
@@ -273,7 +299,11 @@ pub async fn get_note(path: Path<String>, req: HttpRequest, db: Data<Mutex<Datab
 ///                     "Test",
 ///                     "Note",
 ///                     "Updated"
-///                 ]
+///                 ],
+///                 "version": 1,
+///                 "created_at": "2022-04-11T12:20:28.120Z",
+///                 "updated_at": "2022-04-11T12:25:03.512Z",
+///                 "last_editor_id": "testUser"
 ///             },
 ///             "allowance": "Owner"
 ///         },
@@ -325,8 +355,23 @@ pub async fn get_note(path: Path<String>, req: HttpRequest, db: Data<Mutex<Datab
 ///         "time": "2022-04-11 12:20:19"
 ///     }
 /// ```
+/// ```text
+/// PUT-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l` with the header `If-Match: "0"` to a note modified in the meantime
+///     {
+///         "title": "Test-Note",
+///         "content": "This is but a simple demonstration",
+///         "tags": ["Test", "Note", "Updated"]
+///     }
+/// => 412
+///     {
+///         "success": false,
+///         "code": 23,
+///         "message": "resource has been modified in the meantime (current version: 1)",
+///         "time": "2022-04-11 12:20:19"
+///     }
+/// ```
 #[put("/note/{note_id}")]
-pub async fn update_note(path: Path<String>, req: HttpRequest, note_req: web::Json<NoteRequest>, db: Data<Mutex<Database>>) -> impl Responder {
+pub async fn update_note(path: Path<String>, req: HttpRequest, note_req: web::Json<NoteRequest>, db: Data<dyn Storage>) -> impl Responder {
     let note_req = note_req.into_inner();
     let note_id = path.into_inner();
     // Check for potential injection-attempt
@@ -334,20 +379,34 @@ pub async fn update_note(path: Path<String>, req: HttpRequest, note_req: web::Js
         return APIError::InvalidIDError.gen_response()
     }
     // Check if the user has the clearance to update the note
-    match get_allow_level_for_note(&note_id, req.clone(), &db).await {
+    match get_allow_level_for_note(&note_id, req.clone(), db.get_ref()).await {
         Ok(AllowanceLevel::Read) => APIError::NoPermissionError.gen_response(), //Read-Only Access
         Ok(allowance) => {
+            let user_id = match get_user_id_from_request(req.clone(), db.get_ref()).await {
+                Ok(user_id) => user_id,
+                Err(e) => return e.gen_response()
+            };
+            // Keep the current state of the note as a revision
+            let note = match db.get_note(&note_id).await {
+                Ok(note) => note,
+                Err(DBError::NoDocumentFoundError) => return APIError::DBInconsistencyError(user_id, note_id).gen_response(), //user has allowance for a nonexisting note
+                Err(_) => return APIError::QueryError("failed to retrieve note".to_string()).gen_response() //unknown
+            };
+            // Check whether the update is based on the current version
+            if !matches_if_match(&req, note.version) {
+                return APIError::VersionConflictError(note.version).gen_response()
+            }
+            let revision = gen_revision(&note_id, &note, &user_id);
             // Update all fields of the note
-            match update_dbo_by_id::<Note>(NOTES, note_id.clone(), doc! {"$set": { //TODO? Only update changed fields
-                "title": &note_req.title,
-                "content": &note_req.content,
-                "tags": &note_req.tags
-            }}, &db).await {
-                Ok(_res) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(NoteResponse { //TODO? Re-fetch object instead of putting together
-                    note_id,
-                    note: note_req.to_note(&get_user_id_from_request(req).unwrap()),
-                    allowance
-                })),
+            let updated = Note { created_at: note.created_at, last_editor_id: user_id, ..note_req.into_note(&note.owner_id) };
+            match db.set_note_fields(&note_id, &updated, note.version, Some(&revision)).await { //TODO? Only update changed fields
+                Ok(version) => HttpResponse::Ok().insert_header((ETAG, gen_etag(version)))
+                    .json(ResponseObjectWithPayload::new(NoteResponse { //TODO? Re-fetch object instead of putting together
+                        note_id,
+                        note: Note { version, ..updated },
+                        allowance
+                    })),
+                Err(DBError::VersionMismatchError(version)) => APIError::VersionConflictError(version).gen_response(), //modified concurrently
                 Err(_) => APIError::QueryError("update of note failed".to_string()).gen_response() //unknown
             }
         }
@@ -373,7 +432,7 @@ pub async fn update_note(path: Path<String>, req: HttpRequest, note_req: web::Js
 ///
 /// * `path` - A Path-object containing the id of the to-be-deleted note
 /// * `req` - The HttpRequest that was made
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `db` - The AppData containing the storage-backend
 ///
 /// # Examples
 ///
@@ -416,22 +475,26 @@ pub async fn update_note(path: Path<String>, req: HttpRequest, note_req: web::Js
 ///     }
 /// ```
 #[delete("/note/{note_id}")]
-pub async fn remove_note(path: Path<String>, req: HttpRequest, db: Data<Mutex<Database>>) -> impl Responder {
+pub async fn remove_note(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
     let note_id = path.into_inner();
     // Check for potential injection-attempt
     if !is_safe(&note_id) {
         return APIError::InvalidIDError.gen_response()
     }
     // Check if the user has the clearance to deleting the note
-    match get_allow_level_for_note(&note_id, req, &db).await {
+    match get_allow_level_for_note(&note_id, req, db.get_ref()).await {
         Ok(AllowanceLevel::Owner) =>  {
-            // Remove all allowances
-            let users = db.lock().unwrap().collection::<User>(USER);
-            match users.update_many(doc! {}, doc! {"$pull": {"allowances": {"note_id": &note_id}}}, None).await { //TODO Dont queue through all notes
+            // Remove all allowances and public links
+            match db.pull_note_allowances(&note_id).await.and(db.pull_note_group_allowances(&note_id).await)
+                .and(db.pull_folder_note(&note_id).await).and(db.remove_links(&note_id).await)
+                .and(db.remove_transfer(&note_id).await) {
                 Ok(_res) => {
-                    // Remove note
-                    match del_dbo_by_id::<Note>(NOTES, note_id, &db).await {
-                        Ok(_res) => HttpResponse::Ok().json(ResponseObject::new()),
+                    // Remove note and its history
+                    match db.remove_note(&note_id).await {
+                        Ok(_res) => match db.remove_revisions(&note_id).await {
+                            Ok(_res) => HttpResponse::Ok().json(ResponseObject::new()),
+                            Err(_) => APIError::QueryError("revisions of note could not be removed".to_string()).gen_response()
+                        },
                         Err(_) => APIError::QueryError("note-object could not be removed".to_string()).gen_response()
                     }
                 }
@@ -443,23 +506,79 @@ pub async fn remove_note(path: Path<String>, req: HttpRequest, db: Data<Mutex<Da
     }
 }
 
-/// Looks up and returns the level of access the current user has regarding the given note
+/// Looks up and returns the level of access the current user has regarding the given note.
+/// Allowances given to groups of the user count as well, the highest level being the effective one
 ///
 /// # Arguments
 ///
 /// * `note_id` - The identifier of the note in question
 /// * `req` - The HttpRequest that was made
-/// * `db` - A reference to the Mutex-secured Database-connection
-pub async fn get_allow_level_for_note(note_id: &str, req: HttpRequest, db: &Mutex<Database>) -> Result<AllowanceLevel, APIError> {
+/// * `db` - A reference to the storage-backend
+pub async fn get_allow_level_for_note(note_id: &str, req: HttpRequest, db: &dyn Storage) -> Result<AllowanceLevel, APIError> {
     // Get the User making the request
     match get_user_from_request(req, db).await {
-        Ok(user) => {
-            // Check if there is an allowance for this note
-            match user.allowances.iter().find(|all| all.note_id.eq(&note_id)) {
-                Some(allowance) => Ok(allowance.clone().level),
-                None => Err(APIError::NoPermissionError)
-            }
-        }
+        Ok(user) => get_allow_level_of_user(note_id, &user, db).await,
         Err(e) => Err(e)
     }
 }
+
+/// Looks up and returns the level of access a user has regarding the given note, see [`get_allow_level_for_note`]
+///
+/// # Arguments
+///
+/// * `note_id` - The identifier of the note in question
+/// * `user` - The user in question
+/// * `db` - A reference to the storage-backend
+pub async fn get_allow_level_of_user(note_id: &str, user: &User, db: &dyn Storage) -> Result<AllowanceLevel, APIError> {
+    let allowances = get_effective_allowances(user, db).await
+        .map_err(|_| APIError::QueryError("groups could not be retrieved from database".to_string()))?;
+    // Check if there is an allowance for this note
+    match allowances.iter().find(|all| all.note_id.eq(&note_id)) {
+        Some(allowance) => Ok(allowance.level),
+        None => Err(APIError::NoPermissionError)
+    }
+}
+
+/// Creates the revision keeping the given state of a note, to be added once the note has been updated
+///
+/// # Arguments
+///
+/// * `note_id` - The identifier of the note
+/// * `note` - The state of the note to be kept
+/// * `author_id` - The user replacing this state
+pub fn gen_revision(note_id: &str, note: &Note, author_id: &str) -> Revision {
+    Revision {
+        note_id: note_id.to_string(),
+        rev: 0, // Assigned by the storage-backend
+        author_id: author_id.to_string(),
+        created_at: Utc::now(),
+        title: note.title.clone(),
+        content: note.content.clone(),
+        tags: note.tags.clone()
+    }
+}
+
+/// Generates the ETag representing a version of a note
+///
+/// # Arguments
+///
+/// * `version` - The version of the note
+pub fn gen_etag(version: u64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Checks whether the `If-Match`-header of a request (if given) matches the current version of a note
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `version` - The current version of the note
+fn matches_if_match(req: &HttpRequest, version: u64) -> bool {
+    match req.headers().get(IF_MATCH).map(|header| header.to_str()) {
+        None => true,
+        Some(Ok(header)) => header.split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/").eq(&gen_etag(version))),
+        Some(Err(_)) => false
+    }
+}
@@ -0,0 +1,488 @@
+//! Login using an external OpenID Connect identity-provider
+//!
+//! The login follows the authorization-code flow secured with PKCE:
+//! 1. [`start_login`] redirects the browser to the identity-provider,
+//!    remembering the state, nonce and code-verifier of the attempt within a short-lived cookie.
+//! 2. The identity-provider redirects the browser back to [`complete_login`] with an authorization-code,
+//!    which is redeemed for an ID-token at the identity-provider.
+//! 3. Once the ID-token has been verified using the published keys of the identity-provider,
+//!    the account (`sub`) is mapped to a user and a session is started just like at [`authenticate`](crate::web::auth::authenticate).
+//!
+//! Accounts are mapped to the user they have been linked to before. Accounts are never linked to existing users
+//! by their name, a logged in user links an account by logging in with `link=true` instead.
+//! Other unknown accounts get a new user named after their `preferred_username` (falling back to `sub`)
+//! if auto-provisioning is enabled and the name is not taken yet, otherwise the login is rejected.
+//! Further factors are left to the identity-provider, a second factor configured within writeUp is not requested.
+//!
+//! See [`OidcConfig::from_env`] on how to configure the identity-provider.
+
+use std::env;
+use actix_web::{get, HttpRequest, HttpResponse, Responder, web};
+use actix_web::cookie::{CookieBuilder, SameSite, time::Duration};
+use actix_web::http::header::LOCATION;
+use actix_web::middleware::from_fn;
+use actix_web::web::Data;
+use awc::Client;
+use chrono::Utc;
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{Algorithm, decode, decode_header, DecodingKey, Validation};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::db_access::{Credential, DBError, Identity, is_safe, Storage, User};
+use crate::secret::{Secret, secret_from_env};
+use crate::web::auth::{get_session_from_request, start_session_with};
+use crate::web::error::APIError;
+use crate::web::keys::get_keys;
+use crate::web::limit::limit_requests;
+use crate::web::oidc::json_objects::{CallbackRequest, IdClaims, Jwks, LoginRequest, ProviderMetadata, TokenResponse};
+use crate::has_dev_flag;
+
+/// Name of the cookie carrying the state of a pending login
+const FLOW_COOKIE_NAME: &str = "writeup_oidc";
+/// Time in minutes a user has to log in at the identity-provider
+const FLOW_DURATION_MINUTES: i64 = 10;
+/// Audience of JWTs carrying the state of a pending login, which are not accepted as a login
+const FLOW_AUDIENCE: &str = "writeup-oidc";
+/// Amount of characters making up the state and the nonce of a login
+const FLOW_SECRET_SIZE: usize = 32;
+/// Amount of characters making up the PKCE code-verifier of a login
+const CODE_VERIFIER_SIZE: usize = 64;
+/// Amount of characters making up the password of an auto-provisioned user, which is never handed out
+const PROVISIONED_PASSWORD_SIZE: usize = 48;
+/// The scopes requested from the identity-provider
+const SCOPES: &str = "openid profile";
+
+/// The identity-provider to log in with, to be added to the app-data of the web-server
+#[derive(Clone, Debug)]
+pub struct OidcConfig {
+    /// The issuer-URL of the identity-provider, under which its discovery-document is published
+    pub issuer: String,
+    /// The identifier writeUp is registered with at the identity-provider
+    pub client_id: String,
+    /// The secret writeUp authenticates itself with at the identity-provider (none for public clients)
+    pub client_secret: Option<Secret>,
+    /// The URL of [`complete_login`] the identity-provider redirects to
+    pub redirect_uri: String,
+    /// Whether unknown accounts get a new user instead of being rejected
+    pub auto_provision: bool,
+    /// The URL the browser is redirected to once logged in
+    pub post_login_uri: String
+}
+
+impl OidcConfig {
+    /// Reads the identity-provider from the following environment-variables.
+    /// Returns None if `OIDC_ISSUER` is not set, disabling the login using an identity-provider:
+    /// * `OIDC_ISSUER` - The issuer-URL of the identity-provider
+    /// * `OIDC_CLIENT_ID` - The identifier writeUp is registered with
+    /// * `OIDC_CLIENT_SECRET` - The secret writeUp authenticates itself with *[default: none]*
+    /// * `OIDC_REDIRECT_URI` - The public URL of `GET {api-url}/auth/oidc/callback`
+    /// * `OIDC_AUTO_PROVISION` - Whether to create users for unknown accounts (`true` or `false`) *[default: `false`]*
+    /// * `OIDC_POST_LOGIN_URI` - The URL to return to once logged in *[default: `/`]*
+    pub fn from_env() -> Option<OidcConfig> {
+        let issuer = env::var("OIDC_ISSUER").ok()?;
+        Some(OidcConfig {
+            issuer,
+            client_id: env::var("OIDC_CLIENT_ID").expect("Env-Variable 'OIDC_CLIENT_ID' needs to be set"),
+            client_secret: secret_from_env("OIDC_CLIENT_SECRET"),
+            redirect_uri: env::var("OIDC_REDIRECT_URI").expect("Env-Variable 'OIDC_REDIRECT_URI' needs to be set"),
+            auto_provision: env::var("OIDC_AUTO_PROVISION").is_ok_and(|value| value.parse()
+                .expect("Env-Variable 'OIDC_AUTO_PROVISION' needs to be either 'true' or 'false'")),
+            post_login_uri: env::var("OIDC_POST_LOGIN_URI").unwrap_or_else(|_| "/".to_string())
+        })
+    }
+}
+
+/// Struct containing all information to be encoded in the JWT carrying the state of a pending login
+#[derive(Debug, Deserialize, Serialize)]
+struct FlowClaims {
+    /// The value the identity-provider has to return unchanged
+    state: String,
+    /// The value the ID-token has to contain
+    nonce: String,
+    /// The PKCE code-verifier the authorization-code is redeemed with
+    verifier: String,
+    /// Whether the session to be started should end together with the browser-session
+    session_only: bool,
+    /// The logged in user the account is to be linked to
+    link_user_id: Option<String>,
+    /// Audience distinguishing the state from a regular JWT
+    aud: String,
+    /// Timestamp of state-expiration
+    exp: usize
+}
+
+// Response-/Request-Objects
+/// Structs modelling the request- and response-bodies, including those exchanged with the identity-provider
+mod json_objects {
+    use serde::Deserialize;
+
+    /// Query-parameters of a request to log in
+    #[derive(Deserialize)]
+    pub struct LoginRequest {
+        /// Define whether or not to verify the user for longer than a single session
+        pub session_only: Option<bool>,
+        /// Define whether to link the account to the user who is logged in
+        pub link: Option<bool>
+    }
+
+    /// Query-parameters the identity-provider redirects back with
+    #[derive(Deserialize)]
+    pub struct CallbackRequest {
+        /// The authorization-code to be redeemed
+        pub code: Option<String>,
+        /// The state handed to the identity-provider
+        pub state: Option<String>,
+        /// The reason the identity-provider did not issue an authorization-code
+        pub error: Option<String>
+    }
+
+    /// The parts of the discovery-document of an identity-provider writeUp relies on
+    #[derive(Deserialize)]
+    pub struct ProviderMetadata {
+        /// The issuer-URL of the identity-provider
+        pub issuer: String,
+        /// The URL to redirect to for logging in
+        pub authorization_endpoint: String,
+        /// The URL authorization-codes are redeemed at
+        pub token_endpoint: String,
+        /// The URL the keys signing ID-tokens are published at
+        pub jwks_uri: String
+    }
+
+    /// The parts of the response to redeeming an authorization-code writeUp relies on
+    #[derive(Deserialize)]
+    pub struct TokenResponse {
+        /// The ID-token identifying the account
+        pub id_token: String
+    }
+
+    /// A set of published keys
+    #[derive(Deserialize)]
+    pub struct Jwks {
+        /// The keys
+        pub keys: Vec<Jwk>
+    }
+
+    /// A single published key, only RSA-keys are supported
+    #[derive(Deserialize)]
+    pub struct Jwk {
+        /// The identifier of the key
+        pub kid: Option<String>,
+        /// The type of the key
+        pub kty: String,
+        /// The modulus of an RSA-key (base64url)
+        pub n: Option<String>,
+        /// The exponent of an RSA-key (base64url)
+        pub e: Option<String>
+    }
+
+    /// The claims of an ID-token writeUp relies on
+    #[derive(Deserialize)]
+    pub struct IdClaims {
+        /// The identifier of the account at the identity-provider
+        pub sub: String,
+        /// The nonce of the login
+        pub nonce: Option<String>,
+        /// The name the user prefers to be known by
+        pub preferred_username: Option<String>
+    }
+}
+
+/// ENDPOINT: Starts a login at the identity-provider by redirecting to it
+///
+/// Returns one of the following HttpResponses:
+/// * `302`
+///     - \[COOKIE: OIDC\] Redirect to the identity-provider
+/// * `400`
+///     - **\[20\]** Invalid query-parameters
+/// * `401`
+///     - **\[10\]** Linking an account without being logged in
+/// * `404`
+///     - **\[22\]** No identity-provider has been configured
+/// * `500`
+///     - Something went wrong internally, e.g. the identity-provider could not be reached (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `login_req` - The query-parameters of the request parsed to a LoginRequest-object
+/// * `db` - The AppData containing the storage-backend
+/// * `config` - The AppData containing the identity-provider, if one has been configured
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/auth/oidc?session_only=false`
+/// => 302 [cookie with the state of the login is set]
+///     Location: https://id.example.com/authorize?response_type=code&client_id=writeup&...
+/// ```
+/// ```text
+/// GET-Request at `{api-url}/auth/oidc?link=true` with JWT-cookie
+/// => 302 [cookie with the state of the login is set, the account is linked to the user once logged in]
+///     Location: https://id.example.com/authorize?response_type=code&client_id=writeup&...
+/// ```
+#[get("/auth/oidc")]
+pub async fn start_login(req: HttpRequest, login_req: web::Query<LoginRequest>, db: Data<dyn Storage>, config: Option<Data<OidcConfig>>) -> impl Responder {
+    let config = match config {
+        Some(config) => config,
+        None => return APIError::ResourceNotFoundError("single sign-on is not configured".to_string()).gen_response()
+    };
+    // Only the browser-session itself may link an account, personal access-tokens can't
+    let link_user_id = match login_req.link.unwrap_or(false) {
+        true => match get_session_from_request(&req, db.get_ref()).await {
+            Ok(session) => Some(session.user_id),
+            Err(e) => return e.gen_response()
+        },
+        false => None
+    };
+    let metadata = match discover(&config).await {
+        Ok(metadata) => metadata,
+        Err(e) => return e.gen_response()
+    };
+    let mut rng = rand::thread_rng();
+    let mut gen_secret = |size: usize| -> String { (&mut rng).sample_iter(&Alphanumeric).take(size).map(char::from).collect() };
+    let claims = FlowClaims {
+        state: gen_secret(FLOW_SECRET_SIZE),
+        nonce: gen_secret(FLOW_SECRET_SIZE),
+        verifier: gen_secret(CODE_VERIFIER_SIZE),
+        session_only: login_req.session_only.unwrap_or(true),
+        link_user_id,
+        aud: FLOW_AUDIENCE.to_string(),
+        exp: (Utc::now() + chrono::Duration::minutes(FLOW_DURATION_MINUTES)).timestamp() as usize
+    };
+    let flow = match get_keys(&req).map(|keys| keys.encode(&claims)) {
+        Ok(Ok(flow)) => flow,
+        Ok(Err(_)) | Err(_) => return APIError::InternalServerError("login could not be started".to_string()).gen_response()
+    };
+    let challenge = BASE64URL_NOPAD.encode(&Sha256::digest(claims.verifier.as_bytes()));
+    let params = [("response_type", "code"), ("client_id", &config.client_id), ("redirect_uri", &config.redirect_uri),
+        ("scope", SCOPES), ("state", &claims.state), ("nonce", &claims.nonce),
+        ("code_challenge", &challenge), ("code_challenge_method", "S256")];
+    let query: Vec<String> = params.iter()
+        .map(|(key, value)| format!("{}={}", key, utf8_percent_encode(value, NON_ALPHANUMERIC))).collect();
+    let separator = if metadata.authorization_endpoint.contains('?') { '&' } else { '?' };
+
+    // The cookie has to be sent along with the redirect back from the identity-provider
+    let cookie = CookieBuilder::new(FLOW_COOKIE_NAME, flow)
+        .same_site(SameSite::Lax)
+        .http_only(true)
+        .secure(!has_dev_flag())
+        .max_age(Duration::minutes(FLOW_DURATION_MINUTES))
+        .finish();
+    HttpResponse::Found()
+        .insert_header((LOCATION, format!("{}{}{}", metadata.authorization_endpoint, separator, query.join("&"))))
+        .cookie(cookie)
+        .finish()
+}
+
+/// ENDPOINT: Completes a login at the identity-provider, starting a new session
+/// and setting a JWT- and a refresh-cookie as proof before redirecting to the app
+///
+/// Returns one of the following HttpResponses:
+/// * `303`
+///     - \[COOKIE: JWT, REFRESH\] Logged in, redirect to the app
+/// * `200`
+///     - **\[11\]** The identity-provider rejected the login or the ID-token is invalid
+///     - **\[24\]** The account is already linked to another user than the one linking it
+/// * `400`
+///     - **\[20\]** Invalid query-parameters
+///     - **\[21\]** The username provided by the identity-provider contains forbidden characters
+/// * `401`
+///     - **\[10\]** Missing, expired or mismatching state of the login, or the user linking the account is gone
+/// * `403`
+///     - **\[12\]** No user exists for the account and either auto-provisioning is disabled
+///       or the name of the account is taken by a user, who has to link the account explicitly
+/// * `404`
+///     - **\[22\]** No identity-provider has been configured
+/// * `429`
+///     - **\[13\]** \[Retry-After\] Too many requests
+/// * `500`
+///     - Something went wrong internally, e.g. the identity-provider could not be reached (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `callback_req` - The query-parameters of the request parsed to a CallbackRequest-object
+/// * `db` - The AppData containing the storage-backend
+/// * `config` - The AppData containing the identity-provider, if one has been configured
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/auth/oidc/callback?code=SplxlOBeZQQYbYS6WxSbIA&state=af0ifjsldkj` with the cookie set by `start_login`
+/// => 303 [cookies with JWT and refresh-token are set, cookie with the state of the login is removed]
+///     Location: /
+/// ```
+/// ```text
+/// GET-Request at `{api-url}/auth/oidc/callback?error=access_denied&state=af0ifjsldkj`
+/// => 200
+///     {
+///         "success": false,
+///         "code": 11,
+///         "message": "failed to process credentials: identity-provider denied the login (access_denied)",
+///         "time": "2022-04-11 12:05:57"
+///     }
+/// ```
+#[get("/auth/oidc/callback", wrap = "from_fn(limit_requests)")]
+pub async fn complete_login(req: HttpRequest, callback_req: web::Query<CallbackRequest>, db: Data<dyn Storage>, config: Option<Data<OidcConfig>>) -> impl Responder {
+    let config = match config {
+        Some(config) => config,
+        None => return APIError::ResourceNotFoundError("single sign-on is not configured".to_string()).gen_response()
+    };
+    if let Some(error) = &callback_req.error {
+        return APIError::InvalidCredentialsError(format!("identity-provider denied the login ({})", error)).gen_response()
+    }
+    // Verify the state of the login
+    let keys = match get_keys(&req) {
+        Ok(keys) => keys,
+        Err(e) => return e.gen_response()
+    };
+    let flow = match req.cookie(FLOW_COOKIE_NAME).and_then(|cookie| keys.decode::<FlowClaims>(cookie.value(), Some(FLOW_AUDIENCE))) {
+        Some(flow) if callback_req.state.as_ref() == Some(&flow.state) => flow,
+        _ => return APIError::AuthenticationError.gen_response()
+    };
+    let code = match &callback_req.code {
+        Some(code) => code,
+        None => return APIError::InvalidPayloadError.gen_response()
+    };
+
+    // Identify the account
+    let claims = match redeem_code(&config, code, &flow).await {
+        Ok(claims) => claims,
+        Err(e) => return e.gen_response()
+    };
+    let user_id = match find_or_provision_user(db.get_ref(), &config, claims, flow.link_user_id.as_deref()).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e.gen_response()
+    };
+
+    let mut response = HttpResponse::SeeOther().insert_header((LOCATION, config.post_login_uri.as_str())).finish();
+    if response.add_removal_cookie(&CookieBuilder::new(FLOW_COOKIE_NAME, "").finish()).is_err() {
+        return APIError::InternalServerError("failed to remove the state of the login".to_string()).gen_response()
+    }
+    start_session_with(&req, db.get_ref(), &user_id, flow.session_only, response).await
+}
+
+/// Retrieves the discovery-document of the identity-provider
+///
+/// # Arguments
+///
+/// * `config` - The identity-provider
+async fn discover(config: &OidcConfig) -> Result<ProviderMetadata, APIError> {
+    let url = format!("{}/.well-known/openid-configuration", config.issuer.trim_end_matches('/'));
+    let metadata: ProviderMetadata = fetch_json(Client::default().get(url)).await?;
+    if metadata.issuer != config.issuer {
+        return Err(APIError::InternalServerError("identity-provider is misconfigured".to_string()))
+    }
+    Ok(metadata)
+}
+
+/// Redeems an authorization-code for an ID-token at the identity-provider, returning its verified claims
+///
+/// # Arguments
+///
+/// * `config` - The identity-provider
+/// * `code` - The authorization-code to be redeemed
+/// * `flow` - The state of the login
+async fn redeem_code(config: &OidcConfig, code: &str, flow: &FlowClaims) -> Result<IdClaims, APIError> {
+    let metadata = discover(config).await?;
+    let client = Client::default();
+    let mut token_req = client.post(&metadata.token_endpoint);
+    if let Some(secret) = &config.client_secret {
+        token_req = token_req.basic_auth(&config.client_id, secret.expose());
+    }
+    let mut resp = token_req.send_form(&[("grant_type", "authorization_code"), ("code", code),
+        ("redirect_uri", &config.redirect_uri), ("client_id", &config.client_id), ("code_verifier", &flow.verifier)]).await
+        .map_err(|_| APIError::InternalServerError("identity-provider could not be reached".to_string()))?;
+    if !resp.status().is_success() {
+        return Err(APIError::InvalidCredentialsError("authorization-code could not be redeemed".to_string()))
+    }
+    let tokens: TokenResponse = resp.json().await
+        .map_err(|_| APIError::InternalServerError("identity-provider returned no ID-token".to_string()))?;
+
+    // Verify the ID-token using the published keys
+    let invalid_token = || APIError::InvalidCredentialsError("invalid ID-token".to_string());
+    let header = decode_header(&tokens.id_token).map_err(|_| invalid_token())?;
+    if !matches!(header.alg, Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512) {
+        return Err(invalid_token())
+    }
+    let jwks: Jwks = fetch_json(client.get(&metadata.jwks_uri)).await?;
+    let key = jwks.keys.iter()
+        .filter(|key| key.kty == "RSA")
+        .find(|key| header.kid.is_none() || key.kid == header.kid)
+        .and_then(|key| DecodingKey::from_rsa_components(key.n.as_ref()?, key.e.as_ref()?).ok())
+        .ok_or_else(invalid_token)?;
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&[&config.client_id]);
+    validation.set_issuer(&[&config.issuer]);
+    let claims = decode::<IdClaims>(&tokens.id_token, &key, &validation).map_err(|_| invalid_token())?.claims;
+    if claims.nonce.as_ref() != Some(&flow.nonce) {
+        return Err(invalid_token())
+    }
+    Ok(claims)
+}
+
+/// Returns the user an account has been linked to, linking it first if necessary.
+/// Unknown accounts are only linked to the given logged in user or to a newly provisioned one,
+/// never to an existing user sharing their name
+///
+/// # Arguments
+///
+/// * `db` - Reference to the storage-backend
+/// * `config` - The identity-provider
+/// * `claims` - The verified claims of the account
+/// * `link_user_id` - The logged in user an unknown account is to be linked to
+async fn find_or_provision_user(db: &dyn Storage, config: &OidcConfig, claims: IdClaims, link_user_id: Option<&str>) -> Result<String, APIError> {
+    let identity_id = Identity::gen_id(&config.issuer, &claims.sub);
+    match db.get_identity(&identity_id).await {
+        Ok(identity) if link_user_id.is_none_or(|user_id| user_id == identity.user_id) => return Ok(identity.user_id),
+        Ok(_) => return Err(APIError::InvalidInstructionsError("account is already linked to another user".to_string())),
+        Err(DBError::NoDocumentFoundError) => {}
+        Err(_) => return Err(APIError::QueryError("linked account could not be retrieved".to_string()))
+    }
+    let username = match link_user_id {
+        Some(user_id) => user_id.to_string(),
+        None => claims.preferred_username.unwrap_or(claims.sub)
+    };
+    if !is_safe(&username) {
+        return Err(APIError::InvalidIDError)
+    }
+    match db.get_user(&username).await {
+        Ok(_) if link_user_id.is_some() => {}
+        // Whoever controls the account at the identity-provider does not necessarily own the user of the same name
+        Ok(_) => return Err(APIError::NoPermissionError),
+        Err(DBError::NoDocumentFoundError) if link_user_id.is_some() => return Err(APIError::AuthenticationError),
+        Err(DBError::NoDocumentFoundError) if config.auto_provision => {
+            // The password is never handed out, but can be reset
+            let passwd: String = rand::thread_rng().sample_iter(&Alphanumeric)
+                .take(PROVISIONED_PASSWORD_SIZE).map(char::from).collect();
+            let user = User { _id: username.clone(), allowances: Vec::new(), connections: Vec::new(), roles: Vec::new() };
+            if db.insert_user(&user).await.is_err() || db.insert_credential(&Credential::new(username.clone(), &passwd)).await.is_err() {
+                return Err(APIError::QueryError("user/credentials could not be created".to_string()))
+            }
+        }
+        Err(DBError::NoDocumentFoundError) => return Err(APIError::NoPermissionError),
+        Err(_) => return Err(APIError::QueryError("can not access user".to_string()))
+    }
+    let identity = Identity { _id: identity_id, user_id: username.clone(), created_at: Utc::now() };
+    db.insert_identity(&identity).await
+        .map_err(|_| APIError::QueryError("account could not be linked".to_string()))?;
+    Ok(username)
+}
+
+/// Sends a request to the identity-provider and parses the JSON-body of its response
+///
+/// # Arguments
+///
+/// * `req` - The request to be sent
+async fn fetch_json<T: serde::de::DeserializeOwned>(req: awc::ClientRequest) -> Result<T, APIError> {
+    let unreachable = || APIError::InternalServerError("identity-provider could not be reached".to_string());
+    let mut resp = req.send().await.map_err(|_| unreachable())?;
+    if !resp.status().is_success() {
+        return Err(unreachable())
+    }
+    resp.json().await.map_err(|_| unreachable())
+}
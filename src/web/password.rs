@@ -0,0 +1,308 @@
+//! Endpoints regarding the passwords of users, their change and their reset
+//!
+//! A password can either be changed by providing the old one, or reset using a reset-token.
+//! Reset-tokens are either requested at [`request_reset`], which hands them to the [`Mailer`],
+//! or issued by an administrator using `writeUp reset-password <username>`.
+//! Only a hash of each token is stored and only the latest token of a user is accepted.
+//! Either way, all sessions of the user end once their password has been changed.
+
+use actix_web::{post, put, Responder, HttpRequest, HttpResponse, web};
+use actix_web::middleware::from_fn;
+use actix_web::web::Data;
+use chrono::Utc;
+use log::error;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use crate::db_access::{Credential, DBError, is_safe, PasswordReset, Storage};
+use crate::mail::Mailer;
+use crate::web::auth::{gen_logout_response, get_session_from_request, hash_token};
+use crate::web::error::APIError;
+use crate::web::limit::{limit_requests, RateLimiter};
+use crate::web::ResponseObject;
+use crate::web::password::json_objects::{PasswordRequest, ResetConfirmation, ResetRequest};
+
+/// Amount of characters making up the identifier of a reset-token
+const RESET_ID_SIZE: usize = 16;
+/// Amount of characters making up the secret part of a reset-token
+const RESET_SECRET_SIZE: usize = 40;
+/// Time in minutes a reset-token is valid for
+pub const RESET_DURATION_MINUTES: i64 = 60;
+
+// Response-/Request-Objects
+/// Structs modelling the request- and response-bodies
+mod json_objects {
+    use serde::Deserialize;
+
+    /// Body of a request to change the password
+    #[derive(Deserialize)]
+    pub struct PasswordRequest {
+        /// The current password
+        pub old_password: String,
+        /// The password replacing the current one
+        pub new_password: String
+    }
+
+    /// Body of a request for a reset-token
+    #[derive(Deserialize)]
+    pub struct ResetRequest {
+        /// The user whose password is to be reset
+        pub username: String
+    }
+
+    /// Body of a request to reset the password using a reset-token
+    #[derive(Deserialize)]
+    pub struct ResetConfirmation {
+        /// The reset-token
+        pub token: String,
+        /// The password replacing the current one
+        pub new_password: String
+    }
+}
+
+/// ENDPOINT: Changes the password of the current user, given their current password.
+/// Ends all sessions of the user, including the current one, and revokes their personal access-tokens
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[REMOVAL_COOKIE: JWT, REFRESH\] Password has been changed
+///     - **\[11\]** Wrong password
+///     - **\[24\]** Password is managed by the directory
+/// * `400`
+///     - **\[20\]** Invalid payload
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `429`
+///     - **\[13\]** \[Retry-After\] Too many requests or the account is locked
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `passwd_req` - The body of the request parsed to a PasswordRequest-object
+/// * `db` - The AppData containing the storage-backend
+/// * `limiter` - The AppData containing the RateLimiter
+///
+/// # Examples
+///
+/// ```text
+/// PUT-Request at `{api-url}/user/password` with a cookie containing a valid JWT
+///     {
+///         "old_password": "testPass",
+///         "new_password": "passTest"
+///     }
+/// => 200 [cookies with JWT and refresh-token are removed]
+///     {
+///         "success": true,
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+/// ```text
+/// PUT-Request at `{api-url}/user/password` (wrong password)
+///     {
+///         "old_password": "wrongPass",
+///         "new_password": "passTest"
+///     }
+/// => 200
+///     {
+///         "success": false,
+///         "code": 11,
+///         "message": "failed to process credentials: wrong password",
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+#[put("/user/password", wrap = "from_fn(limit_requests)")]
+pub async fn change_password(req: HttpRequest, passwd_req: web::Json<PasswordRequest>, db: Data<dyn Storage>, limiter: Data<RateLimiter>) -> impl Responder {
+    let session = match get_session_from_request(&req, db.get_ref()).await {
+        Ok(session) => session,
+        Err(e) => return e.gen_response()
+    };
+    let mut cred = match db.get_credential(&session.user_id).await {
+        Ok(cred) => cred,
+        Err(DBError::NoDocumentFoundError) => return match db.get_user(&session.user_id).await {
+            Ok(_) => APIError::InvalidInstructionsError("password is managed by the directory".to_string()).gen_response(),
+            Err(_) => APIError::AuthenticationError.gen_response() // User removed in the meantime
+        },
+        Err(_) => return APIError::QueryError("can not access credentials".to_string()).gen_response()
+    };
+    // Wrong passwords count towards the lockout of the account, even with a valid session
+    if let Err(retry_after) = limiter.check_account(&cred._id, Utc::now()) {
+        return APIError::TooManyAttemptsError(retry_after).gen_response()
+    }
+    if !cred.verify(&passwd_req.old_password) {
+        limiter.record_failure(&cred._id, Utc::now());
+        return APIError::InvalidCredentialsError("wrong password".to_string()).gen_response()
+    }
+    limiter.record_success(&cred._id);
+    cred.set_password(&passwd_req.new_password);
+    match set_credential_and_end_sessions(db.get_ref(), &cred).await {
+        Ok(_) => gen_logout_response(),
+        Err(e) => e.gen_response()
+    }
+}
+
+/// ENDPOINT: Requests a reset-token for a user, which is handed to the configured [`Mailer`].
+/// Always succeeds, so that it can't be used to find out whether a user exists
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - Request has been received
+/// * `400`
+///     - **\[20\]** Invalid payload
+/// * `429`
+///     - **\[13\]** \[Retry-After\] Too many requests
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `reset_req` - The body of the request parsed to a ResetRequest-object
+/// * `db` - The AppData containing the storage-backend
+/// * `mailer` - The AppData containing the Mailer
+///
+/// # Examples
+///
+/// ```text
+/// POST-Request at `{api-url}/user/password/reset`
+///     {
+///         "username": "testUser"
+///     }
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+#[post("/user/password/reset", wrap = "from_fn(limit_requests)")]
+pub async fn request_reset(reset_req: web::Json<ResetRequest>, db: Data<dyn Storage>, mailer: Data<dyn Mailer>) -> impl Responder {
+    match db.get_credential(&reset_req.username).await {
+        Ok(cred) => {
+            let token = match issue_reset_token(db.get_ref(), &cred._id).await {
+                Ok((token, _)) => token,
+                Err(_) => return APIError::QueryError("reset-token could not be created".to_string()).gen_response()
+            };
+            let body = format!("Use the following token within {} minutes to set a new password: {}", RESET_DURATION_MINUTES, token);
+            if mailer.send(&cred._id, "Resetting your password", &body).is_err() {
+                error!("Failed to deliver the reset-token of '{}'", cred._id);
+            }
+        }
+        Err(DBError::NoDocumentFoundError) => {} // Indistinguishable from an existing user
+        Err(_) => return APIError::QueryError("can not access credentials".to_string()).gen_response()
+    }
+    HttpResponse::Ok().json(ResponseObject::new())
+}
+
+/// ENDPOINT: Sets a new password using a reset-token, ending all sessions of the user and revoking their personal access-tokens
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - Password has been changed
+///     - **\[11\]** Invalid or expired reset-token
+/// * `400`
+///     - **\[20\]** Invalid payload
+/// * `429`
+///     - **\[13\]** \[Retry-After\] Too many requests
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `reset_req` - The body of the request parsed to a ResetConfirmation-object
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// PUT-Request at `{api-url}/user/password/reset`
+///     {
+///         "token": "x7mWbk3Jd9sLq0Pz.Gq2vN8rTxY5cLh1ZpK0aWm7sJd3Fb9QeRt6UoXiV",
+///         "new_password": "passTest"
+///     }
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+/// ```text
+/// PUT-Request at `{api-url}/user/password/reset` (token already used)
+///     {
+///         "token": "x7mWbk3Jd9sLq0Pz.Gq2vN8rTxY5cLh1ZpK0aWm7sJd3Fb9QeRt6UoXiV",
+///         "new_password": "passTest"
+///     }
+/// => 200
+///     {
+///         "success": false,
+///         "code": 11,
+///         "message": "failed to process credentials: invalid or expired reset-token",
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+#[put("/user/password/reset", wrap = "from_fn(limit_requests)")]
+pub async fn reset_password(reset_req: web::Json<ResetConfirmation>, db: Data<dyn Storage>) -> impl Responder {
+    let invalid_token = || APIError::InvalidCredentialsError("invalid or expired reset-token".to_string()).gen_response();
+    let (reset_id, secret) = match reset_req.token.split_once('.') {
+        Some(parts) => parts,
+        None => return invalid_token()
+    };
+    // Check for potential injection-attempt
+    if !is_safe(reset_id) {
+        return invalid_token()
+    }
+    let reset = match db.get_reset(reset_id).await {
+        Ok(reset) if reset.token_hash.eq(&hash_token(secret)) && reset.expires_at > Utc::now() => reset,
+        Ok(_) | Err(DBError::NoDocumentFoundError) => return invalid_token(),
+        Err(_) => return APIError::QueryError("reset-token could not be retrieved from database".to_string()).gen_response()
+    };
+    let mut cred = match db.get_credential(&reset.user_id).await {
+        Ok(cred) => cred,
+        Err(DBError::NoDocumentFoundError) => return invalid_token(),
+        Err(_) => return APIError::QueryError("can not access credentials".to_string()).gen_response()
+    };
+    cred.set_password(&reset_req.new_password);
+    match set_credential_and_end_sessions(db.get_ref(), &cred).await {
+        Ok(_) => HttpResponse::Ok().json(ResponseObject::new()),
+        Err(e) => e.gen_response()
+    }
+}
+
+/// Creates a new reset-token for a user, replacing all previous ones.
+/// Returns the token together with the stored object
+///
+/// # Arguments
+///
+/// * `db` - Reference to the storage-backend
+/// * `user_id` - The identifier of the user
+pub async fn issue_reset_token(db: &dyn Storage, user_id: &str) -> Result<(String, PasswordReset), DBError> {
+    let mut rng = rand::thread_rng();
+    let reset_id: String = (&mut rng).sample_iter(&Alphanumeric).take(RESET_ID_SIZE).map(char::from).collect();
+    let secret: String = (&mut rng).sample_iter(&Alphanumeric).take(RESET_SECRET_SIZE).map(char::from).collect();
+    let now = Utc::now();
+    let reset = PasswordReset {
+        _id: reset_id.clone(),
+        user_id: user_id.to_string(),
+        token_hash: hash_token(&secret),
+        created_at: now,
+        expires_at: now + chrono::Duration::minutes(RESET_DURATION_MINUTES)
+    };
+    db.remove_resets(user_id).await?;
+    db.insert_reset(&reset).await?;
+    Ok((format!("{}.{}", reset_id, secret), reset))
+}
+
+/// Stores the changed credentials of a user, ending all of their sessions and invalidating all of their reset- and personal access-tokens,
+/// as whoever learned the old password may have created one
+///
+/// # Arguments
+///
+/// * `db` - Reference to the storage-backend
+/// * `cred` - The changed credentials
+async fn set_credential_and_end_sessions(db: &dyn Storage, cred: &Credential) -> Result<(), APIError> {
+    if db.update_credential(cred).await.is_err() {
+        return Err(APIError::QueryError("failed to store the new password".to_string()))
+    }
+    if db.remove_resets(&cred._id).await.is_err() || db.remove_sessions(&cred._id).await.is_err() || db.remove_tokens(&cred._id).await.is_err() {
+        return Err(APIError::QueryError("sessions could not be ended".to_string()))
+    }
+    Ok(())
+}
@@ -0,0 +1,509 @@
+//! Endpoints regarding the revision-history of notes
+//!
+//! Whenever a note gets updated, its previous state is kept as an immutable revision.
+//! Revisions are numbered per note, counting up from 1.
+
+use actix_web::{get, post, Responder, HttpRequest, HttpResponse, web::{Data, Path, Query}};
+use actix_web::http::header::ETAG;
+use chrono::Utc;
+use crate::db_access::{AllowanceLevel, DBError, is_safe, Note, Storage};
+use crate::web::error::APIError;
+use crate::web::auth::get_user_id_from_request;
+use crate::web::note::{gen_etag, gen_revision, get_allow_level_for_note};
+use crate::web::note::json_objects::NoteResponse;
+use crate::web::revision::json_objects::{DiffLine, DiffQuery, DiffResponse, LineKind, RevisionInfo};
+use crate::web::ResponseObjectWithPayload;
+
+/// Maximum amount of lines of either text that may differ when comparing two texts
+const MAX_DIFF_LINES: usize = 10_000;
+
+// Response-/Request-Objects
+/// Structs modelling the request- and response-bodies
+mod json_objects {
+    use chrono::{DateTime, Utc};
+    use serde::{Serialize, Deserialize};
+    use crate::db_access::Revision;
+
+    /// Body of a response containing the metadata of a revision
+    #[derive(Serialize)]
+    pub struct RevisionInfo {
+        /// The number of the revision
+        pub rev: u32,
+        /// The user whose modification replaced this revision
+        pub author_id: String,
+        /// Timestamp of when this revision got replaced
+        pub created_at: DateTime<Utc>,
+        /// The title at the time
+        pub title: String
+    }
+    impl From<Revision> for RevisionInfo {
+        fn from(revision: Revision) -> Self {
+            RevisionInfo { rev: revision.rev, author_id: revision.author_id, created_at: revision.created_at, title: revision.title }
+        }
+    }
+
+    /// Query-parameters of a diff-request
+    #[derive(Deserialize)]
+    pub struct DiffQuery {
+        /// The revision to compare against (the current note if omitted)
+        pub to: Option<u32>
+    }
+
+    /// The way a line changed between two versions
+    #[derive(Serialize, Debug, PartialEq, Eq)]
+    pub enum LineKind {
+        /// The line is part of both versions
+        Unchanged,
+        /// The line is only part of the newer version
+        Added,
+        /// The line is only part of the older version
+        Removed
+    }
+
+    /// A single line of a diff
+    #[derive(Serialize, Debug, PartialEq, Eq)]
+    pub struct DiffLine {
+        /// How the line changed
+        pub kind: LineKind,
+        /// The line itself
+        pub line: String
+    }
+
+    /// Body of a response containing a diff between two versions of a note
+    #[derive(Serialize)]
+    pub struct DiffResponse {
+        /// The revision compared from
+        pub from: u32,
+        /// The revision compared to (`null` for the current note)
+        pub to: Option<u32>,
+        /// The content's lines and how they changed
+        pub lines: Vec<DiffLine>
+    }
+}
+
+/// ENDPOINT: Lists the revisions of a note, oldest first
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] List could be compiled
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** Insufficient access-level (no read-access)
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the note
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/revisions` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": [
+///             {
+///                 "rev": 1,
+///                 "author_id": "otherUser",
+///                 "created_at": "2022-04-11T12:20:28.120Z",
+///                 "title": "Test-Note"
+///             }
+///         ],
+///         "time": "2022-04-11 12:22:41"
+///     }
+/// ```
+/// ```text
+/// GET-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/revisions` to a note the current user is not allowed to read
+/// => 403
+///     {
+///         "success": false,
+///         "code": 12,
+///         "message": "no permission",
+///         "time": "2022-04-11 12:20:19"
+///     }
+/// ```
+#[get("/note/{note_id}/revisions")]
+pub async fn list_revisions(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let note_id = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&note_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    // Check if the user has clearance to view this note
+    match get_allow_level_for_note(&note_id, req, db.get_ref()).await {
+        Ok(_) => match db.get_revisions(&note_id).await {
+            Ok(revisions) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(
+                revisions.into_iter().map(RevisionInfo::from).collect::<Vec<RevisionInfo>>())),
+            Err(_) => APIError::QueryError("failed to retrieve revisions".to_string()).gen_response() //unknown
+        },
+        Err(e) => e.gen_response()
+    }
+}
+
+/// ENDPOINT: Returns a specific revision of a note
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] Revision can be returned
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** Insufficient access-level (no read-access)
+/// * `404`
+///     - **\[22\]** The note has no such revision
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the note and the number of the revision
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/revisions/1` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": {
+///             "note_id": "7254fa970b62u3ag62dr4d3l",
+///             "rev": 1,
+///             "author_id": "otherUser",
+///             "created_at": "2022-04-11T12:20:28.120Z",
+///             "title": "Test-Note",
+///             "content": "This is but a simple demonstration",
+///             "tags": [
+///                 "Test",
+///                 "Note"
+///             ]
+///         },
+///         "time": "2022-04-11 12:22:41"
+///     }
+/// ```
+/// ```text
+/// GET-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/revisions/7` with a cookie containing a valid JWT
+/// => 404
+///     {
+///         "success": false,
+///         "code": 22,
+///         "message": "requested resource does not exist: revision 7",
+///         "time": "2022-04-11 12:20:19"
+///     }
+/// ```
+#[get("/note/{note_id}/revisions/{rev}")]
+pub async fn get_revision(path: Path<(String, u32)>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let (note_id, rev) = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&note_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    // Check if the user has clearance to view this note
+    match get_allow_level_for_note(&note_id, req, db.get_ref()).await {
+        Ok(_) => match db.get_revision(&note_id, rev).await {
+            Ok(revision) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(revision)),
+            Err(DBError::NoDocumentFoundError) => APIError::ResourceNotFoundError(format!("revision {}", rev)).gen_response(),
+            Err(_) => APIError::QueryError("failed to retrieve revision".to_string()).gen_response() //unknown
+        },
+        Err(e) => e.gen_response()
+    }
+}
+
+/// ENDPOINT: Compares the content of a revision line by line with either another revision or the current note
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] Diff could be compiled
+///     - **\[24\]** The texts differ in too many lines to be compared
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** Insufficient access-level (no read-access)
+/// * `404`
+///     - **\[22\]** The note has no such revision
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the note and the number of the revision
+/// * `query` - The query-parameters containing the revision to compare against
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/revisions/1/diff?to=2` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": {
+///             "from": 1,
+///             "to": 2,
+///             "lines": [
+///                 {
+///                     "kind": "Unchanged",
+///                     "line": "This is but a simple demonstration"
+///                 },
+///                 {
+///                     "kind": "Removed",
+///                     "line": "of an old line"
+///                 },
+///                 {
+///                     "kind": "Added",
+///                     "line": "of a new line"
+///                 }
+///             ]
+///         },
+///         "time": "2022-04-11 12:22:41"
+///     }
+/// ```
+#[get("/note/{note_id}/revisions/{rev}/diff")]
+pub async fn diff_revision(path: Path<(String, u32)>, query: Query<DiffQuery>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let (note_id, rev) = path.into_inner();
+    let to = query.into_inner().to;
+    // Check for potential injection-attempt
+    if !is_safe(&note_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    // Check if the user has clearance to view this note
+    if let Err(e) = get_allow_level_for_note(&note_id, req.clone(), db.get_ref()).await {
+        return e.gen_response()
+    }
+    // Get the older version
+    let from_content = match db.get_revision(&note_id, rev).await {
+        Ok(revision) => revision.content,
+        Err(DBError::NoDocumentFoundError) => return APIError::ResourceNotFoundError(format!("revision {}", rev)).gen_response(),
+        Err(_) => return APIError::QueryError("failed to retrieve revision".to_string()).gen_response() //unknown
+    };
+    // Get the newer version
+    let to_content = match to {
+        Some(to) => match db.get_revision(&note_id, to).await {
+            Ok(revision) => revision.content,
+            Err(DBError::NoDocumentFoundError) => return APIError::ResourceNotFoundError(format!("revision {}", to)).gen_response(),
+            Err(_) => return APIError::QueryError("failed to retrieve revision".to_string()).gen_response() //unknown
+        },
+        None => match db.get_note(&note_id).await {
+            Ok(note) => note.content,
+            Err(DBError::NoDocumentFoundError) => return match get_user_id_from_request(req, db.get_ref()).await {
+                Ok(user_id) => APIError::DBInconsistencyError(user_id, note_id).gen_response(), //user has allowance for a nonexisting note
+                Err(e) => e.gen_response()
+            },
+            Err(_) => return APIError::QueryError("failed to retrieve note".to_string()).gen_response() //unknown
+        }
+    };
+    match diff_lines(&from_content, &to_content) {
+        Ok(lines) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(DiffResponse { from: rev, to, lines })),
+        Err(e) => e.gen_response()
+    }
+}
+
+/// ENDPOINT: Restores a note to the state of one of its revisions.
+/// The state being replaced is kept as a new revision, so a restore can be undone as well
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] Note was restored successfully
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** Insufficient access-level (no write-access)
+/// * `404`
+///     - **\[22\]** The note has no such revision
+/// * `412`
+///     - **\[23\]** Note has been modified concurrently
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the note and the number of the revision
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// POST-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/revisions/1/restore` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": {
+///             "note_id": "7254fa970b62u3ag62dr4d3l",
+///             "note": {
+///                 "title": "Test-Note",
+///                 "content": "This is but a simple demonstration",
+///                 "owner_id": "testUser",
+///                 "tags": [
+///                     "Test",
+///                     "Note"
+///                 ],
+///                 "version": 3,
+///                 "created_at": "2022-04-11T12:20:28.120Z",
+///                 "updated_at": "2022-04-11T12:31:47.015Z",
+///                 "last_editor_id": "testUser"
+///             },
+///             "allowance": "Owner"
+///         },
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+/// ```text
+/// POST-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/revisions/1/restore` to a note the current user is not allowed to write to
+/// => 403
+///     {
+///         "success": false,
+///         "code": 12,
+///         "message": "no permission",
+///         "time": "2022-04-11 12:20:19"
+///     }
+/// ```
+#[post("/note/{note_id}/revisions/{rev}/restore")]
+pub async fn restore_revision(path: Path<(String, u32)>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let (note_id, rev) = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&note_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    // Check if the user has the clearance to update the note
+    let allowance = match get_allow_level_for_note(&note_id, req.clone(), db.get_ref()).await {
+        Ok(AllowanceLevel::ReadWrite) => AllowanceLevel::ReadWrite,
+        Ok(AllowanceLevel::Owner) => AllowanceLevel::Owner,
+        Ok(_) => return APIError::NoPermissionError.gen_response(),
+        Err(e) => return e.gen_response()
+    };
+    let user_id = match get_user_id_from_request(req, db.get_ref()).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e.gen_response()
+    };
+    // Get the revision to be restored
+    let revision = match db.get_revision(&note_id, rev).await {
+        Ok(revision) => revision,
+        Err(DBError::NoDocumentFoundError) => return APIError::ResourceNotFoundError(format!("revision {}", rev)).gen_response(),
+        Err(_) => return APIError::QueryError("failed to retrieve revision".to_string()).gen_response() //unknown
+    };
+    // Keep the current state of the note as a revision
+    let note = match db.get_note(&note_id).await {
+        Ok(note) => note,
+        Err(DBError::NoDocumentFoundError) => return APIError::DBInconsistencyError(user_id, note_id).gen_response(), //user has allowance for a nonexisting note
+        Err(_) => return APIError::QueryError("failed to retrieve note".to_string()).gen_response() //unknown
+    };
+    let kept = gen_revision(&note_id, &note, &user_id);
+    // Restore all fields of the note
+    let restored = Note {
+        title: revision.title,
+        content: revision.content,
+        tags: revision.tags,
+        updated_at: Utc::now(),
+        last_editor_id: user_id,
+        ..note
+    };
+    match db.set_note_fields(&note_id, &restored, restored.version, Some(&kept)).await {
+        Ok(version) => {
+            let note = Note { version, ..restored };
+            HttpResponse::Ok().insert_header((ETAG, gen_etag(version)))
+                .json(ResponseObjectWithPayload::new(NoteResponse { note_id, note, allowance }))
+        }
+        Err(DBError::VersionMismatchError(version)) => APIError::VersionConflictError(version).gen_response(), //modified concurrently
+        Err(_) => APIError::QueryError("restore of note failed".to_string()).gen_response() //unknown
+    }
+}
+
+/// Compares two texts line by line using their longest common subsequence.
+/// Fails if the texts differ in more than [`MAX_DIFF_LINES`] lines of either text
+///
+/// # Arguments
+///
+/// * `from` - The older text
+/// * `to` - The newer text
+fn diff_lines(from: &str, to: &str) -> Result<Vec<DiffLine>, APIError> {
+    let from: Vec<&str> = from.lines().collect();
+    let to: Vec<&str> = to.lines().collect();
+    // Only the part in between the common start and end has to be compared
+    let prefix = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let suffix = from[prefix..].iter().rev().zip(to[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (changed_from, changed_to) = (&from[prefix..from.len() - suffix], &to[prefix..to.len() - suffix]);
+    if changed_from.len() > MAX_DIFF_LINES || changed_to.len() > MAX_DIFF_LINES {
+        return Err(APIError::InvalidInstructionsError(format!("texts differ in more than {} lines", MAX_DIFF_LINES)))
+    }
+    let mut matches = Vec::new();
+    common_lines(changed_from, changed_to, (prefix, prefix), &mut matches);
+
+    // Lines in between two common lines were removed before others were added
+    let line = |kind, line: &str| DiffLine { kind, line: line.to_string() };
+    let mut lines: Vec<DiffLine> = from[..prefix].iter().map(|unchanged| line(LineKind::Unchanged, unchanged)).collect();
+    let (mut i, mut j) = (prefix, prefix);
+    for (common_i, common_j) in matches {
+        lines.extend(from[i..common_i].iter().map(|removed| line(LineKind::Removed, removed)));
+        lines.extend(to[j..common_j].iter().map(|added| line(LineKind::Added, added)));
+        lines.push(line(LineKind::Unchanged, from[common_i]));
+        (i, j) = (common_i + 1, common_j + 1);
+    }
+    lines.extend(from[i..from.len() - suffix].iter().map(|removed| line(LineKind::Removed, removed)));
+    lines.extend(to[j..to.len() - suffix].iter().map(|added| line(LineKind::Added, added)));
+    lines.extend(from[from.len() - suffix..].iter().map(|unchanged| line(LineKind::Unchanged, unchanged)));
+    Ok(lines)
+}
+
+/// Collects the positions of the lines making up a longest common subsequence of two texts in order,
+/// taking linear space by splitting the texts in halves (Hirschberg's algorithm)
+///
+/// # Arguments
+///
+/// * `from` - The lines of the older text
+/// * `to` - The lines of the newer text
+/// * `offset` - The positions of the first lines within the entire texts
+/// * `matches` - The positions of the common lines found so far
+fn common_lines(from: &[&str], to: &[&str], offset: (usize, usize), matches: &mut Vec<(usize, usize)>) {
+    if from.is_empty() || to.is_empty() {
+        return
+    }
+    if from.len() == 1 {
+        if let Some(j) = to.iter().position(|line| *line == from[0]) {
+            matches.push((offset.0, offset.1 + j));
+        }
+        return
+    }
+    // Split the newer text where the common subsequences of both halves of the older text add up the most
+    let mid = from.len() / 2;
+    let upper = lcs_lengths(from[..mid].iter(), to.iter());
+    let lower = lcs_lengths(from[mid..].iter().rev(), to.iter().rev());
+    let split = (0..=to.len()).max_by_key(|&j| (upper[j] + lower[to.len() - j], std::cmp::Reverse(j))).unwrap_or(0);
+    common_lines(&from[..mid], &to[..split], offset, matches);
+    common_lines(&from[mid..], &to[split..], (offset.0 + mid, offset.1 + split), matches);
+}
+
+/// Returns the lengths of the longest common subsequences of a text and every beginning of another one,
+/// keeping only a single row of the usual table
+///
+/// # Arguments
+///
+/// * `from` - The lines of the first text
+/// * `to` - The lines of the second text
+fn lcs_lengths<'a>(from: impl Iterator<Item = &'a &'a str>, to: impl Iterator<Item = &'a &'a str> + Clone) -> Vec<usize> {
+    let mut row = vec![0usize; to.clone().count() + 1];
+    for a in from {
+        let mut diagonal = 0;
+        for (j, b) in to.clone().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if a == b { diagonal + 1 } else { above.max(row[j]) };
+            diagonal = above;
+        }
+    }
+    row
+}
@@ -0,0 +1,294 @@
+//! Endpoints regarding the search through notes
+//!
+//! The search is done by writeUp itself rather than by the database, so it works with every storage-backend.
+//!
+//! # Query-Syntax
+//!
+//! * `word` - The note has to contain the word (in its title, content or tags)
+//! * `"exact phrase"` - The note has to contain the phrase as a whole
+//! * `tag:name` - The note has to be tagged with the given tag
+//!
+//! All parts of a query are case-insensitive and have to be satisfied by a note for it to be found.
+
+use actix_web::{get, Responder, HttpRequest, HttpResponse, web::{Data, Query}};
+use crate::db_access::{get_effective_allowances, Note, Storage};
+use crate::web::auth::get_user_from_request;
+use crate::web::error::APIError;
+use crate::web::search::json_objects::{SearchRequest, SearchResult};
+use crate::web::{get_allowed_notes, ResponseObjectWithPayload};
+
+// Response-/Request-Objects
+/// Structs modelling the request- and response-bodies
+mod json_objects {
+    use serde::{Serialize, Deserialize};
+    use crate::db_access::AllowanceLevel;
+
+    /// Query-parameters of a search-request
+    #[derive(Deserialize)]
+    pub struct SearchRequest {
+        /// The search-query
+        pub q: String
+    }
+
+    /// A single note matching a search-query
+    #[derive(Serialize)]
+    pub struct SearchResult {
+        /// The note's identifier
+        pub note_id: String,
+        /// The note's title
+        pub title: String,
+        /// The tags associated with the note
+        pub tags: Vec<String>,
+        /// The level of access the current user has regarding this note
+        pub allowance: AllowanceLevel,
+        /// How well the note matches the query (higher is better)
+        pub score: u32,
+        /// An excerpt of the note's content surrounding the first match
+        pub snippet: String,
+        /// The ranges (start inclusive, end exclusive; counted in characters) of all matches within the snippet
+        pub highlights: Vec<[usize; 2]>
+    }
+}
+
+/// The amount of characters shown around the first match in a snippet
+const SNIPPET_CONTEXT: usize = 60;
+/// Weight of a match inside of the title
+const TITLE_WEIGHT: u32 = 5;
+/// Weight of a match inside of the tags
+const TAG_WEIGHT: u32 = 3;
+/// Weight of a match inside of the content
+const CONTENT_WEIGHT: u32 = 1;
+
+/// A parsed search-query
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    /// Single words to be matched (lowercase)
+    pub terms: Vec<String>,
+    /// Phrases to be matched as a whole (lowercase)
+    pub phrases: Vec<String>,
+    /// Tags a note has to be tagged with (lowercase)
+    pub tags: Vec<String>
+}
+
+impl SearchQuery {
+    /// Parses a query (see [module-level documentation](self))
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query to be parsed
+    pub fn parse(query: &str) -> SearchQuery {
+        let mut parsed = SearchQuery::default();
+        let mut chars = query.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c.is_whitespace() {
+                continue
+            }
+            if c == '"' {
+                // Read until the closing quote (or the end of the query)
+                let phrase: String = chars.by_ref().take_while(|c| *c != '"').collect();
+                let phrase = fold(phrase.trim());
+                if !phrase.is_empty() {
+                    parsed.phrases.push(phrase)
+                }
+                continue
+            }
+            // Read until the end of the word
+            let mut word = c.to_string();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '"') {
+                word.push(c)
+            }
+            let word = fold(&word);
+            match word.strip_prefix("tag:") {
+                Some(tag) if !tag.is_empty() => parsed.tags.push(tag.to_string()),
+                _ => parsed.terms.push(word)
+            }
+        }
+        parsed
+    }
+
+    /// Checks whether the query contains no criteria at all
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.phrases.is_empty() && self.tags.is_empty()
+    }
+
+    /// Matches a note against the query.
+    /// Returns the score of the note together with a highlighted snippet, or nothing if the note does not match
+    ///
+    /// # Arguments
+    ///
+    /// * `note` - The note to be matched
+    pub fn match_note(&self, note: &Note) -> Option<(u32, String, Vec<[usize; 2]>)> {
+        let tags: Vec<String> = note.tags.iter().map(|tag| fold(tag)).collect();
+        if !self.tags.iter().all(|tag| tags.contains(tag)) {
+            return None
+        }
+        let title: Vec<char> = fold(&note.title).chars().collect();
+        let content: Vec<char> = fold(&note.content).chars().collect();
+        let mut score = 0;
+        let mut matches = Vec::new();
+        for needle in self.terms.iter().chain(&self.phrases) {
+            let needle: Vec<char> = needle.chars().collect();
+            let title_hits = find_all(&title, &needle).len() as u32;
+            let tag_hits = tags.iter().filter(|tag| !find_all(&tag.chars().collect::<Vec<char>>(), &needle).is_empty()).count() as u32;
+            let content_hits = find_all(&content, &needle);
+            if title_hits == 0 && tag_hits == 0 && content_hits.is_empty() {
+                return None
+            }
+            score += TITLE_WEIGHT * title_hits + TAG_WEIGHT * tag_hits + CONTENT_WEIGHT * content_hits.len() as u32;
+            matches.extend(content_hits.into_iter().map(|start| [start, start + needle.len()]));
+        }
+        let (snippet, highlights) = gen_snippet(&note.content, matches);
+        Some((score, snippet, highlights))
+    }
+}
+
+/// Lowercases a text character by character, so character-positions stay the same
+///
+/// # Arguments
+///
+/// * `text` - The text to be lowercased
+fn fold(text: &str) -> String {
+    text.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect()
+}
+
+/// Returns the start of every (non-overlapping) occurrence of the needle inside of the haystack
+///
+/// # Arguments
+///
+/// * `haystack` - The text to be searched
+/// * `needle` - The text to look for
+fn find_all(haystack: &[char], needle: &[char]) -> Vec<usize> {
+    let mut found = Vec::new();
+    let mut start = 0;
+    while !needle.is_empty() && start + needle.len() <= haystack.len() {
+        if haystack[start..start + needle.len()] == *needle {
+            found.push(start);
+            start += needle.len();
+        } else {
+            start += 1;
+        }
+    }
+    found
+}
+
+/// Cuts the part surrounding the first match out of a text and highlights all matches inside of it
+///
+/// # Arguments
+///
+/// * `content` - The text to be cut
+/// * `matches` - The ranges of all matches inside of the text
+fn gen_snippet(content: &str, mut matches: Vec<[usize; 2]>) -> (String, Vec<[usize; 2]>) {
+    let content: Vec<char> = content.chars().collect();
+    matches.sort_unstable();
+    let (start, end) = match matches.first() {
+        Some([first_start, first_end]) => (first_start.saturating_sub(SNIPPET_CONTEXT), (first_end + SNIPPET_CONTEXT).min(content.len())),
+        None => (0, (2 * SNIPPET_CONTEXT).min(content.len()))
+    };
+    // Mark cut off parts
+    let prefix = if start > 0 { "…" } else { "" };
+    let suffix = if end < content.len() { "…" } else { "" };
+    let snippet = format!("{}{}{}", prefix, content[start..end].iter().collect::<String>(), suffix);
+    // Merge overlapping matches and move them into the snippet
+    let offset = prefix.chars().count();
+    let mut highlights: Vec<[usize; 2]> = Vec::new();
+    for [match_start, match_end] in matches.into_iter().filter(|[s, e]| *s >= start && *e <= end) {
+        let range = [match_start - start + offset, match_end - start + offset];
+        match highlights.last_mut() {
+            Some(last) if last[1] >= range[0] => last[1] = last[1].max(range[1]),
+            _ => highlights.push(range)
+        }
+    }
+    (snippet, highlights)
+}
+
+/// ENDPOINT: Searches through the title, content and tags of all notes the current user has access to.
+/// The results are ordered by their score, best first (see [module-level documentation](self) for the query-syntax)
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] Search was successful
+///     - **\[24\]** Query is empty
+/// * `400`
+///     - **\[20\]** Query-parameter `q` is missing
+/// * `401`
+///     - **\[10\]** No user could be verified
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `query` - The query-parameters containing the search-query
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/notes/search?q=tag:rust%20%22simple%20demo%22` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": [
+///             {
+///                 "note_id": "7254fa970b62u3ag62dr4d3l",
+///                 "title": "Test-Note",
+///                 "tags": [
+///                     "Rust",
+///                     "Note"
+///                 ],
+///                 "allowance": "Owner",
+///                 "score": 1,
+///                 "snippet": "This is but a simple demonstration",
+///                 "highlights": [
+///                     [14, 25]
+///                 ]
+///             }
+///         ],
+///         "time": "2022-04-11 12:00:05"
+///     }
+/// ```
+/// ```text
+/// GET-Request at `{api-url}/notes/search?q=%20` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": false,
+///         "code": 24,
+///         "message": "invalid instruction: search-query is empty",
+///         "time": "2022-04-11 12:00:05"
+///     }
+/// ```
+#[get("/notes/search")]
+pub async fn search_notes(query: Query<SearchRequest>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let query = SearchQuery::parse(&query.into_inner().q);
+    if query.is_empty() {
+        return APIError::InvalidInstructionsError("search-query is empty".to_string()).gen_response()
+    }
+    match get_user_from_request(req, db.get_ref()).await {
+        Ok(user) => {
+            // Read all accessible notes at once and match them
+            let allowances = match get_effective_allowances(&user, db.get_ref()).await {
+                Ok(allowances) => allowances,
+                Err(_) => return APIError::QueryError("groups could not be retrieved from database".to_string()).gen_response()
+            };
+            let notes = match get_allowed_notes(&user._id, allowances, db.get_ref()).await {
+                Ok(notes) => notes,
+                Err(e) => return e.gen_response()
+            };
+            let mut results: Vec<SearchResult> = notes.into_iter().filter_map(|(allowance, note)| {
+                query.match_note(&note).map(|(score, snippet, highlights)| SearchResult {
+                    note_id: allowance.note_id,
+                    title: note.title,
+                    tags: note.tags,
+                    allowance: allowance.level,
+                    score,
+                    snippet,
+                    highlights
+                })
+            }).collect();
+            // Best matches first
+            results.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.title.cmp(&b.title)));
+            HttpResponse::Ok().json(ResponseObjectWithPayload::new(results))
+        }
+        Err(e) => e.gen_response()
+    }
+}
@@ -1,20 +1,17 @@
 //! Endpoints regarding the sharing of notes and connecting of users
 
-use std::env;
-use std::sync::Mutex;
 use actix_web::{get, put, post, delete, Responder, HttpRequest, HttpResponse, web};
 use actix_web::web::{Data, Path};
 use chrono::Utc;
 use jsonwebtoken::{Algorithm, decode, DecodingKey, encode, EncodingKey, Header, Validation};
-use mongodb::bson::doc;
 use serde::{Serialize, Deserialize};
-use mongodb::{bson, Database};
-use crate::db_access::{update_dbo_by_id, USER, User, filter_allowances_by_user_id, AllowanceLevel, get_dbo_by_id, is_safe};
-use crate::db_access::{AllowanceLevel::Forbidden, DBError::QueryError};
-use crate::SHARE_SECRET_ENV_VAR_KEY;
+use crate::db_access::{filter_allowances_by_user_id, AllowanceLevel, is_safe, Storage};
+use crate::db_access::{AllowanceLevel::Forbidden, DBError::NoDocumentFoundError};
 use crate::web::{auth::get_user_from_request, note::get_allow_level_for_note, ResponseObject, ResponseObjectWithPayload};
 use crate::web::error::APIError;
-use crate::web::share::json_objects::{InviteBody, RelationResponse, ShareRequest};
+use crate::web::group::{get_group_of_member, pull_member_and_notes};
+use crate::secret::{AppSecrets, Secret};
+use crate::web::share::json_objects::{GroupShareRequest, InviteBody, RelationResponse, ShareRequest, ShareResult, ShareStatus};
 
 // Invite-Assets
 /// Time in minutes until an invite expires
@@ -51,6 +48,55 @@ mod json_objects {
         pub allowance: AllowanceLevel
     }
 
+    /// Body of a request for new or altered Allowances of a group
+    #[derive(Deserialize)]
+    pub struct GroupShareRequest {
+        /// Group a note is to be shared with
+        pub group_id: String,
+        /// The level of access being given to all members of the group
+        pub allowance: AllowanceLevel
+    }
+
+    /// The outcome of a single entry of a request for new or altered Allowances
+    #[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+    pub enum ShareStatus {
+        /// The user has been given access to the note
+        Granted,
+        /// The level of access of the user has been altered
+        Changed,
+        /// The access of the user has been revoked
+        Revoked,
+        /// The user already had the requested level of access
+        Unchanged,
+        /// The entry is valid, but has not been applied as other entries are not
+        Skipped,
+        /// The user has already been listed before
+        Duplicate,
+        /// Notes can't be shared as their owner
+        OwnerNotGrantable,
+        /// The user is not connected with the owner of the note
+        NotConnected,
+        /// The user does not exist
+        UnknownUser
+    }
+    impl ShareStatus {
+        /// Returns whether the entry prevents the request from being applied
+        pub fn is_rejection(&self) -> bool {
+            matches!(self, ShareStatus::Duplicate | ShareStatus::OwnerNotGrantable | ShareStatus::NotConnected | ShareStatus::UnknownUser)
+        }
+    }
+
+    /// Body of a response describing the outcome of a single entry of a request for new or altered Allowances
+    #[derive(Serialize)]
+    pub struct ShareResult {
+        /// The user of the entry
+        pub user_id: String,
+        /// The requested level of access
+        pub allowance: AllowanceLevel,
+        /// What became of the entry
+        pub status: ShareStatus
+    }
+
     /// Body of a response after a relation between two user has been established
     #[derive(Serialize)]
     pub struct RelationResponse {
@@ -72,7 +118,8 @@ mod json_objects {
 /// # Arguments
 ///
 /// * `req` - The HttpRequest that was made
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `db` - The AppData containing the storage-backend
+/// * `secrets` - The AppData containing the secret signing invite-codes
 ///
 /// # Examples
 ///
@@ -98,10 +145,10 @@ mod json_objects {
 ///     }
 /// ```
 #[get("/share")]
-pub async fn get_relation_code(req: HttpRequest, db:Data<Mutex<Database>>) -> impl Responder {
-    match get_user_from_request(req, &db).await {
+pub async fn get_relation_code(req: HttpRequest, db: Data<dyn Storage>, secrets: Data<AppSecrets>) -> impl Responder {
+    match get_user_from_request(req, db.get_ref()).await {
         Ok(user) => {
-            match gen_invite(&user._id) {
+            match gen_invite(&user._id, &secrets.share_secret) {
                 Ok(code) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(InviteBody {code})),
                 Err(e) => e.gen_response()
             }
@@ -126,7 +173,8 @@ pub async fn get_relation_code(req: HttpRequest, db:Data<Mutex<Database>>) -> im
 ///
 /// * `req` - The HttpRequest that was made
 /// * `code_req` - The body of the request parsed to an InviteBody-object
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `db` - The AppData containing the storage-backend
+/// * `secrets` - The AppData containing the secret signing invite-codes
 ///
 /// # Examples
 ///
@@ -184,10 +232,10 @@ pub async fn get_relation_code(req: HttpRequest, db:Data<Mutex<Database>>) -> im
 ///     }
 /// ```
 #[post("/share")]
-pub async fn create_relation(req: HttpRequest, code_req: web::Json<InviteBody>, db: Data<Mutex<Database>>) -> impl Responder {
-    match get_user_from_request(req, &db).await {
+pub async fn create_relation(req: HttpRequest, code_req: web::Json<InviteBody>, db: Data<dyn Storage>, secrets: Data<AppSecrets>) -> impl Responder {
+    match get_user_from_request(req, db.get_ref()).await {
         Ok(user) => {
-            match get_user_id_from_invite_code(&code_req.code) {
+            match get_user_id_from_invite_code(&code_req.code, &secrets.share_secret) {
                 Ok(invite_user_id) => {
                     if invite_user_id.eq(&user._id) {
                         return APIError::InvalidInstructionsError("user can't connect with themselves".to_string()).gen_response()
@@ -198,14 +246,8 @@ pub async fn create_relation(req: HttpRequest, code_req: web::Json<InviteBody>,
                     }
 
                     // Add each user to the others relation-list
-                    let update_curr_user =
-                        update_dbo_by_id::<User>(USER, user._id.clone(),
-                                                 doc! {"$push": {"connections": &invite_user_id}},
-                                                 &db);
-                    let update_invite_user =
-                        update_dbo_by_id::<User>(USER, invite_user_id.clone(),
-                                                 doc! {"$push": {"connections": user._id}},
-                                                 &db);
+                    let update_curr_user = db.add_connection(&user._id, &invite_user_id);
+                    let update_invite_user = db.add_connection(&invite_user_id, &user._id);
 
                     // Wait for the queries to finish and check for an error
                     if update_curr_user.await.is_err() || update_invite_user.await.is_err() {
@@ -237,7 +279,7 @@ pub async fn create_relation(req: HttpRequest, code_req: web::Json<InviteBody>,
 ///
 /// * `path` - A Path-object containing the id of the related user
 /// * `req` - The HttpRequest that was made
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `db` - The AppData containing the storage-backend
 ///
 /// # Examples
 ///
@@ -280,13 +322,13 @@ pub async fn create_relation(req: HttpRequest, code_req: web::Json<InviteBody>,
 ///     }
 /// ```
 #[delete("/share/{user_id}")]
-pub async fn remove_relation(path: Path<String>, req: HttpRequest, db: Data<Mutex<Database>>) -> impl Responder {
+pub async fn remove_relation(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
     let related_user = path.into_inner();
     // Check for potential injection-attempt
     if !is_safe(&related_user) {
         return APIError::InvalidIDError.gen_response()
     }
-    match get_user_from_request(req, &db).await {
+    match get_user_from_request(req, db.get_ref()).await {
         Ok(user) => {
             if user._id.eq(&related_user) {
                 return APIError::InvalidInstructionsError("user can't remove connection to themselves".to_string()).gen_response()
@@ -298,28 +340,45 @@ pub async fn remove_relation(path: Path<String>, req: HttpRequest, db: Data<Mute
 
             // Compile all notes that have been shared between both user
             let allow_curr_user =
-                filter_allowances_by_user_id(&user._id, &related_user, &db);
+                filter_allowances_by_user_id(&user._id, &related_user, db.get_ref());
             let allow_rel_user =
-                filter_allowances_by_user_id(&related_user, &user._id, &db);
+                filter_allowances_by_user_id(&related_user, &user._id, db.get_ref());
+
+            let (allow_curr_user, allow_rel_user) = match (allow_curr_user.await, allow_rel_user.await) {
+                (Ok(allow_curr_user), Ok(allow_rel_user)) => (allow_curr_user, allow_rel_user),
+                _ => return APIError::QueryError("shared notes could not be compiled".to_string()).gen_response()
+            };
+
+            // Remove the other user from all groups managed by each of the user
+            let groups = match db.get_groups(&user._id).await {
+                Ok(groups) => groups,
+                Err(_) => return APIError::QueryError("groups could not be compiled".to_string()).gen_response()
+            };
+            for group in groups.iter().filter(|group| group.members.contains(&related_user)) {
+                let member = if group.owner_id.eq(&user._id) { &related_user } else if group.owner_id.eq(&related_user) { &user._id } else { continue };
+                if pull_member_and_notes(group, member, db.get_ref()).await.is_err() {
+                    return APIError::QueryError("group-memberships could not be removed".to_string()).gen_response()
+                }
+            }
+
+            // Revoke the folders shared between both user
+            let (shared_curr_user, shared_rel_user) = match (db.get_shared_folders(&user._id).await, db.get_shared_folders(&related_user).await) {
+                (Ok(shared_curr_user), Ok(shared_rel_user)) => (shared_curr_user, shared_rel_user),
+                _ => return APIError::QueryError("shared folders could not be compiled".to_string()).gen_response()
+            };
+            for (folder, shared_user) in shared_curr_user.iter().filter(|folder| folder.owner_id.eq(&related_user)).map(|folder| (folder, &user._id))
+                .chain(shared_rel_user.iter().filter(|folder| folder.owner_id.eq(&user._id)).map(|folder| (folder, &related_user))) {
+                if db.pull_folder_share(&folder._id, shared_user).await.is_err() {
+                    return APIError::QueryError("folder-shares could not be removed".to_string()).gen_response()
+                }
+            }
 
             // Remove all allowances to notes of the other host
-            let remove_allow_curr_user =
-                update_dbo_by_id::<User>(USER,user._id.clone(),
-                                         doc! {"$pull": {"allowances": {"note_id": {"$in": allow_curr_user.await.unwrap()}}}},
-                                         &db);
-            let remove_allow_rel_user =
-                update_dbo_by_id::<User>(USER, related_user.clone(),
-                                         doc! {"$pull": {"allowances": {"note_id": {"$in": allow_rel_user.await.unwrap()}}}},
-                                         &db);
+            let remove_allow_curr_user = db.pull_allowances(&user._id, &allow_curr_user);
+            let remove_allow_rel_user = db.pull_allowances(&related_user, &allow_rel_user);
             // Remove the relation from each of the user
-            let remove_conn_curr_user =
-                update_dbo_by_id::<User>(USER, user._id.clone(),
-                                         doc! {"$pull": {"connections": &related_user}},
-                                         &db);
-            let remove_conn_rel_user =
-                update_dbo_by_id::<User>(USER, related_user.clone(),
-                                         doc! {"$pull": {"connections": user._id.clone()}},
-                                         &db);
+            let remove_conn_curr_user = db.pull_connection(&user._id, &related_user);
+            let remove_conn_rel_user = db.pull_connection(&related_user, &user._id);
 
             // Sync all tasks and check for an error
             let mut error = Vec::new();
@@ -338,26 +397,30 @@ pub async fn remove_relation(path: Path<String>, req: HttpRequest, db: Data<Mute
     }
 }
 
-/// ENDPOINT: Takes a list of allowed users and their allowed level of access and updates them accordingly
+/// ENDPOINT: Takes a list of allowed users and their allowed level of access and updates them accordingly.
+/// The whole list is checked before anything is changed, after which all changes are applied at once.
+/// Every entry is answered with its outcome, in the order they were given in
 ///
 /// Returns one of the following HttpResponses:
 /// * `200`
 ///     - \[Body: JSON\] All Shares have been updated
+///     - **\[24\]** \[Details: JSON\] Invalid instruction (a user does not exist or is not connected with the owner), nothing has been changed
 /// * `400`
+///     - **\[20\]** \[Details: JSON\] A user is listed twice or is to become owner of the note, nothing has been changed
 ///     - **\[21\]** id contains invalid symbols
 /// * `401`
 ///     - **\[10\]** Missing or invalid JWT
 /// * `403`
 ///     - **\[12\]** Insufficient access-level (not owner)
 /// * `500`
-///     - Something went wrong internally (debug)
+///     - Something went wrong internally, nothing has been changed (debug)
 ///
 /// # Arguments
 ///
 /// * `path` - A Path-object containing the id of the to-be-shared note
 /// * `req` - The HttpRequest that was made
 /// * `allow_req` - The body of the request parsed to a Vector containing ShareRequest-objects
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `db` - The AppData containing the storage-backend
 ///
 /// # Examples
 ///
@@ -376,46 +439,66 @@ pub async fn remove_relation(path: Path<String>, req: HttpRequest, db: Data<Mute
 /// => 200
 ///     {
 ///         "success": true,
+///         "content": [
+///             {
+///                 "user_id": "testUser",
+///                 "allowance": "ReadWrite",
+///                 "status": "Granted"
+///             },
+///             {
+///                 "user_id": "otherUser",
+///                 "allowance": "Forbidden",
+///                 "status": "Revoked"
+///             }
+///         ],
 ///         "time": "2022-04-11 12:05:57"
 ///     }
 /// ```
 /// ```text
-/// PUT-Request at `{api-url}/share/72}4fa97$b62u3:2dr4d3l` with a cookie containing a JWT
+/// PUT-Request at `{api-url}/share/7254fa970b62u3ag62dr4d3l` with a cookie containing a valid JWT [unknownUser is not connected to this user]
 ///     [
 ///         {
 ///             "user_id": "testUser",
 ///             "allowance": "ReadWrite"
 ///         },
 ///         {
-///             "user_id": "otherUser",
-///             "allowance": "Forbidden"
+///             "user_id": "unknownUser",
+///             "allowance": "Read"
 ///         }
 ///     ]
-/// => 400
+/// => 200
 ///     {
 ///         "success": false,
-///         "code": 21,
-///         "message": "requested id contains forbidden character",
+///         "code": 24,
+///         "message": "invalid instruction: not all allowances can be updated",
+///         "details": [
+///             {
+///                 "user_id": "testUser",
+///                 "allowance": "ReadWrite",
+///                 "status": "Skipped"
+///             },
+///             {
+///                 "user_id": "unknownUser",
+///                 "allowance": "Read",
+///                 "status": "NotConnected"
+///             }
+///         ],
 ///         "time": "2022-04-11 12:20:19"
 ///     }
 /// ```
 /// ```text
-/// PUT-Request at `{api-url}/share/7254fa970b62u3ag62dr4d3l` without a cookie containing a JWT
+/// PUT-Request at `{api-url}/share/72}4fa97$b62u3:2dr4d3l` with a cookie containing a JWT
 ///     [
 ///         {
 ///             "user_id": "testUser",
 ///             "allowance": "ReadWrite"
-///         },
-///         {
-///             "user_id": "otherUser",
-///             "allowance": "Forbidden"
 ///         }
 ///     ]
-/// => 401
+/// => 400
 ///     {
 ///         "success": false,
-///         "code": 10,
-///         "message": "user is not logged in",
+///         "code": 21,
+///         "message": "requested id contains forbidden character",
 ///         "time": "2022-04-11 12:20:19"
 ///     }
 /// ```
@@ -425,10 +508,6 @@ pub async fn remove_relation(path: Path<String>, req: HttpRequest, db: Data<Mute
 ///         {
 ///             "user_id": "testUser",
 ///             "allowance": "ReadWrite"
-///         },
-///         {
-///             "user_id": "otherUser",
-///             "allowance": "Forbidden"
 ///         }
 ///     ]
 /// => 403
@@ -440,66 +519,164 @@ pub async fn remove_relation(path: Path<String>, req: HttpRequest, db: Data<Mute
 ///     }
 /// ```
 #[put("/share/{note_id}")]
-pub async fn update_allowances(path: Path<String>, req: HttpRequest, allow_req: web::Json<Vec<ShareRequest>>, db: Data<Mutex<Database>>) -> impl Responder {
+pub async fn update_allowances(path: Path<String>, req: HttpRequest, allow_req: web::Json<Vec<ShareRequest>>, db: Data<dyn Storage>) -> impl Responder {
     let note_id = path.into_inner();
     // Check for potential injection-attempt
     if !is_safe(&note_id) {
         return APIError::InvalidIDError.gen_response()
     }
-    match get_allow_level_for_note(&note_id, req.clone(), &db).await {
-        Ok(AllowanceLevel::Owner) => { // Sharing of a note is only allowed to the owner of said note
-            let curr_user = get_user_from_request(req, &db).await.unwrap();
-            let mut errors = Vec::new();
-            // Iterate through all changes in allowances
-            for share in allow_req.into_inner() { //TODO Multithread (check for duplicates in user_id first)
-                if !curr_user.connections.contains(&share.user_id) { //TODO? Add to error-report
-                    continue // No allowances if the user is not connected to the owner
-                }
-                match get_dbo_by_id::<User>(USER, share.user_id, &db).await {
-                    Ok(user) => {
-                        match user.allowances.iter().find(|allow| allow.note_id.eq(&note_id)) {
-                            // The user already has an existing allowance for the note
-                            Some(_) => {
-                                if share.allowance.eq(&Forbidden) { // The allowance is to be revoked
-                                    if update_dbo_by_id::<User>(USER, user._id,
-                                                                doc! {"$pull": {"allowances": {"note_id": &note_id}}}, &db).await.is_err() {
-                                        errors.push(QueryError)
-                                    }
-                                } else { // The allowance is to be altered
-                                    let user_coll = db.lock().unwrap().collection::<User>(USER);
-                                    if user_coll.update_one(doc! {"_id": user._id, "allowances.note_id": &note_id},
-                                                            doc! {"$set": {"allowances.$.level": bson::to_bson(&share.allowance).unwrap()}},
-                                                            None).await.is_err() {
-                                        errors.push(QueryError)
-                                    }
-                                }
-                            }
-                            // The user has no current allowance with the note
-                            None => {
-                                if share.allowance.eq(&Forbidden) { //TODO? Add to error-report
-                                    continue // Can't revoke an allowance that doesn't exist
-                                }
-                                if update_dbo_by_id::<User>(USER, user._id,
-                                                            doc! {"$push": {"allowances":
-                                                                {"note_id": &note_id, "level": bson::to_bson(&share.allowance).unwrap()}}},
-                                                            &db).await.is_err() {
-                                    errors.push(QueryError)
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => errors.push(e) // Can't share with nonexisting user
-                }
-            }
-            // Check for a failed query
-            if !errors.is_empty() { //TODO Create an error report and return it
-                return APIError::QueryError("not all allowances were updated".to_string()).gen_response()
+    // Sharing of a note is only allowed to the owner of said note
+    match get_allow_level_for_note(&note_id, req.clone(), db.get_ref()).await {
+        Ok(AllowanceLevel::Owner) => {}
+        Ok(_) => return APIError::NoPermissionError.gen_response(),
+        Err(e) => return e.gen_response()
+    }
+    let curr_user = match get_user_from_request(req, db.get_ref()).await {
+        Ok(user) => user,
+        Err(e) => return e.gen_response()
+    };
+
+    // Check every entry and compile what it is going to change
+    let allow_req = allow_req.into_inner();
+    let mut report = Vec::new();
+    for (i, share) in allow_req.iter().enumerate() {
+        let status = if allow_req[..i].iter().any(|other| other.user_id.eq(&share.user_id)) {
+            ShareStatus::Duplicate
+        } else if share.allowance.eq(&AllowanceLevel::Owner) {
+            ShareStatus::OwnerNotGrantable
+        } else if !curr_user.connections.contains(&share.user_id) {
+            ShareStatus::NotConnected // No allowances if the user is not connected to the owner
+        } else {
+            match db.get_user(&share.user_id).await {
+                Ok(user) => match user.allowances.iter().find(|allow| allow.note_id.eq(&note_id)) {
+                    Some(allow) if allow.level.eq(&share.allowance) => ShareStatus::Unchanged,
+                    Some(_) if share.allowance.eq(&Forbidden) => ShareStatus::Revoked,
+                    Some(_) => ShareStatus::Changed,
+                    None if share.allowance.eq(&Forbidden) => ShareStatus::Unchanged, // Can't revoke an allowance that doesn't exist
+                    None => ShareStatus::Granted
+                },
+                Err(NoDocumentFoundError) => ShareStatus::UnknownUser,
+                Err(_) => return APIError::QueryError("user could not be retrieved from database".to_string()).gen_response()
             }
-            HttpResponse::Ok().json(ResponseObject::new())
+        };
+        report.push(ShareResult { user_id: share.user_id.clone(), allowance: share.allowance, status });
+    }
+
+    // Reject the whole batch if a single entry is invalid
+    if report.iter().any(|result| result.status.is_rejection()) {
+        let error = if report.iter().any(|result| matches!(result.status, ShareStatus::Duplicate | ShareStatus::OwnerNotGrantable)) {
+            APIError::InvalidPayloadError
+        } else {
+            APIError::InvalidInstructionsError("not all allowances can be updated".to_string())
+        };
+        for result in report.iter_mut().filter(|result| !result.status.is_rejection()) {
+            result.status = ShareStatus::Skipped
         }
-        Ok(_) =>  APIError::NoPermissionError.gen_response(), // Not owner of the note
-        Err(e) => e.gen_response() // unknown
+        return error.gen_response_with_details(report)
+    }
+
+    // Apply all changes at once
+    let changes: Vec<(String, AllowanceLevel)> = report.iter().filter(|result| result.status != ShareStatus::Unchanged)
+        .map(|result| (result.user_id.clone(), result.allowance)).collect();
+    if !changes.is_empty() && db.set_note_allowances(&note_id, &changes).await.is_err() {
+        return APIError::QueryError("allowances could not be updated".to_string()).gen_response()
+    }
+    HttpResponse::Ok().json(ResponseObjectWithPayload::new(report))
+}
+
+/// ENDPOINT: Takes a list of groups and their level of access and updates the allowances of a note accordingly.
+/// Notes can only be shared with groups managed by the user, whose members are all connections of the user.
+/// The whole list is checked before anything is changed, and either all allowances are updated or none of them
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - All Shares have been updated
+/// * `400`
+///     - **\[20\]** A group is listed twice or is to become owner of the note
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** Insufficient access-level (not owner of the note or of a group)
+/// * `404`
+///     - **\[22\]** A group does not exist or the user is not a member of it
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the to-be-shared note
+/// * `req` - The HttpRequest that was made
+/// * `allow_req` - The body of the request parsed to a Vector containing GroupShareRequest-objects
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// PUT-Request at `{api-url}/share/7254fa970b62u3ag62dr4d3l/groups` with a cookie containing a valid JWT
+///     [
+///         {
+///             "group_id": "Zt4cW9qKa2LmN0xB",
+///             "allowance": "Read"
+///         },
+///         {
+///             "group_id": "Lw7pRb3YcE1sTn8Q",
+///             "allowance": "Forbidden"
+///         }
+///     ]
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-11 12:05:57"
+///     }
+/// ```
+/// ```text
+/// PUT-Request at `{api-url}/share/7254fa970b62u3ag62dr4d3l/groups` with a cookie containing a valid JWT [user is not a member of the group]
+///     [
+///         {
+///             "group_id": "Zt4cW9qKa2LmN0xB",
+///             "allowance": "Read"
+///         }
+///     ]
+/// => 404
+///     {
+///         "success": false,
+///         "code": 22,
+///         "message": "requested resource does not exist: group",
+///         "time": "2022-04-11 12:20:19"
+///     }
+/// ```
+#[put("/share/{note_id}/groups")]
+pub async fn update_group_allowances(path: Path<String>, req: HttpRequest, allow_req: web::Json<Vec<GroupShareRequest>>, db: Data<dyn Storage>) -> impl Responder {
+    let note_id = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&note_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    match get_allow_level_for_note(&note_id, req.clone(), db.get_ref()).await {
+        Ok(AllowanceLevel::Owner) => {} // Sharing of a note is only allowed to the owner of said note
+        Ok(_) => return APIError::NoPermissionError.gen_response(),
+        Err(e) => return e.gen_response()
+    }
+    // Check all changes before applying any of them
+    let allow_req = allow_req.into_inner();
+    for (i, share) in allow_req.iter().enumerate() {
+        if share.allowance.eq(&AllowanceLevel::Owner) || allow_req[..i].iter().any(|other| other.group_id.eq(&share.group_id)) {
+            return APIError::InvalidPayloadError.gen_response()
+        }
+        // Members other than the owner may not be connected to everyone else in the group
+        match get_group_of_member(&share.group_id, req.clone(), db.get_ref()).await {
+            Ok((user, group)) if group.owner_id.eq(&user._id) => {}
+            Ok(_) => return APIError::NoPermissionError.gen_response(),
+            Err(e) => return e.gen_response()
+        }
+    }
+    // Either all allowances are updated or none of them
+    let changes: Vec<(String, AllowanceLevel)> = allow_req.into_iter().map(|share| (share.group_id, share.allowance)).collect();
+    if db.set_group_note_allowances(&note_id, &changes).await.is_err() {
+        return APIError::QueryError("allowances could not be updated".to_string()).gen_response()
     }
+    HttpResponse::Ok().json(ResponseObject::new())
 }
 
 /// Creates an invite-code with the inviting users name as its payload
@@ -507,7 +684,8 @@ pub async fn update_allowances(path: Path<String>, req: HttpRequest, allow_req:
 /// # Arguments
 ///
 /// * `uid` - The username to save
-fn gen_invite(uid: &str) -> Result<String, APIError> {
+/// * `secret` - The secret signing the invite-code
+fn gen_invite(uid: &str, secret: &Secret) -> Result<String, APIError> {
     // Set all required values
     let expiration = Utc::now()
         .checked_add_signed(chrono::Duration::minutes(INVITE_DURATION_MINUTES))
@@ -520,7 +698,7 @@ fn gen_invite(uid: &str) -> Result<String, APIError> {
     let header = Header::new(Algorithm::HS256);
 
     // Generate the invite
-    encode(&header, &claims, &EncodingKey::from_secret(env::var(SHARE_SECRET_ENV_VAR_KEY).unwrap().as_bytes()))
+    encode(&header, &claims, &EncodingKey::from_secret(secret.expose().as_bytes()))
         .map_err(|_| APIError::InternalServerError("invite-code-creation failed".to_string()))
 }
 
@@ -529,9 +707,10 @@ fn gen_invite(uid: &str) -> Result<String, APIError> {
 /// # Arguments
 ///
 /// * `code` - Invite-code to be verified
-fn get_user_id_from_invite_code(code: &String) -> Result<String, APIError> {
-    decode::<Claims>(&code,
-                     &DecodingKey::from_secret(env::var(SHARE_SECRET_ENV_VAR_KEY).unwrap().as_bytes()),
+/// * `secret` - The secret the invite-code was signed with
+fn get_user_id_from_invite_code(code: &str, secret: &Secret) -> Result<String, APIError> {
+    decode::<Claims>(code,
+                     &DecodingKey::from_secret(secret.expose().as_bytes()),
                      &Validation::new(Algorithm::HS256))
         .map(|dec|dec.claims.sub).map_err(|_| APIError::InvalidInviteError)
 }
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+use actix_web::cookie::Cookie;
+use actix_web::http::header::USER_AGENT;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use actix_web::test::TestRequest;
+use serde_json::json;
+use crate::db_access::memory::MemoryStorage;
+use crate::web::tests::{assert_error, call, init_app, JWT_COOKIE, login, login_with_refresh, PASSWORD, read_only_storage_with_user, REFRESH_COOKIE, session_cookies, signup, signup_and_login};
+
+#[actix_rt::test]
+async fn login_sets_cookie_and_status() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    assert_eq!(cookie.name(), JWT_COOKIE);
+
+    let (status, body) = call(&app, TestRequest::get().uri("/api/auth").cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["username"], "testUser");
+}
+
+#[actix_rt::test]
+async fn login_with_wrong_credentials() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    signup(&app, "testUser").await;
+
+    let wrong_passwd = call(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "testUser", "password": "passTest", "session_only": true}))).await;
+    assert_error(wrong_passwd, StatusCode::OK, 11);
+    let unknown_user = call(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "otherUser", "password": PASSWORD, "session_only": true}))).await;
+    assert_error(unknown_user, StatusCode::OK, 11);
+}
+
+#[actix_rt::test]
+async fn login_with_invalid_payload() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let response = call(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "testUser"}))).await;
+    assert_error(response, StatusCode::BAD_REQUEST, 20);
+}
+
+#[actix_rt::test]
+async fn status_without_login() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let (status, body) = call(&app, TestRequest::get().uri("/api/auth")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["success"], false);
+}
+
+#[actix_rt::test]
+async fn logout() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+
+    let (status, body) = call(&app, TestRequest::delete().uri("/api/auth").cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["success"], true);
+    assert_error(call(&app, TestRequest::delete().uri("/api/auth")).await, StatusCode::UNAUTHORIZED, 10);
+}
+
+#[actix_rt::test]
+async fn invalid_jwt_is_rejected() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let response = call(&app, TestRequest::get().uri("/api/user")
+        .cookie(actix_web::cookie::Cookie::new(JWT_COOKIE, "not.a.jwt"))).await;
+    assert_error(response, StatusCode::UNAUTHORIZED, 10);
+}
+
+#[actix_rt::test]
+async fn login_with_failing_storage() {
+    let app = init_app(read_only_storage_with_user("testUser").await).await;
+    let (status, body) = call(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "testUser", "password": PASSWORD, "session_only": true}))).await;
+    // Reading is enough to log in
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["success"], true);
+}
+
+#[actix_rt::test]
+async fn logout_revokes_session() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    signup(&app, "testUser").await;
+    let (cookie, refresh) = login_with_refresh(&app, "testUser").await;
+
+    let resp = test::call_service(&app, TestRequest::delete().uri("/api/auth").cookie(cookie.clone()).to_request()).await;
+    assert!(resp.response().cookies().any(|cookie| cookie.name().eq(REFRESH_COOKIE) && cookie.value().is_empty()));
+    // Neither the JWT nor the refresh-token are of any use afterwards
+    assert_error(call(&app, TestRequest::get().uri("/api/user").cookie(cookie)).await, StatusCode::UNAUTHORIZED, 10);
+    assert_error(call(&app, TestRequest::post().uri("/api/auth/refresh").cookie(refresh)).await, StatusCode::UNAUTHORIZED, 10);
+}
+
+#[actix_rt::test]
+async fn refresh_rotates_token() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    signup(&app, "testUser").await;
+    let (_, refresh) = login_with_refresh(&app, "testUser").await;
+
+    let resp = test::call_service(&app, TestRequest::post().uri("/api/auth/refresh").cookie(refresh.clone()).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let (cookie, new_refresh) = session_cookies(&resp).expect("refresh failed");
+    assert_ne!(refresh.value(), new_refresh.value());
+    let (status, body) = call(&app, TestRequest::get().uri("/api/auth").cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["username"], "testUser");
+
+    // Reusing the replaced refresh-token revokes the entire session
+    assert_error(call(&app, TestRequest::post().uri("/api/auth/refresh").cookie(refresh)).await, StatusCode::UNAUTHORIZED, 10);
+    assert_error(call(&app, TestRequest::get().uri("/api/user").cookie(cookie)).await, StatusCode::UNAUTHORIZED, 10);
+    assert_error(call(&app, TestRequest::post().uri("/api/auth/refresh").cookie(new_refresh)).await, StatusCode::UNAUTHORIZED, 10);
+
+    // Malformed or missing refresh-tokens
+    assert_error(call(&app, TestRequest::post().uri("/api/auth/refresh")).await, StatusCode::UNAUTHORIZED, 10);
+    assert_error(call(&app, TestRequest::post().uri("/api/auth/refresh")
+        .cookie(Cookie::new(REFRESH_COOKIE, "no-token"))).await, StatusCode::UNAUTHORIZED, 10);
+}
+
+#[actix_rt::test]
+async fn list_and_revoke_sessions() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    signup(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    let resp = test::call_service(&app, TestRequest::post().uri("/api/auth")
+        .insert_header((USER_AGENT, "Phone"))
+        .set_json(json!({"username": "testUser", "password": PASSWORD, "session_only": true})).to_request()).await;
+    let (phone, _) = session_cookies(&resp).unwrap();
+    let cookie = login(&app, "testUser").await;
+
+    let (status, body) = call(&app, TestRequest::get().uri("/api/auth/sessions").cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let sessions = body["content"].as_array().unwrap();
+    assert_eq!(sessions.len(), 2);
+    let phone_session = sessions.iter().find(|session| session["device"] == "Phone").unwrap();
+    assert_eq!(phone_session["current"], false);
+    assert_eq!(phone_session["ip"], "unknown");
+    let phone_id = phone_session["session_id"].as_str().unwrap().to_string();
+    let current_id = sessions.iter().find(|session| session["current"] == true).unwrap()["session_id"].as_str().unwrap().to_string();
+
+    // Sessions of other user can't be revoked
+    assert_error(call(&app, TestRequest::delete().uri(&format!("/api/auth/sessions/{}", phone_id)).cookie(other)).await, StatusCode::NOT_FOUND, 22);
+    assert_error(call(&app, TestRequest::delete().uri("/api/auth/sessions/unknown").cookie(cookie.clone())).await, StatusCode::NOT_FOUND, 22);
+    assert_error(call(&app, TestRequest::delete().uri("/api/auth/sessions/%7B%24ne%3Anull%7D").cookie(cookie.clone())).await, StatusCode::BAD_REQUEST, 21);
+
+    // Revoking a session logs its device out
+    let (status, _) = call(&app, TestRequest::delete().uri(&format!("/api/auth/sessions/{}", phone_id)).cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&app, TestRequest::get().uri("/api/user").cookie(phone)).await, StatusCode::UNAUTHORIZED, 10);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/auth/sessions").cookie(cookie.clone())).await;
+    assert_eq!(body["content"].as_array().unwrap().len(), 1);
+
+    // Including the current one
+    let (status, _) = call(&app, TestRequest::delete().uri(&format!("/api/auth/sessions/{}", current_id)).cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&app, TestRequest::get().uri("/api/auth/sessions").cookie(cookie)).await, StatusCode::UNAUTHORIZED, 10);
+}
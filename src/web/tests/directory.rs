@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use async_trait::async_trait;
+use serde_json::json;
+use crate::db_access::{CredentialStore, UserStore};
+use crate::db_access::memory::MemoryStorage;
+use crate::directory::{Directory, DirectoryAccount, LdapConfig, LdapDirectory, parse_group_roles};
+use crate::web::tests::{AppConfig, assert_error, call, init_app_with, login, PASSWORD, signup, signup_and_login};
+
+/// A Directory knowing a fixed set of users, all sharing the same password
+#[derive(Default)]
+struct FakeDirectory {
+    /// The roles of all users, mapped by their username
+    users: Mutex<HashMap<String, Vec<String>>>
+}
+
+impl FakeDirectory {
+    /// Adds a user or replaces their roles
+    fn set_user(&self, username: &str, roles: &[&str]) {
+        self.users.lock().unwrap().insert(username.to_string(), roles.iter().map(|role| role.to_string()).collect());
+    }
+}
+
+#[async_trait]
+impl Directory for FakeDirectory {
+    async fn verify(&self, username: &str, password: &str) -> io::Result<Option<DirectoryAccount>> {
+        if username == "unreachableUser" {
+            return Err(io::Error::other("connection refused"))
+        }
+        Ok(self.users.lock().unwrap().get(username).filter(|_| password == PASSWORD)
+            .map(|roles| DirectoryAccount { roles: roles.clone() }))
+    }
+}
+
+#[actix_rt::test]
+async fn login_with_directory() {
+    let db = Arc::new(MemoryStorage::new());
+    let directory = Arc::new(FakeDirectory::default());
+    directory.set_user("dirUser", &["editor"]);
+    let app = init_app_with(db.clone(), AppConfig { directory: Some(directory.clone()), ..AppConfig::default() }).await;
+
+    // The user is created on their first login, without local credentials
+    assert!(db.get_user("dirUser").await.is_err());
+    let cookie = login(&app, "dirUser").await;
+    let (status, body) = call(&app, TestRequest::get().uri("/api/user").cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"], json!({"username": "dirUser", "relations": [], "roles": ["editor"]}));
+    assert!(db.get_credential("dirUser").await.is_err());
+
+    // Roles are updated on every login
+    directory.set_user("dirUser", &["admin", "editor"]);
+    login(&app, "dirUser").await;
+    assert_eq!(db.get_user("dirUser").await.unwrap().roles, vec!["admin", "editor"]);
+
+    // Wrong passwords and unknown users are indistinguishable
+    for username in ["dirUser", "unknownUser"] {
+        assert_error(call(&app, TestRequest::post().uri("/api/auth")
+            .set_json(json!({"username": username, "password": "wrongPass", "session_only": false}))).await, StatusCode::OK, 11);
+    }
+    assert_error(call(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "unreachableUser", "password": PASSWORD, "session_only": false}))).await, StatusCode::INTERNAL_SERVER_ERROR, 50);
+
+    // The password can only be changed within the directory
+    let cookie = login(&app, "dirUser").await;
+    assert_error(call(&app, TestRequest::put().uri("/api/user/password").cookie(cookie)
+        .set_json(json!({"old_password": PASSWORD, "new_password": "newPass"}))).await, StatusCode::OK, 24);
+}
+
+#[actix_rt::test]
+async fn local_accounts() {
+    let db = Arc::new(MemoryStorage::new());
+    let directory = Arc::new(FakeDirectory::default());
+    let app = init_app_with(db.clone(), AppConfig { directory: Some(directory.clone()), ..AppConfig::default() }).await;
+
+    // Local users keep working and take precedence over the directory
+    signup_and_login(&app, "testUser").await;
+    directory.set_user("testUser", &["admin"]);
+    assert_error(call(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "testUser", "password": "otherPass", "session_only": false}))).await, StatusCode::OK, 11);
+    login(&app, "testUser").await;
+    assert!(db.get_user("testUser").await.unwrap().roles.is_empty());
+
+    // Users of the directory can't be taken over by signing up
+    directory.set_user("dirUser", &[]);
+    login(&app, "dirUser").await;
+    assert_error(signup(&app, "dirUser").await, StatusCode::OK, 11);
+}
+
+#[actix_rt::test]
+async fn unreachable_directory() {
+    let config = LdapConfig {
+        url: "ldap://127.0.0.1:1".to_string(),
+        base_dn: "dc=example,dc=com".to_string(),
+        user_filter: "(uid={username})".to_string(),
+        search_bind: None,
+        group_attribute: "memberOf".to_string(),
+        group_roles: Vec::new()
+    };
+    let app = init_app_with(Arc::new(MemoryStorage::new()), AppConfig { directory: Some(Arc::new(LdapDirectory::new(config))), ..AppConfig::default() }).await;
+    assert_error(call(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "dirUser", "password": PASSWORD, "session_only": false}))).await, StatusCode::INTERNAL_SERVER_ERROR, 50);
+    // Empty passwords would bind anonymously
+    assert_error(call(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "dirUser", "password": "", "session_only": false}))).await, StatusCode::OK, 11);
+}
+
+#[test]
+fn group_roles() {
+    assert_eq!(parse_group_roles("admin:cn=admins,dc=example; editor:cn=staff,dc=example;"), Some(vec![
+        ("cn=admins,dc=example".to_string(), "admin".to_string()), ("cn=staff,dc=example".to_string(), "editor".to_string())]));
+    assert_eq!(parse_group_roles(""), Some(Vec::new()));
+    assert_eq!(parse_group_roles("admin:cn=admins,dc=example;cn=staff,dc=example"), None);
+    assert_eq!(parse_group_roles(":cn=admins,dc=example"), None);
+
+    // Groups are compared case-insensitively and each role is assigned once, in the order configured
+    let directory = LdapDirectory::new(LdapConfig {
+        url: "ldap://localhost".to_string(),
+        base_dn: "dc=example".to_string(),
+        user_filter: "(uid={username})".to_string(),
+        search_bind: None,
+        group_attribute: "memberOf".to_string(),
+        group_roles: parse_group_roles("admin:cn=admins,dc=example;editor:cn=staff,dc=example;editor:cn=admins,dc=example").unwrap()
+    });
+    assert_eq!(directory.roles_of(&["CN=Staff,DC=example".to_string(), "cn=admins,dc=example".to_string()]), vec!["admin", "editor"]);
+    assert_eq!(directory.roles_of(&["cn=guests,dc=example".to_string()]), Vec::<String>::new());
+}
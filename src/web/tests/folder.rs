@@ -0,0 +1,214 @@
+use std::env;
+use std::sync::Arc;
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use chrono::Utc;
+use serde_json::{json, Value};
+use crate::db_access::{AllowanceLevel, DBError, Folder, FolderStore, Note, Storage, User};
+use crate::db_access::memory::MemoryStorage;
+use crate::db_access::sqlite::SqliteStorage;
+use crate::web::tests::{assert_error, call, connect, create_note, init_app, share_note, signup_and_login};
+
+/// Creates a folder, returning its identifier
+macro_rules! create_folder {
+    ($app:expr, $cookie:expr, $name:expr, $parent_id:expr) => {{
+        let (status, body) = call(&$app, TestRequest::post().uri("/api/folders").cookie($cookie.clone())
+            .set_json(json!({"name": $name, "parent_id": $parent_id}))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        body["content"]["folder_id"].as_str().unwrap().to_string()
+    }};
+}
+
+/// Moves a note into a folder, returning the response
+macro_rules! move_note {
+    ($app:expr, $cookie:expr, $note_id:expr, $folder_id:expr) => {
+        call(&$app, TestRequest::put().uri(&format!("/api/note/{}/folder", $note_id)).cookie($cookie.clone())
+            .set_json(json!({"folder_id": $folder_id}))).await
+    };
+}
+
+/// Returns the level of access listed for each note, in the order they have been created
+fn levels(body: &Value) -> Vec<(String, String)> {
+    body["content"]["notes"].as_array().unwrap().iter()
+        .map(|note| (note["title"].as_str().unwrap().to_string(), note["allowance"].as_str().unwrap().to_string())).collect()
+}
+
+#[actix_rt::test]
+async fn folder_hierarchy() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+
+    for invalid in ["", " ", &"x".repeat(65)] {
+        assert_error(call(&app, TestRequest::post().uri("/api/folders").cookie(cookie.clone())
+            .set_json(json!({"name": invalid}))).await, StatusCode::BAD_REQUEST, 20);
+    }
+    let parent = create_folder!(app, cookie, "Projects", Value::Null);
+    let child = create_folder!(app, cookie, "Archive", parent);
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let (status, _) = move_note!(app, cookie, note_id, child);
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = call(&app, TestRequest::get().uri(&format!("/api/folders/{}", parent)).cookie(cookie.clone())).await;
+    assert_eq!(body["content"]["folders"], json!([child]));
+    let (_, body) = call(&app, TestRequest::get().uri(&format!("/api/folders/{}", child)).cookie(cookie.clone())).await;
+    assert_eq!(body["content"]["notes"], json!([note_id]));
+
+    // Folders of others are neither visible nor usable
+    assert_error(call(&app, TestRequest::get().uri(&format!("/api/folders/{}", parent)).cookie(other.clone())).await,
+                 StatusCode::NOT_FOUND, 22);
+    assert_error(call(&app, TestRequest::post().uri("/api/folders").cookie(other.clone())
+        .set_json(json!({"name": "Intruder", "parent_id": parent}))).await, StatusCode::NOT_FOUND, 22);
+    let other_note = create_note(&app, &other, "Other-Note").await;
+    assert_error(move_note!(app, other, other_note, parent), StatusCode::NOT_FOUND, 22);
+
+    // Folders can't end up inside of themselves
+    for target in [&parent, &child] {
+        assert_error(call(&app, TestRequest::put().uri(&format!("/api/folders/{}", parent)).cookie(cookie.clone())
+            .set_json(json!({"name": "Projects", "parent_id": target}))).await, StatusCode::OK, 24);
+    }
+    let (status, _) = call(&app, TestRequest::put().uri(&format!("/api/folders/{}", child)).cookie(cookie.clone())
+        .set_json(json!({"name": "Old Projects", "parent_id": null}))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = call(&app, TestRequest::put().uri(&format!("/api/folders/{}", parent)).cookie(cookie.clone())
+        .set_json(json!({"name": "Projects", "parent_id": child}))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Removing a folder hands its contents to its parent
+    let (status, _) = call(&app, TestRequest::delete().uri(&format!("/api/folders/{}", child)).cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/folders").cookie(cookie.clone())).await;
+    let folders = body["content"].as_array().unwrap();
+    assert_eq!(folders.len(), 1);
+    assert_eq!(folders[0]["folder_id"], parent.as_str());
+    assert_eq!(folders[0]["parent_id"], Value::Null);
+    assert!(folders[0]["notes"].as_array().unwrap().is_empty());
+
+    // Removing a note takes it out of its folder
+    move_note!(app, cookie, note_id, parent);
+    call(&app, TestRequest::delete().uri(&format!("/api/note/{}", note_id)).cookie(cookie)).await;
+    assert!(db.get_folder(&parent).await.unwrap().notes.is_empty());
+}
+
+#[actix_rt::test]
+async fn inherited_permissions() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    signup_and_login(&app, "strangerUser").await;
+    let folder = create_folder!(app, cookie, "Projects", Value::Null);
+    let sub_folder = create_folder!(app, cookie, "Drafts", folder);
+    let outer_note = create_note(&app, &cookie, "Outer-Note").await;
+    let inner_note = create_note(&app, &cookie, "Inner-Note").await;
+    move_note!(app, cookie, outer_note, folder);
+    move_note!(app, cookie, inner_note, sub_folder);
+    let share_uri = format!("/api/folders/{}/share", folder);
+
+    // Only connections can be given access, owners can't be appointed
+    assert_error(call(&app, TestRequest::put().uri(&share_uri).cookie(cookie.clone())
+        .set_json(json!([{"user_id": "otherUser", "allowance": "Read"}]))).await, StatusCode::OK, 24);
+    connect(&app, &cookie, &other).await;
+    for invalid in [json!([{"user_id": "otherUser", "allowance": "Owner"}]),
+                    json!([{"user_id": "otherUser", "allowance": "Read"}, {"user_id": "otherUser", "allowance": "ReadWrite"}])] {
+        assert_error(call(&app, TestRequest::put().uri(&share_uri).cookie(cookie.clone()).set_json(invalid)).await,
+                     StatusCode::BAD_REQUEST, 20);
+    }
+
+    // Every note inside of the folder and its sub-folders inherits the level
+    let (status, _) = call(&app, TestRequest::put().uri(&share_uri).cookie(cookie.clone())
+        .set_json(json!([{"user_id": "otherUser", "allowance": "ReadWrite"}]))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(other.clone())).await;
+    assert_eq!(levels(&body), vec![("Outer-Note".to_string(), "ReadWrite".to_string()), ("Inner-Note".to_string(), "ReadWrite".to_string())]);
+    let (_, body) = call(&app, TestRequest::get().uri(&format!("/api/folders/{}", sub_folder)).cookie(other.clone())).await;
+    assert_eq!(body["content"]["allowance"], "ReadWrite");
+    assert!(body["content"].get("shares").is_none());
+
+    // Shares closer to a note, or on the note itself, take precedence
+    call(&app, TestRequest::put().uri(&format!("/api/folders/{}/share", sub_folder)).cookie(cookie.clone())
+        .set_json(json!([{"user_id": "otherUser", "allowance": "Read"}]))).await;
+    share_note(&app, &cookie, &outer_note, "otherUser", "Read").await;
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(other.clone())).await;
+    assert_eq!(levels(&body), vec![("Outer-Note".to_string(), "Read".to_string()), ("Inner-Note".to_string(), "Read".to_string())]);
+    assert_error(call(&app, TestRequest::put().uri(&format!("/api/note/{}", inner_note)).cookie(other.clone())
+        .set_json(json!({"title": "Changed", "content": "", "tags": []}))).await, StatusCode::FORBIDDEN, 12);
+
+    // Only the owner manages the folder
+    assert_error(call(&app, TestRequest::put().uri(&format!("/api/folders/{}", folder)).cookie(other.clone())
+        .set_json(json!({"name": "Mine", "parent_id": null}))).await, StatusCode::FORBIDDEN, 12);
+    assert_error(call(&app, TestRequest::put().uri(&share_uri).cookie(other.clone())
+        .set_json(json!([{"user_id": "testUser", "allowance": "Read"}]))).await, StatusCode::FORBIDDEN, 12);
+    let other_note = create_note(&app, &other, "Other-Note").await;
+    assert_error(move_note!(app, other, other_note, folder), StatusCode::FORBIDDEN, 12);
+
+    // Notes moved out of the folder no longer inherit its level, neither do notes after revoking the share
+    move_note!(app, cookie, inner_note, Value::Null);
+    assert_error(call(&app, TestRequest::get().uri(&format!("/api/note/{}", inner_note)).cookie(other.clone())).await,
+                 StatusCode::FORBIDDEN, 12);
+    share_note(&app, &cookie, &outer_note, "otherUser", "Forbidden").await;
+    call(&app, TestRequest::put().uri(&share_uri).cookie(cookie.clone())
+        .set_json(json!([{"user_id": "otherUser", "allowance": "Forbidden"}]))).await;
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(other.clone())).await;
+    assert_eq!(levels(&body), vec![("Other-Note".to_string(), "Owner".to_string())]);
+
+    // Severing the connection revokes the share as well
+    call(&app, TestRequest::put().uri(&share_uri).cookie(cookie.clone())
+        .set_json(json!([{"user_id": "otherUser", "allowance": "Read"}]))).await;
+    call(&app, TestRequest::delete().uri("/api/share/testUser").cookie(other)).await;
+    assert!(db.get_shared_folders("otherUser").await.unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn removal_hands_over_contents() {
+    let path = env::temp_dir().join(format!("writeup-folders-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let backends: [Box<dyn Storage>; 2] = [Box::new(MemoryStorage::new()), Box::new(SqliteStorage::open(path.to_str().unwrap()).unwrap())];
+    for db in backends {
+        db.insert_user(&User { _id: "testUser".to_string(), allowances: Vec::new(), connections: Vec::new(), roles: Vec::new() }).await.unwrap();
+        let note = Note { title: "Test-Note".to_string(), content: String::new(), owner_id: "testUser".to_string(), tags: Vec::new(),
+            version: 0, created_at: Utc::now(), updated_at: Utc::now(), last_editor_id: "testUser".to_string() };
+        let note_id = db.insert_note(&note).await.unwrap();
+        let folder = |id: &str, parent_id: Option<&str>| Folder { _id: id.to_string(), name: id.to_string(), owner_id: "testUser".to_string(),
+            parent_id: parent_id.map(str::to_string), notes: Vec::new(), shares: Vec::new(), created_at: Utc::now() };
+        db.insert_folder(&folder("top", None)).await.unwrap();
+        db.insert_folder(&folder("middle", Some("top"))).await.unwrap();
+        db.insert_folder(&folder("bottom", Some("middle"))).await.unwrap();
+        db.add_folder_note("middle", &note_id).await.unwrap();
+
+        // Notes and folders move up a level
+        db.remove_folder("middle").await.unwrap();
+        assert_eq!(db.get_folder("top").await.unwrap().notes, vec![note_id.clone()]);
+        assert_eq!(db.get_folder("bottom").await.unwrap().parent_id.as_deref(), Some("top"));
+        assert!(matches!(db.get_folder("middle").await, Err(DBError::NoDocumentFoundError)));
+        // The contents of top-level folders become top-level themselves
+        db.remove_folder("top").await.unwrap();
+        assert_eq!(db.get_folder("bottom").await.unwrap().parent_id, None);
+        assert!(db.get_folders("testUser").await.unwrap().iter().all(|folder| folder.notes.is_empty()));
+        assert!(matches!(db.remove_folder("top").await, Err(DBError::NoDocumentFoundError)));
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[actix_rt::test]
+async fn batch_shares() {
+    let path = env::temp_dir().join(format!("writeup-folder-shares-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let backends: [Box<dyn Storage>; 2] = [Box::new(MemoryStorage::new()), Box::new(SqliteStorage::open(path.to_str().unwrap()).unwrap())];
+    for db in backends {
+        for user_id in ["testUser", "firstUser", "secondUser"] {
+            db.insert_user(&User { _id: user_id.to_string(), allowances: Vec::new(), connections: Vec::new(), roles: Vec::new() }).await.unwrap();
+        }
+        db.insert_folder(&Folder { _id: "folder".to_string(), name: "Projects".to_string(), owner_id: "testUser".to_string(),
+            parent_id: None, notes: Vec::new(), shares: Vec::new(), created_at: Utc::now() }).await.unwrap();
+        let shares = |folder: Folder| folder.shares.into_iter().map(|share| (share.user_id, share.level)).collect::<Vec<_>>();
+
+        db.set_folder_shares("folder", &[("firstUser".to_string(), AllowanceLevel::Read), ("secondUser".to_string(), AllowanceLevel::ReadWrite)]).await.unwrap();
+        assert_eq!(shares(db.get_folder("folder").await.unwrap()),
+                   vec![("firstUser".to_string(), AllowanceLevel::Read), ("secondUser".to_string(), AllowanceLevel::ReadWrite)]);
+        // Earlier shares keep their place, revoked ones are removed
+        db.set_folder_shares("folder", &[("secondUser".to_string(), AllowanceLevel::Forbidden), ("firstUser".to_string(), AllowanceLevel::ReadWrite)]).await.unwrap();
+        assert_eq!(shares(db.get_folder("folder").await.unwrap()), vec![("firstUser".to_string(), AllowanceLevel::ReadWrite)]);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
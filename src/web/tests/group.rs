@@ -0,0 +1,204 @@
+use std::env;
+use std::sync::Arc;
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use chrono::Utc;
+use serde_json::json;
+use crate::db_access::{AllowanceLevel, DBError, Group, GroupStore, Note, Storage, User};
+use crate::db_access::memory::MemoryStorage;
+use crate::db_access::sqlite::SqliteStorage;
+use crate::web::tests::{assert_error, call, connect, create_note, init_app, share_note, signup_and_login};
+
+#[actix_rt::test]
+async fn group_membership() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    let stranger = signup_and_login(&app, "strangerUser").await;
+    connect(&app, &cookie, &other).await;
+
+    for invalid in ["", "  ", &"x".repeat(65)] {
+        assert_error(call(&app, TestRequest::post().uri("/api/groups").cookie(cookie.clone())
+            .set_json(json!({"name": invalid}))).await, StatusCode::BAD_REQUEST, 20);
+    }
+    let (status, body) = call(&app, TestRequest::post().uri("/api/groups").cookie(cookie.clone())
+        .set_json(json!({"name": "Team"}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["content"]["members"], json!(["testUser"]));
+    let group_uri = format!("/api/groups/{}", body["content"]["group_id"].as_str().unwrap());
+
+    // Only connections can be added, and only once
+    let add_member = |user_id: &str| TestRequest::post().uri(&format!("{}/members", group_uri)).cookie(cookie.clone())
+        .set_json(json!({"user_id": user_id}));
+    assert_error(call(&app, add_member("strangerUser")).await, StatusCode::OK, 24);
+    let (status, _) = call(&app, add_member("otherUser")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&app, add_member("otherUser")).await, StatusCode::OK, 24);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/groups").cookie(other.clone())).await;
+    assert_eq!(body["content"][0]["members"], json!(["testUser", "otherUser"]));
+
+    // Outsiders can't see the group, members can't manage it
+    assert_error(call(&app, TestRequest::get().uri(&group_uri).cookie(stranger)).await, StatusCode::NOT_FOUND, 22);
+    assert_error(call(&app, TestRequest::post().uri(&format!("{}/members", group_uri)).cookie(other.clone())
+        .set_json(json!({"user_id": "testUser"}))).await, StatusCode::FORBIDDEN, 12);
+    assert_error(call(&app, TestRequest::delete().uri(&format!("{}/members/testUser", group_uri)).cookie(other.clone())).await,
+                 StatusCode::FORBIDDEN, 12);
+    assert_error(call(&app, TestRequest::delete().uri(&group_uri).cookie(other.clone())).await, StatusCode::FORBIDDEN, 12);
+    assert_error(call(&app, TestRequest::delete().uri(&format!("{}/members/testUser", group_uri)).cookie(cookie.clone())).await,
+                 StatusCode::OK, 24);
+
+    // Members may leave on their own
+    let (status, _) = call(&app, TestRequest::delete().uri(&format!("{}/members/otherUser", group_uri)).cookie(other.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&app, TestRequest::get().uri(&group_uri).cookie(other)).await, StatusCode::NOT_FOUND, 22);
+
+    let (status, _) = call(&app, TestRequest::delete().uri(&group_uri).cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&app, TestRequest::get().uri(&group_uri).cookie(cookie)).await, StatusCode::NOT_FOUND, 22);
+    assert!(db.get_groups("testUser").await.unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn group_allowances() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let (_, body) = call(&app, TestRequest::post().uri("/api/groups").cookie(cookie.clone())
+        .set_json(json!({"name": "Team"}))).await;
+    let group_id = body["content"]["group_id"].as_str().unwrap().to_string();
+    call(&app, TestRequest::post().uri(&format!("/api/groups/{}/members", group_id)).cookie(cookie.clone())
+        .set_json(json!({"user_id": "otherUser"}))).await;
+    let share_uri = format!("/api/share/{}/groups", note_id);
+
+    // Invalid batches change nothing
+    for invalid in [json!([{"group_id": group_id, "allowance": "Owner"}]),
+                    json!([{"group_id": group_id, "allowance": "Read"}, {"group_id": group_id, "allowance": "ReadWrite"}])] {
+        assert_error(call(&app, TestRequest::put().uri(&share_uri).cookie(cookie.clone()).set_json(invalid)).await,
+                     StatusCode::BAD_REQUEST, 20);
+    }
+    assert_error(call(&app, TestRequest::put().uri(&share_uri).cookie(cookie.clone())
+        .set_json(json!([{"group_id": group_id, "allowance": "Read"}, {"group_id": "noGroup", "allowance": "Read"}]))).await,
+                 StatusCode::NOT_FOUND, 22);
+    assert_error(call(&app, TestRequest::get().uri(&format!("/api/note/{}", note_id)).cookie(other.clone())).await,
+                 StatusCode::FORBIDDEN, 12);
+
+    // Members gain access through the group
+    let (status, _) = call(&app, TestRequest::put().uri(&share_uri).cookie(cookie.clone())
+        .set_json(json!([{"group_id": group_id, "allowance": "Read"}]))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = call(&app, TestRequest::get().uri(&format!("/api/note/{}", note_id)).cookie(other.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(other.clone())).await;
+    assert_eq!(body["content"]["notes"][0]["note_id"], note_id.as_str());
+    assert_eq!(body["content"]["notes"][0]["allowance"], "Read");
+    assert_error(call(&app, TestRequest::put().uri(&share_uri).cookie(other.clone())
+        .set_json(json!([{"group_id": group_id, "allowance": "ReadWrite"}]))).await, StatusCode::FORBIDDEN, 12);
+
+    // The higher of the direct and the group allowance applies
+    share_note(&app, &cookie, &note_id, "otherUser", "ReadWrite").await;
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(other.clone())).await;
+    assert_eq!(body["content"]["notes"].as_array().unwrap().len(), 1);
+    assert_eq!(body["content"]["notes"][0]["allowance"], "ReadWrite");
+    share_note(&app, &cookie, &note_id, "otherUser", "Forbidden").await;
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(other.clone())).await;
+    assert_eq!(body["content"]["notes"][0]["allowance"], "Read");
+
+    // Revoking the group allowance, or removing the note, revokes the access
+    let (status, _) = call(&app, TestRequest::put().uri(&share_uri).cookie(cookie.clone())
+        .set_json(json!([{"group_id": group_id, "allowance": "Forbidden"}]))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&app, TestRequest::get().uri(&format!("/api/note/{}", note_id)).cookie(other.clone())).await,
+                 StatusCode::FORBIDDEN, 12);
+    call(&app, TestRequest::put().uri(&share_uri).cookie(cookie.clone())
+        .set_json(json!([{"group_id": group_id, "allowance": "ReadWrite"}]))).await;
+    let (status, _) = call(&app, TestRequest::delete().uri(&format!("/api/note/{}", note_id)).cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(db.get_group(&group_id).await.unwrap().allowances.is_empty());
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(other.clone())).await;
+    assert!(body["content"]["notes"].as_array().unwrap().is_empty());
+
+    // Only the owner of the group shares with it, as the members need not be connected among each other
+    let other_note = create_note(&app, &other, "Other-Note").await;
+    assert_error(call(&app, TestRequest::put().uri(&format!("/api/share/{}/groups", other_note)).cookie(other)
+        .set_json(json!([{"group_id": group_id, "allowance": "Read"}]))).await, StatusCode::FORBIDDEN, 12);
+    assert!(db.get_group(&group_id).await.unwrap().allowances.iter().all(|allowance| allowance.note_id.ne(&other_note)));
+}
+
+#[actix_rt::test]
+async fn severed_connections() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let (_, body) = call(&app, TestRequest::post().uri("/api/groups").cookie(cookie.clone())
+        .set_json(json!({"name": "Team"}))).await;
+    let group_id = body["content"]["group_id"].as_str().unwrap().to_string();
+    call(&app, TestRequest::post().uri(&format!("/api/groups/{}/members", group_id)).cookie(cookie.clone())
+        .set_json(json!({"user_id": "otherUser"}))).await;
+
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let other_note = create_note(&app, &other, "Other-Note").await;
+    for note_id in [&note_id, &other_note] {
+        db.set_group_note_allowances(note_id, &[(group_id.clone(), AllowanceLevel::Read)]).await.unwrap();
+    }
+
+    // Removing a connection removes the other user from the group along with the notes they shared with it
+    let (status, _) = call(&app, TestRequest::delete().uri("/api/share/testUser").cookie(other.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let group = db.get_group(&group_id).await.unwrap();
+    assert_eq!(group.members, vec!["testUser".to_string()]);
+    assert_eq!(group.allowances.iter().map(|allowance| &allowance.note_id).collect::<Vec<_>>(), vec![&note_id]);
+    assert_error(call(&app, TestRequest::get().uri(&format!("/api/note/{}", other_note)).cookie(cookie.clone())).await,
+                 StatusCode::FORBIDDEN, 12);
+
+    // So does leaving the group
+    connect(&app, &cookie, &other).await;
+    call(&app, TestRequest::post().uri(&format!("/api/groups/{}/members", group_id)).cookie(cookie.clone())
+        .set_json(json!({"user_id": "otherUser"}))).await;
+    db.set_group_note_allowances(&other_note, &[(group_id.clone(), AllowanceLevel::Read)]).await.unwrap();
+    let (status, _) = call(&app, TestRequest::delete().uri(&format!("/api/groups/{}/members/otherUser", group_id)).cookie(other)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(db.get_group(&group_id).await.unwrap().allowances.len(), 1);
+
+    // Removing the owner removes the group
+    let (status, _) = call(&app, TestRequest::delete().uri("/api/user").cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(db.get_group(&group_id).await.is_err());
+}
+
+#[actix_rt::test]
+async fn batch_allowances() {
+    let path = env::temp_dir().join(format!("writeup-group-allowances-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let backends: [Box<dyn Storage>; 2] = [Box::new(MemoryStorage::new()), Box::new(SqliteStorage::open(path.to_str().unwrap()).unwrap())];
+    for db in backends {
+        db.insert_user(&User { _id: "testUser".to_string(), allowances: Vec::new(), connections: Vec::new(), roles: Vec::new() }).await.unwrap();
+        let note = Note { title: "Test-Note".to_string(), content: String::new(), owner_id: "testUser".to_string(), tags: Vec::new(),
+            version: 0, created_at: Utc::now(), updated_at: Utc::now(), last_editor_id: "testUser".to_string() };
+        let note_id = db.insert_note(&note).await.unwrap();
+        for group_id in ["firstGroup", "secondGroup"] {
+            db.insert_group(&Group { _id: group_id.to_string(), name: group_id.to_string(), owner_id: "testUser".to_string(),
+                members: vec!["testUser".to_string()], allowances: Vec::new(), created_at: Utc::now() }).await.unwrap();
+        }
+        let level = |group: Group| group.allowances.into_iter().find(|allow| allow.note_id.eq(&note_id)).map(|allow| allow.level);
+
+        db.set_group_note_allowances(&note_id, &[("firstGroup".to_string(), AllowanceLevel::Read), ("secondGroup".to_string(), AllowanceLevel::ReadWrite)]).await.unwrap();
+        assert_eq!(level(db.get_group("firstGroup").await.unwrap()), Some(AllowanceLevel::Read));
+        assert_eq!(level(db.get_group("secondGroup").await.unwrap()), Some(AllowanceLevel::ReadWrite));
+
+        // Nothing is changed if a single group is unknown
+        assert!(matches!(db.set_group_note_allowances(&note_id, &[("firstGroup".to_string(), AllowanceLevel::Forbidden),
+                                                                     ("unknownGroup".to_string(), AllowanceLevel::Read)]).await,
+                         Err(DBError::NoDocumentFoundError)));
+        assert_eq!(level(db.get_group("firstGroup").await.unwrap()), Some(AllowanceLevel::Read));
+        db.set_group_note_allowances(&note_id, &[("firstGroup".to_string(), AllowanceLevel::Forbidden), ("secondGroup".to_string(), AllowanceLevel::Read)]).await.unwrap();
+        assert_eq!(level(db.get_group("firstGroup").await.unwrap()), None);
+        assert_eq!(level(db.get_group("secondGroup").await.unwrap()), Some(AllowanceLevel::Read));
+    }
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+use actix_web::cookie::Cookie;
+use actix_web::http::StatusCode;
+use actix_web::http::header::CACHE_CONTROL;
+use actix_web::test::{call_service, TestRequest};
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{Algorithm, decode, decode_header, DecodingKey, encode, EncodingKey, Header, Validation};
+use ring::rand::SystemRandom;
+use ring::signature::Ed25519KeyPair;
+use serde_json::{json, Value};
+use crate::db_access::memory::MemoryStorage;
+use crate::web::keys::{KeySet, SigningKey};
+use crate::web::tests::{AppConfig, assert_error, call, init_app, init_app_with, JWT_COOKIE, login, signup_and_login};
+use crate::web::tests::oidc::{SIGNING_KEY, SIGNING_KEY_MODULUS};
+
+/// Generates a new Ed25519-key, encoded as a PEM-file
+fn gen_ed_pem() -> String {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+    pem::encode(&pem::Pem { tag: "PRIVATE KEY".to_string(), contents: pkcs8.as_ref().to_vec() })
+}
+
+#[actix_rt::test]
+async fn ed25519_keys() {
+    let db = Arc::new(MemoryStorage::new());
+    let (old_pem, new_pem) = (gen_ed_pem(), gen_ed_pem());
+    let old_app = init_app_with(db.clone(), AppConfig { keys: Some(KeySet::new(SigningKey::from_pem(old_pem.as_bytes()).unwrap(), Vec::new())), ..AppConfig::default() }).await;
+    let old_session = signup_and_login(&old_app, "testUser").await;
+    let secret_session = login(&init_app(db.clone()).await, "testUser").await;
+
+    // New JWTs are signed by the first key, naming it
+    let new_key = SigningKey::from_pem(new_pem.as_bytes()).unwrap();
+    let kid = new_key.kid().unwrap().to_string();
+    let app = init_app_with(db.clone(), AppConfig { keys: Some(KeySet::new(new_key,
+        vec![SigningKey::from_pem(old_pem.as_bytes()).unwrap(), SigningKey::from_secret("testJwtSecret")])), ..AppConfig::default() }).await;
+    let session = login(&app, "testUser").await;
+    let header = decode_header(session.value()).unwrap();
+    assert_eq!(header.alg, Algorithm::EdDSA);
+    assert_eq!(header.kid.as_ref(), Some(&kid));
+
+    // JWTs signed by previous keys or the secret are still accepted
+    for cookie in [session.clone(), old_session.clone(), secret_session] {
+        let (status, _) = call(&app, TestRequest::get().uri("/api/user").cookie(cookie)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    // Only the public keys are published, the current one first
+    let resp = call_service(&app, TestRequest::get().uri("/api/.well-known/jwks.json").to_request()).await;
+    assert!(resp.headers().get(CACHE_CONTROL).unwrap().to_str().unwrap().contains("max-age=300"));
+    let (status, body) = call(&app, TestRequest::get().uri("/api/.well-known/jwks.json")).await;
+    assert_eq!(status, StatusCode::OK);
+    let keys = body["keys"].as_array().unwrap();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys[0]["kid"], json!(kid));
+    assert_eq!((&keys[0]["kty"], &keys[0]["crv"], &keys[0]["alg"], &keys[0]["use"]), (&json!("OKP"), &json!("Ed25519"), &json!("EdDSA"), &json!("sig")));
+    assert!(keys.iter().all(|key| key.get("d").is_none()));
+
+    // Other services can verify JWTs using the published keys
+    let public_key = BASE64URL_NOPAD.decode(keys[0]["x"].as_str().unwrap().as_bytes()).unwrap();
+    let claims = decode::<Value>(session.value(), &DecodingKey::from_ed_der(&public_key), &Validation::new(Algorithm::EdDSA)).unwrap().claims;
+    assert_eq!(claims["sub"], json!("testUser"));
+
+    // The algorithm is dictated by the key, not by the JWT
+    let mut forged_header = Header::new(Algorithm::HS512);
+    forged_header.kid = Some(kid);
+    let forged = encode(&forged_header, &claims, &EncodingKey::from_secret(&public_key)).unwrap();
+    assert_error(call(&app, TestRequest::get().uri("/api/user").cookie(Cookie::new(JWT_COOKIE, forged))).await, StatusCode::UNAUTHORIZED, 10);
+
+    // Removed keys are no longer accepted
+    let rotated = init_app_with(db.clone(), AppConfig { keys: Some(KeySet::new(SigningKey::from_pem(new_pem.as_bytes()).unwrap(), Vec::new())), ..AppConfig::default() }).await;
+    let (status, _) = call(&rotated, TestRequest::get().uri("/api/user").cookie(session)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&rotated, TestRequest::get().uri("/api/user").cookie(old_session)).await, StatusCode::UNAUTHORIZED, 10);
+}
+
+#[actix_rt::test]
+async fn rsa_keys() {
+    let key = SigningKey::from_pem(SIGNING_KEY.as_bytes()).unwrap();
+    let kid = key.kid().unwrap().to_string();
+    let app = init_app_with(Arc::new(MemoryStorage::new()), AppConfig { keys: Some(KeySet::new(key, Vec::new())), ..AppConfig::default() }).await;
+    let session = signup_and_login(&app, "testUser").await;
+    let header = decode_header(session.value()).unwrap();
+    assert_eq!((header.alg, header.kid.as_ref()), (Algorithm::RS256, Some(&kid)));
+
+    let (_, body) = call(&app, TestRequest::get().uri("/api/.well-known/jwks.json")).await;
+    assert_eq!(body["keys"], json!([{"kty": "RSA", "use": "sig", "alg": "RS256", "kid": kid, "n": SIGNING_KEY_MODULUS, "e": "AQAB"}]));
+    let key = DecodingKey::from_rsa_components(SIGNING_KEY_MODULUS, "AQAB").unwrap();
+    let claims = decode::<Value>(session.value(), &key, &Validation::new(Algorithm::RS256)).unwrap().claims;
+    assert_eq!(claims["sub"], json!("testUser"));
+}
+
+#[actix_rt::test]
+async fn shared_secret() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let session = signup_and_login(&app, "testUser").await;
+    let header = decode_header(session.value()).unwrap();
+    assert_eq!((header.alg, header.kid), (Algorithm::HS512, None));
+
+    // The secret is never published
+    let (status, body) = call(&app, TestRequest::get().uri("/api/.well-known/jwks.json")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"keys": []}));
+
+    // Only private keys are accepted
+    assert!(SigningKey::from_pem(b"no key").is_none());
+    let public_key = "-----BEGIN PUBLIC KEY-----\nMCowBQYDK2VwAyEAGb9ECWmEzf6FQbrBZ9w7lshQhqowtrbLDFw4rXAxZuE=\n-----END PUBLIC KEY-----\n";
+    assert!(SigningKey::from_pem(public_key.as_bytes()).is_none());
+}
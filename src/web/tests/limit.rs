@@ -0,0 +1,166 @@
+use std::sync::Arc;
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, read_body_json, TestRequest};
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use crate::db_access::memory::MemoryStorage;
+use crate::web::{RateLimitConfig, RateLimiter};
+use crate::web::tests::{AppConfig, assert_error, BETA_KEY, call, init_app_with, PASSWORD, signup, signup_and_login};
+use crate::web::totp::gen_totp;
+
+/// Limits small enough to be reached within a test
+const LIMITS: RateLimitConfig = RateLimitConfig {
+    requests_per_window: 3,
+    window_seconds: 60,
+    lockout_threshold: 3,
+    lockout_seconds: 10,
+    max_lockout_seconds: 40,
+    ip_header: None
+};
+
+#[test]
+fn request_window() {
+    let limiter = RateLimiter::new(LIMITS);
+    let now = Utc::now();
+    for _ in 0..3 {
+        assert!(limiter.hit_ip("127.0.0.1", now).is_ok());
+    }
+    assert_eq!(limiter.hit_ip("127.0.0.1", now + Duration::seconds(15)), Err(45));
+    assert!(limiter.hit_ip("127.0.0.2", now).is_ok());
+    assert!(limiter.hit_ip("127.0.0.1", now + Duration::seconds(60)).is_ok());
+}
+
+#[test]
+fn account_lockout() {
+    let limiter = RateLimiter::new(LIMITS);
+    let now = Utc::now();
+    limiter.record_failure("testUser", now);
+    limiter.record_failure("testUser", now);
+    assert!(limiter.check_account("testUser", now).is_ok());
+
+    // The lockout doubles with each further failure, up to its upper bound
+    limiter.record_failure("testUser", now);
+    assert_eq!(limiter.check_account("testUser", now), Err(10));
+    assert_eq!(limiter.check_account("testUser", now + Duration::milliseconds(9500)), Err(1));
+    assert!(limiter.check_account("testUser", now + Duration::seconds(10)).is_ok());
+    assert!(limiter.check_account("otherUser", now).is_ok());
+    for expected in [20, 40, 40] {
+        limiter.record_failure("testUser", now);
+        assert_eq!(limiter.check_account("testUser", now), Err(expected));
+    }
+
+    // Failures are forgotten after a successful login or once they are old enough
+    limiter.record_success("testUser");
+    assert!(limiter.check_account("testUser", now).is_ok());
+    for _ in 0..2 {
+        limiter.record_failure("testUser", now);
+    }
+    limiter.record_failure("testUser", now + Duration::seconds(40));
+    assert!(limiter.check_account("testUser", now + Duration::seconds(40)).is_ok());
+}
+
+#[test]
+fn huge_limits() {
+    // Durations far beyond any point in time must not overflow while the limiter is locked
+    let limiter = RateLimiter::new(RateLimitConfig { requests_per_window: 1, window_seconds: i64::MAX, lockout_threshold: 1, lockout_seconds: i64::MAX, max_lockout_seconds: i64::MAX, ip_header: None });
+    let now = Utc::now();
+    assert!(limiter.hit_ip("127.0.0.1", now).is_ok());
+    assert!(limiter.hit_ip("127.0.0.1", now).is_err());
+    limiter.record_failure("testUser", now);
+    limiter.record_failure("testUser", now);
+    assert!(limiter.check_account("testUser", now).is_err());
+}
+
+#[test]
+fn forwarded_ip() {
+    let req = TestRequest::default().peer_addr("10.0.0.1:4000".parse().unwrap())
+        .insert_header(("X-Forwarded-For", "127.0.0.2, 127.0.0.3")).to_http_request();
+    assert_eq!(RateLimiter::new(LIMITS).client_ip(&req), "10.0.0.1");
+    let limiter = RateLimiter::new(RateLimitConfig { ip_header: Some("X-Forwarded-For".to_string()), ..LIMITS });
+    assert_eq!(limiter.client_ip(&req), "127.0.0.3");
+    let direct = TestRequest::default().peer_addr("10.0.0.1:4000".parse().unwrap()).to_http_request();
+    assert_eq!(limiter.client_ip(&direct), "10.0.0.1");
+}
+
+#[actix_rt::test]
+async fn login_lockout() {
+    let app = init_app_with(Arc::new(MemoryStorage::new()), AppConfig { limits: RateLimitConfig { requests_per_window: u32::MAX, ..LIMITS }, ..AppConfig::default() }).await;
+    signup(&app, "testUser").await;
+    signup(&app, "otherUser").await;
+    for _ in 0..3 {
+        assert_error(call(&app, TestRequest::post().uri("/api/auth")
+            .set_json(json!({"username": "testUser", "password": "wrongPass", "session_only": false}))).await, StatusCode::OK, 11);
+    }
+
+    // Even the correct password is rejected while the account is locked
+    let resp = call_service(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "testUser", "password": PASSWORD, "session_only": false})).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: i64 = resp.headers().get(RETRY_AFTER).unwrap().to_str().unwrap().parse().unwrap();
+    assert!((1..=10).contains(&retry_after));
+    let body: Value = read_body_json(resp).await;
+    assert_eq!(body["code"], 13);
+
+    // Unknown users get locked the same way, other accounts are unaffected
+    for _ in 0..3 {
+        call(&app, TestRequest::post().uri("/api/auth")
+            .set_json(json!({"username": "unknownUser", "password": "wrongPass", "session_only": false}))).await;
+    }
+    assert_error(call(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "unknownUser", "password": "wrongPass", "session_only": false}))).await, StatusCode::TOO_MANY_REQUESTS, 13);
+    let (_, body) = call(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "otherUser", "password": PASSWORD, "session_only": false}))).await;
+    assert_eq!(body["success"], true);
+}
+
+#[actix_rt::test]
+async fn request_limit() {
+    let app = init_app_with(Arc::new(MemoryStorage::new()), AppConfig { limits: LIMITS, ..AppConfig::default() }).await;
+    for username in ["firstUser", "secondUser", "thirdUser"] {
+        let (status, _) = signup(&app, username).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+    let resp = call_service(&app, TestRequest::post().uri("/api/user")
+        .set_json(json!({"username": "fourthUser", "password": PASSWORD, "beta_key": BETA_KEY})).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(resp.headers().contains_key(RETRY_AFTER));
+    // Logins share the limit, other endpoints are not limited
+    assert_error(call(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "firstUser", "password": PASSWORD, "session_only": false}))).await, StatusCode::TOO_MANY_REQUESTS, 13);
+    let (status, _) = call(&app, TestRequest::get().uri("/api/system")).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn second_factor_lockout() {
+    let app = init_app_with(Arc::new(MemoryStorage::new()), AppConfig { limits: RateLimitConfig { requests_per_window: u32::MAX, ..LIMITS }, ..AppConfig::default() }).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let (_, body) = call(&app, TestRequest::post().uri("/api/user/totp").cookie(cookie.clone())).await;
+    let secret = body["content"]["secret"].as_str().unwrap().to_string();
+    for _ in 0..3 {
+        assert_error(call(&app, TestRequest::put().uri("/api/user/totp").cookie(cookie.clone())
+            .set_json(json!({"code": "123456"}))).await, StatusCode::OK, 11);
+    }
+
+    // Even the correct one-time password is rejected while the account is locked
+    let code = gen_totp(&secret, Utc::now()).unwrap();
+    assert_error(call(&app, TestRequest::put().uri("/api/user/totp").cookie(cookie.clone())
+        .set_json(json!({"code": code}))).await, StatusCode::TOO_MANY_REQUESTS, 13);
+    assert_error(call(&app, TestRequest::delete().uri("/api/user/totp").cookie(cookie)
+        .set_json(json!({"code": code}))).await, StatusCode::TOO_MANY_REQUESTS, 13);
+}
+
+#[actix_rt::test]
+async fn password_lockout() {
+    let app = init_app_with(Arc::new(MemoryStorage::new()), AppConfig { limits: RateLimitConfig { requests_per_window: u32::MAX, ..LIMITS }, ..AppConfig::default() }).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    for _ in 0..3 {
+        assert_error(call(&app, TestRequest::put().uri("/api/user/password").cookie(cookie.clone())
+            .set_json(json!({"old_password": "wrongPass", "new_password": "newPass"}))).await, StatusCode::OK, 11);
+    }
+
+    // A session alone does not allow guessing the password any faster
+    assert_error(call(&app, TestRequest::put().uri("/api/user/password").cookie(cookie)
+        .set_json(json!({"old_password": PASSWORD, "new_password": "newPass"}))).await, StatusCode::TOO_MANY_REQUESTS, 13);
+}
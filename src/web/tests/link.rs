@@ -0,0 +1,123 @@
+use std::sync::Arc;
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use chrono::{Duration, Utc};
+use serde_json::json;
+use crate::db_access::{LinkStore, ShareLink};
+use crate::db_access::memory::MemoryStorage;
+use crate::web::auth::hash_token;
+use crate::web::link::SHARE_PASSWORD_HEADER;
+use crate::web::tests::{assert_error, call, connect, create_note, init_app, share_note, signup_and_login};
+
+#[actix_rt::test]
+async fn link_lifecycle() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+
+    let (status, body) = call(&app, TestRequest::post().uri(&format!("/api/note/{}/links", note_id)).cookie(cookie.clone())
+        .set_json(json!({}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let token = body["content"]["token"].as_str().unwrap().to_string();
+    let link_id = body["content"]["link_id"].as_str().unwrap().to_string();
+    assert!(token.starts_with(&format!("{}.", link_id)));
+    assert_eq!(body["content"]["protected"], false);
+
+    // Anyone knowing the link can read the note, each time counting as a view
+    for _ in 0..2 {
+        let (status, body) = call(&app, TestRequest::get().uri(&format!("/api/public/{}", token))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["content"]["title"], "Test-Note");
+        assert!(body["content"].get("owner_id").is_none());
+    }
+    assert_error(call(&app, TestRequest::get().uri(&format!("/api/public/{}x", token))).await, StatusCode::NOT_FOUND, 22);
+    assert_error(call(&app, TestRequest::get().uri("/api/public/noLink")).await, StatusCode::NOT_FOUND, 22);
+
+    // Listing never reveals the link itself
+    let (_, body) = call(&app, TestRequest::get().uri(&format!("/api/note/{}/links", note_id)).cookie(cookie.clone())).await;
+    let links = body["content"].as_array().unwrap();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0]["link_id"], link_id.as_str());
+    assert_eq!(links[0]["views"], 2);
+    assert!(links[0].get("token").is_none());
+
+    // Revoked links stop working
+    let (status, _) = call(&app, TestRequest::delete().uri(&format!("/api/note/{}/links/{}", note_id, link_id)).cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&app, TestRequest::get().uri(&format!("/api/public/{}", token))).await, StatusCode::NOT_FOUND, 22);
+    assert_error(call(&app, TestRequest::delete().uri(&format!("/api/note/{}/links/{}", note_id, link_id)).cookie(cookie.clone())).await,
+                 StatusCode::NOT_FOUND, 22);
+
+    // Removing the note removes its links
+    let (_, body) = call(&app, TestRequest::post().uri(&format!("/api/note/{}/links", note_id)).cookie(cookie.clone())
+        .set_json(json!({}))).await;
+    let token = body["content"]["token"].as_str().unwrap().to_string();
+    let (status, _) = call(&app, TestRequest::delete().uri(&format!("/api/note/{}", note_id)).cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&app, TestRequest::get().uri(&format!("/api/public/{}", token))).await, StatusCode::NOT_FOUND, 22);
+    assert!(db.get_links(&note_id).await.unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn restricted_links() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+
+    for invalid in [json!({"password": ""}), json!({"max_views": 0}), json!({"expires_in_days": 0}), json!({"expires_in_days": u32::MAX})] {
+        assert_error(call(&app, TestRequest::post().uri(&format!("/api/note/{}/links", note_id)).cookie(cookie.clone())
+            .set_json(invalid)).await, StatusCode::BAD_REQUEST, 20);
+    }
+
+    // Protected links require their password
+    let (_, body) = call(&app, TestRequest::post().uri(&format!("/api/note/{}/links", note_id)).cookie(cookie.clone())
+        .set_json(json!({"password": "linkPass", "max_views": 1, "expires_in_days": 7}))).await;
+    let token = body["content"]["token"].as_str().unwrap().to_string();
+    assert_eq!(body["content"]["protected"], true);
+    assert!(body["content"]["expires_at"].is_string());
+    let uri = format!("/api/public/{}", token);
+    assert_error(call(&app, TestRequest::get().uri(&uri)).await, StatusCode::OK, 11);
+    assert_error(call(&app, TestRequest::get().uri(&uri).insert_header((SHARE_PASSWORD_HEADER, "wrongPass"))).await, StatusCode::OK, 11);
+    let (status, body) = call(&app, TestRequest::get().uri(&uri).insert_header((SHARE_PASSWORD_HEADER, "linkPass"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["title"], "Test-Note");
+
+    // Exhausted links stop working
+    assert_error(call(&app, TestRequest::get().uri(&uri).insert_header((SHARE_PASSWORD_HEADER, "linkPass"))).await, StatusCode::NOT_FOUND, 22);
+
+    // Expired links stop working
+    let mut expired = ShareLink::new("expiredLink".to_string(), note_id, "testUser".to_string(), hash_token("secret"), None);
+    expired.expires_at = Some(Utc::now() - Duration::hours(1));
+    db.insert_link(&expired).await.unwrap();
+    assert_error(call(&app, TestRequest::get().uri("/api/public/expiredLink.secret")).await, StatusCode::NOT_FOUND, 22);
+}
+
+#[actix_rt::test]
+async fn owner_only() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    connect(&app, &cookie, &other).await;
+    share_note(&app, &cookie, &note_id, "otherUser", "ReadWrite").await;
+
+    let (_, body) = call(&app, TestRequest::post().uri(&format!("/api/note/{}/links", note_id)).cookie(cookie.clone())
+        .set_json(json!({}))).await;
+    let link_id = body["content"]["link_id"].as_str().unwrap().to_string();
+
+    // Users the note is shared with can neither publish it nor manage its links
+    assert_error(call(&app, TestRequest::post().uri(&format!("/api/note/{}/links", note_id)).cookie(other.clone())
+        .set_json(json!({}))).await, StatusCode::FORBIDDEN, 12);
+    assert_error(call(&app, TestRequest::get().uri(&format!("/api/note/{}/links", note_id)).cookie(other.clone())).await,
+                 StatusCode::FORBIDDEN, 12);
+    assert_error(call(&app, TestRequest::delete().uri(&format!("/api/note/{}/links/{}", note_id, link_id)).cookie(other)).await,
+                 StatusCode::FORBIDDEN, 12);
+    assert_error(call(&app, TestRequest::get().uri(&format!("/api/note/{}/links", note_id))).await, StatusCode::UNAUTHORIZED, 10);
+
+    // Links can only be revoked through the note they lead to
+    let other_note = create_note(&app, &cookie, "Other-Note").await;
+    assert_error(call(&app, TestRequest::delete().uri(&format!("/api/note/{}/links/{}", other_note, link_id)).cookie(cookie)).await,
+                 StatusCode::NOT_FOUND, 22);
+}
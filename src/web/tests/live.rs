@@ -0,0 +1,274 @@
+use std::sync::Arc;
+use actix_test::TestServer;
+use actix_web::App;
+use actix_web::cookie::Cookie;
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use actix_web::web::{Data, QueryConfig, scope};
+use awc::ws::{Frame, Message};
+use chrono::Utc;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use crate::db_access::{Allowance, AllowanceLevel, Credential, CredentialStore, Note, NoteStore, RevisionStore, Storage, User, UserStore};
+use crate::db_access::memory::MemoryStorage;
+use crate::secret::AppSecrets;
+use crate::web::{handler_config, KeySet, LiveHub, query_error_handler, RateLimitConfig, RateLimiter};
+use crate::web::live::{LiveDocument, LiveError, MAX_HISTORY_LENGTH, Operation};
+use crate::web::tests::{assert_error, call, create_note, init_app, init_env, PASSWORD, signup_and_login};
+
+/// Creates an insert-operation
+fn insert(pos: usize, text: &str) -> Operation {
+    Operation::Insert { pos, text: text.to_string() }
+}
+
+/// Creates a delete-operation
+fn delete(pos: usize, len: usize) -> Operation {
+    Operation::Delete { pos, len }
+}
+
+#[test]
+fn concurrent_inserts() {
+    let mut doc = LiveDocument::new("Hello".to_string(), 0);
+    assert_eq!(doc.apply(0, insert(5, " World")), Ok(vec![insert(5, " World")]));
+    // Inserts at the same position are ordered by arrival
+    assert_eq!(doc.apply(0, insert(5, "!")), Ok(vec![insert(11, "!")]));
+    // Inserts in front of concurrent ones stay in place
+    assert_eq!(doc.apply(1, insert(0, ">")), Ok(vec![insert(0, ">")]));
+    assert_eq!(doc.content(), ">Hello World!");
+    assert_eq!(doc.rev(), 3);
+}
+
+#[test]
+fn concurrent_deletes() {
+    let mut doc = LiveDocument::new("abcdefgh".to_string(), 0);
+    doc.apply(0, delete(2, 3)).unwrap(); // ab|fgh
+    // Overlapping deletes do not remove more than intended
+    assert_eq!(doc.apply(0, delete(1, 3)), Ok(vec![delete(1, 1)]));
+    // Deleting what is already gone is a no-op
+    assert_eq!(doc.apply(0, delete(3, 1)), Ok(vec![]));
+    assert_eq!(doc.content(), "afgh");
+    assert_eq!(doc.rev(), 2);
+}
+
+#[test]
+fn delete_around_concurrent_insert() {
+    let mut doc = LiveDocument::new("Hello World".to_string(), 0);
+    doc.apply(0, insert(6, "dear ")).unwrap();
+    // The concurrently inserted text survives the deletion surrounding it
+    assert_eq!(doc.apply(0, delete(2, 7)), Ok(vec![delete(2, 4), delete(7, 3)]));
+    assert_eq!(doc.content(), "Hedear ld");
+    // Inserting into deleted text keeps the insert at the gap
+    assert_eq!(doc.apply(1, insert(4, "X")), Ok(vec![insert(2, "X")]));
+    assert_eq!(doc.content(), "HeXdear ld");
+}
+
+#[test]
+fn bounded_history() {
+    let mut doc = LiveDocument::new(String::new(), 0);
+    for _ in 0..MAX_HISTORY_LENGTH + 1 {
+        doc.apply(doc.rev(), insert(0, "a")).unwrap();
+    }
+    // Operations based on forgotten revisions require a resynchronization
+    assert_eq!(doc.apply(0, insert(0, "b")), Err(LiveError::UnknownRevision));
+    assert_eq!(doc.apply(1, insert(0, "b")), Ok(vec![insert(MAX_HISTORY_LENGTH, "b")]));
+    assert_eq!(doc.rev(), MAX_HISTORY_LENGTH as u64 + 2);
+}
+
+#[test]
+fn invalid_operations() {
+    let mut doc = LiveDocument::new("Grüße".to_string(), 3);
+    assert_eq!(doc.apply(2, insert(0, "a")), Err(LiveError::UnknownRevision));
+    assert_eq!(doc.apply(4, insert(0, "a")), Err(LiveError::UnknownRevision));
+    assert_eq!(doc.apply(3, insert(6, "a")), Err(LiveError::InvalidOperation));
+    assert_eq!(doc.apply(3, delete(4, 2)), Err(LiveError::InvalidOperation));
+    // Positions beyond what can be represented are rejected instead of overflowing
+    assert_eq!(doc.apply(3, delete(usize::MAX, 2)), Err(LiveError::InvalidOperation));
+    doc.apply(3, insert(0, "a")).unwrap();
+    assert_eq!(doc.apply(3, insert(usize::MAX, "b")), Err(LiveError::InvalidOperation));
+    assert_eq!(doc.apply(3, delete(1, usize::MAX)), Err(LiveError::InvalidOperation));
+    doc.apply(4, delete(0, 1)).unwrap();
+    // Positions count characters, not bytes
+    assert_eq!(doc.apply(3, delete(2, 2)), Ok(vec![delete(2, 2)]));
+    assert_eq!(doc.content(), "Gre");
+}
+
+#[actix_rt::test]
+async fn live_note_errors() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let uri = format!("/api/note/{}/live", note_id);
+
+    assert_error(call(&app, TestRequest::get().uri(&uri)).await, StatusCode::UNAUTHORIZED, 10);
+    assert_error(call(&app, TestRequest::get().uri(&uri).cookie(other)).await, StatusCode::FORBIDDEN, 12);
+    assert_error(call(&app, TestRequest::get().uri("/api/note/a$b/live").cookie(cookie.clone())).await,
+                 StatusCode::BAD_REQUEST, 21);
+    // Plain requests can not be upgraded
+    assert_error(call(&app, TestRequest::get().uri(&uri).cookie(cookie.clone())).await, StatusCode::OK, 24);
+
+    let resp = actix_web::test::call_service(&app, TestRequest::get().uri(&uri).cookie(cookie)
+        .insert_header(("upgrade", "websocket"))
+        .insert_header(("connection", "upgrade"))
+        .insert_header(("sec-websocket-version", "13"))
+        .insert_header(("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==")).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::SWITCHING_PROTOCOLS);
+}
+
+/// Starts a server containing all endpoints backed by the given storage
+///
+/// # Arguments
+///
+/// * `db` - The storage-backend to be used
+fn start_server(db: Arc<dyn Storage>) -> TestServer {
+    init_env();
+    let db = Data::from(db);
+    let hub = Data::new(LiveHub::default());
+    let limiter = Data::new(RateLimiter::new(RateLimitConfig::default()));
+    let keys = Data::new(KeySet::from_env());
+    let secrets = Data::new(AppSecrets::from_env());
+    actix_test::start(move || App::new()
+        .app_data(db.clone())
+        .app_data(hub.clone())
+        .app_data(limiter.clone())
+        .app_data(keys.clone())
+        .app_data(secrets.clone())
+        .app_data(QueryConfig::default().error_handler(query_error_handler))
+        .service(scope("/api").configure(handler_config)))
+}
+
+/// Creates a user with access to a note
+///
+/// # Arguments
+///
+/// * `db` - The storage-backend to add the user to
+/// * `username` - The name of the new user
+/// * `note_id` - The identifier of the note
+/// * `level` - The level of access to the note
+async fn add_user(db: &MemoryStorage, username: &str, note_id: &str, level: AllowanceLevel) {
+    db.insert_user(&User { _id: username.to_string(), allowances: Vec::new(), connections: Vec::new(), roles: Vec::new() }).await.unwrap();
+    db.insert_credential(&Credential::new(username.to_string(), PASSWORD)).await.unwrap();
+    db.add_allowance(username, &Allowance { note_id: note_id.to_string(), level }).await.unwrap();
+}
+
+/// The client-side of a WebSocket-connection
+type Connection = actix_codec::Framed<awc::BoxedSocket, awc::ws::Codec>;
+
+/// Logs a user in and connects them to the live-session of a note
+///
+/// # Arguments
+///
+/// * `srv` - The server to connect to
+/// * `username` - The name of the user
+/// * `note_id` - The identifier of the note
+async fn connect_live(srv: &TestServer, username: &str, note_id: &str) -> Connection {
+    let client = awc::Client::new();
+    let resp = client.post(srv.url("/api/auth"))
+        .send_json(&json!({"username": username, "password": PASSWORD, "session_only": true})).await.unwrap();
+    let cookie: Cookie<'static> = resp.cookies().unwrap().iter().find(|cookie| cookie.name().eq("writeup_jwt")).unwrap().clone();
+    let (_, connection) = client.ws(srv.url(&format!("/api/note/{}/live", note_id))).cookie(cookie).connect().await.unwrap();
+    connection
+}
+
+/// Sends a message over a WebSocket-connection
+async fn send(connection: &mut Connection, msg: Value) {
+    connection.send(Message::Text(msg.to_string().into())).await.unwrap();
+}
+
+/// Waits for the next message sent over a WebSocket-connection
+async fn receive(connection: &mut Connection) -> Value {
+    match connection.next().await {
+        Some(Ok(Frame::Text(text))) => serde_json::from_slice(&text).unwrap(),
+        frame => panic!("unexpected frame: {:?}", frame)
+    }
+}
+
+#[actix_rt::test]
+async fn live_editing() {
+    init_env();
+    let db = Arc::new(MemoryStorage::new());
+    let note = Note { title: "Test-Note".to_string(), content: "Hello".to_string(), owner_id: "owner".to_string(), tags: Vec::new(),
+        version: 0, created_at: Utc::now(), updated_at: Utc::now(), last_editor_id: "owner".to_string() };
+    let note_id = db.insert_note(&note).await.unwrap();
+    add_user(&db, "owner", &note_id, AllowanceLevel::Owner).await;
+    add_user(&db, "writer", &note_id, AllowanceLevel::ReadWrite).await;
+    add_user(&db, "reader", &note_id, AllowanceLevel::Read).await;
+    let srv = start_server(db.clone());
+
+    let mut owner = connect_live(&srv, "owner", &note_id).await;
+    assert_eq!(receive(&mut owner).await, json!({"type": "snapshot", "rev": 0, "title": "Test-Note",
+        "content": "Hello", "tags": [], "allowance": "Owner"}));
+    let mut reader = connect_live(&srv, "reader", &note_id).await;
+    assert_eq!(receive(&mut reader).await["allowance"], "Read");
+
+    // Operations are acknowledged and distributed
+    send(&mut owner, json!({"rev": 0, "op": {"type": "insert", "pos": 5, "text": " World"}})).await;
+    assert_eq!(receive(&mut owner).await, json!({"type": "ack", "rev": 1}));
+    assert_eq!(receive(&mut reader).await, json!({"type": "op", "rev": 1, "author_id": "owner",
+        "op": {"type": "insert", "pos": 5, "text": " World"}}));
+
+    // Read-only editors may not modify the note
+    send(&mut reader, json!({"rev": 1, "op": {"type": "delete", "pos": 0, "len": 5}})).await;
+    assert_eq!(receive(&mut reader).await, json!({"type": "error", "message": "no permission"}));
+
+    // Concurrent operations are merged
+    let mut writer = connect_live(&srv, "writer", &note_id).await;
+    assert_eq!(receive(&mut writer).await["content"], "Hello World");
+    send(&mut owner, json!({"rev": 1, "op": {"type": "insert", "pos": 0, "text": "A"}})).await;
+    assert_eq!(receive(&mut owner).await, json!({"type": "ack", "rev": 2}));
+    send(&mut writer, json!({"rev": 1, "op": {"type": "delete", "pos": 0, "len": 5}})).await;
+    assert_eq!(receive(&mut writer).await["rev"], 2);
+    assert_eq!(receive(&mut writer).await, json!({"type": "ack", "rev": 3}));
+    assert_eq!(receive(&mut owner).await, json!({"type": "op", "rev": 3, "author_id": "writer",
+        "op": {"type": "delete", "pos": 1, "len": 5}}));
+
+    // The merged content is persisted, keeping the state before the session as a revision
+    let note = db.get_note(&note_id).await.unwrap();
+    assert_eq!(note.content, "A World");
+    assert_eq!(note.version, 3);
+    assert_eq!(note.last_editor_id, "writer");
+    let revisions = db.get_revisions(&note_id).await.unwrap();
+    assert_eq!(revisions.len(), 1);
+    assert_eq!(revisions[0].content, "Hello");
+}
+
+#[actix_rt::test]
+async fn revoked_live_editing() {
+    init_env();
+    let db = Arc::new(MemoryStorage::new());
+    let note = Note { title: "Test-Note".to_string(), content: "Hello".to_string(), owner_id: "owner".to_string(), tags: Vec::new(),
+        version: 0, created_at: Utc::now(), updated_at: Utc::now(), last_editor_id: "owner".to_string() };
+    let note_id = db.insert_note(&note).await.unwrap();
+    add_user(&db, "owner", &note_id, AllowanceLevel::Owner).await;
+    add_user(&db, "writer", &note_id, AllowanceLevel::ReadWrite).await;
+    add_user(&db, "reader", &note_id, AllowanceLevel::Read).await;
+    let srv = start_server(db.clone());
+
+    let mut writer = connect_live(&srv, "writer", &note_id).await;
+    receive(&mut writer).await;
+    let mut reader = connect_live(&srv, "reader", &note_id).await;
+    receive(&mut reader).await;
+    send(&mut writer, json!({"rev": 0, "op": {"type": "insert", "pos": 5, "text": "!"}})).await;
+    assert_eq!(receive(&mut writer).await, json!({"type": "ack", "rev": 1}));
+    assert_eq!(receive(&mut reader).await["rev"], 1);
+
+    // Changes of the access take effect within a running session
+    db.set_note_allowances(&note_id, &[("writer".to_string(), AllowanceLevel::Read)]).await.unwrap();
+    send(&mut writer, json!({"rev": 1, "op": {"type": "insert", "pos": 0, "text": ">"}})).await;
+    assert_eq!(receive(&mut writer).await, json!({"type": "error", "message": "no permission"}));
+    db.set_note_allowances(&note_id, &[("writer".to_string(), AllowanceLevel::Forbidden)]).await.unwrap();
+    send(&mut writer, json!({"rev": 1, "op": {"type": "insert", "pos": 0, "text": ">"}})).await;
+    assert_eq!(receive(&mut writer).await, json!({"type": "error", "message": "no permission"}));
+    assert_eq!(db.get_note(&note_id).await.unwrap().content, "Hello!");
+    db.set_note_allowances(&note_id, &[("writer".to_string(), AllowanceLevel::ReadWrite)]).await.unwrap();
+    send(&mut writer, json!({"rev": 1, "op": {"type": "insert", "pos": 0, "text": ">"}})).await;
+    assert_eq!(receive(&mut writer).await, json!({"type": "ack", "rev": 2}));
+    assert_eq!(receive(&mut reader).await["rev"], 2);
+
+    // Clients who lost their access stop receiving updates and get disconnected
+    db.set_note_allowances(&note_id, &[("reader".to_string(), AllowanceLevel::Forbidden)]).await.unwrap();
+    send(&mut writer, json!({"rev": 2, "op": {"type": "insert", "pos": 0, "text": ">"}})).await;
+    assert_eq!(receive(&mut writer).await, json!({"type": "ack", "rev": 3}));
+    assert_eq!(receive(&mut reader).await, json!({"type": "error", "message": "no permission"}));
+    assert!(matches!(reader.next().await, Some(Ok(Frame::Close(_))) | None));
+}
@@ -0,0 +1,417 @@
+//! End-to-end tests of all endpoints registered in [`handler_config`](crate::web::handler_config)
+//!
+//! Every test spins up its own service backed by a fresh [`MemoryStorage`],
+//! so tests neither depend on a running database nor on each other.
+
+mod system;
+mod auth;
+mod note;
+mod user;
+mod share;
+mod revision;
+mod live;
+mod search;
+mod token;
+mod link;
+mod transfer;
+mod group;
+mod folder;
+mod totp;
+mod password;
+mod limit;
+mod oidc;
+mod directory;
+mod keys;
+mod secret;
+
+use std::env;
+use std::sync::{Arc, Once};
+use actix_http::Request;
+use actix_web::{App, Error, test};
+use actix_web::body::MessageBody;
+use actix_web::cookie::Cookie;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use actix_web::web::{Data, JsonConfig, QueryConfig, scope};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use crate::db_access::{Allowance, ApiToken, AllowanceLevel, Credential, CredentialStore, DBError, DBInfo, Folder, FolderStore, Group, GroupStore, Identity, IdentityStore, LinkStore, MigrationStore, Note, NoteStore, NoteSummary, PasswordReset, ResetStore, Revision, RevisionStore, Session, SessionStore, ShareLink, Storage, TokenStore, Transfer, TransferStore, User, UserStore};
+use crate::db_access::memory::MemoryStorage;
+use crate::directory::Directory;
+use crate::mail::{LogMailer, Mailer};
+use crate::secret::AppSecrets;
+use crate::web::{handler_config, json_error_handler, KeySet, LiveHub, OidcConfig, query_error_handler, RateLimitConfig, RateLimiter};
+use crate::{BETA_KEY_ENV_VAR_KEY, JWT_SECRET_ENV_VAR_KEY, PASSWD_SECRET_ENV_VAR_KEY, SHARE_SECRET_ENV_VAR_KEY};
+
+/// The beta-key used for all signups
+pub const BETA_KEY: &str = "B757B";
+/// The password used for all test-user
+pub const PASSWORD: &str = "testPass";
+/// Name of the cookie carrying the JWT
+pub const JWT_COOKIE: &str = "writeup_jwt";
+/// Name of the cookie carrying the refresh-token
+pub const REFRESH_COOKIE: &str = "writeup_refresh";
+
+/// Limits that are never reached by the tests not concerned with them
+const UNLIMITED: RateLimitConfig = RateLimitConfig {
+    requests_per_window: u32::MAX,
+    window_seconds: 60,
+    lockout_threshold: u32::MAX,
+    lockout_seconds: 0,
+    max_lockout_seconds: 0,
+    ip_header: None
+};
+
+/// Sets all environment-variables the endpoints rely on
+fn init_env() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        env::set_var(PASSWD_SECRET_ENV_VAR_KEY, "testPasswdSecret");
+        env::set_var(JWT_SECRET_ENV_VAR_KEY, "testJwtSecret");
+        env::set_var(SHARE_SECRET_ENV_VAR_KEY, "testShareSecret");
+        env::set_var(BETA_KEY_ENV_VAR_KEY, BETA_KEY);
+    });
+}
+
+/// Creates a service containing all endpoints backed by the given storage
+///
+/// # Arguments
+///
+/// * `db` - The storage-backend to be used
+pub async fn init_app(db: Arc<dyn Storage>) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    init_app_with(db, AppConfig::default()).await
+}
+
+/// The app-data of a service besides its storage-backend, each part of which can be replaced while keeping the defaults of the rest
+pub struct AppConfig {
+    /// The Mailer delivering messages to users
+    pub mailer: Arc<dyn Mailer>,
+    /// The limits to be enforced
+    pub limits: RateLimitConfig,
+    /// The identity-provider to log in with, if any
+    pub oidc: Option<OidcConfig>,
+    /// The Directory verifying the passwords of other users, if any
+    pub directory: Option<Arc<dyn Directory>>,
+    /// The keys signing and verifying JWTs *[default: from the environment]*
+    pub keys: Option<KeySet>
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            mailer: Arc::new(LogMailer),
+            limits: UNLIMITED,
+            oidc: None,
+            directory: None,
+            keys: None
+        }
+    }
+}
+
+/// Creates a service containing all endpoints backed by the given storage, using the given app-data
+///
+/// # Arguments
+///
+/// * `db` - The storage-backend to be used
+/// * `config` - The rest of the app-data to be used
+pub async fn init_app_with(db: Arc<dyn Storage>, config: AppConfig) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    init_env();
+    let app = App::new()
+        .app_data(Data::from(db))
+        .app_data(Data::new(LiveHub::default()))
+        .app_data(Data::from(config.mailer))
+        .app_data(Data::new(RateLimiter::new(config.limits)))
+        .app_data(Data::new(config.keys.unwrap_or_else(KeySet::from_env)))
+        .app_data(Data::new(AppSecrets::from_env()))
+        .app_data(JsonConfig::default().error_handler(json_error_handler))
+        .app_data(QueryConfig::default().error_handler(query_error_handler));
+    let app = match config.oidc {
+        Some(oidc) => app.app_data(Data::new(oidc)),
+        None => app
+    };
+    let app = match config.directory {
+        Some(directory) => app.app_data(Data::from(directory)),
+        None => app
+    };
+    test::init_service(app.service(scope("/api").configure(handler_config))).await
+}
+
+/// Sends a request to the service and returns the status and the parsed body of the response
+///
+/// # Arguments
+///
+/// * `app` - The service to be called
+/// * `req` - The request to be sent
+pub async fn call<S, B>(app: &S, req: TestRequest) -> (StatusCode, Value)
+    where S: Service<Request, Response = ServiceResponse<B>, Error = Error>, B: MessageBody {
+    let resp = test::call_service(app, req.to_request()).await;
+    let status = resp.status();
+    let body = test::read_body(resp).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Creates a new user using the signup-endpoint
+///
+/// # Arguments
+///
+/// * `app` - The service to be called
+/// * `username` - The name of the new user
+pub async fn signup<S, B>(app: &S, username: &str) -> (StatusCode, Value)
+    where S: Service<Request, Response = ServiceResponse<B>, Error = Error>, B: MessageBody {
+    call(app, TestRequest::post().uri("/api/user")
+        .set_json(json!({"username": username, "password": PASSWORD, "beta_key": BETA_KEY}))).await
+}
+
+/// Logs a user in and returns the JWT-cookie proving it
+///
+/// # Arguments
+///
+/// * `app` - The service to be called
+/// * `username` - The name of the user
+pub async fn login<S, B>(app: &S, username: &str) -> Cookie<'static>
+    where S: Service<Request, Response = ServiceResponse<B>, Error = Error>, B: MessageBody {
+    login_with_refresh(app, username).await.0
+}
+
+/// Logs a user in and returns both the JWT- and the refresh-cookie of the new session
+///
+/// # Arguments
+///
+/// * `app` - The service to be called
+/// * `username` - The name of the user
+pub async fn login_with_refresh<S, B>(app: &S, username: &str) -> (Cookie<'static>, Cookie<'static>)
+    where S: Service<Request, Response = ServiceResponse<B>, Error = Error>, B: MessageBody {
+    let resp = test::call_service(app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": username, "password": PASSWORD, "session_only": false})).to_request()).await;
+    session_cookies(&resp).expect("login failed")
+}
+
+/// Extracts the JWT- and the refresh-cookie set by a response
+///
+/// # Arguments
+///
+/// * `resp` - The response setting the cookies
+pub fn session_cookies<B>(resp: &ServiceResponse<B>) -> Option<(Cookie<'static>, Cookie<'static>)> {
+    let cookie = |name: &str| resp.response().cookies().find(|cookie| cookie.name().eq(name)).map(|cookie| cookie.into_owned());
+    Some((cookie(JWT_COOKIE)?, cookie(REFRESH_COOKIE)?))
+}
+
+/// Creates a new user and logs them in
+///
+/// # Arguments
+///
+/// * `app` - The service to be called
+/// * `username` - The name of the new user
+pub async fn signup_and_login<S, B>(app: &S, username: &str) -> Cookie<'static>
+    where S: Service<Request, Response = ServiceResponse<B>, Error = Error>, B: MessageBody {
+    assert_eq!(signup(app, username).await.0, StatusCode::CREATED);
+    login(app, username).await
+}
+
+/// Creates a note and returns its identifier
+///
+/// # Arguments
+///
+/// * `app` - The service to be called
+/// * `cookie` - The JWT-cookie of the owner
+/// * `title` - The title of the note
+pub async fn create_note<S, B>(app: &S, cookie: &Cookie<'static>, title: &str) -> String
+    where S: Service<Request, Response = ServiceResponse<B>, Error = Error>, B: MessageBody {
+    let (status, body) = call(app, TestRequest::post().uri("/api/note").cookie(cookie.clone())
+        .set_json(json!({"title": title, "content": "Some content", "tags": ["Test"]}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    body["content"]["note_id"].as_str().unwrap().to_string()
+}
+
+/// Connects two user using an invite-code
+///
+/// # Arguments
+///
+/// * `app` - The service to be called
+/// * `inviting` - The JWT-cookie of the inviting user
+/// * `invited` - The JWT-cookie of the invited user
+pub async fn connect<S, B>(app: &S, inviting: &Cookie<'static>, invited: &Cookie<'static>)
+    where S: Service<Request, Response = ServiceResponse<B>, Error = Error>, B: MessageBody {
+    let (_, body) = call(app, TestRequest::get().uri("/api/share").cookie(inviting.clone())).await;
+    let code = body["content"]["code"].as_str().unwrap().to_string();
+    let (_, body) = call(app, TestRequest::post().uri("/api/share").cookie(invited.clone())
+        .set_json(json!({"code": code}))).await;
+    assert_eq!(body["success"], true);
+}
+
+/// Shares a note with another user
+///
+/// # Arguments
+///
+/// * `app` - The service to be called
+/// * `owner` - The JWT-cookie of the note's owner
+/// * `note_id` - The identifier of the note
+/// * `user_id` - The user the note is shared with
+/// * `level` - The level of access to be granted
+pub async fn share_note<S, B>(app: &S, owner: &Cookie<'static>, note_id: &str, user_id: &str, level: &str) -> (StatusCode, Value)
+    where S: Service<Request, Response = ServiceResponse<B>, Error = Error>, B: MessageBody {
+    call(app, TestRequest::put().uri(&format!("/api/share/{}", note_id)).cookie(owner.clone())
+        .set_json(json!([{"user_id": user_id, "allowance": level}]))).await
+}
+
+/// Asserts that a response represents the given error
+///
+/// # Arguments
+///
+/// * `response` - The status and body of the response
+/// * `status` - The expected status
+/// * `code` - The expected error-code
+pub fn assert_error(response: (StatusCode, Value), status: StatusCode, code: i64) {
+    assert_eq!(response.0, status, "unexpected status, body: {}", response.1);
+    assert_eq!(response.1["success"], false);
+    assert_eq!(response.1["code"], code, "unexpected code, body: {}", response.1);
+}
+
+/// A storage-backend able to read, whose every write fails
+pub struct ReadOnlyStorage(pub MemoryStorage);
+
+#[async_trait]
+impl CredentialStore for ReadOnlyStorage {
+    async fn get_credential(&self, username: &str) -> Result<Credential, DBError> { self.0.get_credential(username).await }
+    async fn insert_credential(&self, _cred: &Credential) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn update_credential(&self, _cred: &Credential) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn remove_credential(&self, _username: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+}
+
+#[async_trait]
+impl UserStore for ReadOnlyStorage {
+    async fn get_user(&self, user_id: &str) -> Result<User, DBError> { self.0.get_user(user_id).await }
+    async fn insert_user(&self, _user: &User) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn remove_user(&self, _user_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn add_connection(&self, _user_id: &str, _connection_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn pull_connection(&self, _user_id: &str, _connection_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn add_allowance(&self, _user_id: &str, _allowance: &Allowance) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn pull_allowances(&self, _user_id: &str, _note_ids: &[String]) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn pull_note_allowances(&self, _note_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn set_note_allowances(&self, _note_id: &str, _changes: &[(String, AllowanceLevel)]) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn set_roles(&self, _user_id: &str, _roles: &[String]) -> Result<(), DBError> { Err(DBError::QueryError) }
+}
+
+#[async_trait]
+impl NoteStore for ReadOnlyStorage {
+    async fn get_note(&self, note_id: &str) -> Result<Note, DBError> { self.0.get_note(note_id).await }
+    async fn get_notes(&self, note_ids: &[String]) -> Result<Vec<(String, Note)>, DBError> { self.0.get_notes(note_ids).await }
+    async fn get_note_summaries(&self, note_ids: &[String]) -> Result<Vec<(String, NoteSummary)>, DBError> { self.0.get_note_summaries(note_ids).await }
+    async fn insert_note(&self, _note: &Note) -> Result<String, DBError> { Err(DBError::QueryError) }
+    async fn set_note_fields(&self, _note_id: &str, _note: &Note, _version: u64, _revision: Option<&Revision>) -> Result<u64, DBError> { Err(DBError::QueryError) }
+    async fn set_note_owner(&self, _note_id: &str, _owner_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn remove_note(&self, _note_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+}
+
+#[async_trait]
+impl RevisionStore for ReadOnlyStorage {
+    async fn get_revisions(&self, note_id: &str) -> Result<Vec<Revision>, DBError> { self.0.get_revisions(note_id).await }
+    async fn get_revision(&self, note_id: &str, rev: u32) -> Result<Revision, DBError> { self.0.get_revision(note_id, rev).await }
+    async fn remove_revisions(&self, _note_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+}
+
+// Sessions stay writable, as logging in requires one
+#[async_trait]
+impl SessionStore for ReadOnlyStorage {
+    async fn get_session(&self, session_id: &str) -> Result<Session, DBError> { self.0.get_session(session_id).await }
+    async fn get_sessions(&self, user_id: &str) -> Result<Vec<Session>, DBError> { self.0.get_sessions(user_id).await }
+    async fn insert_session(&self, session: &Session) -> Result<(), DBError> { self.0.insert_session(session).await }
+    async fn set_session_fields(&self, session: &Session, refresh_hash: &str) -> Result<(), DBError> { self.0.set_session_fields(session, refresh_hash).await }
+    async fn touch_session(&self, session_id: &str, last_seen: DateTime<Utc>) -> Result<(), DBError> { self.0.touch_session(session_id, last_seen).await }
+    async fn remove_session(&self, session_id: &str) -> Result<(), DBError> { self.0.remove_session(session_id).await }
+    async fn remove_sessions(&self, user_id: &str) -> Result<(), DBError> { self.0.remove_sessions(user_id).await }
+}
+
+#[async_trait]
+impl TokenStore for ReadOnlyStorage {
+    async fn get_token(&self, token_id: &str) -> Result<ApiToken, DBError> { self.0.get_token(token_id).await }
+    async fn get_tokens(&self, user_id: &str) -> Result<Vec<ApiToken>, DBError> { self.0.get_tokens(user_id).await }
+    async fn insert_token(&self, _token: &ApiToken) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn touch_token(&self, _token_id: &str, _last_used: DateTime<Utc>) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn remove_token(&self, _token_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn remove_tokens(&self, _user_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+}
+
+#[async_trait]
+impl ResetStore for ReadOnlyStorage {
+    async fn get_reset(&self, reset_id: &str) -> Result<PasswordReset, DBError> { self.0.get_reset(reset_id).await }
+    async fn insert_reset(&self, _reset: &PasswordReset) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn remove_resets(&self, _user_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+}
+
+#[async_trait]
+impl IdentityStore for ReadOnlyStorage {
+    async fn get_identity(&self, identity_id: &str) -> Result<Identity, DBError> { self.0.get_identity(identity_id).await }
+    async fn insert_identity(&self, _identity: &Identity) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn remove_identities(&self, _user_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+}
+
+#[async_trait]
+impl LinkStore for ReadOnlyStorage {
+    async fn get_link(&self, link_id: &str) -> Result<ShareLink, DBError> { self.0.get_link(link_id).await }
+    async fn get_links(&self, note_id: &str) -> Result<Vec<ShareLink>, DBError> { self.0.get_links(note_id).await }
+    async fn insert_link(&self, _link: &ShareLink) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn count_view(&self, _link_id: &str) -> Result<ShareLink, DBError> { Err(DBError::QueryError) }
+    async fn remove_link(&self, _link_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn remove_links(&self, _note_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+}
+
+#[async_trait]
+impl GroupStore for ReadOnlyStorage {
+    async fn get_group(&self, group_id: &str) -> Result<Group, DBError> { self.0.get_group(group_id).await }
+    async fn get_groups(&self, user_id: &str) -> Result<Vec<Group>, DBError> { self.0.get_groups(user_id).await }
+    async fn insert_group(&self, _group: &Group) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn remove_group(&self, _group_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn add_member(&self, _group_id: &str, _user_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn pull_member(&self, _group_id: &str, _user_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn pull_group_allowance(&self, _group_id: &str, _note_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn set_group_note_allowances(&self, _note_id: &str, _changes: &[(String, AllowanceLevel)]) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn pull_note_group_allowances(&self, _note_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+}
+
+#[async_trait]
+impl FolderStore for ReadOnlyStorage {
+    async fn get_folder(&self, folder_id: &str) -> Result<Folder, DBError> { self.0.get_folder(folder_id).await }
+    async fn get_folders(&self, owner_id: &str) -> Result<Vec<Folder>, DBError> { self.0.get_folders(owner_id).await }
+    async fn get_shared_folders(&self, user_id: &str) -> Result<Vec<Folder>, DBError> { self.0.get_shared_folders(user_id).await }
+    async fn insert_folder(&self, _folder: &Folder) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn update_folder(&self, _folder_id: &str, _name: &str, _parent_id: Option<&str>) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn remove_folder(&self, _folder_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn add_folder_note(&self, _folder_id: &str, _note_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn pull_folder_note(&self, _note_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn set_folder_shares(&self, _folder_id: &str, _changes: &[(String, AllowanceLevel)]) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn pull_folder_share(&self, _folder_id: &str, _user_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+}
+
+#[async_trait]
+impl TransferStore for ReadOnlyStorage {
+    async fn get_transfer(&self, note_id: &str) -> Result<Transfer, DBError> { self.0.get_transfer(note_id).await }
+    async fn get_transfers(&self, user_id: &str) -> Result<Vec<Transfer>, DBError> { self.0.get_transfers(user_id).await }
+    async fn set_transfer(&self, _transfer: &Transfer) -> Result<(), DBError> { Err(DBError::QueryError) }
+    async fn remove_transfer(&self, _note_id: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+}
+
+#[async_trait]
+impl MigrationStore for ReadOnlyStorage {
+    async fn get_schema_version(&self) -> Result<u32, DBError> { self.0.get_schema_version().await }
+    async fn apply_migration(&self, _version: u32, _description: &str) -> Result<(), DBError> { Err(DBError::QueryError) }
+}
+
+impl Storage for ReadOnlyStorage {
+    fn get_info(&self) -> DBInfo { self.0.get_info() }
+}
+
+/// Creates a read-only storage containing a single user
+///
+/// # Arguments
+///
+/// * `username` - The name of the user
+pub async fn read_only_storage_with_user(username: &str) -> Arc<dyn Storage> {
+    init_env();
+    let db = MemoryStorage::new();
+    db.insert_user(&User { _id: username.to_string(), allowances: Vec::new(), connections: Vec::new(), roles: Vec::new() }).await.unwrap();
+    db.insert_credential(&Credential::new(username.to_string(), PASSWORD)).await.unwrap();
+    Arc::new(ReadOnlyStorage(db))
+}
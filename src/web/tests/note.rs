@@ -0,0 +1,276 @@
+use std::sync::Arc;
+use actix_web::http::header::{ETAG, IF_MATCH};
+use actix_web::http::StatusCode;
+use actix_web::test;
+use actix_web::test::TestRequest;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use crate::db_access::{Allowance, AllowanceLevel, UserStore};
+use crate::db_access::memory::MemoryStorage;
+use crate::web::tests::{assert_error, call, connect, create_note, init_app, login, read_only_storage_with_user, share_note, signup_and_login};
+
+#[actix_rt::test]
+async fn add_and_get_note() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+
+    let (status, body) = call(&app, TestRequest::post().uri("/api/note").cookie(cookie.clone())
+        .set_json(json!({"title": "Test-Note", "content": "Some content", "tags": ["Test", "Note"]}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["content"]["allowance"], "Owner");
+    let note_id = body["content"]["note_id"].as_str().unwrap();
+
+    let (status, body) = call(&app, TestRequest::get().uri(&format!("/api/note/{}", note_id)).cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["note"]["title"], "Test-Note");
+    assert_eq!(body["content"]["note"]["owner_id"], "testUser");
+    assert_eq!(body["content"]["note"]["tags"], json!(["Test", "Note"]));
+    assert_eq!(body["content"]["allowance"], "Owner");
+}
+
+#[actix_rt::test]
+async fn add_note_without_login() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let response = call(&app, TestRequest::post().uri("/api/note")
+        .set_json(json!({"title": "Test-Note", "content": "Some content", "tags": []}))).await;
+    assert_error(response, StatusCode::UNAUTHORIZED, 10);
+}
+
+#[actix_rt::test]
+async fn add_note_with_invalid_payload() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let response = call(&app, TestRequest::post().uri("/api/note").cookie(cookie)
+        .set_json(json!({"title": "Test-Note"}))).await;
+    assert_error(response, StatusCode::BAD_REQUEST, 20);
+}
+
+#[actix_rt::test]
+async fn add_note_with_failing_storage() {
+    let app = init_app(read_only_storage_with_user("testUser").await).await;
+    let cookie = login(&app, "testUser").await;
+    let response = call(&app, TestRequest::post().uri("/api/note").cookie(cookie)
+        .set_json(json!({"title": "Test-Note", "content": "Some content", "tags": []}))).await;
+    assert_error(response, StatusCode::INTERNAL_SERVER_ERROR, 54);
+}
+
+#[actix_rt::test]
+async fn list_notes() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let own_note = create_note(&app, &cookie, "Own").await;
+    let shared_note = create_note(&app, &other, "Shared").await;
+    share_note(&app, &other, &shared_note, "testUser", "Read").await;
+
+    let (status, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    let notes = &body["content"]["notes"];
+    assert_eq!(notes[0]["created_at"], notes[0]["updated_at"]);
+    assert_eq!(notes, &json!([
+        {"note_id": own_note, "title": "Own", "tags": ["Test"], "allowance": "Owner", "version": 0,
+            "created_at": notes[0]["created_at"], "updated_at": notes[0]["updated_at"], "last_editor_id": "testUser"},
+        {"note_id": shared_note, "title": "Shared", "tags": ["Test"], "allowance": "Read", "version": 0,
+            "created_at": notes[1]["created_at"], "updated_at": notes[1]["updated_at"], "last_editor_id": "otherUser"}
+    ]));
+    assert_error(call(&app, TestRequest::get().uri("/api/notes")).await, StatusCode::UNAUTHORIZED, 10);
+}
+
+#[actix_rt::test]
+async fn list_notes_paginated() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let b = create_note(&app, &cookie, "b").await;
+    let a = create_note(&app, &cookie, "A").await;
+    let c = create_note(&app, &other, "C").await;
+    share_note(&app, &other, &c, "testUser", "ReadWrite").await;
+    let list = |body: &Value| -> Vec<String> {
+        body["content"]["notes"].as_array().unwrap().iter().map(|note| note["note_id"].as_str().unwrap().to_string()).collect()
+    };
+
+    // Follow the cursors through all pages
+    let (status, body) = call(&app, TestRequest::get().uri("/api/notes?sort=title&order=desc&limit=2").cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(list(&body), vec![c.clone(), b.clone()]);
+    let uri = format!("/api/notes?sort=title&order=desc&limit=2&cursor={}", body["content"]["next_cursor"].as_str().unwrap());
+    let (_, body) = call(&app, TestRequest::get().uri(&uri).cookie(cookie.clone())).await;
+    assert_eq!(list(&body), vec![a.clone()]);
+    assert_eq!(body["content"]["next_cursor"], Value::Null);
+
+    // Ordered by creation by default
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(cookie.clone())).await;
+    assert_eq!(list(&body), vec![b.clone(), a.clone(), c.clone()]);
+
+    // Filters
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes?owner=otherUser").cookie(cookie.clone())).await;
+    assert_eq!(list(&body), vec![c.clone()]);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes?level=Owner&order=desc").cookie(cookie.clone())).await;
+    assert_eq!(list(&body), vec![a, b]);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes?tag=test&owner=otherUser&level=ReadWrite").cookie(cookie.clone())).await;
+    assert_eq!(list(&body), vec![c]);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes?tag=other").cookie(cookie.clone())).await;
+    assert_eq!(list(&body), Vec::<String>::new());
+
+    // Malformed parameters
+    assert_error(call(&app, TestRequest::get().uri("/api/notes?cursor=xyz").cookie(cookie.clone())).await, StatusCode::BAD_REQUEST, 20);
+    assert_error(call(&app, TestRequest::get().uri("/api/notes?sort=size").cookie(cookie.clone())).await, StatusCode::BAD_REQUEST, 20);
+    assert_error(call(&app, TestRequest::get().uri("/api/notes?limit=-1").cookie(cookie)).await, StatusCode::BAD_REQUEST, 20);
+}
+
+#[actix_rt::test]
+async fn get_note_errors() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let uri = format!("/api/note/{}", note_id);
+
+    assert_error(call(&app, TestRequest::get().uri("/api/note/72$4fa97b62u3:2dr4d3l").cookie(cookie)).await,
+                 StatusCode::BAD_REQUEST, 21);
+    assert_error(call(&app, TestRequest::get().uri(&uri)).await, StatusCode::UNAUTHORIZED, 10);
+    assert_error(call(&app, TestRequest::get().uri(&uri).cookie(other)).await, StatusCode::FORBIDDEN, 12);
+}
+
+#[actix_rt::test]
+async fn get_note_with_inconsistent_storage() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    // Link the user to a note that doesn't exist
+    db.add_allowance("testUser", &Allowance { note_id: "missingNote".to_string(), level: AllowanceLevel::Owner }).await.unwrap();
+
+    let response = call(&app, TestRequest::get().uri("/api/note/missingNote").cookie(cookie.clone())).await;
+    assert_error(response, StatusCode::INTERNAL_SERVER_ERROR, 55);
+    let response = call(&app, TestRequest::get().uri("/api/notes").cookie(cookie)).await;
+    assert_error(response, StatusCode::INTERNAL_SERVER_ERROR, 55);
+}
+
+#[actix_rt::test]
+async fn update_note() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let uri = format!("/api/note/{}", note_id);
+
+    let (status, body) = call(&app, TestRequest::put().uri(&uri).cookie(cookie.clone())
+        .set_json(json!({"title": "Updated", "content": "New content", "tags": ["Updated"]}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["note"]["title"], "Updated");
+
+    let (_, body) = call(&app, TestRequest::get().uri(&uri).cookie(cookie)).await;
+    assert_eq!(body["content"]["note"]["title"], "Updated");
+    assert_eq!(body["content"]["note"]["content"], "New content");
+    assert_eq!(body["content"]["note"]["tags"], json!(["Updated"]));
+}
+
+#[actix_rt::test]
+async fn update_note_metadata() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let newer_id = create_note(&app, &cookie, "Newer").await;
+    share_note(&app, &cookie, &note_id, "otherUser", "ReadWrite").await;
+    let uri = format!("/api/note/{}", note_id);
+    let (_, body) = call(&app, TestRequest::get().uri(&uri).cookie(cookie.clone())).await;
+    let created = body["content"]["note"].clone();
+    assert_eq!(created["last_editor_id"], "testUser");
+    assert_eq!(created["created_at"], created["updated_at"]);
+
+    // Modifications keep track of who made them and when
+    let (_, body) = call(&app, TestRequest::put().uri(&uri).cookie(other)
+        .set_json(json!({"title": "Updated", "content": "New content", "tags": []}))).await;
+    let updated = &body["content"]["note"];
+    assert_eq!(updated["last_editor_id"], "otherUser");
+    assert_eq!(updated["created_at"], created["created_at"]);
+    let parse = |time: &Value| time.as_str().unwrap().parse::<DateTime<Utc>>().unwrap();
+    assert!(parse(&updated["updated_at"]) > parse(&created["updated_at"]));
+
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes?sort=modified&order=desc").cookie(cookie.clone())).await;
+    assert_eq!(body["content"]["notes"][0]["note_id"], note_id);
+    assert_eq!(body["content"]["notes"][0]["last_editor_id"], "otherUser");
+    assert_eq!(body["content"]["notes"][0]["updated_at"], updated["updated_at"]);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes?sort=created&order=desc").cookie(cookie)).await;
+    assert_eq!(body["content"]["notes"][0]["note_id"], newer_id);
+}
+
+#[actix_rt::test]
+async fn update_note_errors() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    share_note(&app, &cookie, &note_id, "otherUser", "Read").await;
+    let uri = format!("/api/note/{}", note_id);
+    let update = json!({"title": "Updated", "content": "New content", "tags": []});
+
+    assert_error(call(&app, TestRequest::put().uri("/api/note/72$4fa97b62u3:2dr4d3l").cookie(cookie)
+        .set_json(update.clone())).await, StatusCode::BAD_REQUEST, 21);
+    assert_error(call(&app, TestRequest::put().uri(&uri).set_json(update.clone())).await, StatusCode::UNAUTHORIZED, 10);
+    assert_error(call(&app, TestRequest::put().uri(&uri).cookie(other).set_json(update)).await, StatusCode::FORBIDDEN, 12);
+}
+
+#[actix_rt::test]
+async fn update_note_with_if_match() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let uri = format!("/api/note/{}", note_id);
+    let update = json!({"title": "Updated", "content": "New content", "tags": []});
+
+    // The version is returned as part of the note and as ETag
+    let resp = test::call_service(&app, TestRequest::get().uri(&uri).cookie(cookie.clone()).to_request()).await;
+    assert_eq!(resp.headers().get(ETAG).unwrap(), "\"0\"");
+
+    let (status, body) = call(&app, TestRequest::put().uri(&uri).cookie(cookie.clone())
+        .insert_header((IF_MATCH, "\"0\"")).set_json(update.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["note"]["version"], 1);
+
+    // A second update based on the same version is stale
+    let resp = test::call_service(&app, TestRequest::put().uri(&uri).cookie(cookie.clone())
+        .insert_header((IF_MATCH, "\"0\"")).set_json(update.clone()).to_request()).await;
+    assert_eq!(resp.headers().get(ETAG).unwrap(), "\"1\"");
+    let body: Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_error((StatusCode::PRECONDITION_FAILED, body), StatusCode::PRECONDITION_FAILED, 23);
+
+    // Wildcards and updates without precondition are always applied
+    let (status, _) = call(&app, TestRequest::put().uri(&uri).cookie(cookie.clone())
+        .insert_header((IF_MATCH, "*")).set_json(update.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = call(&app, TestRequest::put().uri(&uri).cookie(cookie.clone()).set_json(update)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["note"]["version"], 3);
+
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(cookie)).await;
+    assert_eq!(body["content"]["notes"][0]["version"], 3);
+}
+
+#[actix_rt::test]
+async fn remove_note() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    share_note(&app, &cookie, &note_id, "otherUser", "ReadWrite").await;
+    let uri = format!("/api/note/{}", note_id);
+
+    // Only the owner may remove a note
+    assert_error(call(&app, TestRequest::delete().uri(&uri).cookie(other.clone())).await, StatusCode::FORBIDDEN, 12);
+    assert_error(call(&app, TestRequest::delete().uri(&uri)).await, StatusCode::UNAUTHORIZED, 10);
+    assert_error(call(&app, TestRequest::delete().uri("/api/note/72$4fa97b62u3:2dr4d3l").cookie(cookie.clone())).await,
+                 StatusCode::BAD_REQUEST, 21);
+
+    let (status, _) = call(&app, TestRequest::delete().uri(&uri).cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    // All allowances are gone as well
+    assert_error(call(&app, TestRequest::get().uri(&uri).cookie(cookie)).await, StatusCode::FORBIDDEN, 12);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(other)).await;
+    assert_eq!(body["content"]["notes"], json!([]));
+}
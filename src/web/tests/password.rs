@@ -0,0 +1,153 @@
+use std::fs;
+use std::sync::Arc;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, TestRequest};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use crate::db_access::{Credential, CredentialStore, HashPolicy, PasswordReset, ResetStore, Verification};
+use crate::db_access::memory::MemoryStorage;
+use crate::mail::FileMailer;
+use crate::secret::Secret;
+use crate::web::auth::hash_token;
+use crate::web::issue_reset_token;
+use crate::web::tests::{AppConfig, assert_error, call, init_app, init_app_with, login, PASSWORD, signup_and_login};
+
+/// Attempts to log a user in with the given password, returning whether it succeeded
+macro_rules! can_login {
+    ($app:expr, $username:expr, $password:expr) => {{
+        let (_, body) = call(&$app, TestRequest::post().uri("/api/auth")
+            .set_json(json!({"username": $username, "password": $password, "session_only": false}))).await;
+        body["success"].as_bool().unwrap()
+    }};
+}
+
+#[actix_rt::test]
+async fn change_password() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other_session = login(&app, "testUser").await;
+    let (_, body) = call(&app, TestRequest::post().uri("/api/user/tokens").cookie(cookie.clone())
+        .set_json(json!({"name": "Backup", "scopes": ["notes:read"]}))).await;
+    let token = body["content"]["token"].as_str().unwrap().to_string();
+
+    assert_error(call(&app, TestRequest::put().uri("/api/user/password")
+        .set_json(json!({"old_password": PASSWORD, "new_password": "newPass"}))).await, StatusCode::UNAUTHORIZED, 10);
+    assert_error(call(&app, TestRequest::put().uri("/api/user/password").cookie(cookie.clone())
+        .set_json(json!({"old_password": "wrongPass", "new_password": "newPass"}))).await, StatusCode::OK, 11);
+
+    // All sessions end, including the current one
+    let resp = call_service(&app, TestRequest::put().uri("/api/user/password").cookie(cookie.clone())
+        .set_json(json!({"old_password": PASSWORD, "new_password": "newPass"})).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.response().cookies().all(|cookie| cookie.value().is_empty()));
+    for session in [cookie, other_session] {
+        assert_error(call(&app, TestRequest::get().uri("/api/user").cookie(session)).await, StatusCode::UNAUTHORIZED, 10);
+    }
+    // Personal access-tokens are revoked as well
+    assert_error(call(&app, TestRequest::get().uri("/api/notes").insert_header((AUTHORIZATION, format!("Bearer {}", token)))).await,
+                 StatusCode::UNAUTHORIZED, 10);
+    assert!(!can_login!(app, "testUser", PASSWORD));
+    assert!(can_login!(app, "testUser", "newPass"));
+}
+
+#[actix_rt::test]
+async fn reset_password() {
+    let outbox = std::env::temp_dir().join(format!("writeup-outbox-{}.txt", rand::random::<u32>()));
+    let app = init_app_with(Arc::new(MemoryStorage::new()), AppConfig { mailer: Arc::new(FileMailer::new(&outbox)), ..AppConfig::default() }).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+
+    // Unknown users are indistinguishable, but nothing is sent
+    let (status, _) = call(&app, TestRequest::post().uri("/api/user/password/reset")
+        .set_json(json!({"username": "unknownUser"}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!outbox.exists());
+
+    let (status, _) = call(&app, TestRequest::post().uri("/api/user/password/reset")
+        .set_json(json!({"username": "testUser"}))).await;
+    assert_eq!(status, StatusCode::OK);
+    let mail = fs::read_to_string(&outbox).unwrap();
+    fs::remove_file(&outbox).unwrap();
+    assert!(mail.contains("To: testUser\nSubject: Resetting your password"));
+    let token = mail.trim_end().rsplit(' ').next().unwrap().to_string();
+
+    assert_error(call(&app, TestRequest::put().uri("/api/user/password/reset")
+        .set_json(json!({"token": format!("{}x", token), "new_password": "newPass"}))).await, StatusCode::OK, 11);
+    assert_error(call(&app, TestRequest::put().uri("/api/user/password/reset")
+        .set_json(json!({"token": "noToken", "new_password": "newPass"}))).await, StatusCode::OK, 11);
+    assert_error(call(&app, TestRequest::put().uri("/api/user/password/reset")
+        .set_json(json!({"token": "%7B%24ne%3Anull%7D.secret", "new_password": "newPass"}))).await, StatusCode::OK, 11);
+    let (status, _) = call(&app, TestRequest::put().uri("/api/user/password/reset")
+        .set_json(json!({"token": token, "new_password": "newPass"}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&app, TestRequest::get().uri("/api/user").cookie(cookie)).await, StatusCode::UNAUTHORIZED, 10);
+    assert!(can_login!(app, "testUser", "newPass"));
+
+    // Tokens can only be used once
+    assert_error(call(&app, TestRequest::put().uri("/api/user/password/reset")
+        .set_json(json!({"token": token, "new_password": "otherPass"}))).await, StatusCode::OK, 11);
+    assert!(!can_login!(app, "testUser", "otherPass"));
+}
+
+#[actix_rt::test]
+async fn reset_token_validity() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    signup_and_login(&app, "testUser").await;
+
+    // Only the latest token is accepted
+    let (old_token, _) = issue_reset_token(db.as_ref(), "testUser").await.unwrap();
+    let (token, reset) = issue_reset_token(db.as_ref(), "testUser").await.unwrap();
+    assert_eq!(reset.expires_at - reset.created_at, Duration::minutes(60));
+    assert_error(call(&app, TestRequest::put().uri("/api/user/password/reset")
+        .set_json(json!({"token": old_token, "new_password": "newPass"}))).await, StatusCode::OK, 11);
+
+    // Expired tokens are rejected
+    let expired = PasswordReset { _id: "expiredToken".to_string(), user_id: "testUser".to_string(), token_hash: hash_token("secret"),
+        created_at: Utc::now() - Duration::hours(2), expires_at: Utc::now() - Duration::hours(1) };
+    db.insert_reset(&expired).await.unwrap();
+    assert_error(call(&app, TestRequest::put().uri("/api/user/password/reset")
+        .set_json(json!({"token": "expiredToken.secret", "new_password": "newPass"}))).await, StatusCode::OK, 11);
+
+    // Removing the user removes their tokens
+    let cookie = login(&app, "testUser").await;
+    let (status, _) = call(&app, TestRequest::delete().uri("/api/user").cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(db.get_reset(token.split_once('.').unwrap().0).await.is_err());
+    assert!(db.get_credential("testUser").await.is_err());
+}
+
+#[actix_rt::test]
+async fn hash_upgrade() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    signup_and_login(&app, "testUser").await;
+    let policy = HashPolicy::from_env();
+    assert_eq!(db.get_credential("testUser").await.unwrap().hash_params(), Some((policy.memory_size, policy.iterations)));
+
+    // Weaker hashes are replaced on the next successful login only
+    let mut cred = db.get_credential("testUser").await.unwrap();
+    cred.set_password_with(PASSWORD, &HashPolicy { memory_size: 8192, iterations: 2, ..policy.clone() });
+    db.update_credential(&cred).await.unwrap();
+    assert!(!can_login!(app, "testUser", "wrongPass"));
+    assert_eq!(db.get_credential("testUser").await.unwrap().hash_params(), Some((8192, 2)));
+    assert!(can_login!(app, "testUser", PASSWORD));
+    assert_eq!(db.get_credential("testUser").await.unwrap().hash_params(), Some((policy.memory_size, policy.iterations)));
+    assert!(can_login!(app, "testUser", PASSWORD));
+}
+
+#[test]
+fn pepper_rotation() {
+    let old = HashPolicy { memory_size: 8192, iterations: 2, pepper: Secret::new("oldSecret"), old_peppers: Vec::new() };
+    let rotated = HashPolicy { pepper: Secret::new("newSecret"), old_peppers: vec![Secret::new("oldSecret")], ..old.clone() };
+    let mut cred = Credential::new("testUser".to_string(), PASSWORD);
+    cred.set_password_with(PASSWORD, &old);
+
+    // Hashes peppered with an old secret are accepted once and replaced
+    assert_eq!(cred.verify_and_upgrade("wrongPass", &rotated), Verification::Invalid);
+    assert_eq!(cred.verify_and_upgrade(PASSWORD, &rotated), Verification::Upgraded);
+    assert_eq!(cred.verify_and_upgrade(PASSWORD, &rotated), Verification::Valid);
+    assert_eq!(cred.verify_and_upgrade(PASSWORD, &old), Verification::Invalid);
+    let retired = HashPolicy { old_peppers: Vec::new(), ..rotated };
+    assert_eq!(cred.verify_and_upgrade(PASSWORD, &retired), Verification::Valid);
+}
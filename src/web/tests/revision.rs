@@ -0,0 +1,177 @@
+use std::env;
+use std::sync::Arc;
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use chrono::Utc;
+use serde_json::json;
+use crate::db_access::{DBError, Note, RevisionStore, Storage, User};
+use crate::db_access::memory::MemoryStorage;
+use crate::db_access::sqlite::SqliteStorage;
+use crate::web::note::gen_revision;
+use crate::web::tests::{assert_error, call, connect, create_note, init_app, share_note, signup_and_login};
+
+#[actix_rt::test]
+async fn update_records_revisions() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    share_note(&app, &cookie, &note_id, "otherUser", "ReadWrite").await;
+    let uri = format!("/api/note/{}", note_id);
+
+    let (status, body) = call(&app, TestRequest::put().uri(&uri).cookie(other.clone())
+        .set_json(json!({"title": "Overwritten", "content": "Other content", "tags": []}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["note"]["owner_id"], "testUser");
+
+    let (status, body) = call(&app, TestRequest::get().uri(&format!("{}/revisions", uri)).cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let revisions = body["content"].as_array().unwrap();
+    assert_eq!(revisions.len(), 1);
+    assert_eq!(revisions[0]["rev"], 1);
+    assert_eq!(revisions[0]["author_id"], "otherUser");
+    assert_eq!(revisions[0]["title"], "Test-Note");
+
+    let (status, body) = call(&app, TestRequest::get().uri(&format!("{}/revisions/1", uri)).cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["content"], "Some content");
+    assert_eq!(body["content"]["tags"], json!(["Test"]));
+}
+
+#[actix_rt::test]
+async fn revision_errors() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let uri = format!("/api/note/{}/revisions", note_id);
+
+    assert_error(call(&app, TestRequest::get().uri(&uri)).await, StatusCode::UNAUTHORIZED, 10);
+    assert_error(call(&app, TestRequest::get().uri(&uri).cookie(other)).await, StatusCode::FORBIDDEN, 12);
+    assert_error(call(&app, TestRequest::get().uri("/api/note/a$b/revisions").cookie(cookie.clone())).await,
+                 StatusCode::BAD_REQUEST, 21);
+    assert_error(call(&app, TestRequest::get().uri(&format!("{}/7", uri)).cookie(cookie.clone())).await,
+                 StatusCode::NOT_FOUND, 22);
+    assert_error(call(&app, TestRequest::post().uri(&format!("{}/7/restore", uri)).cookie(cookie)).await,
+                 StatusCode::NOT_FOUND, 22);
+}
+
+#[actix_rt::test]
+async fn diff_revisions() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let uri = format!("/api/note/{}", note_id);
+    for content in ["first\nsecond\nthird", "first\nchanged\nthird\nfourth"] {
+        call(&app, TestRequest::put().uri(&uri).cookie(cookie.clone())
+            .set_json(json!({"title": "Test-Note", "content": content, "tags": []}))).await;
+    }
+
+    let (status, body) = call(&app, TestRequest::get().uri(&format!("{}/revisions/2/diff", uri)).cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"], json!({"from": 2, "to": null, "lines": [
+        {"kind": "Unchanged", "line": "first"},
+        {"kind": "Removed", "line": "second"},
+        {"kind": "Added", "line": "changed"},
+        {"kind": "Unchanged", "line": "third"},
+        {"kind": "Added", "line": "fourth"}
+    ]}));
+
+    let (status, body) = call(&app, TestRequest::get().uri(&format!("{}/revisions/1/diff?to=2", uri)).cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["lines"], json!([
+        {"kind": "Removed", "line": "Some content"},
+        {"kind": "Added", "line": "first"},
+        {"kind": "Added", "line": "second"},
+        {"kind": "Added", "line": "third"}
+    ]));
+
+    assert_error(call(&app, TestRequest::get().uri(&format!("{}/revisions/1/diff?to=9", uri)).cookie(cookie.clone())).await,
+                 StatusCode::NOT_FOUND, 22);
+
+    // Lines moved around are found in between the changes
+    for content in ["a\nb\nc\nd\ne\nf\ng", "x\nb\nd\nc\ny\nf\nz"] {
+        call(&app, TestRequest::put().uri(&uri).cookie(cookie.clone())
+            .set_json(json!({"title": "Test-Note", "content": content, "tags": []}))).await;
+    }
+    let (_, body) = call(&app, TestRequest::get().uri(&format!("{}/revisions/4/diff", uri)).cookie(cookie.clone())).await;
+    let lines: Vec<String> = body["content"]["lines"].as_array().unwrap().iter()
+        .map(|line| format!("{}{}", &line["kind"].as_str().unwrap()[..1], line["line"].as_str().unwrap())).collect();
+    assert_eq!(lines, ["Ra", "Ax", "Ub", "Rc", "Ud", "Re", "Ac", "Ay", "Uf", "Rg", "Az"]);
+
+    // Texts differing too much are not compared
+    let content: Vec<String> = (0..=10_000).map(|line| line.to_string()).collect();
+    call(&app, TestRequest::put().uri(&uri).cookie(cookie.clone())
+        .set_json(json!({"title": "Test-Note", "content": content.join("\n"), "tags": []}))).await;
+    assert_error(call(&app, TestRequest::get().uri(&format!("{}/revisions/5/diff", uri)).cookie(cookie)).await,
+                 StatusCode::OK, 24);
+}
+
+#[actix_rt::test]
+async fn restore_revision() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let uri = format!("/api/note/{}", note_id);
+    call(&app, TestRequest::put().uri(&uri).cookie(cookie.clone())
+        .set_json(json!({"title": "Overwritten", "content": "Other content", "tags": []}))).await;
+
+    // Read-only collaborators may not restore
+    share_note(&app, &cookie, &note_id, "otherUser", "Read").await;
+    assert_error(call(&app, TestRequest::post().uri(&format!("{}/revisions/1/restore", uri)).cookie(other)).await,
+                 StatusCode::FORBIDDEN, 12);
+
+    let (status, body) = call(&app, TestRequest::post().uri(&format!("{}/revisions/1/restore", uri)).cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["note"]["title"], "Test-Note");
+    assert_eq!(body["content"]["note"]["content"], "Some content");
+
+    // The overwritten state is kept as well
+    let (_, body) = call(&app, TestRequest::get().uri(&format!("{}/revisions/2", uri)).cookie(cookie.clone())).await;
+    assert_eq!(body["content"]["title"], "Overwritten");
+    let (_, body) = call(&app, TestRequest::get().uri(&uri).cookie(cookie)).await;
+    assert_eq!(body["content"]["note"]["content"], "Some content");
+}
+
+#[actix_rt::test]
+async fn remove_note_removes_revisions() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let uri = format!("/api/note/{}", note_id);
+    call(&app, TestRequest::put().uri(&uri).cookie(cookie.clone())
+        .set_json(json!({"title": "Overwritten", "content": "Other content", "tags": []}))).await;
+    assert_eq!(db.get_revisions(&note_id).await.unwrap().len(), 1);
+
+    let (status, _) = call(&app, TestRequest::delete().uri(&uri).cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(db.get_revisions(&note_id).await.unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn conflicting_updates_keep_no_revision() {
+    let path = env::temp_dir().join(format!("writeup-revisions-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let backends: [Box<dyn Storage>; 2] = [Box::new(MemoryStorage::new()), Box::new(SqliteStorage::open(path.to_str().unwrap()).unwrap())];
+    for db in backends {
+        db.insert_user(&User { _id: "testUser".to_string(), allowances: Vec::new(), connections: Vec::new(), roles: Vec::new() }).await.unwrap();
+        let note = Note { title: "Test-Note".to_string(), content: "Hello".to_string(), owner_id: "testUser".to_string(), tags: Vec::new(),
+            version: 0, created_at: Utc::now(), updated_at: Utc::now(), last_editor_id: "testUser".to_string() };
+        let note_id = db.insert_note(&note).await.unwrap();
+        let revision = gen_revision(&note_id, &note, "testUser");
+        let updated = Note { content: "Hello World".to_string(), ..note.clone() };
+
+        // The revision is only kept along with the update of the note
+        assert!(matches!(db.set_note_fields(&note_id, &updated, 1, Some(&revision)).await, Err(DBError::VersionMismatchError(0))));
+        assert!(db.get_revisions(&note_id).await.unwrap().is_empty());
+        assert_eq!(db.set_note_fields(&note_id, &updated, 0, Some(&revision)).await.unwrap(), 1);
+        let revisions = db.get_revisions(&note_id).await.unwrap();
+        assert_eq!(revisions.len(), 1);
+        assert_eq!((revisions[0].rev, revisions[0].content.as_str()), (1, "Hello"));
+    }
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use chrono::Utc;
+use serde_json::json;
+use crate::db_access::Note;
+use crate::db_access::memory::MemoryStorage;
+use crate::web::search::SearchQuery;
+use crate::web::tests::{assert_error, call, connect, init_app, share_note, signup_and_login};
+
+/// Creates a note with the given content using the service
+macro_rules! note {
+    ($app:expr, $cookie:expr, $title:expr, $content:expr, $tags:expr) => {{
+        let (_, body) = call(&$app, TestRequest::post().uri("/api/note").cookie($cookie.clone())
+            .set_json(json!({"title": $title, "content": $content, "tags": $tags}))).await;
+        body["content"]["note_id"].as_str().unwrap().to_string()
+    }};
+}
+
+#[test]
+fn parse_query() {
+    assert_eq!(SearchQuery::parse(r#"  Tag:Rust "Exact  Phrase" word tag: "unclosed"#), SearchQuery {
+        terms: vec!["word".to_string(), "tag:".to_string()],
+        phrases: vec!["exact  phrase".to_string(), "unclosed".to_string()],
+        tags: vec!["rust".to_string()]
+    });
+    assert!(SearchQuery::parse(r#" "" "#).is_empty());
+}
+
+#[test]
+fn match_note() {
+    let note = Note { title: "Rust".to_string(), content: "Rust is fast. rust is safe.".to_string(),
+        owner_id: "testUser".to_string(), tags: vec!["Lang".to_string()], version: 0,
+        created_at: Utc::now(), updated_at: Utc::now(), last_editor_id: "testUser".to_string() };
+    let (score, snippet, highlights) = SearchQuery::parse("RUST").match_note(&note).unwrap();
+    assert_eq!(score, 5 + 2);
+    assert_eq!(snippet, "Rust is fast. rust is safe.");
+    assert_eq!(highlights, vec![[0, 4], [14, 18]]);
+    assert!(SearchQuery::parse("rust go").match_note(&note).is_none());
+    assert!(SearchQuery::parse("tag:rust").match_note(&note).is_none());
+    assert_eq!(SearchQuery::parse("tag:lang").match_note(&note).unwrap().0, 0);
+
+    // Snippets are cut around the first match
+    let note = Note { content: format!("{}needle{}", "a".repeat(100), "b".repeat(100)), ..note };
+    let (_, snippet, highlights) = SearchQuery::parse("needle").match_note(&note).unwrap();
+    assert_eq!(snippet, format!("…{}needle{}…", "a".repeat(60), "b".repeat(60)));
+    assert_eq!(highlights, vec![[61, 67]]);
+}
+
+#[actix_rt::test]
+async fn search_notes() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let content_match = note!(app, cookie, "Notes", "Some thoughts on Rust", ["Rust"]);
+    let title_match = note!(app, cookie, "Rust in action", "Nothing but rust", ["Book"]);
+    note!(app, cookie, "Unrelated", "Not a match", ["Other"]);
+    let shared = note!(app, other, "Shared", "rust and \"exact phrase\"", ["rust"]);
+    note!(app, other, "Private", "rust", ["Rust"]);
+    share_note(&app, &other, &shared, "testUser", "Read").await;
+
+    // Ordered by score, excluding inaccessible notes
+    let (status, body) = call(&app, TestRequest::get().uri("/api/notes/search?q=rust").cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let ids: Vec<&str> = body["content"].as_array().unwrap().iter().map(|res| res["note_id"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec![title_match.as_str(), content_match.as_str(), shared.as_str()]);
+    assert_eq!(body["content"][2], json!({"note_id": shared, "title": "Shared", "tags": ["rust"], "allowance": "Read",
+        "score": 4, "snippet": "rust and \"exact phrase\"", "highlights": [[0, 4]]}));
+
+    // Phrases and tags
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes/search?q=tag:RUST%20%22exact%20phrase%22").cookie(cookie.clone())).await;
+    assert_eq!(body["content"].as_array().unwrap().len(), 1);
+    assert_eq!(body["content"][0]["highlights"], json!([[10, 22]]));
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes/search?q=tag:book").cookie(cookie.clone())).await;
+    assert_eq!(body["content"][0]["note_id"], title_match);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes/search?q=%22phrase%20exact%22").cookie(cookie)).await;
+    assert_eq!(body["content"], json!([]));
+}
+
+#[actix_rt::test]
+async fn search_notes_errors() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    assert_error(call(&app, TestRequest::get().uri("/api/notes/search?q=rust")).await, StatusCode::UNAUTHORIZED, 10);
+    assert_error(call(&app, TestRequest::get().uri("/api/notes/search").cookie(cookie.clone())).await, StatusCode::BAD_REQUEST, 20);
+    assert_error(call(&app, TestRequest::get().uri("/api/notes/search?q=%20%20").cookie(cookie)).await, StatusCode::OK, 24);
+}
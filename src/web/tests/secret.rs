@@ -0,0 +1,60 @@
+use std::env;
+use std::fs;
+use crate::db_access::HashPolicy;
+use crate::directory::LdapConfig;
+use crate::secret::{load_or_generate_secret, load_secret, Secret, secret_from_env, SecretSource};
+use crate::web::OidcConfig;
+
+/// Returns the path to a new temporary file containing the given content
+fn secret_file(content: &str) -> String {
+    let path = env::temp_dir().join(format!("writeup-secret-{}.txt", rand::random::<u32>()));
+    fs::write(&path, content).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn redaction() {
+    let secret = Secret::new("hunter2");
+    assert_eq!(secret.expose(), "hunter2");
+    assert_eq!(format!("{} {:?} {:#?}", secret, secret, Some(&secret)), "[redacted] [redacted] Some(\n    [redacted],\n)");
+
+    // Configurations containing secrets can be logged safely
+    let policy = HashPolicy { memory_size: 8192, iterations: 2, pepper: Secret::new("pepperSecret"), old_peppers: vec![Secret::new("oldPepper")] };
+    let oidc = OidcConfig { issuer: "https://id.example.com".to_string(), client_id: "writeup".to_string(),
+        client_secret: Some(Secret::new("clientSecret")), redirect_uri: "http://localhost/api/auth/oidc/callback".to_string(),
+        auto_provision: false, post_login_uri: "/".to_string() };
+    let ldap = LdapConfig { url: "ldap://localhost".to_string(), base_dn: "dc=example".to_string(), user_filter: "(uid={username})".to_string(),
+        search_bind: Some(("cn=search,dc=example".to_string(), Secret::new("bindPassword"))), group_attribute: "memberOf".to_string(), group_roles: Vec::new() };
+    let logged = format!("{:?} {:?} {:?}", policy, oidc, ldap);
+    for secret in ["pepperSecret", "oldPepper", "clientSecret", "bindPassword"] {
+        assert!(!logged.contains(secret), "{} is revealed", secret);
+    }
+    assert!(logged.contains("cn=search,dc=example"));
+}
+
+#[test]
+fn secret_sources() {
+    // Secrets are read from files, without their trailing line-break
+    let path = secret_file("fileSecret\n");
+    env::set_var("WRITEUP_TEST_SECRET_FILE", &path);
+    assert_eq!(load_secret("WRITEUP_TEST_SECRET").unwrap(), SecretSource::File(path.clone()));
+    assert_eq!(secret_from_env("WRITEUP_TEST_SECRET"), Some(Secret::new("fileSecret")));
+    assert_eq!(SecretSource::File(path.clone()).to_string(), format!("set (file '{}')", path));
+
+    // Setting both is ambiguous
+    assert!(load_secret("WRITEUP_TEST_SECRET").is_err());
+    env::remove_var("WRITEUP_TEST_SECRET_FILE");
+    assert_eq!(load_secret("WRITEUP_TEST_SECRET").unwrap(), SecretSource::Env);
+    fs::remove_file(&path).unwrap();
+
+    // Missing files are reported without revealing anything
+    env::set_var("WRITEUP_TEST_MISSING_FILE", &path);
+    assert!(load_secret("WRITEUP_TEST_MISSING").unwrap_err().to_string().contains(&path));
+    env::remove_var("WRITEUP_TEST_MISSING_FILE");
+
+    // Unset secrets are either reported as such or generated
+    assert_eq!(load_secret("WRITEUP_TEST_UNSET").unwrap(), SecretSource::Unset);
+    assert_eq!(load_or_generate_secret("WRITEUP_TEST_GENERATED", 16).unwrap(), SecretSource::Generated);
+    assert_eq!(secret_from_env("WRITEUP_TEST_GENERATED").unwrap().expose().len(), 16);
+    assert_eq!(load_or_generate_secret("WRITEUP_TEST_GENERATED", 16).unwrap(), SecretSource::Env);
+}
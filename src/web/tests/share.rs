@@ -0,0 +1,179 @@
+use std::sync::Arc;
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use serde_json::{json, Value};
+use crate::db_access::memory::MemoryStorage;
+use crate::web::tests::{assert_error, call, connect, create_note, init_app, share_note, signup_and_login};
+
+#[actix_rt::test]
+async fn create_relation() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+
+    let (status, body) = call(&app, TestRequest::get().uri("/api/share").cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let code = body["content"]["code"].as_str().unwrap().to_string();
+
+    // Users can't connect with themselves
+    let response = call(&app, TestRequest::post().uri("/api/share").cookie(cookie.clone())
+        .set_json(json!({"code": code}))).await;
+    assert_error(response, StatusCode::OK, 24);
+
+    let (status, body) = call(&app, TestRequest::post().uri("/api/share").cookie(other.clone())
+        .set_json(json!({"code": code}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["user_id"], "testUser");
+
+    // The connection can only be established once
+    let response = call(&app, TestRequest::post().uri("/api/share").cookie(other)
+        .set_json(json!({"code": code}))).await;
+    assert_error(response, StatusCode::OK, 24);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/user").cookie(cookie)).await;
+    assert_eq!(body["content"]["relations"], json!(["otherUser"]));
+}
+
+#[actix_rt::test]
+async fn create_relation_errors() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+
+    let response = call(&app, TestRequest::post().uri("/api/share").cookie(cookie)
+        .set_json(json!({"code": "invalid"}))).await;
+    assert_error(response, StatusCode::OK, 27);
+    assert_error(call(&app, TestRequest::get().uri("/api/share")).await, StatusCode::UNAUTHORIZED, 10);
+    let response = call(&app, TestRequest::post().uri("/api/share").set_json(json!({"code": "invalid"}))).await;
+    assert_error(response, StatusCode::UNAUTHORIZED, 10);
+}
+
+#[actix_rt::test]
+async fn remove_relation() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let own_note = create_note(&app, &cookie, "Own").await;
+    let other_note = create_note(&app, &other, "Other").await;
+    share_note(&app, &cookie, &own_note, "otherUser", "Read").await;
+    share_note(&app, &other, &other_note, "testUser", "ReadWrite").await;
+
+    let (status, body) = call(&app, TestRequest::delete().uri("/api/share/otherUser").cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["success"], true);
+
+    // Both the relation and all shares are gone
+    let (_, body) = call(&app, TestRequest::get().uri("/api/user").cookie(other.clone())).await;
+    assert_eq!(body["content"]["relations"], json!([]));
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(cookie)).await;
+    let note = &body["content"]["notes"][0];
+    assert_eq!(body["content"]["notes"], json!([{"note_id": own_note, "title": "Own", "tags": ["Test"], "allowance": "Owner", "version": 0,
+        "created_at": note["created_at"], "updated_at": note["updated_at"], "last_editor_id": "testUser"}]));
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(other)).await;
+    let note = &body["content"]["notes"][0];
+    assert_eq!(body["content"]["notes"], json!([{"note_id": other_note, "title": "Other", "tags": ["Test"], "allowance": "Owner", "version": 0,
+        "created_at": note["created_at"], "updated_at": note["updated_at"], "last_editor_id": "otherUser"}]));
+}
+
+#[actix_rt::test]
+async fn remove_relation_errors() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+
+    assert_error(call(&app, TestRequest::delete().uri("/api/share/testUser").cookie(cookie.clone())).await,
+                 StatusCode::OK, 24);
+    assert_error(call(&app, TestRequest::delete().uri("/api/share/otherUser").cookie(cookie.clone())).await,
+                 StatusCode::OK, 24);
+    assert_error(call(&app, TestRequest::delete().uri("/api/share/te$t:User").cookie(cookie)).await,
+                 StatusCode::BAD_REQUEST, 21);
+    assert_error(call(&app, TestRequest::delete().uri("/api/share/otherUser")).await, StatusCode::UNAUTHORIZED, 10);
+}
+
+#[actix_rt::test]
+async fn update_allowances() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let uri = format!("/api/note/{}", note_id);
+    let update = json!({"title": "Updated", "content": "New content", "tags": []});
+
+    // Grant read-access
+    let (status, _) = share_note(&app, &cookie, &note_id, "otherUser", "Read").await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = call(&app, TestRequest::get().uri(&uri).cookie(other.clone())).await;
+    assert_eq!(body["content"]["allowance"], "Read");
+    assert_error(call(&app, TestRequest::put().uri(&uri).cookie(other.clone()).set_json(update.clone())).await,
+                 StatusCode::FORBIDDEN, 12);
+
+    // Alter to write-access
+    share_note(&app, &cookie, &note_id, "otherUser", "ReadWrite").await;
+    let (status, _) = call(&app, TestRequest::put().uri(&uri).cookie(other.clone()).set_json(update)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Revoke access
+    share_note(&app, &cookie, &note_id, "otherUser", "Forbidden").await;
+    assert_error(call(&app, TestRequest::get().uri(&uri).cookie(other)).await, StatusCode::FORBIDDEN, 12);
+}
+
+#[actix_rt::test]
+async fn update_allowances_errors() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    share_note(&app, &cookie, &note_id, "otherUser", "ReadWrite").await;
+
+    // Only the owner may share a note
+    assert_error(share_note(&app, &other, &note_id, "testUser", "Read").await, StatusCode::FORBIDDEN, 12);
+    assert_error(share_note(&app, &cookie, "72$4fa97b62u3:2dr4d3l", "otherUser", "Read").await, StatusCode::BAD_REQUEST, 21);
+    let response = call(&app, TestRequest::put().uri(&format!("/api/share/{}", note_id))
+        .set_json(json!([{"user_id": "otherUser", "allowance": "Read"}]))).await;
+    assert_error(response, StatusCode::UNAUTHORIZED, 10);
+}
+
+#[actix_rt::test]
+async fn bulk_allowances() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    let third = signup_and_login(&app, "thirdUser").await;
+    signup_and_login(&app, "strangerUser").await;
+    connect(&app, &cookie, &other).await;
+    connect(&app, &cookie, &third).await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    share_note(&app, &cookie, &note_id, "thirdUser", "Read").await;
+    let share = |body: Value| TestRequest::put().uri(&format!("/api/share/{}", note_id)).cookie(cookie.clone()).set_json(body);
+    let statuses = |body: &Value, key: &str| body[key].as_array().unwrap().iter()
+        .map(|result| result["status"].as_str().unwrap().to_string()).collect::<Vec<String>>();
+
+    // Malformed batches are rejected as a whole, reporting every entry
+    let response = call(&app, share(json!([{"user_id": "otherUser", "allowance": "Read"}, {"user_id": "otherUser", "allowance": "ReadWrite"},
+                                           {"user_id": "thirdUser", "allowance": "Owner"}]))).await;
+    assert_eq!(statuses(&response.1, "details"), vec!["Skipped", "Duplicate", "OwnerNotGrantable"]);
+    assert_error(response, StatusCode::BAD_REQUEST, 20);
+
+    // Batches naming users that can't be given access are rejected as a whole
+    let response = call(&app, share(json!([{"user_id": "otherUser", "allowance": "Read"}, {"user_id": "strangerUser", "allowance": "Read"},
+                                           {"user_id": "noUser", "allowance": "Read"}]))).await;
+    assert_eq!(statuses(&response.1, "details"), vec!["Skipped", "NotConnected", "NotConnected"]);
+    assert_error(response, StatusCode::OK, 24);
+    assert_error(call(&app, TestRequest::get().uri(&format!("/api/note/{}", note_id)).cookie(other.clone())).await,
+                 StatusCode::FORBIDDEN, 12);
+
+    // Valid batches are applied entirely, reporting the outcome for each user
+    let (status, body) = call(&app, share(json!([{"user_id": "otherUser", "allowance": "ReadWrite"},
+                                                 {"user_id": "thirdUser", "allowance": "Read"}]))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(statuses(&body, "content"), vec!["Granted", "Unchanged"]);
+    assert_eq!(body["content"][0]["user_id"], "otherUser");
+    assert_eq!(body["content"][0]["allowance"], "ReadWrite");
+    let (_, body) = call(&app, share(json!([{"user_id": "otherUser", "allowance": "Read"},
+                                            {"user_id": "thirdUser", "allowance": "Forbidden"}]))).await;
+    assert_eq!(statuses(&body, "content"), vec!["Changed", "Revoked"]);
+    let (_, body) = call(&app, TestRequest::get().uri(&format!("/api/note/{}", note_id)).cookie(other)).await;
+    assert_eq!(body["content"]["allowance"], "Read");
+    assert_error(call(&app, TestRequest::get().uri(&format!("/api/note/{}", note_id)).cookie(third)).await,
+                 StatusCode::FORBIDDEN, 12);
+}
@@ -0,0 +1,73 @@
+use std::env;
+use std::sync::Arc;
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use crate::db_access::{CredentialStore, DBError, MigrationStore, NoteStore};
+use crate::db_access::memory::MemoryStorage;
+use crate::db_access::migration::{migrate, pending_migrations, SCHEMA_VERSION};
+use crate::db_access::sqlite::SqliteStorage;
+use crate::web::tests::{call, init_app};
+
+#[actix_rt::test]
+async fn system_status_reports_backend() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let (status, body) = call(&app, TestRequest::get().uri("/api/system")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["application"], env!("CARGO_PKG_NAME"));
+    assert_eq!(body["content"]["db"]["type"], "memory");
+}
+
+#[actix_rt::test]
+async fn migrations_are_applied_once() {
+    let db = MemoryStorage::new();
+    assert_eq!(db.get_schema_version().await.unwrap(), 0);
+    assert_eq!(migrate(&db).await.unwrap(), (1..=SCHEMA_VERSION).collect::<Vec<u32>>());
+    assert_eq!(db.get_schema_version().await.unwrap(), SCHEMA_VERSION);
+    assert!(migrate(&db).await.unwrap().is_empty());
+
+    // Databases newer than the binary are rejected
+    db.apply_migration(SCHEMA_VERSION + 1, "from the future").await.unwrap();
+    assert!(matches!(pending_migrations(&db).await, Err(DBError::UnsupportedSchemaError(version)) if version == SCHEMA_VERSION + 1));
+}
+
+#[actix_rt::test]
+async fn migrate_legacy_sqlite_database() {
+    let path = env::temp_dir().join(format!("writeup-migration-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    // A database created before notes were versioned and timestamped and credentials had a second factor
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch("
+        CREATE TABLE user (id TEXT PRIMARY KEY NOT NULL);
+        CREATE TABLE note (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT NOT NULL,
+            content TEXT NOT NULL,
+            owner_id TEXT NOT NULL REFERENCES user(id) ON DELETE CASCADE
+        );
+        CREATE TABLE credential (
+            username TEXT PRIMARY KEY NOT NULL REFERENCES user(id) ON DELETE CASCADE,
+            passwd_hash TEXT NOT NULL
+        );
+        INSERT INTO user (id) VALUES ('testUser');
+        INSERT INTO credential (username, passwd_hash) VALUES ('testUser', 'hash');
+        INSERT INTO note (title, content, owner_id) VALUES ('Old', 'Some content', 'testUser');
+    ").unwrap();
+    drop(conn);
+
+    let db = SqliteStorage::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(migrate(&db).await.unwrap().len() as u32, SCHEMA_VERSION);
+    let note = db.get_note("1").await.unwrap();
+    assert_eq!(note.version, 0);
+    assert_eq!(note.last_editor_id, "testUser");
+    assert_eq!(note.created_at, note.updated_at);
+    let summaries = db.get_note_summaries(&["1".to_string(), "2".to_string(), "malformed".to_string()]).await.unwrap();
+    assert_eq!(summaries.len(), 1);
+    assert_eq!((summaries[0].0.as_str(), summaries[0].1.title.as_str(), summaries[0].1.version), ("1", "Old", 0));
+    let cred = db.get_credential("testUser").await.unwrap();
+    assert!(!cred.totp_enabled && cred.totp_secret.is_none() && cred.recovery_hashes.is_empty());
+    // Migrations are recorded persistently
+    drop(db);
+    let db = SqliteStorage::open(path.to_str().unwrap()).unwrap();
+    assert!(pending_migrations(&db).await.unwrap().is_empty());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,104 @@
+use std::sync::Arc;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use serde_json::json;
+use crate::db_access::memory::MemoryStorage;
+use crate::web::tests::{assert_error, call, create_note, init_app, signup_and_login};
+
+/// Attaches a personal access-token to a request
+fn bearer(req: TestRequest, token: &str) -> TestRequest {
+    req.insert_header((AUTHORIZATION, format!("Bearer {}", token)))
+}
+
+#[actix_rt::test]
+async fn token_lifecycle() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+
+    let (status, body) = call(&app, TestRequest::post().uri("/api/user/tokens").cookie(cookie.clone())
+        .set_json(json!({"name": " Backup ", "scopes": ["notes:read", "notes:read"], "expires_in_days": 30}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let token = body["content"]["token"].as_str().unwrap().to_string();
+    let token_id = body["content"]["token_id"].as_str().unwrap().to_string();
+    assert!(token.starts_with(&format!("wup_{}.", token_id)));
+    assert_eq!(body["content"]["name"], "Backup");
+    assert_eq!(body["content"]["scopes"], json!(["notes:read"]));
+
+    // The token grants access to the endpoints covered by its scopes only
+    let (status, body) = call(&app, bearer(TestRequest::get().uri(&format!("/api/note/{}", note_id)), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["note"]["title"], "Test-Note");
+    let (status, _) = call(&app, bearer(TestRequest::get().uri("/api/notes"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&app, bearer(TestRequest::post().uri("/api/note"), &token)
+        .set_json(json!({"title": "Script", "content": "", "tags": []}))).await, StatusCode::FORBIDDEN, 12);
+    assert_error(call(&app, bearer(TestRequest::get().uri("/api/share"), &token)).await, StatusCode::FORBIDDEN, 12);
+    assert_error(call(&app, bearer(TestRequest::get().uri("/api/user"), &token)).await, StatusCode::FORBIDDEN, 12);
+    // Tokens can't be used to manage tokens
+    assert_error(call(&app, bearer(TestRequest::get().uri("/api/user/tokens"), &token)).await, StatusCode::UNAUTHORIZED, 10);
+
+    // Listing never reveals the token itself
+    let (_, body) = call(&app, TestRequest::get().uri("/api/user/tokens").cookie(cookie.clone())).await;
+    let tokens = body["content"].as_array().unwrap();
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens[0]["token_id"], token_id.as_str());
+    assert!(tokens[0].get("token").is_none());
+    assert!(tokens[0]["last_used"].is_string());
+
+    // Revoked tokens are rejected
+    let (status, _) = call(&app, TestRequest::delete().uri(&format!("/api/user/tokens/{}", token_id)).cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&app, bearer(TestRequest::get().uri("/api/notes"), &token)).await, StatusCode::UNAUTHORIZED, 10);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/user/tokens").cookie(cookie)).await;
+    assert_eq!(body["content"], json!([]));
+}
+
+#[actix_rt::test]
+async fn write_and_share_scopes() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let (_, body) = call(&app, TestRequest::post().uri("/api/user/tokens").cookie(cookie.clone())
+        .set_json(json!({"name": "Sync", "scopes": ["notes:write", "share:manage"]}))).await;
+    let token = body["content"]["token"].as_str().unwrap().to_string();
+    assert_eq!(body["content"]["expires_at"], json!(null));
+
+    let (status, body) = call(&app, bearer(TestRequest::post().uri("/api/note"), &token)
+        .set_json(json!({"title": "Script", "content": "", "tags": []}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["content"]["note"]["owner_id"], "testUser");
+    let (status, _) = call(&app, bearer(TestRequest::get().uri("/api/share"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&app, bearer(TestRequest::get().uri("/api/notes"), &token)).await, StatusCode::FORBIDDEN, 12);
+}
+
+#[actix_rt::test]
+async fn invalid_tokens() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    let (_, body) = call(&app, TestRequest::post().uri("/api/user/tokens").cookie(cookie.clone())
+        .set_json(json!({"name": "Backup", "scopes": ["notes:read"]}))).await;
+    let token = body["content"]["token"].as_str().unwrap().to_string();
+    let token_id = body["content"]["token_id"].as_str().unwrap().to_string();
+
+    // Wrong secrets and malformed tokens
+    let (id_part, _) = token.split_once('.').unwrap();
+    for invalid in [format!("{}.wrongSecret", id_part), "wup_".to_string(), "not-a-token".to_string(), token.replace("wup_", "")] {
+        assert_error(call(&app, bearer(TestRequest::get().uri("/api/notes"), &invalid)).await, StatusCode::UNAUTHORIZED, 10);
+    }
+    // Tokens of other user can't be revoked
+    assert_error(call(&app, TestRequest::delete().uri(&format!("/api/user/tokens/{}", token_id)).cookie(other)).await, StatusCode::NOT_FOUND, 22);
+
+    // Invalid requests for new tokens
+    for payload in [json!({"name": "Backup", "scopes": []}), json!({"name": "  ", "scopes": ["notes:read"]}),
+                    json!({"name": "Backup", "scopes": ["notes:read"], "expires_in_days": 0}),
+                    json!({"name": "Backup", "scopes": ["notes:read"], "expires_in_days": u32::MAX}),
+                    json!({"name": "Backup", "scopes": ["notes:everything"]})] {
+        assert_error(call(&app, TestRequest::post().uri("/api/user/tokens").cookie(cookie.clone()).set_json(payload)).await,
+                     StatusCode::BAD_REQUEST, 20);
+    }
+    assert_error(call(&app, TestRequest::post().uri("/api/user/tokens")
+        .set_json(json!({"name": "Backup", "scopes": ["notes:read"]}))).await, StatusCode::UNAUTHORIZED, 10);
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+use actix_web::cookie::Cookie;
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, read_body_json};
+use actix_web::test::TestRequest;
+use chrono::{Duration, TimeZone, Utc};
+use serde_json::{json, Value};
+use crate::db_access::memory::MemoryStorage;
+use crate::web::tests::{assert_error, call, init_app, JWT_COOKIE, PASSWORD, session_cookies, signup_and_login};
+use crate::web::totp::{gen_totp, verify_totp};
+
+/// Logs a user in using their password, returning the body of the response
+macro_rules! password_login {
+    ($app:expr, $username:expr) => {{
+        call(&$app, TestRequest::post().uri("/api/auth")
+            .set_json(json!({"username": $username, "password": PASSWORD, "session_only": false}))).await
+    }};
+}
+
+#[test]
+fn one_time_passwords() {
+    // Test-vector of RFC 6238
+    let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    let time = Utc.timestamp(59, 0);
+    assert_eq!(gen_totp(secret, time).unwrap(), "287082");
+    assert_eq!(gen_totp(secret, Utc.timestamp(1111111109, 0)).unwrap(), "081804");
+    assert!(gen_totp("not base32!", time).is_none());
+
+    // Neighbouring steps are accepted, used ones are not
+    assert_eq!(verify_totp(secret, " 287082 ", 0, time), Some(1));
+    assert_eq!(verify_totp(secret, "287082", 0, time + Duration::seconds(30)), Some(1));
+    assert_eq!(verify_totp(secret, "287082", 0, time + Duration::seconds(60)), None);
+    assert_eq!(verify_totp(secret, "287082", 1, time), None);
+    assert_eq!(verify_totp(secret, "28708", 0, time), None);
+}
+
+#[actix_rt::test]
+async fn enrolment_and_login() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+
+    // Confirming requires a started enrolment
+    assert_error(call(&app, TestRequest::put().uri("/api/user/totp").cookie(cookie.clone())
+        .set_json(json!({"code": "123456"}))).await, StatusCode::OK, 24);
+    let (status, body) = call(&app, TestRequest::post().uri("/api/user/totp").cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    let secret = body["content"]["secret"].as_str().unwrap().to_string();
+    assert_eq!(body["content"]["uri"], format!("otpauth://totp/writeUp:testUser?secret={}&issuer=writeUp&algorithm=SHA1&digits=6&period=30", secret));
+    // Not enabled before the confirmation
+    let (_, body) = password_login!(app, "testUser");
+    assert!(body["content"].is_null());
+
+    assert_error(call(&app, TestRequest::put().uri("/api/user/totp").cookie(cookie.clone())
+        .set_json(json!({"code": "abcdef"}))).await, StatusCode::OK, 11);
+    let code = gen_totp(&secret, Utc::now()).unwrap();
+    let (status, body) = call(&app, TestRequest::put().uri("/api/user/totp").cookie(cookie.clone())
+        .set_json(json!({"code": code}))).await;
+    assert_eq!(status, StatusCode::OK);
+    let recovery_codes: Vec<String> = serde_json::from_value(body["content"]["recovery_codes"].clone()).unwrap();
+    assert_eq!(recovery_codes.len(), 10);
+    assert_error(call(&app, TestRequest::post().uri("/api/user/totp").cookie(cookie.clone())).await, StatusCode::OK, 24);
+
+    // The password alone only yields a challenge
+    let resp = call_service(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "testUser", "password": PASSWORD, "session_only": false})).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(session_cookies(&resp).is_none());
+    let body: Value = read_body_json(resp).await;
+    assert_eq!(body["content"]["second_factor_required"], true);
+    let challenge = body["content"]["challenge"].as_str().unwrap().to_string();
+    // The challenge is no JWT
+    let (_, body) = call(&app, TestRequest::get().uri("/api/auth")
+        .cookie(Cookie::new(JWT_COOKIE, challenge.clone()))).await;
+    assert_eq!(body["success"], false);
+
+    // Used one-time passwords are rejected
+    assert_error(call(&app, TestRequest::post().uri("/api/auth/totp")
+        .set_json(json!({"challenge": challenge, "code": code}))).await, StatusCode::OK, 11);
+    assert_error(call(&app, TestRequest::post().uri("/api/auth/totp")
+        .set_json(json!({"challenge": "invalid", "code": code}))).await, StatusCode::UNAUTHORIZED, 10);
+    let next_code = gen_totp(&secret, Utc::now() + Duration::seconds(30)).unwrap();
+    let resp = call_service(&app, TestRequest::post().uri("/api/auth/totp")
+        .set_json(json!({"challenge": challenge, "code": next_code})).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let (jwt, _) = session_cookies(&resp).expect("login failed");
+    let (status, _) = call(&app, TestRequest::get().uri("/api/auth").cookie(jwt)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Recovery-codes can only be used once
+    let (_, body) = password_login!(app, "testUser");
+    let challenge = body["content"]["challenge"].as_str().unwrap().to_string();
+    let resp = call_service(&app, TestRequest::post().uri("/api/auth/totp")
+        .set_json(json!({"challenge": challenge, "code": recovery_codes[0].to_uppercase()})).to_request()).await;
+    assert!(session_cookies(&resp).is_some());
+    assert_error(call(&app, TestRequest::post().uri("/api/auth/totp")
+        .set_json(json!({"challenge": challenge, "code": recovery_codes[0]}))).await, StatusCode::OK, 11);
+
+    // Disabling requires the second factor
+    assert_error(call(&app, TestRequest::delete().uri("/api/user/totp").cookie(cookie.clone())
+        .set_json(json!({"code": recovery_codes[0]}))).await, StatusCode::OK, 11);
+    let (status, _) = call(&app, TestRequest::delete().uri("/api/user/totp").cookie(cookie.clone())
+        .set_json(json!({"code": recovery_codes[1]}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(call(&app, TestRequest::delete().uri("/api/user/totp").cookie(cookie)
+        .set_json(json!({"code": recovery_codes[2]}))).await, StatusCode::OK, 24);
+    let (_, body) = password_login!(app, "testUser");
+    assert!(body["content"].is_null());
+}
@@ -0,0 +1,189 @@
+use std::sync::Arc;
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use serde_json::{json, Value};
+use crate::db_access::{FolderStore, NoteStore, TransferStore};
+use crate::db_access::memory::MemoryStorage;
+use crate::web::tests::{assert_error, call, connect, create_note, init_app, share_note, signup_and_login};
+
+/// Offers a note to another user, returning the response
+macro_rules! offer_note {
+    ($app:expr, $cookie:expr, $note_id:expr, $body:expr) => {
+        call(&$app, TestRequest::post().uri(&format!("/api/note/{}/transfer", $note_id)).cookie($cookie.clone())
+            .set_json($body)).await
+    };
+}
+
+/// Accepts the offer of a note, returning the response
+macro_rules! accept_note {
+    ($app:expr, $cookie:expr, $note_id:expr) => {
+        call(&$app, TestRequest::post().uri(&format!("/api/note/{}/transfer/accept", $note_id)).cookie($cookie.clone())).await
+    };
+}
+
+#[actix_rt::test]
+async fn transfer_ownership() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    signup_and_login(&app, "strangerUser").await;
+    connect(&app, &cookie, &other).await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let (_, body) = call(&app, TestRequest::post().uri("/api/folders").cookie(cookie.clone())
+        .set_json(json!({"name": "Projects"}))).await;
+    let folder_id = body["content"]["folder_id"].as_str().unwrap().to_string();
+    call(&app, TestRequest::put().uri(&format!("/api/note/{}/folder", note_id)).cookie(cookie.clone())
+        .set_json(json!({"folder_id": folder_id}))).await;
+
+    // Only connections can be offered a note, and only by its owner
+    assert_error(offer_note!(app, cookie, note_id, json!({"user_id": "strangerUser"})), StatusCode::OK, 24);
+    assert_error(offer_note!(app, cookie, note_id, json!({"user_id": "testUser"})), StatusCode::OK, 24);
+    assert_error(offer_note!(app, cookie, note_id, json!({"user_id": "otherUser", "previous_owner_level": "Owner"})),
+                 StatusCode::BAD_REQUEST, 20);
+    share_note(&app, &cookie, &note_id, "otherUser", "ReadWrite").await;
+    assert_error(offer_note!(app, other, note_id, json!({"user_id": "testUser"})), StatusCode::FORBIDDEN, 12);
+
+    // The note stays untouched until the offer is accepted
+    let (status, body) = offer_note!(app, cookie, note_id, json!({"user_id": "otherUser", "previous_owner_level": "Read"}));
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["content"]["to_id"], "otherUser");
+    for session in [&cookie, &other] {
+        let (_, body) = call(&app, TestRequest::get().uri("/api/transfers").cookie(session.clone())).await;
+        assert_eq!(body["content"][0]["note_id"], note_id.as_str());
+        assert_eq!(body["content"][0]["previous_owner_level"], "Read");
+    }
+    assert_error(accept_note!(app, cookie, note_id), StatusCode::NOT_FOUND, 22);
+    let (_, body) = call(&app, TestRequest::get().uri(&format!("/api/note/{}", note_id)).cookie(other.clone())).await;
+    assert_eq!(body["content"]["allowance"], "ReadWrite");
+
+    // Accepting swaps the roles, the previous owner keeps the chosen level
+    let (status, _) = accept_note!(app, other, note_id);
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = call(&app, TestRequest::get().uri(&format!("/api/note/{}", note_id)).cookie(other.clone())).await;
+    assert_eq!(body["content"]["note"]["owner_id"], "otherUser");
+    assert_eq!(body["content"]["allowance"], "Owner");
+    let (_, body) = call(&app, TestRequest::get().uri(&format!("/api/note/{}", note_id)).cookie(cookie.clone())).await;
+    assert_eq!(body["content"]["allowance"], "Read");
+    assert_error(offer_note!(app, cookie, note_id, json!({"user_id": "otherUser"})), StatusCode::FORBIDDEN, 12);
+    assert!(db.get_folder(&folder_id).await.unwrap().notes.is_empty());
+    let (_, body) = call(&app, TestRequest::get().uri("/api/transfers").cookie(other.clone())).await;
+    assert_eq!(body["content"], json!([]));
+    assert_error(accept_note!(app, other, note_id), StatusCode::NOT_FOUND, 22);
+
+    // The new owner may hand it back, revoking the access of the previous owner entirely
+    offer_note!(app, other, note_id, json!({"user_id": "testUser", "previous_owner_level": "Forbidden"}));
+    accept_note!(app, cookie, note_id);
+    assert_error(call(&app, TestRequest::get().uri(&format!("/api/note/{}", note_id)).cookie(other)).await,
+                 StatusCode::FORBIDDEN, 12);
+}
+
+#[actix_rt::test]
+async fn cancelled_transfers() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    let third = signup_and_login(&app, "thirdUser").await;
+    connect(&app, &cookie, &other).await;
+    connect(&app, &cookie, &third).await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let uri = format!("/api/note/{}/transfer", note_id);
+
+    // Offers can only be seen and used by both parties
+    offer_note!(app, cookie, note_id, json!({"user_id": "otherUser"}));
+    assert_error(accept_note!(app, third, note_id), StatusCode::NOT_FOUND, 22);
+    assert_error(call(&app, TestRequest::delete().uri(&uri).cookie(third.clone())).await, StatusCode::NOT_FOUND, 22);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/transfers").cookie(third.clone())).await;
+    assert_eq!(body["content"], json!([]));
+
+    // A new offer replaces the pending one
+    offer_note!(app, cookie, note_id, json!({"user_id": "thirdUser"}));
+    assert_error(accept_note!(app, other, note_id), StatusCode::NOT_FOUND, 22);
+
+    // Offers can be declined by the receiver and withdrawn by the owner
+    let (status, _) = call(&app, TestRequest::delete().uri(&uri).cookie(third.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(accept_note!(app, third, note_id), StatusCode::NOT_FOUND, 22);
+    offer_note!(app, cookie, note_id, json!({"user_id": "thirdUser"}));
+    let (status, _) = call(&app, TestRequest::delete().uri(&uri).cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_error(accept_note!(app, third, note_id), StatusCode::NOT_FOUND, 22);
+
+    // Offers of severed connections can't be accepted, offers of removed notes are gone
+    offer_note!(app, cookie, note_id, json!({"user_id": "otherUser"}));
+    call(&app, TestRequest::delete().uri("/api/share/testUser").cookie(other.clone())).await;
+    assert_error(accept_note!(app, other, note_id), StatusCode::OK, 24);
+    call(&app, TestRequest::delete().uri(&format!("/api/note/{}", note_id)).cookie(cookie)).await;
+    assert!(db.get_transfer(&note_id).await.is_err());
+}
+
+#[actix_rt::test]
+async fn transfer_before_removal() {
+    let db = Arc::new(MemoryStorage::new());
+    let app = init_app(db.clone()).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    let private_note = create_note(&app, &cookie, "Private-Note").await;
+    share_note(&app, &cookie, &note_id, "otherUser", "Read").await;
+    let other_note = create_note(&app, &other, "Other-Note").await;
+    offer_note!(app, other, other_note, json!({"user_id": "testUser"}));
+
+    // Removing the user is refused as long as they share notes
+    let (_, body) = call(&app, TestRequest::delete().uri("/api/user").cookie(cookie.clone())).await;
+    assert_eq!(body["code"], 24);
+    assert_eq!(body["details"][0]["note_id"], note_id.as_str());
+    offer_note!(app, cookie, note_id, json!({"user_id": "otherUser"}));
+    accept_note!(app, other, note_id);
+
+    // Handed over notes survive, everything else is removed along with the offers made to the user
+    let (status, _) = call(&app, TestRequest::delete().uri("/api/user").cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(other.clone())).await;
+    let titles: Vec<&Value> = body["content"]["notes"].as_array().unwrap().iter().map(|note| &note["title"]).collect();
+    assert_eq!(titles, vec!["Test-Note", "Other-Note"]);
+    assert!(db.get_note(&private_note).await.is_err());
+    let (_, body) = call(&app, TestRequest::get().uri("/api/transfers").cookie(other)).await;
+    assert_eq!(body["content"], json!([]));
+}
+
+#[actix_rt::test]
+async fn removal_with_indirect_shares() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let folder_note = create_note(&app, &cookie, "Folder-Note").await;
+    let group_note = create_note(&app, &cookie, "Group-Note").await;
+    create_note(&app, &cookie, "Private-Note").await;
+
+    // Share one note through a folder and the other through a group
+    let (_, body) = call(&app, TestRequest::post().uri("/api/folders").cookie(cookie.clone())
+        .set_json(json!({"name": "Projects"}))).await;
+    let folder_id = body["content"]["folder_id"].as_str().unwrap().to_string();
+    call(&app, TestRequest::put().uri(&format!("/api/note/{}/folder", folder_note)).cookie(cookie.clone())
+        .set_json(json!({"folder_id": folder_id}))).await;
+    call(&app, TestRequest::put().uri(&format!("/api/folders/{}/share", folder_id)).cookie(cookie.clone())
+        .set_json(json!([{"user_id": "otherUser", "allowance": "Read"}]))).await;
+    let (_, body) = call(&app, TestRequest::post().uri("/api/groups").cookie(cookie.clone())
+        .set_json(json!({"name": "Team"}))).await;
+    let group_id = body["content"]["group_id"].as_str().unwrap().to_string();
+    call(&app, TestRequest::post().uri(&format!("/api/groups/{}/members", group_id)).cookie(cookie.clone())
+        .set_json(json!({"user_id": "otherUser"}))).await;
+    call(&app, TestRequest::put().uri(&format!("/api/share/{}/groups", group_note)).cookie(cookie.clone())
+        .set_json(json!([{"group_id": group_id, "allowance": "Read"}]))).await;
+
+    // Indirectly shared notes are reported just like directly shared ones
+    let (_, body) = call(&app, TestRequest::delete().uri("/api/user").cookie(cookie.clone())).await;
+    assert_eq!(body["code"], 24);
+    let mut shared: Vec<&str> = body["details"].as_array().unwrap().iter().map(|note| note["note_id"].as_str().unwrap()).collect();
+    shared.sort();
+    let mut expected = vec![folder_note.as_str(), group_note.as_str()];
+    expected.sort();
+    assert_eq!(shared, expected);
+    assert!(body["details"].as_array().unwrap().iter().all(|note| note["shared_with"] == json!(["otherUser"])));
+    let (status, _) = call(&app, TestRequest::delete().uri("/api/user?delete_shared_notes=true").cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+}
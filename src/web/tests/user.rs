@@ -0,0 +1,98 @@
+use std::sync::Arc;
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use serde_json::json;
+use crate::db_access::memory::MemoryStorage;
+use crate::web::tests::{assert_error, call, connect, create_note, init_app, login, PASSWORD, read_only_storage_with_user, share_note, signup, signup_and_login};
+
+#[actix_rt::test]
+async fn add_user() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let (status, body) = signup(&app, "testUser").await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["content"]["username"], "testUser");
+    assert_eq!(body["content"]["relations"], json!([]));
+}
+
+#[actix_rt::test]
+async fn add_user_with_existing_name() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    signup(&app, "testUser").await;
+    assert_error(signup(&app, "testUser").await, StatusCode::OK, 11);
+}
+
+#[actix_rt::test]
+async fn add_user_while_logged_in() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let response = call(&app, TestRequest::post().uri("/api/user").cookie(cookie)
+        .set_json(json!({"username": "otherUser", "password": PASSWORD, "beta_key": super::BETA_KEY}))).await;
+    assert_error(response, StatusCode::FORBIDDEN, 12);
+}
+
+#[actix_rt::test]
+async fn add_user_with_invalid_beta_key() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let response = call(&app, TestRequest::post().uri("/api/user")
+        .set_json(json!({"username": "testUser", "password": PASSWORD, "beta_key": "invalid"}))).await;
+    assert_error(response, StatusCode::FORBIDDEN, 12);
+}
+
+#[actix_rt::test]
+async fn add_user_with_failing_storage() {
+    let app = init_app(read_only_storage_with_user("testUser").await).await;
+    assert_error(signup(&app, "otherUser").await, StatusCode::INTERNAL_SERVER_ERROR, 54);
+}
+
+#[actix_rt::test]
+async fn get_user() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+
+    let (status, body) = call(&app, TestRequest::get().uri("/api/user").cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"]["username"], "testUser");
+    assert_eq!(body["content"]["relations"], json!(["otherUser"]));
+    assert_error(call(&app, TestRequest::get().uri("/api/user")).await, StatusCode::UNAUTHORIZED, 10);
+}
+
+#[actix_rt::test]
+async fn remove_user() {
+    let app = init_app(Arc::new(MemoryStorage::new())).await;
+    let cookie = signup_and_login(&app, "testUser").await;
+    let other = signup_and_login(&app, "otherUser").await;
+    connect(&app, &cookie, &other).await;
+    let note_id = create_note(&app, &cookie, "Test-Note").await;
+    share_note(&app, &cookie, &note_id, "otherUser", "Read").await;
+
+    // Shared notes are listed instead of being removed, unless asked for
+    let response = call(&app, TestRequest::delete().uri("/api/user").cookie(cookie.clone())).await;
+    assert_eq!(response.1["details"], json!([{"note_id": note_id, "shared_with": ["otherUser"]}]));
+    assert_error(response, StatusCode::OK, 24);
+    let (status, _) = call(&app, TestRequest::get().uri(&format!("/api/note/{}", note_id)).cookie(other.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = call(&app, TestRequest::delete().uri("/api/user?delete_shared_notes=true").cookie(cookie.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["success"], true);
+
+    // The user, their notes and their relations are gone
+    assert_error(call(&app, TestRequest::get().uri("/api/user").cookie(cookie)).await, StatusCode::UNAUTHORIZED, 10);
+    let (_, body) = call(&app, TestRequest::get().uri("/api/user").cookie(other.clone())).await;
+    assert_eq!(body["content"]["relations"], json!([]));
+    let (_, body) = call(&app, TestRequest::get().uri("/api/notes").cookie(other)).await;
+    assert_eq!(body["content"]["notes"], json!([]));
+    let response = call(&app, TestRequest::post().uri("/api/auth")
+        .set_json(json!({"username": "testUser", "password": PASSWORD, "session_only": true}))).await;
+    assert_error(response, StatusCode::OK, 11);
+}
+
+#[actix_rt::test]
+async fn remove_user_with_failing_storage() {
+    let app = init_app(read_only_storage_with_user("testUser").await).await;
+    let cookie = login(&app, "testUser").await;
+    let response = call(&app, TestRequest::delete().uri("/api/user").cookie(cookie)).await;
+    assert_error(response, StatusCode::INTERNAL_SERVER_ERROR, 54);
+}
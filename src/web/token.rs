@@ -0,0 +1,297 @@
+//! Endpoints regarding personal access-tokens, which let scripts access the API without a login
+//!
+//! A token is handed out exactly once upon its creation, only a hash of it is stored.
+//! Requests carry it inside of an `Authorization: Bearer <token>`-header
+//! and may only access the endpoints covered by the [`TokenScope`]s granted to it.
+//! Tokens can only be managed using a session, never using another token.
+
+use actix_web::{get, post, delete, Responder, HttpRequest, HttpResponse, web};
+use actix_web::web::{Data, Path};
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use crate::db_access::{ApiToken, DBError, is_safe, Storage, TokenScope};
+use crate::web::auth::{API_TOKEN_PREFIX, get_session_from_request, hash_token};
+use crate::web::error::APIError;
+use crate::web::{ResponseObject, ResponseObjectWithPayload};
+use crate::web::token::json_objects::{CreatedTokenResponse, TokenRequest, TokenResponse};
+
+/// Amount of characters making up the identifier of a token
+const TOKEN_ID_SIZE: usize = 16;
+/// Amount of characters making up the secret part of a token
+const TOKEN_SECRET_SIZE: usize = 40;
+/// The maximum length of the name of a token
+const MAX_TOKEN_NAME_LENGTH: usize = 64;
+
+// Response-/Request-Objects
+/// Structs modelling the request- and response-bodies
+mod json_objects {
+    use chrono::{DateTime, Utc};
+    use serde::{Serialize, Deserialize};
+    use crate::db_access::{ApiToken, TokenScope};
+
+    /// Body of a request for a new token
+    #[derive(Deserialize)]
+    pub struct TokenRequest {
+        /// A name describing the purpose of the token
+        pub name: String,
+        /// The permissions to be granted to the token
+        pub scopes: Vec<TokenScope>,
+        /// Amount of days until the token expires (never if not set)
+        pub expires_in_days: Option<u32>
+    }
+
+    /// Body of a response containing a token without its secret
+    #[derive(Serialize)]
+    pub struct TokenResponse {
+        /// The identifier of the token
+        pub token_id: String,
+        /// A name describing the purpose of the token
+        pub name: String,
+        /// The permissions granted to the token
+        pub scopes: Vec<TokenScope>,
+        /// Timestamp of the creation
+        pub created_at: DateTime<Utc>,
+        /// Timestamp after which the token is no longer accepted
+        pub expires_at: Option<DateTime<Utc>>,
+        /// Timestamp of when the token was last used
+        pub last_used: Option<DateTime<Utc>>
+    }
+    impl From<ApiToken> for TokenResponse {
+        fn from(token: ApiToken) -> Self {
+            TokenResponse {
+                token_id: token._id,
+                name: token.name,
+                scopes: token.scopes,
+                created_at: token.created_at,
+                expires_at: token.expires_at,
+                last_used: token.last_used
+            }
+        }
+    }
+
+    /// Body of a response containing a newly created token including its secret
+    #[derive(Serialize)]
+    pub struct CreatedTokenResponse {
+        /// The token to be used in the Authorization-header
+        pub token: String,
+        /// Everything else known about the token
+        #[serde(flatten)]
+        pub info: TokenResponse
+    }
+}
+
+/// ENDPOINT: Creates a new personal access-token for the current user.
+/// The token itself is only ever returned by this endpoint
+///
+/// Returns one of the following HttpResponses:
+/// * `201`
+///     - \[Body: JSON\] Token was created successfully
+/// * `400`
+///     - **\[20\]** Missing name or scopes, or an expiration of zero days or too far in the future
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `token_req` - The body of the request parsed to a TokenRequest-object
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// POST-Request at `{api-url}/user/tokens` with a cookie containing a valid JWT
+///     {
+///         "name": "Backup-Script",
+///         "scopes": ["notes:read"],
+///         "expires_in_days": 90
+///     }
+/// => 201
+///     {
+///         "success": true,
+///         "content": {
+///             "token": "wup_k3Jd9sLq0PzX7mWb.Gq2vN8rTxY5cLh1ZpK0aWm7sJd3Fb9QeRt6UoXiV",
+///             "token_id": "k3Jd9sLq0PzX7mWb",
+///             "name": "Backup-Script",
+///             "scopes": ["notes:read"],
+///             "created_at": "2022-04-11T12:20:28.120Z",
+///             "expires_at": "2022-07-10T12:20:28.120Z",
+///             "last_used": null
+///         },
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+/// ```text
+/// POST-Request at `{api-url}/user/tokens` without any scopes
+///     {
+///         "name": "Backup-Script",
+///         "scopes": []
+///     }
+/// => 400
+///     {
+///         "success": false,
+///         "code": 20,
+///         "message": "payload does not match expectations",
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+#[post("/user/tokens")]
+pub async fn add_token(req: HttpRequest, token_req: web::Json<TokenRequest>, db: Data<dyn Storage>) -> impl Responder {
+    let token_req = token_req.into_inner();
+    let name = token_req.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_TOKEN_NAME_LENGTH || token_req.scopes.is_empty() || token_req.expires_in_days == Some(0) {
+        return APIError::InvalidPayloadError.gen_response()
+    }
+    // Expirations beyond what can be represented are rejected as well
+    let now = Utc::now();
+    let expires_at = match token_req.expires_in_days.map(|days| now.checked_add_signed(chrono::Duration::days(days as i64))) {
+        Some(None) => return APIError::InvalidPayloadError.gen_response(),
+        expires_at => expires_at.flatten()
+    };
+    let session = match get_session_from_request(&req, db.get_ref()).await {
+        Ok(session) => session,
+        Err(e) => return e.gen_response()
+    };
+    // Every scope is only granted once
+    let mut scopes: Vec<TokenScope> = Vec::new();
+    for scope in token_req.scopes {
+        if !scopes.contains(&scope) {
+            scopes.push(scope)
+        }
+    }
+    // Generate the token
+    let mut rng = rand::thread_rng();
+    let token_id: String = (&mut rng).sample_iter(&Alphanumeric).take(TOKEN_ID_SIZE).map(char::from).collect();
+    let secret: String = (&mut rng).sample_iter(&Alphanumeric).take(TOKEN_SECRET_SIZE).map(char::from).collect();
+    let token = ApiToken {
+        _id: token_id.clone(),
+        user_id: session.user_id,
+        name,
+        token_hash: hash_token(&secret),
+        scopes,
+        created_at: now,
+        expires_at,
+        last_used: None
+    };
+    match db.insert_token(&token).await {
+        Ok(_) => HttpResponse::Created().json(ResponseObjectWithPayload::new(CreatedTokenResponse {
+            token: format!("{}{}.{}", API_TOKEN_PREFIX, token_id, secret),
+            info: token.into()
+        })),
+        Err(_) => APIError::QueryError("token could not be created".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Lists all personal access-tokens of the current user, without their secrets
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] Tokens have been compiled
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/user/tokens` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": [
+///             {
+///                 "token_id": "k3Jd9sLq0PzX7mWb",
+///                 "name": "Backup-Script",
+///                 "scopes": ["notes:read"],
+///                 "created_at": "2022-04-11T12:20:28.120Z",
+///                 "expires_at": "2022-07-10T12:20:28.120Z",
+///                 "last_used": "2022-04-12T03:00:01.512Z"
+///             }
+///         ],
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[get("/user/tokens")]
+pub async fn list_tokens(req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let session = match get_session_from_request(&req, db.get_ref()).await {
+        Ok(session) => session,
+        Err(e) => return e.gen_response()
+    };
+    match db.get_tokens(&session.user_id).await {
+        Ok(tokens) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(
+            tokens.into_iter().map(TokenResponse::from).collect::<Vec<TokenResponse>>())),
+        Err(_) => APIError::QueryError("tokens could not be retrieved from database".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Revokes a personal access-token of the current user
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - Token has been revoked
+/// * `400`
+///     - **\[21\]** Invalid token-ID
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `404`
+///     - **\[22\]** The user has no token with the given ID
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the to-be-revoked token
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// DELETE-Request at `{api-url}/user/tokens/k3Jd9sLq0PzX7mWb` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+/// ```text
+/// DELETE-Request at `{api-url}/user/tokens/k3Jd9sLq0PzX7mWb` (token of another user)
+/// => 404
+///     {
+///         "success": false,
+///         "code": 22,
+///         "message": "requested resource does not exist: token",
+///         "time": "2022-04-12 09:12:45"
+///     }
+/// ```
+#[delete("/user/tokens/{token_id}")]
+pub async fn remove_token(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let token_id = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&token_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    let session = match get_session_from_request(&req, db.get_ref()).await {
+        Ok(session) => session,
+        Err(e) => return e.gen_response()
+    };
+    // Only the own tokens can be revoked
+    match db.get_token(&token_id).await {
+        Ok(token) if token.user_id.eq(&session.user_id) => {}
+        Ok(_) | Err(DBError::NoDocumentFoundError) => return APIError::ResourceNotFoundError("token".to_string()).gen_response(),
+        Err(_) => return APIError::QueryError("token could not be retrieved from database".to_string()).gen_response()
+    }
+    match db.remove_token(&token_id).await {
+        Ok(_) => HttpResponse::Ok().json(ResponseObject::new()),
+        Err(_) => APIError::QueryError("token could not be revoked".to_string()).gen_response()
+    }
+}
@@ -0,0 +1,411 @@
+//! Endpoints and logic regarding time-based one-time passwords (RFC 6238) serving as a second factor
+//!
+//! Enrolment takes two steps: A secret is generated and handed out (also as an `otpauth://`-URI to be displayed as a QR-code),
+//! after which a one-time password generated from it has to confirm the enrolment.
+//! Upon confirmation a set of recovery-codes is handed out, each of which can replace a one-time password once.
+//! From then on [`authenticate`](crate::web::auth::authenticate) requires the second factor
+//! to be provided at [`verify_second_factor`](crate::web::auth::verify_second_factor) before a session is started.
+
+use actix_web::{post, put, delete, Responder, HttpRequest, HttpResponse, web};
+use actix_web::middleware::from_fn;
+use actix_web::web::Data;
+use chrono::{DateTime, Utc};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac, NewMac};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use rand::Rng;
+use sha1::Sha1;
+use crate::db_access::{Credential, DBError, Storage};
+use crate::web::auth::{get_user_id_from_request, hash_token};
+use crate::web::error::APIError;
+use crate::web::limit::{limit_requests, RateLimiter};
+use crate::web::{ResponseObject, ResponseObjectWithPayload};
+use crate::web::totp::json_objects::{CodeRequest, EnrolmentResponse, RecoveryCodesResponse};
+
+// TOTP-Assets
+/// Size of a generated secret in bytes (as recommended by RFC 4226)
+const TOTP_SECRET_SIZE: usize = 20;
+/// Time in seconds each one-time password is valid for
+const TOTP_STEP_SECONDS: i64 = 30;
+/// Amount of digits making up a one-time password
+const TOTP_DIGITS: usize = 6;
+/// Amount of time-steps a one-time password may be off, compensating for clock-drift
+const TOTP_SKEW_STEPS: i64 = 1;
+/// The issuer displayed by authenticator-apps
+const TOTP_ISSUER: &str = "writeUp";
+
+// Recovery-Assets
+/// Amount of recovery-codes handed out upon enrolment
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Amount of characters making up a recovery-code
+const RECOVERY_CODE_SIZE: usize = 10;
+/// Characters a recovery-code is made of
+const RECOVERY_CODE_CHARSET: &[u8] = b"abcdefghijkmnpqrstuvwxyz23456789";
+
+// Response-/Request-Objects
+/// Structs modelling the request- and response-bodies
+mod json_objects {
+    use serde::{Serialize, Deserialize};
+
+    /// Body of a request containing a one-time password or recovery-code
+    #[derive(Deserialize)]
+    pub struct CodeRequest {
+        /// The one-time password or recovery-code
+        pub code: String
+    }
+
+    /// Body of a response after an enrolment has been started
+    #[derive(Serialize)]
+    pub struct EnrolmentResponse {
+        /// The generated secret (base32)
+        pub secret: String,
+        /// The secret as an URI to be displayed as a QR-code
+        pub uri: String
+    }
+
+    /// Body of a response containing newly generated recovery-codes
+    #[derive(Serialize)]
+    pub struct RecoveryCodesResponse {
+        /// The recovery-codes, each of which can replace a one-time password once
+        pub recovery_codes: Vec<String>
+    }
+}
+
+/// ENDPOINT: Starts the enrolment of a second factor by generating a new secret.
+/// Restarting an unconfirmed enrolment replaces its secret
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] Secret has been generated
+///     - **\[24\]** A second factor is already enabled
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// POST-Request at `{api-url}/user/totp` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": {
+///             "secret": "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP",
+///             "uri": "otpauth://totp/writeUp:testUser?secret=JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP&issuer=writeUp&algorithm=SHA1&digits=6&period=30"
+///         },
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+#[post("/user/totp")]
+pub async fn start_enrolment(req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let mut cred = match get_own_credential(req, db.get_ref()).await {
+        Ok(cred) => cred,
+        Err(e) => return e.gen_response()
+    };
+    if cred.totp_enabled {
+        return APIError::InvalidInstructionsError("second factor is already enabled".to_string()).gen_response()
+    }
+    let secret = BASE32_NOPAD.encode(&rand::thread_rng().gen::<[u8; TOTP_SECRET_SIZE]>());
+    cred.totp_secret = Some(secret.clone());
+    match db.update_credential(&cred).await {
+        Ok(_) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(EnrolmentResponse {
+            uri: gen_totp_uri(&cred._id, &secret),
+            secret
+        })),
+        Err(_) => APIError::QueryError("failed to store the secret".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Confirms the enrolment of a second factor using a one-time password, enabling it.
+/// Returns the recovery-codes, which can't be retrieved again later on
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] Second factor has been enabled
+///     - **\[11\]** Wrong one-time password
+///     - **\[24\]** No enrolment has been started or a second factor is already enabled
+/// * `400`
+///     - **\[20\]** Invalid payload
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `429`
+///     - **\[13\]** \[Retry-After\] Too many requests or the account is locked
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `code_req` - The body of the request parsed to a CodeRequest-object
+/// * `db` - The AppData containing the storage-backend
+/// * `limiter` - The AppData containing the RateLimiter
+///
+/// # Examples
+///
+/// ```text
+/// PUT-Request at `{api-url}/user/totp` with a cookie containing a valid JWT
+///     {
+///         "code": "492039"
+///     }
+/// => 200
+///     {
+///         "success": true,
+///         "content": {
+///             "recovery_codes": ["k3jd9-sq2pz", "x7mwb-gq2vn", ...]
+///         },
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+/// ```text
+/// PUT-Request at `{api-url}/user/totp` (wrong one-time password)
+///     {
+///         "code": "123456"
+///     }
+/// => 200
+///     {
+///         "success": false,
+///         "code": 11,
+///         "message": "failed to process credentials: wrong one-time password",
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+#[put("/user/totp", wrap = "from_fn(limit_requests)")]
+pub async fn confirm_enrolment(req: HttpRequest, code_req: web::Json<CodeRequest>, db: Data<dyn Storage>, limiter: Data<RateLimiter>) -> impl Responder {
+    let mut cred = match get_own_credential(req, db.get_ref()).await {
+        Ok(cred) => cred,
+        Err(e) => return e.gen_response()
+    };
+    // Wrong one-time passwords count towards the lockout of the account as well
+    if let Err(retry_after) = limiter.check_account(&cred._id, Utc::now()) {
+        return APIError::TooManyAttemptsError(retry_after).gen_response()
+    }
+    if cred.totp_enabled {
+        return APIError::InvalidInstructionsError("second factor is already enabled".to_string()).gen_response()
+    }
+    let secret = match &cred.totp_secret {
+        Some(secret) => secret,
+        None => return APIError::InvalidInstructionsError("no enrolment has been started".to_string()).gen_response()
+    };
+    match verify_totp(secret, &code_req.code, cred.totp_last_step, Utc::now()) {
+        Some(step) => cred.totp_last_step = step,
+        None => {
+            limiter.record_failure(&cred._id, Utc::now());
+            return APIError::InvalidCredentialsError("wrong one-time password".to_string()).gen_response()
+        }
+    }
+    limiter.record_success(&cred._id);
+    // Hand out the recovery-codes, keeping only their hashes
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| gen_recovery_code()).collect();
+    cred.recovery_hashes = recovery_codes.iter().map(|code| hash_token(&normalize_recovery_code(code))).collect();
+    cred.totp_enabled = true;
+    match db.update_credential(&cred).await {
+        Ok(_) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(RecoveryCodesResponse { recovery_codes })),
+        Err(_) => APIError::QueryError("failed to enable the second factor".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Disables the second factor, given a one-time password or recovery-code
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - Second factor has been disabled
+///     - **\[11\]** Wrong one-time password or recovery-code
+///     - **\[24\]** No second factor is enabled
+/// * `400`
+///     - **\[20\]** Invalid payload
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `429`
+///     - **\[13\]** \[Retry-After\] Too many requests or the account is locked
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `code_req` - The body of the request parsed to a CodeRequest-object
+/// * `db` - The AppData containing the storage-backend
+/// * `limiter` - The AppData containing the RateLimiter
+///
+/// # Examples
+///
+/// ```text
+/// DELETE-Request at `{api-url}/user/totp` with a cookie containing a valid JWT
+///     {
+///         "code": "k3jd9-sq2pz"
+///     }
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+#[delete("/user/totp", wrap = "from_fn(limit_requests)")]
+pub async fn disable_second_factor(req: HttpRequest, code_req: web::Json<CodeRequest>, db: Data<dyn Storage>, limiter: Data<RateLimiter>) -> impl Responder {
+    let mut cred = match get_own_credential(req, db.get_ref()).await {
+        Ok(cred) => cred,
+        Err(e) => return e.gen_response()
+    };
+    // Wrong one-time passwords count towards the lockout of the account as well
+    if let Err(retry_after) = limiter.check_account(&cred._id, Utc::now()) {
+        return APIError::TooManyAttemptsError(retry_after).gen_response()
+    }
+    if !cred.totp_enabled {
+        return APIError::InvalidInstructionsError("second factor is not enabled".to_string()).gen_response()
+    }
+    if !check_second_factor(&mut cred, &code_req.code) {
+        limiter.record_failure(&cred._id, Utc::now());
+        return APIError::InvalidCredentialsError("wrong one-time password".to_string()).gen_response()
+    }
+    limiter.record_success(&cred._id);
+    cred.totp_secret = None;
+    cred.totp_enabled = false;
+    cred.recovery_hashes = Vec::new();
+    match db.update_credential(&cred).await {
+        Ok(_) => HttpResponse::Ok().json(ResponseObject::new()),
+        Err(_) => APIError::QueryError("failed to disable the second factor".to_string()).gen_response()
+    }
+}
+
+/// Retrieves the credentials of the current user
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `db` - Reference to the storage-backend
+async fn get_own_credential(req: HttpRequest, db: &dyn Storage) -> Result<Credential, APIError> {
+    let user_id = get_user_id_from_request(req, db).await?;
+    match db.get_credential(&user_id).await {
+        Ok(cred) => Ok(cred),
+        Err(DBError::NoDocumentFoundError) => Err(APIError::AuthenticationError),
+        Err(_) => Err(APIError::QueryError("can not access credentials".to_string()))
+    }
+}
+
+/// Checks a one-time password or recovery-code against the second factor of a user.
+/// Accepted one-time passwords and recovery-codes are consumed by modifying the credentials,
+/// which have to be stored afterwards
+///
+/// # Arguments
+///
+/// * `cred` - The credentials of the user
+/// * `code` - The one-time password or recovery-code
+pub fn check_second_factor(cred: &mut Credential, code: &str) -> bool {
+    if let Some(step) = cred.totp_secret.as_ref().and_then(|secret| verify_totp(secret, code, cred.totp_last_step, Utc::now())) {
+        cred.totp_last_step = step;
+        return true
+    }
+    let hash = hash_token(&normalize_recovery_code(code));
+    match cred.recovery_hashes.iter().position(|recovery_hash| recovery_hash.eq(&hash)) {
+        Some(index) => {
+            cred.recovery_hashes.remove(index);
+            true
+        }
+        None => false
+    }
+}
+
+/// Generates the one-time password of a secret for the time-step the given time falls into
+///
+/// # Arguments
+///
+/// * `secret` - The secret (base32)
+/// * `time` - The time the password is generated for
+///
+/// # Examples
+///
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use crate::web::totp::gen_totp;
+///
+/// // Test-vector of RFC 6238
+/// let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"; // "12345678901234567890"
+///
+/// assert_eq!(gen_totp(secret, Utc.timestamp(59, 0)).unwrap(), "287082");
+/// ```
+pub fn gen_totp(secret: &str, time: DateTime<Utc>) -> Option<String> {
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    Some(hotp(&secret, time_step(time)))
+}
+
+/// Checks a one-time password against a secret, returning the time-step it has been generated for.
+/// Passwords of steps up to and including `last_step` are rejected, so that each can only be used once
+///
+/// # Arguments
+///
+/// * `secret` - The secret (base32)
+/// * `code` - The one-time password to be checked
+/// * `last_step` - The last time-step a password has been accepted for
+/// * `time` - The current time
+pub fn verify_totp(secret: &str, code: &str, last_step: u64, time: DateTime<Utc>) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None
+    }
+    // Accept the neighbouring steps as well, in case the clocks are slightly out of sync
+    (-TOTP_SKEW_STEPS..=TOTP_SKEW_STEPS)
+        .map(|skew| time + chrono::Duration::seconds(skew * TOTP_STEP_SECONDS))
+        .filter(|time| time_step(*time) > last_step)
+        .find(|time| gen_totp(secret, *time).is_some_and(|expected| expected.eq(code)))
+        .map(time_step)
+}
+
+/// Returns the time-step the given time falls into
+///
+/// # Arguments
+///
+/// * `time` - The time to be converted
+fn time_step(time: DateTime<Utc>) -> u64 {
+    (time.timestamp() / TOTP_STEP_SECONDS).max(0) as u64
+}
+
+/// Generates the HMAC-based one-time password (RFC 4226) of a secret for the given counter
+///
+/// # Arguments
+///
+/// * `secret` - The raw secret
+/// * `counter` - The counter (time-step) to generate the password for
+fn hotp(secret: &[u8], counter: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    // Dynamic truncation
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([hash[offset], hash[offset + 1], hash[offset + 2], hash[offset + 3]]) & 0x7fff_ffff;
+    format!("{:0width$}", binary % 10_u32.pow(TOTP_DIGITS as u32), width = TOTP_DIGITS)
+}
+
+/// Creates the `otpauth://`-URI of a secret, to be displayed as a QR-code for authenticator-apps
+///
+/// # Arguments
+///
+/// * `username` - The user the secret belongs to
+/// * `secret` - The secret (base32)
+fn gen_totp_uri(username: &str, secret: &str) -> String {
+    let issuer = utf8_percent_encode(TOTP_ISSUER, NON_ALPHANUMERIC);
+    format!("otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            issuer, utf8_percent_encode(username, NON_ALPHANUMERIC), secret, issuer, TOTP_DIGITS, TOTP_STEP_SECONDS)
+}
+
+/// Generates a new recovery-code, split into two halves for readability
+fn gen_recovery_code() -> String {
+    let mut rng = rand::thread_rng();
+    let code: String = (0..RECOVERY_CODE_SIZE)
+        .map(|_| RECOVERY_CODE_CHARSET[rng.gen_range(0..RECOVERY_CODE_CHARSET.len())] as char).collect();
+    format!("{}-{}", &code[..RECOVERY_CODE_SIZE / 2], &code[RECOVERY_CODE_SIZE / 2..])
+}
+
+/// Brings a recovery-code into the form its hash is created from, ignoring case and separators
+///
+/// # Arguments
+///
+/// * `code` - The recovery-code as entered by the user
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars().filter(|c| c.is_ascii_alphanumeric()).map(|c| c.to_ascii_lowercase()).collect()
+}
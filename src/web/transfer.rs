@@ -0,0 +1,383 @@
+//! Endpoints regarding the transfer of a note's ownership to another user
+//!
+//! The owner of a note offers it to one of their connections, who then has to accept the offer.
+//! Until then the note stays untouched and the offer may be withdrawn or declined.
+//! Once accepted, the previous owner keeps the level of access chosen with the offer,
+//! the note leaves their folders and the groups it has been shared with lose their access.
+//! A note has at most one pending offer, a new offer replaces the previous one.
+
+use actix_web::{get, post, delete, Responder, HttpRequest, HttpResponse, web};
+use actix_web::web::{Data, Path};
+use chrono::Utc;
+use crate::db_access::{AllowanceLevel, DBError, is_safe, Storage, Transfer, User};
+use crate::web::auth::get_user_from_request;
+use crate::web::error::APIError;
+use crate::web::note::get_allow_level_for_note;
+use crate::web::transfer::json_objects::{TransferRequest, TransferResponse};
+use crate::web::{ResponseObject, ResponseObjectWithPayload};
+
+/// The level of access the previous owner keeps, if the offer does not state one
+const DEFAULT_PREVIOUS_OWNER_LEVEL: AllowanceLevel = AllowanceLevel::ReadWrite;
+
+// Response-/Request-Objects
+/// Structs modelling the request- and response-bodies
+mod json_objects {
+    use chrono::{DateTime, Utc};
+    use serde::{Serialize, Deserialize};
+    use crate::db_access::{AllowanceLevel, Transfer};
+
+    /// Body of a request offering a note to another user
+    #[derive(Deserialize)]
+    pub struct TransferRequest {
+        /// The user being offered the note
+        pub user_id: String,
+        /// The level of access kept once the offer is accepted (`ReadWrite` if not set)
+        pub previous_owner_level: Option<AllowanceLevel>
+    }
+
+    /// Body of a response containing a pending offer
+    #[derive(Serialize)]
+    pub struct TransferResponse {
+        /// The note being handed over
+        pub note_id: String,
+        /// The user currently owning the note
+        pub from_id: String,
+        /// The user being offered the note
+        pub to_id: String,
+        /// The level of access the current owner keeps
+        pub previous_owner_level: AllowanceLevel,
+        /// Timestamp of the offer
+        pub created_at: DateTime<Utc>
+    }
+    impl From<Transfer> for TransferResponse {
+        fn from(transfer: Transfer) -> Self {
+            TransferResponse {
+                note_id: transfer._id,
+                from_id: transfer.from_id,
+                to_id: transfer.to_id,
+                previous_owner_level: transfer.previous_owner_level,
+                created_at: transfer.created_at
+            }
+        }
+    }
+}
+
+/// ENDPOINT: Offers a note owned by the current user to one of their connections.
+/// Replaces any pending offer regarding the note
+///
+/// Returns one of the following HttpResponses:
+/// * `201`
+///     - \[Body: JSON\] The offer has been made
+/// * `200`
+///     - **\[24\]** Invalid instruction (the note is offered to its owner or a user without a connection to them)
+/// * `400`
+///     - **\[20\]** The previous owner is to remain owner
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `403`
+///     - **\[12\]** The user does not own the note
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the note
+/// * `req` - The HttpRequest that was made
+/// * `transfer_req` - The body of the request parsed to a TransferRequest-object
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// POST-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/transfer` with a cookie containing a valid JWT
+///     {
+///         "user_id": "otherUser",
+///         "previous_owner_level": "Read"
+///     }
+/// => 201
+///     {
+///         "success": true,
+///         "content": {
+///             "note_id": "7254fa970b62u3ag62dr4d3l",
+///             "from_id": "testUser",
+///             "to_id": "otherUser",
+///             "previous_owner_level": "Read",
+///             "created_at": "2022-04-11T12:20:28.120Z"
+///         },
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+/// ```text
+/// POST-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/transfer` with a cookie containing a valid JWT [strangerUser is not connected to this user]
+///     {
+///         "user_id": "strangerUser"
+///     }
+/// => 200
+///     {
+///         "success": false,
+///         "code": 24,
+///         "message": "invalid instruction: user don't share a connection",
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+/// ```text
+/// POST-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/transfer` to a note shared with the current user
+///     {
+///         "user_id": "otherUser"
+///     }
+/// => 403
+///     {
+///         "success": false,
+///         "code": 12,
+///         "message": "no permission",
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+#[post("/note/{note_id}/transfer")]
+pub async fn offer_transfer(path: Path<String>, req: HttpRequest, transfer_req: web::Json<TransferRequest>, db: Data<dyn Storage>) -> impl Responder {
+    let note_id = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&note_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    let transfer_req = transfer_req.into_inner();
+    let previous_owner_level = transfer_req.previous_owner_level.unwrap_or(DEFAULT_PREVIOUS_OWNER_LEVEL);
+    if previous_owner_level == AllowanceLevel::Owner {
+        return APIError::InvalidPayloadError.gen_response()
+    }
+    // Only the owner may hand a note over
+    match get_allow_level_for_note(&note_id, req.clone(), db.get_ref()).await {
+        Ok(AllowanceLevel::Owner) => {}
+        Ok(_) => return APIError::NoPermissionError.gen_response(),
+        Err(e) => return e.gen_response()
+    }
+    let user = match get_user_from_request(req, db.get_ref()).await {
+        Ok(user) => user,
+        Err(e) => return e.gen_response()
+    };
+    if transfer_req.user_id.eq(&user._id) {
+        return APIError::InvalidInstructionsError("user already owns the note".to_string()).gen_response()
+    }
+    if !user.connections.contains(&transfer_req.user_id) {
+        return APIError::InvalidInstructionsError("user don't share a connection".to_string()).gen_response()
+    }
+
+    let transfer = Transfer { _id: note_id, from_id: user._id, to_id: transfer_req.user_id, previous_owner_level, created_at: Utc::now() };
+    match db.set_transfer(&transfer).await {
+        Ok(_) => HttpResponse::Created().json(ResponseObjectWithPayload::new(TransferResponse::from(transfer))),
+        Err(_) => APIError::QueryError("offer could not be made".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Lists all pending offers made by or to the current user
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - \[Body: JSON\] Offers have been compiled
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// GET-Request at `{api-url}/transfers` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "content": [
+///             {
+///                 "note_id": "7254fa970b62u3ag62dr4d3l",
+///                 "from_id": "otherUser",
+///                 "to_id": "testUser",
+///                 "previous_owner_level": "ReadWrite",
+///                 "created_at": "2022-04-11T12:20:28.120Z"
+///             }
+///         ],
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+/// ```text
+/// GET-Request at `{api-url}/transfers` without a cookie containing a JWT
+/// => 401
+///     {
+///         "success": false,
+///         "code": 10,
+///         "message": "user is not logged in",
+///         "time": "2022-04-11 12:20:19"
+///     }
+/// ```
+#[get("/transfers")]
+pub async fn list_transfers(req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let user = match get_user_from_request(req, db.get_ref()).await {
+        Ok(user) => user,
+        Err(e) => return e.gen_response()
+    };
+    match db.get_transfers(&user._id).await {
+        Ok(transfers) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(
+            transfers.into_iter().map(TransferResponse::from).collect::<Vec<TransferResponse>>())),
+        Err(_) => APIError::QueryError("offers could not be retrieved from database".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Accepts the offer of a note made to the current user, making them its owner
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - The user owns the note now
+///     - **\[24\]** Invalid instruction (the user is no longer connected to the owner)
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `404`
+///     - **\[22\]** The note has not been offered to the user
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the note
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// POST-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/transfer/accept` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+/// ```text
+/// POST-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/transfer/accept` with a cookie containing a valid JWT [note has not been offered to the user]
+/// => 404
+///     {
+///         "success": false,
+///         "code": 22,
+///         "message": "requested resource does not exist: transfer",
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+#[post("/note/{note_id}/transfer/accept")]
+pub async fn accept_transfer(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let note_id = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&note_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    let (user, transfer) = match get_transfer_of_party(&note_id, req, db.get_ref()).await {
+        Ok((user, transfer)) if transfer.to_id.eq(&user._id) => (user, transfer),
+        Ok(_) => return APIError::ResourceNotFoundError("transfer".to_string()).gen_response(),
+        Err(e) => return e.gen_response()
+    };
+    if !user.connections.contains(&transfer.from_id) {
+        return APIError::InvalidInstructionsError("user don't share a connection".to_string()).gen_response()
+    }
+
+    // Swap the allowances of both user at once, then hand the note over
+    let changes = [(user._id.clone(), AllowanceLevel::Owner), (transfer.from_id.clone(), transfer.previous_owner_level)];
+    if db.set_note_allowances(&note_id, &changes).await.is_err() {
+        return APIError::QueryError("allowances could not be updated".to_string()).gen_response()
+    }
+    if db.set_note_owner(&note_id, &user._id).await.is_err() {
+        // Restore the allowances, so that they keep matching the owner
+        let prior_level = user.allowances.iter().find(|allow| allow.note_id.eq(&note_id))
+            .map(|allow| allow.level).unwrap_or(AllowanceLevel::Forbidden);
+        let restore = [(user._id.clone(), prior_level), (transfer.from_id.clone(), AllowanceLevel::Owner)];
+        let message = match db.set_note_allowances(&note_id, &restore).await {
+            Ok(_) => "note could not be handed over",
+            Err(_) => "note could not be handed over and allowances could not be restored"
+        };
+        return APIError::QueryError(message.to_string()).gen_response()
+    }
+
+    // Shares of the previous owner's folders and groups no longer apply
+    match db.pull_folder_note(&note_id).await.and(db.pull_note_group_allowances(&note_id).await)
+        .and(db.remove_transfer(&note_id).await) {
+        Ok(_) => HttpResponse::Ok().json(ResponseObject::new()),
+        Err(_) => APIError::QueryError("not all references could be removed".to_string()).gen_response()
+    }
+}
+
+/// ENDPOINT: Withdraws or declines the pending offer of a note, depending on whether the current user made or received it
+///
+/// Returns one of the following HttpResponses:
+/// * `200`
+///     - The offer has been removed
+/// * `400`
+///     - **\[21\]** id contains invalid symbols
+/// * `401`
+///     - **\[10\]** Missing or invalid JWT
+/// * `404`
+///     - **\[22\]** There is no offer of the note made by or to the user
+/// * `500`
+///     - Something went wrong internally (debug)
+///
+/// # Arguments
+///
+/// * `path` - A Path-object containing the id of the note
+/// * `req` - The HttpRequest that was made
+/// * `db` - The AppData containing the storage-backend
+///
+/// # Examples
+///
+/// ```text
+/// DELETE-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/transfer` with a cookie containing a valid JWT
+/// => 200
+///     {
+///         "success": true,
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+/// ```text
+/// DELETE-Request at `{api-url}/note/7254fa970b62u3ag62dr4d3l/transfer` with a cookie containing a valid JWT [note has not been offered]
+/// => 404
+///     {
+///         "success": false,
+///         "code": 22,
+///         "message": "requested resource does not exist: transfer",
+///         "time": "2022-04-11 12:20:28"
+///     }
+/// ```
+#[delete("/note/{note_id}/transfer")]
+pub async fn cancel_transfer(path: Path<String>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    let note_id = path.into_inner();
+    // Check for potential injection-attempt
+    if !is_safe(&note_id) {
+        return APIError::InvalidIDError.gen_response()
+    }
+    if let Err(e) = get_transfer_of_party(&note_id, req, db.get_ref()).await {
+        return e.gen_response()
+    }
+    match db.remove_transfer(&note_id).await {
+        Ok(_) => HttpResponse::Ok().json(ResponseObject::new()),
+        Err(_) => APIError::QueryError("offer could not be removed".to_string()).gen_response()
+    }
+}
+
+/// Retrieves the current user and the pending offer of a note, given they made or received it.
+/// Offers of others are treated as if they didn't exist
+///
+/// # Arguments
+///
+/// * `note_id` - The identifier of the note
+/// * `req` - The HttpRequest that was made
+/// * `db` - A reference to the storage-backend
+async fn get_transfer_of_party(note_id: &str, req: HttpRequest, db: &dyn Storage) -> Result<(User, Transfer), APIError> {
+    let user = get_user_from_request(req, db).await?;
+    match db.get_transfer(note_id).await {
+        Ok(transfer) if transfer.from_id.eq(&user._id) || transfer.to_id.eq(&user._id) => Ok((user, transfer)),
+        Ok(_) | Err(DBError::NoDocumentFoundError) => Err(APIError::ResourceNotFoundError("transfer".to_string())),
+        Err(_) => Err(APIError::QueryError("offer could not be retrieved from database".to_string()))
+    }
+}
@@ -1,18 +1,17 @@
 //! Endpoints regarding user-objects and their manipulation
 
-use std::env;
-use std::sync::Mutex;
 use actix_web::{get, delete, post, Responder, HttpRequest, HttpResponse, web};
-use actix_web::web::Data;
-use mongodb::bson::doc;
-use mongodb::Database;
-use crate::db_access::{Credential, CREDENTIALS, del_dbo_by_id, get_dbo_by_id, insert_dbo, Note, NOTES, update_dbo_by_id, User, USER};
-use crate::db_access::AllowanceLevel::Owner;
+use actix_web::middleware::from_fn;
+use actix_web::web::{Data, Query};
+use crate::db_access::{Credential, get_effective_allowances, Storage, User};
+use crate::db_access::AllowanceLevel::{Forbidden, Owner};
 use crate::db_access::DBError::{NoDocumentFoundError, QueryError};
+use crate::secret::AppSecrets;
 use crate::web::auth::{gen_logout_response, get_user_from_request, get_user_id_from_request};
 use crate::web::error::APIError;
+use crate::web::limit::limit_requests;
 use crate::web::ResponseObjectWithPayload;
-use crate::web::user::json_objects::{UserRequest, UserResponse};
+use crate::web::user::json_objects::{RemovalRequest, SharedNote, UserRequest, UserResponse};
 
 // Response-/Request-Objects
 /// Structs modelling the request- and response-bodies
@@ -25,7 +24,9 @@ mod json_objects {
         /// The username
         pub username: String,
         /// All connected user
-        pub relations: Vec<String>
+        pub relations: Vec<String>,
+        /// The roles assigned by the directory
+        pub roles: Vec<String>
     }
 
     /// Body of a request for a new user
@@ -38,6 +39,23 @@ mod json_objects {
         /// A key indicating the user has access to the beta-deployment
         pub beta_key: String
     }
+
+    /// Query-parameters of a request to remove the current user
+    #[derive(Deserialize)]
+    pub struct RemovalRequest {
+        /// Whether notes shared with other users are to be removed as well, instead of being handed over first
+        #[serde(default)]
+        pub delete_shared_notes: bool
+    }
+
+    /// A note owned by a user that is to be removed, which other users have access to
+    #[derive(Serialize)]
+    pub struct SharedNote {
+        /// The identifier of the note
+        pub note_id: String,
+        /// The user the note is shared with, each of which it could be handed over to
+        pub shared_with: Vec<String>
+    }
 }
 
 /// ENDPOINT: Creates a new user with the given credentials
@@ -49,6 +67,8 @@ mod json_objects {
 ///     - **\[11\]** Username already exists in the database
 /// * `403`
 ///     - **\[12\]** User is currently logged in
+/// * `429`
+///     - **\[13\]** \[Retry-After\] Too many requests
 /// * `500`
 ///     - Something went wrong internally (debug)
 ///
@@ -56,7 +76,8 @@ mod json_objects {
 ///
 /// * `req` - The HttpRequest that was made
 /// * `user_req` - The body of the request parsed to a UserRequest-object
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `db` - The AppData containing the storage-backend
+/// * `secrets` - The AppData containing the beta-key
 ///
 /// # Examples
 ///
@@ -72,7 +93,8 @@ mod json_objects {
 ///         "success": true,
 ///         "content": {
 ///             "username": "otherUser",
-///             "relations": []
+///             "relations": [],
+///             "roles": []
 ///         },
 ///         "time": "2022-04-11 12:20:28"
 ///     }
@@ -122,33 +144,33 @@ mod json_objects {
 ///         "time": "2022-04-11 12:20:19"
 ///     }
 /// ```
-#[post("/user")]
-pub async fn add_user(req: HttpRequest, user_req: web::Json<UserRequest>, db: Data<Mutex<Database>>) -> impl Responder {
+#[post("/user", wrap = "from_fn(limit_requests)")]
+pub async fn add_user(req: HttpRequest, user_req: web::Json<UserRequest>, db: Data<dyn Storage>, secrets: Data<AppSecrets>) -> impl Responder {
     // Check if still logged in
-    if get_user_id_from_request(req).is_ok() { //TODO? necessary to be logged out?
+    if get_user_id_from_request(req, db.get_ref()).await.is_ok() { //TODO? necessary to be logged out?
         return APIError::NoPermissionError.gen_response()
     }
     // Verify access to beta-deploy
-    if user_req.beta_key != env::var("BETA_KEY").unwrap() {
+    if user_req.beta_key != secrets.beta_key.expose() {
         return APIError::NoPermissionError.gen_response()
     }
     let new_user = user_req.into_inner();
-    // Check for unique username
-    match get_dbo_by_id::<Credential>(CREDENTIALS, new_user.username.clone(), &db).await {
+    // Check for unique username, including users of the directory who have no local credentials
+    match db.get_user(&new_user.username).await {
         Err(NoDocumentFoundError) => {
             // Prepare the new dbos
             let creds = Credential::new(new_user.username.clone(), &new_user.password);
-            let user = User {_id: new_user.username, allowances: Vec::new(), connections: Vec::new()};
+            let user = User {_id: new_user.username, allowances: Vec::new(), connections: Vec::new(), roles: Vec::new()};
 
             // Insert the new dbos
-            let add_cred = insert_dbo::<Credential>(CREDENTIALS, &creds, &db);
-            let add_user = insert_dbo::<User>(USER, &user, &db);
+            let add_user = db.insert_user(&user);
+            let add_cred = db.insert_credential(&creds);
 
             // Check for error
-            if add_cred.await.is_err() || add_user.await.is_err() {
+            if add_user.await.is_err() || add_cred.await.is_err() {
                 return APIError::QueryError("user/credentials could not be created".to_string()).gen_response()
             }
-            HttpResponse::Created().json(ResponseObjectWithPayload::new(UserResponse {username: user._id.clone(), relations: user.connections.clone()})) //TODO? login afterwards?
+            HttpResponse::Created().json(ResponseObjectWithPayload::new(UserResponse {username: user._id.clone(), relations: user.connections.clone(), roles: user.roles.clone()})) //TODO? login afterwards?
         }
         Ok(_) => APIError::InvalidCredentialsError("username already exists".to_string()).gen_response(),
         Err(_) => APIError::QueryError("username could not be checked on uniqueness".to_string()).gen_response()
@@ -168,7 +190,7 @@ pub async fn add_user(req: HttpRequest, user_req: web::Json<UserRequest>, db: Da
 /// # Arguments
 ///
 /// * `req` - The HttpRequest that was made
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `db` - The AppData containing the storage-backend
 ///
 /// # Examples
 ///
@@ -179,7 +201,8 @@ pub async fn add_user(req: HttpRequest, user_req: web::Json<UserRequest>, db: Da
 ///         "success": true,
 ///         "content": {
 ///             "username": "testUser",
-///             "relations": ["otherUser", "yetAnotherUser"]
+///             "relations": ["otherUser", "yetAnotherUser"],
+///             "roles": ["editor"]
 ///         },
 ///         "time": "2022-04-11 12:20:28"
 ///     }
@@ -195,19 +218,22 @@ pub async fn add_user(req: HttpRequest, user_req: web::Json<UserRequest>, db: Da
 ///     }
 /// ```
 #[get("/user")]
-pub async fn get_user(req: HttpRequest, db: Data<Mutex<Database>>) -> impl Responder {
-    match get_user_from_request(req, &db).await {
+pub async fn get_user(req: HttpRequest, db: Data<dyn Storage>) -> impl Responder {
+    match get_user_from_request(req, db.get_ref()).await {
         Ok(user) => HttpResponse::Ok().json(ResponseObjectWithPayload::new(
-            UserResponse { username: user._id, relations: user.connections })),
+            UserResponse { username: user._id, relations: user.connections, roles: user.roles })),
         Err(e) => e.gen_response()
     }
 }
 
-/// ENDPOINT: Removes a user from the database and logs them out
+/// ENDPOINT: Removes a user from the database and logs them out.
+/// Notes the user shares with others are only removed if asked for explicitly (`?delete_shared_notes=true`),
+/// otherwise they are listed, so that they can be handed over first (see [`offer_transfer`](crate::web::transfer::offer_transfer))
 ///
 /// Returns one of the following HttpResponses:
 /// * `200`
 ///     - User was removed successfully
+///     - **\[24\]** \[Body: JSON\] Invalid instruction (the user owns notes shared with others)
 /// * `401`
 ///     - Missing or invalid JWT
 /// * `500`
@@ -215,8 +241,9 @@ pub async fn get_user(req: HttpRequest, db: Data<Mutex<Database>>) -> impl Respo
 ///
 /// # Arguments
 ///
+/// * `query` - The query-parameters of the request parsed to a RemovalRequest-object
 /// * `req` - The HttpRequest that was made
-/// * `db` - The AppData containing a Mutex-secured Database-connection
+/// * `db` - The AppData containing the storage-backend
 ///
 /// # Examples
 ///
@@ -229,6 +256,22 @@ pub async fn get_user(req: HttpRequest, db: Data<Mutex<Database>>) -> impl Respo
 ///     }
 /// ```
 /// ```text
+/// DELETE-Request at `{api-url}/user` with a cookie containing a valid JWT [a note of the user is shared with otherUser]
+/// => 200
+///     {
+///         "success": false,
+///         "code": 24,
+///         "message": "invalid instruction: shared notes have to be handed over or deleted explicitly",
+///         "details": [
+///             {
+///                 "note_id": "7254fa970b62u3ag62dr4d3l",
+///                 "shared_with": ["otherUser"]
+///             }
+///         ],
+///         "time": "2022-04-11 12:05:57"
+///     }
+/// ```
+/// ```text
 /// DELETE-Request at `{api-url}/user` without a cookie containing a JWT
 /// => 401
 ///     {
@@ -239,19 +282,30 @@ pub async fn get_user(req: HttpRequest, db: Data<Mutex<Database>>) -> impl Respo
 ///     }
 /// ```
 #[delete("/user")]
-pub async fn remove_user(req: HttpRequest, db: Data<Mutex<Database>>) -> impl Responder { //TODO add security check or something (maybe have a body with the user-information or something, password?)
-    match get_user_from_request(req, &db).await { //TODO This function borrows a lot of lines from other endpoints
+pub async fn remove_user(query: Query<RemovalRequest>, req: HttpRequest, db: Data<dyn Storage>) -> impl Responder { //TODO add security check or something (maybe have a body with the user-information or something, password?)
+    match get_user_from_request(req, db.get_ref()).await { //TODO This function borrows a lot of lines from other endpoints
         Ok(user) => {
+            // Give the user the chance to hand shared notes over, instead of silently removing them
+            if !query.delete_shared_notes {
+                match get_shared_notes(&user, db.get_ref()).await {
+                    Ok(shared) if !shared.is_empty() => return APIError::InvalidInstructionsError(
+                        "shared notes have to be handed over or deleted explicitly".to_string()).gen_response_with_details(shared),
+                    Ok(_) => {}
+                    Err(e) => return e.gen_response()
+                }
+            }
+
             // Remove all notes and their allowances
             let mut note_deletion_error = Vec::new();
             for note in user.allowances { //TODO Multithread
                 if note.level == Owner {
-                    // Remove all allowances
-                    let users = db.lock().unwrap().collection::<User>(USER);
-                    match users.update_many(doc! {}, doc! {"$pull": {"allowances": {"note_id": &note.note_id}}}, None).await { //TODO Dont queue through all notes
+                    // Remove all allowances, public links and pending offers
+                    match db.pull_note_allowances(&note.note_id).await.and(db.pull_note_group_allowances(&note.note_id).await)
+                        .and(db.pull_folder_note(&note.note_id).await).and(db.remove_links(&note.note_id).await)
+                        .and(db.remove_transfer(&note.note_id).await) {
                         Ok(_res) => {
-                            // Remove note
-                            if del_dbo_by_id::<Note>(NOTES, note.note_id, &db).await.is_err() {
+                            // Remove note and its history
+                            if db.remove_note(&note.note_id).await.is_err() || db.remove_revisions(&note.note_id).await.is_err() {
                                 note_deletion_error.push(QueryError) //TODO error-report?
                             }
                         }
@@ -264,12 +318,53 @@ pub async fn remove_user(req: HttpRequest, db: Data<Mutex<Database>>) -> impl Re
                 return APIError::QueryError("notes and allowances could not be fully removed".to_string()).gen_response()
             }
 
+            // Decline all offers made to the user
+            let transfers = match db.get_transfers(&user._id).await {
+                Ok(transfers) => transfers,
+                Err(_) => return APIError::QueryError("offers could not be retrieved from database".to_string()).gen_response()
+            };
+            for transfer in transfers {
+                if db.remove_transfer(&transfer._id).await.is_err() {
+                    return APIError::QueryError("offers could not be fully removed".to_string()).gen_response()
+                }
+            }
+
+            // Remove all groups of the user and leave those of others
+            let groups = match db.get_groups(&user._id).await {
+                Ok(groups) => groups,
+                Err(_) => return APIError::QueryError("groups could not be retrieved from database".to_string()).gen_response()
+            };
+            for group in groups { //TODO Multithread
+                let removal = if group.owner_id.eq(&user._id) {
+                    db.remove_group(&group._id).await
+                } else {
+                    db.pull_member(&group._id, &user._id).await
+                };
+                if removal.is_err() {
+                    return APIError::QueryError("groups could not be fully removed".to_string()).gen_response()
+                }
+            }
+
+            // Remove all folders of the user and revoke those shared with them
+            let folders = match (db.get_folders(&user._id).await, db.get_shared_folders(&user._id).await) {
+                (Ok(owned), Ok(shared)) => (owned, shared),
+                _ => return APIError::QueryError("folders could not be retrieved from database".to_string()).gen_response()
+            };
+            for folder in folders.0 { //TODO Multithread
+                if db.remove_folder(&folder._id).await.is_err() {
+                    return APIError::QueryError("folders could not be fully removed".to_string()).gen_response()
+                }
+            }
+            for folder in folders.1 {
+                if db.pull_folder_share(&folder._id, &user._id).await.is_err() {
+                    return APIError::QueryError("folders could not be fully removed".to_string()).gen_response()
+                }
+            }
+
             // Remove all Relations with other user
             let mut connection_deletion_error = Vec::new();
             for conn_user in user.connections { //TODO Multithread
-                if update_dbo_by_id::<User>(USER, conn_user,
-                                         doc! {"$pull": {"connections": &user._id}},
-                                         &db).await.is_err() {
+                if db.pull_connection(&conn_user, &user._id).await.is_err() {
                     connection_deletion_error.push(QueryError) //TODO error-report?
                 }
             }
@@ -278,13 +373,16 @@ pub async fn remove_user(req: HttpRequest, db: Data<Mutex<Database>>) -> impl Re
                 return APIError::QueryError("relations to other user could not be fully removed".to_string()).gen_response()
             }
 
-            // Remove the user and his credentials
-            let user_removal = del_dbo_by_id::<User>(USER,
-                                                     user._id.clone(), &db);
-            let cred_removal = del_dbo_by_id::<Credential>(CREDENTIALS,
-                                                           user._id.clone(), &db);
-            if user_removal.await.is_err() || cred_removal.await.is_err() {
-                return APIError::QueryError("user and/or credentials could not be removed".to_string()).gen_response()
+            // Remove the user, his credentials, sessions and tokens
+            let user_removal = db.remove_user(&user._id);
+            let cred_removal = db.remove_credential(&user._id);
+            let session_removal = db.remove_sessions(&user._id);
+            let token_removal = db.remove_tokens(&user._id);
+            let reset_removal = db.remove_resets(&user._id);
+            let identity_removal = db.remove_identities(&user._id);
+            if user_removal.await.is_err() || cred_removal.await.is_err() || session_removal.await.is_err()
+                || token_removal.await.is_err() || reset_removal.await.is_err() || identity_removal.await.is_err() {
+                return APIError::QueryError("user, credentials, sessions and/or tokens could not be removed".to_string()).gen_response()
             }
 
             // Log the user out
@@ -292,4 +390,31 @@ pub async fn remove_user(req: HttpRequest, db: Data<Mutex<Database>>) -> impl Re
         }
         Err(e) => e.gen_response()
     }
-}
\ No newline at end of file
+}
+
+/// Compiles the notes owned by a user which are shared with any of their connections,
+/// be it directly, through a group or through a folder
+///
+/// # Arguments
+///
+/// * `user` - The user owning the notes
+/// * `db` - A reference to the storage-backend
+async fn get_shared_notes(user: &User, db: &dyn Storage) -> Result<Vec<SharedNote>, APIError> {
+    let mut connections = Vec::new();
+    for conn_user in &user.connections {
+        let allowances = match db.get_user(conn_user).await {
+            Ok(conn_user) => get_effective_allowances(&conn_user, db).await,
+            Err(e) => Err(e)
+        };
+        match allowances {
+            Ok(allowances) => connections.push((conn_user, allowances)),
+            Err(_) => return Err(APIError::QueryError("connected user could not be retrieved from database".to_string()))
+        }
+    }
+    Ok(user.allowances.iter().filter(|allow| allow.level == Owner).filter_map(|allow| {
+        let shared_with: Vec<String> = connections.iter()
+            .filter(|(_, allowances)| allowances.iter().any(|conn_allow| conn_allow.note_id.eq(&allow.note_id) && conn_allow.level > Forbidden))
+            .map(|(conn_user, _)| conn_user.to_string()).collect();
+        (!shared_with.is_empty()).then(|| SharedNote { note_id: allow.note_id.clone(), shared_with })
+    }).collect())
+}